@@ -0,0 +1,94 @@
+// The engine code that runs for every object on every update, timed natively
+// on the pure modules. The stress example measures the same kind of load in a
+// browser, including drawing.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use wasm_engine::{
+    engine::{Point, Rect},
+    path::Path,
+    scheduler::Scheduler,
+    tween::{Tween, Tweens},
+};
+
+const COLLIDERS: usize = 1000;
+const TIMESTEP: f32 = 1000.0 / 60.0;
+
+fn colliders() -> Vec<Rect> {
+    (0..COLLIDERS)
+        .map(|index| Rect {
+            x: (index * 37 % 2000) as f32,
+            y: (index * 53 % 600) as f32,
+            width: 40.0,
+            height: 40.0,
+        })
+        .collect()
+}
+
+fn collisions(c: &mut Criterion) {
+    let colliders = colliders();
+    let player = Rect {
+        x: 100.0,
+        y: 300.0,
+        width: 70.0,
+        height: 115.0,
+    };
+
+    c.bench_function("player against 1000 colliders", |b| {
+        b.iter(|| {
+            colliders
+                .iter()
+                .filter(|collider| black_box(&player).intersects(collider))
+                .count()
+        })
+    });
+
+    c.bench_function("overlaps of 1000 colliders", |b| {
+        b.iter(|| {
+            colliders
+                .iter()
+                .filter_map(|collider| black_box(&player).intersection(collider))
+                .count()
+        })
+    });
+}
+
+fn timers(c: &mut Criterion) {
+    c.bench_function("tick 1000 timers", |b| {
+        let mut scheduler: Scheduler<u32> = Scheduler::new(TIMESTEP);
+        for index in 0..1000 {
+            scheduler.every(TIMESTEP * (index % 60 + 1) as f32, |count| *count += 1);
+        }
+        let mut count = 0;
+        b.iter(|| scheduler.run(&mut count))
+    });
+
+    c.bench_function("tick 1000 tweens", |b| {
+        let mut tweens: Tweens<f32> = Tweens::new(TIMESTEP);
+        // Long enough that none of them finish while it's timed
+        for _ in 0..1000 {
+            tweens.start(Tween::new(0.0, 1.0, f32::MAX, |value, to| *value = to));
+        }
+        let mut value = 0.0;
+        b.iter(|| tweens.run(&mut value))
+    });
+}
+
+fn paths(c: &mut Criterion) {
+    c.bench_function("move 1000 paths", |b| {
+        let mut paths: Vec<Path> = (0..1000)
+            .map(|_| {
+                Path::new(
+                    vec![
+                        Point { x: 200, y: 0 },
+                        Point { x: 200, y: 100 },
+                        Point { x: 0, y: 0 },
+                    ],
+                    2,
+                )
+            })
+            .collect();
+        b.iter(|| paths.iter_mut().map(|path| path.update().x).sum::<i16>())
+    });
+}
+
+criterion_group!(hot_paths, collisions, timers, paths);
+criterion_main!(hot_paths);
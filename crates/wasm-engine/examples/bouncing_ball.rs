@@ -0,0 +1,119 @@
+// The smallest game the engine runs: a ball that bounces around the canvas,
+// and jumps when space is pressed. Serve it on a page with a 600x600
+// <canvas id="canvas" tabindex="0">.
+use anyhow::Result;
+use async_trait::async_trait;
+use wasm_engine::{
+    engine::{Game, GameLoop, KeyState, Point, Renderer},
+    input::{Action, Gamepads, InputDevice, InputState, Touches},
+};
+
+const SIZE: f32 = 600.0;
+const RADIUS: f32 = 20.0;
+const GRAVITY: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum BallAction {
+    Jump,
+}
+
+impl Action for BallAction {
+    fn all() -> &'static [Self] {
+        &[BallAction::Jump]
+    }
+}
+
+struct Controls<'a> {
+    keys: &'a KeyState,
+    gamepads: &'a Gamepads,
+}
+
+impl InputDevice<BallAction> for Controls<'_> {
+    fn value(&self, action: BallAction) -> f32 {
+        match action {
+            BallAction::Jump => {
+                let key: f32 = if self.keys.is_pressed("Space") {
+                    1.0
+                } else {
+                    0.0
+                };
+                key.max(self.gamepads.value(|pad| pad.button(0)))
+            }
+        }
+    }
+}
+
+struct Ball {
+    x: f32,
+    y: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+}
+
+#[async_trait(?Send)]
+impl Game for Ball {
+    type Action = BallAction;
+
+    async fn initialize(&mut self) -> Result<Box<dyn Game<Action = BallAction>>> {
+        Ok(Box::new(Ball {
+            x: self.x,
+            y: self.y,
+            velocity_x: self.velocity_x,
+            velocity_y: self.velocity_y,
+        }))
+    }
+
+    fn read_input(
+        &self,
+        keys: &KeyState,
+        gamepads: &Gamepads,
+        _touches: &Touches,
+        input: &mut InputState<BallAction>,
+    ) {
+        input.update(&[&Controls { keys, gamepads }]);
+    }
+
+    fn update(&mut self, input: &InputState<BallAction>) {
+        if input.just_pressed(BallAction::Jump) {
+            self.velocity_y = -12.0;
+        }
+        self.velocity_y += GRAVITY;
+        self.x += self.velocity_x;
+        self.y += self.velocity_y;
+
+        if self.x < RADIUS || self.x > SIZE - RADIUS {
+            self.velocity_x = -self.velocity_x;
+        }
+        if self.y > SIZE - RADIUS {
+            self.y = SIZE - RADIUS;
+            self.velocity_y = -self.velocity_y * 0.8;
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.clear_all(None);
+        renderer.draw_circle(
+            "#ff0000",
+            &Point {
+                x: self.x as i16,
+                y: self.y as i16,
+            },
+            RADIUS,
+        );
+    }
+}
+
+fn main() {
+    wasm_engine::spawn_local(async move {
+        let ball = Ball {
+            x: SIZE / 2.0,
+            y: RADIUS,
+            velocity_x: 3.0,
+            velocity_y: 0.0,
+        };
+
+        GameLoop::start(ball)
+            .await
+            .expect("Could not start game loop");
+    });
+}
@@ -0,0 +1,162 @@
+// A benchmark scene: thousands of boxes that move, bounce and check for
+// collisions with a player box every update, then all get drawn. Every two
+// seconds it logs how long updates and draws took on average, which with the
+// frame rate is enough to see whether a change to the engine helped. Serve it
+// on a page with a 600x600 <canvas id="canvas" tabindex="0">, and add
+// ?count=5000 to change how many boxes there are.
+use anyhow::Result;
+use async_trait::async_trait;
+use wasm_engine::{
+    engine::{DebugFlags, Game, GameLoop, KeyState, Rect, Renderer},
+    input::{Action, Gamepads, InputState, Touches},
+    platform,
+};
+
+const SIZE: f32 = 600.0;
+const BOX_SIZE: f32 = 8.0;
+const DEFAULT_COUNT: usize = 2000;
+const REPORT_EVERY: u32 = 120;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum NoAction {}
+
+impl Action for NoAction {
+    fn all() -> &'static [Self] {
+        &[]
+    }
+}
+
+struct Mover {
+    rect: Rect,
+    velocity_x: f32,
+    velocity_y: f32,
+    touching: bool,
+}
+
+#[derive(Default)]
+struct Timings {
+    frames: u32,
+    updating: f64,
+    drawing: std::cell::Cell<f64>,
+}
+
+struct Stress {
+    count: usize,
+    movers: Vec<Mover>,
+    player: Rect,
+    timings: Timings,
+}
+
+impl Stress {
+    fn spawn(count: usize) -> Vec<Mover> {
+        (0..count)
+            .map(|index| Mover {
+                rect: Rect {
+                    x: (index * 37 % 590) as f32,
+                    y: (index * 53 % 590) as f32,
+                    width: BOX_SIZE,
+                    height: BOX_SIZE,
+                },
+                velocity_x: (index % 7) as f32 - 3.0,
+                velocity_y: (index % 5) as f32 - 2.0,
+                touching: false,
+            })
+            .collect()
+    }
+
+    fn report(&mut self) {
+        self.timings.frames += 1;
+        if self.timings.frames < REPORT_EVERY {
+            return;
+        }
+        let frames = f64::from(self.timings.frames);
+        wasm_engine::log!(
+            "{} boxes: {:.3}ms per update, {:.3}ms per draw",
+            self.count,
+            self.timings.updating / frames,
+            self.timings.drawing.get() / frames
+        );
+        self.timings = Timings::default();
+    }
+}
+
+#[async_trait(?Send)]
+impl Game for Stress {
+    type Action = NoAction;
+
+    async fn initialize(&mut self) -> Result<Box<dyn Game<Action = NoAction>>> {
+        let count = platform::query_params()
+            .ok()
+            .and_then(|params| params.get("count"))
+            .unwrap_or(self.count);
+        Ok(Box::new(Stress {
+            count,
+            movers: Stress::spawn(count),
+            player: self.player.clone(),
+            timings: Timings::default(),
+        }))
+    }
+
+    fn read_input(
+        &self,
+        _keys: &KeyState,
+        _gamepads: &Gamepads,
+        _touches: &Touches,
+        _input: &mut InputState<NoAction>,
+    ) {
+    }
+
+    fn update(&mut self, _input: &InputState<NoAction>) {
+        let start = platform::now().unwrap_or_default();
+        for mover in self.movers.iter_mut() {
+            mover.rect.x += mover.velocity_x;
+            mover.rect.y += mover.velocity_y;
+            if mover.rect.x < 0.0 || mover.rect.x > SIZE - BOX_SIZE {
+                mover.velocity_x = -mover.velocity_x;
+            }
+            if mover.rect.y < 0.0 || mover.rect.y > SIZE - BOX_SIZE {
+                mover.velocity_y = -mover.velocity_y;
+            }
+            mover.touching = mover.rect.intersects(&self.player);
+        }
+        self.timings.updating += platform::now().unwrap_or_default() - start;
+        self.report();
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let start = platform::now().unwrap_or_default();
+        renderer.clear_all(Some("#000000"));
+        renderer.draw_rect("#ffffff", &self.player);
+        for mover in self.movers.iter() {
+            let color = if mover.touching { "#ff0000" } else { "#00ff00" };
+            renderer.fill_rect(color, &mover.rect);
+        }
+        let drawing = &self.timings.drawing;
+        drawing.set(drawing.get() + platform::now().unwrap_or_default() - start);
+    }
+}
+
+fn main() {
+    wasm_engine::spawn_local(async move {
+        let stress = Stress {
+            count: DEFAULT_COUNT,
+            movers: vec![],
+            player: Rect {
+                x: 250.0,
+                y: 250.0,
+                width: 100.0,
+                height: 100.0,
+            },
+            timings: Timings::default(),
+        };
+
+        GameLoop::builder(stress)
+            .debug(DebugFlags {
+                show_fps: true,
+                ..DebugFlags::default()
+            })
+            .start()
+            .await
+            .expect("Could not start game loop");
+    });
+}
@@ -0,0 +1,94 @@
+// Reports how players get on, without the game's own code knowing where the
+// reports go. The game loop reports the session starting, and the game
+// reports runs, deaths and levels. Every event does nothing by default, so
+// an implementation only fills in the ones it cares about.
+use serde::Serialize;
+
+use crate::platform;
+
+pub trait Analytics {
+    fn session_start(&self) {}
+    // The seed a run's layout was made from
+    fn run_start(&self, _seed: u64) {}
+    // Distance is in meters
+    fn run_end(&self, _score: u32, _distance: u32) {}
+    fn death(&self, _cause: &str) {}
+    fn level_reached(&self, _level: &str) {}
+}
+
+// What the game loop uses unless it's given something else
+pub struct NoAnalytics;
+
+impl Analytics for NoAnalytics {}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+enum Event<'a> {
+    SessionStart,
+    RunStart { seed: u64 },
+    RunEnd { score: u32, distance: u32 },
+    Death { cause: &'a str },
+    LevelReached { level: &'a str },
+}
+
+// Posts each event as JSON to the endpoint with navigator.sendBeacon, so the
+// last one still gets sent when the player closes the tab. Sending is best
+// effort and failures are only logged.
+pub struct BeaconAnalytics {
+    endpoint: String,
+}
+
+impl BeaconAnalytics {
+    pub fn new(endpoint: &str) -> Self {
+        BeaconAnalytics {
+            endpoint: endpoint.to_string(),
+        }
+    }
+
+    fn send(&self, event: Event) {
+        let sent = serde_json::to_string(&event)
+            .map_err(anyhow::Error::from)
+            .and_then(|body| platform::send_beacon(&self.endpoint, &body));
+        if let Err(err) = sent {
+            log!("Could not send {:?} {:#?}", event, err);
+        }
+    }
+}
+
+impl Analytics for BeaconAnalytics {
+    fn session_start(&self) {
+        self.send(Event::SessionStart);
+    }
+
+    fn run_start(&self, seed: u64) {
+        self.send(Event::RunStart { seed });
+    }
+
+    fn run_end(&self, score: u32, distance: u32) {
+        self.send(Event::RunEnd { score, distance });
+    }
+
+    fn death(&self, cause: &str) {
+        self.send(Event::Death { cause });
+    }
+
+    fn level_reached(&self, level: &str) {
+        self.send(Event::LevelReached { level });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_tagged_by_name() {
+        let json = serde_json::to_string(&Event::RunEnd {
+            score: 12,
+            distance: 340,
+        })
+        .expect("Could not serialize event");
+
+        assert_eq!(json, r#"{"event":"runEnd","score":12,"distance":340}"#);
+    }
+}
@@ -0,0 +1,374 @@
+// Sound through the Web Audio API: effects played once, music that loops, and
+// a volume over all of it that the game loop takes from Game::volume.
+//
+// Browsers won't start an AudioContext until the player has done something on
+// the page, so the engine makes it on the first keydown, click or touch and
+// resumes it if the browser suspended it anyway. Sounds asked for before then
+// wait in a queue and play as soon as it's running. Music waits apart from
+// them, so a burst of effects can't push it out of the queue. Loading sounds
+// doesn't have to wait, since a suspended context can still decode them.
+//
+// There's no audio natively yet, so there every sound loads as silence.
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AudioBuffer, AudioBufferSourceNode, AudioContext, AudioContextState, GainNode};
+
+use crate::{browser, platform};
+
+// Past this, the oldest waiting sounds are dropped, since a burst of them all
+// at once on the first touch is worse than missing a few
+const MAX_QUEUED: usize = 8;
+const GESTURES: [&str; 4] = ["keydown", "click", "touchstart", "touchend"];
+
+type Play = Box<dyn FnOnce(&AudioContext)>;
+
+struct Queue<T> {
+    waiting: VecDeque<T>,
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Queue {
+            waiting: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    fn push(&mut self, item: T) {
+        if self.waiting.len() == MAX_QUEUED {
+            self.waiting.pop_front();
+        }
+        self.waiting.push_back(item);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.waiting.pop_front()
+    }
+}
+
+// Everything plays through the master gain, which sets the volume
+#[derive(Clone)]
+struct Output {
+    context: AudioContext,
+    master: GainNode,
+}
+
+struct Audio {
+    output: Option<Output>,
+    queued: Queue<Play>,
+    // Only the latest music asked for, since it would replace any before it
+    music: Option<Play>,
+    // A resume is on its way, so another gesture doesn't need to ask again
+    resuming: bool,
+    volume: f32,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Audio {
+            output: None,
+            queued: Queue::default(),
+            music: None,
+            resuming: false,
+            volume: 1.0,
+        }
+    }
+}
+
+thread_local! {
+    static AUDIO: RefCell<Audio> = RefCell::new(Audio::default());
+}
+
+// A decoded sound, cheap to clone and play as many times as it's wanted
+#[derive(Clone)]
+pub struct Sound {
+    // None when it's silent
+    buffer: Option<AudioBuffer>,
+}
+
+pub async fn load_sound(source: &str) -> Result<Sound> {
+    if cfg!(feature = "native") {
+        return Ok(Sound { buffer: None });
+    }
+    let context = output()?.context;
+    let bytes = browser::fetch_array_buffer(source).await?;
+    let decoding = context
+        .decode_audio_data(&bytes)
+        .map_err(|err| anyhow!("Could not decode {} {:#?}", source, err))?;
+    let buffer = JsFuture::from(decoding)
+        .await
+        .map_err(|err| anyhow!("Could not decode {} {:#?}", source, err))?
+        .dyn_into::<AudioBuffer>()
+        .map_err(|value| anyhow!("Error converting {:#?} to AudioBuffer", value))?;
+    Ok(Sound {
+        buffer: Some(buffer),
+    })
+}
+
+// Once, from the start, over whatever else is playing
+pub fn play(sound: &Sound) {
+    let buffer = match sound.buffer.clone() {
+        Some(buffer) => buffer,
+        None => return,
+    };
+    when_ready(move |context| {
+        if let Err(err) = start(context, &buffer, false) {
+            log!("Could not play sound {:#?}", err);
+        }
+    });
+}
+
+// Over and over until the music is stopped or dropped
+pub fn play_looping(sound: &Sound) -> Music {
+    let music = Music::default();
+    let buffer = match sound.buffer.clone() {
+        Some(buffer) => buffer,
+        None => return music,
+    };
+    let source = Rc::clone(&music.source);
+    let stopped = Rc::clone(&music.stopped);
+    when_music_ready(move |context| {
+        // It was stopped before audio was unlocked
+        if stopped.get() {
+            return;
+        }
+        match start(context, &buffer, true) {
+            Ok(started) => {
+                source.replace(Some(started));
+            }
+            Err(err) => {
+                log!("Could not play music {:#?}", err);
+            }
+        }
+    });
+    music
+}
+
+#[derive(Default)]
+pub struct Music {
+    source: Rc<RefCell<Option<AudioBufferSourceNode>>>,
+    stopped: Rc<Cell<bool>>,
+}
+
+impl Music {
+    pub fn stop(&self) {
+        self.stopped.set(true);
+        if let Some(source) = self.source.borrow_mut().take() {
+            if let Err(err) = source.stop() {
+                log!("Could not stop music {:#?}", err);
+            }
+        }
+    }
+}
+
+impl Drop for Music {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn start(
+    context: &AudioContext,
+    buffer: &AudioBuffer,
+    looping: bool,
+) -> Result<AudioBufferSourceNode> {
+    let master = AUDIO
+        .with(|audio| {
+            audio
+                .borrow()
+                .output
+                .as_ref()
+                .map(|output| output.master.clone())
+        })
+        .ok_or(anyhow!("No audio output"))?;
+    let source = context
+        .create_buffer_source()
+        .map_err(|err| anyhow!("Could not create buffer source {:#?}", err))?;
+    source.set_buffer(Some(buffer));
+    source.set_loop(looping);
+    source
+        .connect_with_audio_node(&master)
+        .map_err(|err| anyhow!("Could not connect buffer source {:#?}", err))?;
+    source
+        .start()
+        .map_err(|err| anyhow!("Could not start buffer source {:#?}", err))?;
+    Ok(source)
+}
+
+// From 0.0 for silent to 1.0 for full volume
+pub fn volume() -> f32 {
+    AUDIO.with(|audio| audio.borrow().volume)
+}
+
+pub fn set_volume(volume: f32) {
+    let volume = volume.clamp(0.0, 1.0);
+    AUDIO.with(|audio| {
+        let mut audio = audio.borrow_mut();
+        if audio.volume == volume {
+            return;
+        }
+        audio.volume = volume;
+        if let Some(output) = audio.output.as_ref() {
+            output.master.gain().set_value(volume);
+        }
+    });
+}
+
+// Made the first time it's needed, and suspended until the player unlocks it
+fn output() -> Result<Output> {
+    AUDIO.with(|audio| {
+        let mut audio = audio.borrow_mut();
+        if let Some(output) = audio.output.as_ref() {
+            return Ok(output.clone());
+        }
+        let context = AudioContext::new()
+            .map_err(|err| anyhow!("Could not create AudioContext {:#?}", err))?;
+        let master = context
+            .create_gain()
+            .map_err(|err| anyhow!("Could not create gain {:#?}", err))?;
+        master.gain().set_value(audio.volume);
+        master
+            .connect_with_audio_node(&context.destination())
+            .map_err(|err| anyhow!("Could not connect gain {:#?}", err))?;
+        let output = Output { context, master };
+        audio.output = Some(output.clone());
+        Ok(output)
+    })
+}
+
+// Whether sounds play now rather than wait for the player
+pub fn is_audio_ready() -> bool {
+    AUDIO.with(|audio| {
+        matches!(&audio.borrow().output,
+            Some(output) if output.context.state() == AudioContextState::Running)
+    })
+}
+
+// Plays right away once audio is unlocked, and until then waits for it
+pub fn when_ready(play: impl FnOnce(&AudioContext) + 'static) {
+    match running_context() {
+        Some(context) => play(&context),
+        None => AUDIO.with(|audio| audio.borrow_mut().queued.push(Box::new(play))),
+    }
+}
+
+// Like when_ready, but waiting in the music's own slot
+fn when_music_ready(play: impl FnOnce(&AudioContext) + 'static) {
+    match running_context() {
+        Some(context) => play(&context),
+        None => AUDIO.with(|audio| audio.borrow_mut().music = Some(Box::new(play))),
+    }
+}
+
+fn running_context() -> Option<AudioContext> {
+    AUDIO.with(|audio| {
+        audio
+            .borrow()
+            .output
+            .as_ref()
+            .map(|output| output.context.clone())
+            .filter(|context| context.state() == AudioContextState::Running)
+    })
+}
+
+// Listens on the whole window, so a tap outside the canvas counts too. The
+// listeners stay for good, since the browser can suspend the context again
+// later, as iOS does when the page goes to the background.
+pub(crate) fn unlock_on_gesture() -> Result<()> {
+    if cfg!(feature = "native") {
+        return Ok(());
+    }
+    for &name in GESTURES.iter() {
+        let listener = browser::closure_wrap(Box::new(move |_event: JsValue| {
+            if let Err(err) = unlock() {
+                log!("Could not start audio {:#?}", err);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        browser::add_window_listener(name, &listener)?;
+        listener.forget();
+    }
+    Ok(())
+}
+
+fn unlock() -> Result<()> {
+    if AUDIO.with(|audio| audio.borrow().resuming) {
+        return Ok(());
+    }
+    let context = output()?.context;
+    if context.state() == AudioContextState::Running {
+        play_queued(&context);
+        return Ok(());
+    }
+
+    let resume = context
+        .resume()
+        .map_err(|err| anyhow!("Could not resume AudioContext {:#?}", err))?;
+    AUDIO.with(|audio| audio.borrow_mut().resuming = true);
+    platform::spawn_local(async move {
+        let resumed = JsFuture::from(resume).await;
+        AUDIO.with(|audio| audio.borrow_mut().resuming = false);
+        match resumed {
+            Ok(_) => play_queued(&context),
+            Err(err) => {
+                log!("Could not resume AudioContext {:#?}", err);
+            }
+        }
+    });
+    Ok(())
+}
+
+// The queue isn't borrowed while a sound plays, so playing one can ask for more
+fn play_queued(context: &AudioContext) {
+    if let Some(play) = AUDIO.with(|audio| audio.borrow_mut().music.take()) {
+        play(context);
+    }
+    while let Some(play) = AUDIO.with(|audio| audio.borrow_mut().queued.pop()) {
+        play(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume_stays_between_silent_and_full() {
+        set_volume(1.5);
+        assert_eq!(volume(), 1.0);
+        set_volume(-1.0);
+        assert_eq!(volume(), 0.0);
+    }
+
+    #[test]
+    fn a_burst_of_effects_leaves_the_music_waiting() {
+        when_music_ready(|_| {});
+        for _ in 0..MAX_QUEUED * 2 {
+            when_ready(|_| {});
+        }
+
+        AUDIO.with(|audio| {
+            let audio = audio.borrow();
+            assert!(audio.music.is_some());
+            assert_eq!(audio.queued.waiting.len(), MAX_QUEUED);
+        });
+    }
+
+    #[test]
+    fn drops_the_oldest_when_too_many_wait() {
+        let mut queue = Queue::default();
+        for sound in 0..MAX_QUEUED + 2 {
+            queue.push(sound);
+        }
+
+        let waiting: Vec<usize> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(waiting, (2..MAX_QUEUED + 2).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,119 @@
+// A small behavior tree for AI. The tree is evaluated from the root every
+// update, against whatever context it's given, usually the character it
+// controls. Nodes don't remember anything between updates, so an action that
+// takes a while keeps its progress in the context and reports Running.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+pub enum Node<C> {
+    // Runs children in order until one doesn't succeed
+    Sequence(Vec<Node<C>>),
+    // Runs children in order until one doesn't fail
+    Selector(Vec<Node<C>>),
+    Condition(Box<dyn Fn(&C) -> bool>),
+    Action(Box<dyn Fn(&mut C) -> Status>),
+}
+
+pub fn sequence<C>(children: Vec<Node<C>>) -> Node<C> {
+    Node::Sequence(children)
+}
+
+pub fn selector<C>(children: Vec<Node<C>>) -> Node<C> {
+    Node::Selector(children)
+}
+
+pub fn condition<C>(check: impl Fn(&C) -> bool + 'static) -> Node<C> {
+    Node::Condition(Box::new(check))
+}
+
+pub fn action<C>(act: impl Fn(&mut C) -> Status + 'static) -> Node<C> {
+    Node::Action(Box::new(act))
+}
+
+impl<C> Node<C> {
+    pub fn tick(&self, context: &mut C) -> Status {
+        match self {
+            Node::Sequence(children) => {
+                for child in children {
+                    match child.tick(context) {
+                        Status::Success => {}
+                        status => return status,
+                    }
+                }
+                Status::Success
+            }
+            Node::Selector(children) => {
+                for child in children {
+                    match child.tick(context) {
+                        Status::Failure => {}
+                        status => return status,
+                    }
+                }
+                Status::Failure
+            }
+            Node::Condition(check) => {
+                if check(context) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Action(act) => act(context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Guard {
+        sees_intruder: bool,
+        chased: u32,
+        patrolled: u32,
+    }
+
+    fn guard() -> Node<Guard> {
+        selector(vec![
+            sequence(vec![
+                condition(|guard: &Guard| guard.sees_intruder),
+                action(|guard: &mut Guard| {
+                    guard.chased += 1;
+                    Status::Running
+                }),
+            ]),
+            action(|guard: &mut Guard| {
+                guard.patrolled += 1;
+                Status::Success
+            }),
+        ])
+    }
+
+    #[test]
+    fn selector_falls_back_when_a_sequence_fails() {
+        let mut context = Guard::default();
+
+        let status = guard().tick(&mut context);
+
+        assert_eq!(status, Status::Success);
+        assert_eq!((context.chased, context.patrolled), (0, 1));
+    }
+
+    #[test]
+    fn a_running_child_stops_the_selector() {
+        let mut context = Guard {
+            sees_intruder: true,
+            ..Guard::default()
+        };
+
+        let status = guard().tick(&mut context);
+
+        assert_eq!(status, Status::Running);
+        assert_eq!((context.chased, context.patrolled), (1, 0));
+    }
+}
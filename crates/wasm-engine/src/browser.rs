@@ -0,0 +1,435 @@
+use anyhow::{anyhow, Result};
+use futures::Future;
+use js_sys::{ArrayBuffer, Function, Object, Promise, Reflect};
+use wasm_bindgen::{
+    closure::WasmClosure, closure::WasmClosureFnOnce, prelude::Closure, JsCast, JsValue,
+};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, Document, Element, Gamepad, HtmlAnchorElement, HtmlCanvasElement,
+    HtmlElement, HtmlImageElement, IdleDeadline, IdleRequestOptions, Response,
+    ServiceWorkerRegistration, Storage, Window,
+};
+
+use crate::{config::QueryParams, engine::Rect};
+
+// Natively there's no browser to ask, so everything that needs one fails
+// here rather than panicking further in
+#[cfg(feature = "native")]
+pub fn window() -> Result<Window> {
+    Err(anyhow!("No Window outside the browser"))
+}
+
+#[cfg(not(feature = "native"))]
+pub fn window() -> Result<Window> {
+    web_sys::window().ok_or(anyhow!("No Window Found"))
+}
+
+pub fn document() -> Result<Document> {
+    window()?.document().ok_or(anyhow!("No Document Found"))
+}
+
+pub fn canvas() -> Result<HtmlCanvasElement> {
+    canvas_with_id("canvas")
+}
+
+pub fn canvas_with_id(id: &str) -> Result<HtmlCanvasElement> {
+    document()?
+        .get_element_by_id(id)
+        .ok_or(anyhow!("No Canvas Element found with ID '{}'", id))?
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))
+}
+
+// For the test canvas, since games get theirs through open_window
+#[cfg(any(test, feature = "testing"))]
+pub fn context() -> Result<CanvasRenderingContext2d> {
+    context_for(&canvas()?)
+}
+
+pub fn context_for(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d> {
+    canvas
+        .get_context("2d")
+        .map_err(|js_value| anyhow!("Error getting 2d context {:#?}", js_value))?
+        .ok_or(anyhow!("No 2d context found"))?
+        .dyn_into::<web_sys::CanvasRenderingContext2d>()
+        .map_err(|element| {
+            anyhow!(
+                "Error converting {:#?} to CanvasRenderingContext2d",
+                element
+            )
+        })
+}
+
+// Part of an image as RGBA bytes, row by row, drawn on a canvas of its own
+// to read them back. The image has to come from the page's own origin, or the
+// browser won't let its pixels be read.
+pub fn image_pixels(image: &HtmlImageElement, frame: &Rect) -> Result<Vec<u8>> {
+    let canvas = document()?
+        .create_element("canvas")
+        .map_err(|err| anyhow!("Error creating canvas {:#?}", err))?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))?;
+    canvas.set_width(frame.width as u32);
+    canvas.set_height(frame.height as u32);
+    let context = context_for(&canvas)?;
+    context
+        .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+            image,
+            frame.x.into(),
+            frame.y.into(),
+            frame.width.into(),
+            frame.height.into(),
+            0.0,
+            0.0,
+            frame.width.into(),
+            frame.height.into(),
+        )
+        .map_err(|err| anyhow!("Error drawing image {:#?}", err))?;
+    let image_data = context
+        .get_image_data(0.0, 0.0, frame.width.into(), frame.height.into())
+        .map_err(|err| anyhow!("Error reading image pixels {:#?}", err))?;
+    Ok(image_data.data().0)
+}
+
+// The canvas as it was last drawn, as a PNG data URL
+pub fn canvas_to_png() -> Result<String> {
+    canvas()?
+        .to_data_url_with_type("image/png")
+        .map_err(|err| anyhow!("Error capturing canvas {:#?}", err))
+}
+
+// Saves the URL as a file, by clicking a link to it that's never added to the
+// page
+pub fn download(url: &str, filename: &str) -> Result<()> {
+    let link = document()?
+        .create_element("a")
+        .map_err(|err| anyhow!("Error creating link {:#?}", err))?
+        .dyn_into::<HtmlAnchorElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlAnchorElement", element))?;
+    link.set_href(url);
+    link.set_download(filename);
+    link.click();
+    Ok(())
+}
+
+pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
+    document()?
+        .get_element_by_id(id)
+        .ok_or(anyhow!("No element found with ID '{}'", id))?
+        .dyn_into::<HtmlElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlElement", element))
+}
+
+fn find_ui() -> Result<Element> {
+    document()?
+        .get_element_by_id("ui")
+        .ok_or(anyhow!("UI element not found"))
+}
+
+pub fn draw_ui(html: &str) -> Result<()> {
+    find_ui()?
+        .insert_adjacent_html("afterbegin", html)
+        .map_err(|err| anyhow!("Could not insert html {:#?}", err))
+}
+
+// Also gives the canvas back the keyboard focus, which a clicked button takes
+pub fn hide_ui() -> Result<()> {
+    let ui = find_ui()?;
+    while let Some(child) = ui.first_child() {
+        ui.remove_child(&child)
+            .map_err(|err| anyhow!("Failed to remove child {:#?}", err))?;
+    }
+    canvas()?
+        .focus()
+        .map_err(|err| anyhow!("Could not set focus to canvas {:#?}", err))
+}
+
+// navigator.share and navigator.clipboard are still unstable in web-sys, so
+// they're looked up and called by name
+fn call_method(target: &JsValue, name: &str, argument: &JsValue) -> Result<Option<Promise>> {
+    let method = match Reflect::get(target, &JsValue::from_str(name))
+        .ok()
+        .and_then(|method| method.dyn_into::<Function>().ok())
+    {
+        Some(method) => method,
+        None => return Ok(None),
+    };
+    method
+        .call1(target, argument)
+        .map_err(|err| anyhow!("Error calling {} {:#?}", name, err))?
+        .dyn_into::<Promise>()
+        .map(Some)
+        .map_err(|value| anyhow!("{} didn't return a promise {:#?}", name, value))
+}
+
+// None when the browser has no share sheet
+pub fn share(title: &str, text: &str, url: &str) -> Result<Option<Promise>> {
+    let data = Object::new();
+    for (key, value) in [("title", title), ("text", text), ("url", url)].iter() {
+        Reflect::set(&data, &JsValue::from_str(key), &JsValue::from_str(value))
+            .map_err(|err| anyhow!("Error building share data {:#?}", err))?;
+    }
+    call_method(&window()?.navigator(), "share", &data)
+}
+
+pub fn write_to_clipboard(text: &str) -> Result<Promise> {
+    let clipboard = Reflect::get(&window()?.navigator(), &JsValue::from_str("clipboard"))
+        .map_err(|err| anyhow!("Error getting clipboard {:#?}", err))?;
+    call_method(&clipboard, "writeText", &JsValue::from_str(text))?
+        .ok_or(anyhow!("No clipboard found"))
+}
+
+// Keeps the screen from dimming and locking until the sentinel the promise
+// resolves to is released. navigator.wakeLock is unstable in web-sys too, and
+// None when the browser has none.
+pub fn request_wake_lock() -> Result<Option<Promise>> {
+    let wake_lock = Reflect::get(&window()?.navigator(), &JsValue::from_str("wakeLock"))
+        .map_err(|err| anyhow!("Error getting wake lock {:#?}", err))?;
+    if wake_lock.is_undefined() {
+        return Ok(None);
+    }
+    call_method(&wake_lock, "request", &JsValue::from_str("screen"))
+}
+
+pub fn release_wake_lock(sentinel: &JsValue) -> Result<Option<Promise>> {
+    call_method(sentinel, "release", &JsValue::UNDEFINED)
+}
+
+// The page's address without the hash
+pub fn page_url() -> Result<String> {
+    let location = window()?.location();
+    let origin = location
+        .origin()
+        .map_err(|err| anyhow!("Error getting location origin {:#?}", err))?;
+    let path = location
+        .pathname()
+        .map_err(|err| anyhow!("Error getting location path {:#?}", err))?;
+    Ok(format!("{}{}", origin, path))
+}
+
+pub fn is_online() -> Result<bool> {
+    Ok(window()?.navigator().on_line())
+}
+
+// Sends a message to the page's service worker once it's active. This waits
+// forever if the page never registers one.
+pub async fn post_message(message: &JsValue) -> Result<()> {
+    let navigator = window()?.navigator();
+    if !Reflect::has(&navigator, &JsValue::from_str("serviceWorker")).unwrap_or(false) {
+        return Err(anyhow!("No service worker support"));
+    }
+    let ready = navigator
+        .service_worker()
+        .ready()
+        .map_err(|err| anyhow!("Error getting service worker {:#?}", err))?;
+    let registration: ServiceWorkerRegistration = JsFuture::from(ready)
+        .await
+        .map_err(|err| anyhow!("Error waiting for service worker {:#?}", err))?
+        .dyn_into()
+        .map_err(|err| anyhow!("Error converting {:#?} to ServiceWorkerRegistration", err))?;
+    registration
+        .active()
+        .ok_or_else(|| anyhow!("No active service worker"))?
+        .post_message(message)
+        .map_err(|err| anyhow!("Error posting message {:#?}", err))
+}
+
+pub fn query_params() -> Result<QueryParams> {
+    let search = window()?
+        .location()
+        .search()
+        .map_err(|err| anyhow!("Error getting location search {:#?}", err))?;
+    Ok(QueryParams::parse(&search))
+}
+
+pub fn location_hash() -> Result<String> {
+    window()?
+        .location()
+        .hash()
+        .map_err(|err| anyhow!("Error getting location hash {:#?}", err))
+}
+
+pub fn language() -> Result<String> {
+    window()?
+        .navigator()
+        .language()
+        .ok_or_else(|| anyhow!("No language found"))
+}
+
+// The connected gamepads. The browser leaves gaps for disconnected ones,
+// which are skipped.
+pub fn gamepads() -> Result<Vec<Gamepad>> {
+    Ok(window()?
+        .navigator()
+        .get_gamepads()
+        .map_err(|err| anyhow!("Error getting gamepads {:#?}", err))?
+        .iter()
+        .filter_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+        .collect())
+}
+
+// Today's date in UTC as (year, month, day), with January as month 1
+pub fn utc_date() -> (u32, u32, u32) {
+    let date = js_sys::Date::new_0();
+    (
+        date.get_utc_full_year(),
+        date.get_utc_month() + 1,
+        date.get_utc_date(),
+    )
+}
+
+pub fn local_storage() -> Result<Storage> {
+    window()?
+        .local_storage()
+        .map_err(|err| anyhow!("Error getting local storage {:#?}", err))?
+        .ok_or(anyhow!("No Local Storage found"))
+}
+
+pub fn get_item(key: &str) -> Result<Option<String>> {
+    local_storage()?
+        .get_item(key)
+        .map_err(|err| anyhow!("Error reading {} from local storage {:#?}", key, err))
+}
+
+pub fn set_item(key: &str, value: &str) -> Result<()> {
+    local_storage()?
+        .set_item(key, value)
+        .map_err(|err| anyhow!("Error writing {} to local storage {:#?}", key, err))
+}
+
+// Queues a POST that the browser finishes even if the page is closing
+pub fn send_beacon(url: &str, body: &str) -> Result<()> {
+    let queued = window()?
+        .navigator()
+        .send_beacon_with_opt_str(url, Some(body))
+        .map_err(|err| anyhow!("Error sending beacon to {} {:#?}", url, err))?;
+    if queued {
+        Ok(())
+    } else {
+        Err(anyhow!("Beacon to {} was not queued", url))
+    }
+}
+
+pub fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+pub async fn fetch_with_str(resource: &str) -> Result<JsValue> {
+    JsFuture::from(window()?.fetch_with_str(resource))
+        .await
+        .map_err(|err| anyhow!("error fetching {:#?}", err))
+}
+
+pub async fn fetch_json(json_path: &str) -> Result<JsValue> {
+    let resp_value = fetch_with_str(json_path).await?;
+    let resp: Response = resp_value
+        .dyn_into()
+        .map_err(|element| anyhow!("Error converting {:#?} to Response", element))?;
+
+    JsFuture::from(
+        resp.json()
+            .map_err(|err| anyhow!("Could not get JSON from response {:#?}", err))?,
+    )
+    .await
+    .map_err(|err| anyhow!("error fetching json {:#?}", err))
+}
+
+pub async fn fetch_array_buffer(path: &str) -> Result<ArrayBuffer> {
+    let resp: Response = fetch_with_str(path)
+        .await?
+        .dyn_into()
+        .map_err(|element| anyhow!("Error converting {:#?} to Response", element))?;
+
+    JsFuture::from(
+        resp.array_buffer()
+            .map_err(|err| anyhow!("Could not get the bytes of {} {:#?}", path, err))?,
+    )
+    .await
+    .map_err(|err| anyhow!("error fetching {} {:#?}", path, err))?
+    .dyn_into()
+    .map_err(|value| anyhow!("Error converting {:#?} to ArrayBuffer", value))
+}
+
+// Where JavaScript is right now, including the wasm frames in browsers that
+// name them
+pub fn stack_trace() -> Option<String> {
+    Reflect::get(&js_sys::Error::new(""), &JsValue::from_str("stack"))
+        .ok()
+        .and_then(|stack| stack.as_string())
+}
+
+pub fn new_image() -> Result<HtmlImageElement> {
+    HtmlImageElement::new().map_err(|err| anyhow!("Could not create HtmlImageElement: {:#?}", err))
+}
+
+pub type LoopClosure = Closure<dyn FnMut(f64)>;
+pub fn create_raf_closure(f: impl FnMut(f64) + 'static) -> LoopClosure {
+    closure_wrap(Box::new(f))
+}
+
+// Returns the id to cancel the frame with
+pub fn request_animation_frame(callback: &LoopClosure) -> Result<i32> {
+    window()?
+        .request_animation_frame(callback.as_ref().unchecked_ref())
+        .map_err(|err| anyhow!("Cannot request animation frame {:#?}", err))
+}
+
+pub fn cancel_animation_frame(id: i32) -> Result<()> {
+    window()?
+        .cancel_animation_frame(id)
+        .map_err(|err| anyhow!("Cannot cancel animation frame {:#?}", err))
+}
+
+pub type IdleClosure = Closure<dyn FnMut(IdleDeadline)>;
+pub fn create_idle_closure(f: impl FnMut(IdleDeadline) + 'static) -> IdleClosure {
+    closure_wrap(Box::new(f))
+}
+
+// Calls back when the browser has time to spare between frames, or after
+// timeout milliseconds whether it does or not. Safari has no
+// requestIdleCallback, so there this fails and the caller decides what to do
+// instead.
+pub fn request_idle_callback(callback: &IdleClosure, timeout: u32) -> Result<u32> {
+    let window = window()?;
+    if !Reflect::has(&window, &JsValue::from_str("requestIdleCallback")).unwrap_or(false) {
+        return Err(anyhow!("No requestIdleCallback support"));
+    }
+    window
+        .request_idle_callback_with_options(
+            callback.as_ref().unchecked_ref(),
+            IdleRequestOptions::new().timeout(timeout),
+        )
+        .map_err(|err| anyhow!("Cannot request idle callback {:#?}", err))
+}
+
+pub fn closure_once<F, A, R>(fn_once: F) -> Closure<F::FnMut>
+where
+    F: 'static + WasmClosureFnOnce<A, R>,
+{
+    Closure::once(fn_once)
+}
+
+pub fn closure_wrap<T: WasmClosure + ?Sized>(data: Box<T>) -> Closure<T> {
+    Closure::wrap(data)
+}
+
+pub fn add_window_listener(event: &str, listener: &Closure<dyn FnMut(JsValue)>) -> Result<()> {
+    window()?
+        .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+        .map_err(|err| anyhow!("Error adding {} listener {:#?}", event, err))
+}
+
+pub fn device_pixel_ratio() -> Result<f64> {
+    Ok(window()?.device_pixel_ratio())
+}
+
+pub fn now() -> Result<f64> {
+    Ok(window()?
+        .performance()
+        .ok_or(anyhow!("Performance object not found"))?
+        .now())
+}
@@ -0,0 +1,162 @@
+// A chat box drawn over the canvas for players waiting between runs. It keeps
+// the last few messages with who sent them and stops any one player flooding
+// it. Messages are serializable so they can go over whatever connects the
+// players; receive is for the ones that arrive.
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Point, Rect, Renderer};
+
+// Longer messages are cut short, in characters
+const MAX_LENGTH: usize = 120;
+const HISTORY: usize = 8;
+// At most this many messages in this many milliseconds
+const RATE_LIMIT: usize = 3;
+const RATE_WINDOW: f64 = 5000.0;
+const LINE_HEIGHT: i16 = 20;
+const TEXT_SIZE: u8 = 14;
+const PADDING: i16 = 8;
+const BACKGROUND_COLOR: &str = "rgba(0, 0, 0, 0.5)";
+const TEXT_COLOR: &str = "#ffffff";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub sender: String,
+    pub text: String,
+}
+
+impl ChatMessage {
+    // None when there's nothing left to send once it's trimmed
+    fn new(sender: &str, text: &str) -> Option<Self> {
+        let text: String = text.trim().chars().take(MAX_LENGTH).collect();
+        if text.is_empty() {
+            return None;
+        }
+        Some(ChatMessage {
+            sender: sender.to_string(),
+            text,
+        })
+    }
+}
+
+pub struct Chat {
+    name: String,
+    messages: VecDeque<ChatMessage>,
+    // When the local player's recent messages were sent
+    sent: VecDeque<f64>,
+}
+
+impl Chat {
+    pub fn new(name: &str) -> Self {
+        Chat {
+            name: name.to_string(),
+            messages: VecDeque::new(),
+            sent: VecDeque::new(),
+        }
+    }
+
+    // Now is in milliseconds, from platform::now. Returns the message to
+    // pass on to the other players.
+    pub fn send(&mut self, text: &str, now: f64) -> Result<ChatMessage> {
+        while matches!(self.sent.front(), Some(sent) if now - sent >= RATE_WINDOW) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= RATE_LIMIT {
+            return Err(anyhow!("Sending messages too quickly"));
+        }
+        let message = ChatMessage::new(&self.name, text).ok_or(anyhow!("Message is empty"))?;
+        self.sent.push_back(now);
+        self.add(message.clone());
+        Ok(message)
+    }
+
+    // Also trims what arrives, since the other end can't be trusted to
+    pub fn receive(&mut self, message: ChatMessage) {
+        if let Some(message) = ChatMessage::new(&message.sender, &message.text) {
+            self.add(message);
+        }
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.messages.iter()
+    }
+
+    fn add(&mut self, message: ChatMessage) {
+        if self.messages.len() == HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    // Oldest at the top, from the top left corner given
+    pub fn draw(&self, renderer: &Renderer, position: &Point, width: f32) {
+        if self.messages.is_empty() {
+            return;
+        }
+        renderer.fill_rect(
+            BACKGROUND_COLOR,
+            &Rect {
+                x: position.x.into(),
+                y: position.y.into(),
+                width,
+                height: (self.messages.len() as i16 * LINE_HEIGHT + PADDING) as f32,
+            },
+        );
+        for (line, message) in self.messages.iter().enumerate() {
+            renderer.draw_text(
+                &format!("{}: {}", message.sender, message.text),
+                &Point {
+                    x: position.x + PADDING,
+                    y: position.y + (line as i16 + 1) * LINE_HEIGHT,
+                },
+                TEXT_COLOR,
+                TEXT_SIZE,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_how_quickly_a_player_can_send() {
+        let mut chat = Chat::new("rhb");
+        for second in 0..3 {
+            chat.send("woof", f64::from(second) * 1000.0).unwrap();
+        }
+
+        assert!(chat.send("woof", 3000.0).is_err());
+        assert!(chat.send("woof", 5000.0).is_ok());
+    }
+
+    #[test]
+    fn trims_messages_and_drops_empty_ones() {
+        let mut chat = Chat::new("rhb");
+        chat.receive(ChatMessage {
+            sender: "dog".to_string(),
+            text: format!("  {}  ", "a".repeat(200)),
+        });
+
+        assert!(chat.send("   ", 0.0).is_err());
+        let texts: Vec<usize> = chat.messages().map(|message| message.text.len()).collect();
+        assert_eq!(texts, vec![MAX_LENGTH]);
+    }
+
+    #[test]
+    fn keeps_only_the_latest_messages() {
+        let mut chat = Chat::new("rhb");
+        for text in 0..HISTORY + 2 {
+            chat.receive(ChatMessage {
+                sender: "dog".to_string(),
+                text: text.to_string(),
+            });
+        }
+
+        assert_eq!(chat.messages().count(), HISTORY);
+        assert_eq!(chat.messages().next().unwrap().text, "2");
+    }
+}
@@ -0,0 +1,174 @@
+// Streams a long authored level in fixed-width chunks, so only the part near
+// the camera is ever in memory. Each chunk is its own JSON file, named
+// <prefix><index>.json, and what's in it is up to the game, like a wave's
+// kind. The game spawns a chunk's things when it enters and drops them when
+// it leaves; the next chunk ahead is fetched early so it's usually ready by
+// the time the camera gets there, though only when the browser has time to
+// spare.
+use std::{cell::RefCell, collections::HashMap, ops::Range, rc::Rc};
+
+use serde::de::DeserializeOwned;
+
+use crate::{idle, platform};
+
+pub enum ChunkEvent<T> {
+    // Origin is the chunk's left edge in level coordinates
+    Entered { index: usize, origin: f32, data: T },
+    Left { index: usize },
+}
+
+// Which chunks are in range of the camera. It knows nothing about loading,
+// only which indexes come and go as the camera moves.
+#[derive(Debug, Clone)]
+pub struct ChunkWindow {
+    width: f32,
+    // Chunks kept either side of the one the camera is in
+    radius: usize,
+    count: usize,
+}
+
+impl ChunkWindow {
+    pub fn new(width: f32, radius: usize, count: usize) -> Self {
+        ChunkWindow {
+            width,
+            radius,
+            count,
+        }
+    }
+
+    pub fn origin(&self, index: usize) -> f32 {
+        index as f32 * self.width
+    }
+
+    // Clamped to the level, so it's empty once the camera is past the end
+    pub fn around(&self, camera_x: f32) -> Range<usize> {
+        let current = (camera_x.max(0.0) / self.width) as usize;
+        let start = current.saturating_sub(self.radius).min(self.count);
+        let end = (current + self.radius + 1).min(self.count);
+        start..end
+    }
+}
+
+type Loaded<T> = Rc<RefCell<HashMap<usize, T>>>;
+
+pub struct ChunkStream<T> {
+    prefix: String,
+    window: ChunkWindow,
+    // Chunks the game has been told about, in index order
+    active: Vec<usize>,
+    // Fetched but not yet entered. Anything that falls out of reach is
+    // dropped, so this never holds more than the window and the prefetch.
+    loaded: Loaded<T>,
+    requested: Vec<usize>,
+}
+
+impl<T: DeserializeOwned + 'static> ChunkStream<T> {
+    pub fn new(prefix: &str, window: ChunkWindow) -> Self {
+        ChunkStream {
+            prefix: prefix.to_string(),
+            window,
+            active: vec![],
+            loaded: Rc::new(RefCell::new(HashMap::new())),
+            requested: vec![],
+        }
+    }
+
+    // Call once per update. Chunks that haven't arrived yet enter on a later
+    // call, so the game should expect them late rather than in order.
+    pub fn update(&mut self, camera_x: f32) -> Vec<ChunkEvent<T>> {
+        let wanted = self.window.around(camera_x);
+        let mut events = vec![];
+
+        self.active.retain(|index| {
+            let keep = wanted.contains(index);
+            if !keep {
+                events.push(ChunkEvent::Left { index: *index });
+            }
+            keep
+        });
+
+        let prefetch = wanted.end..(wanted.end + 1).min(self.window.count);
+        let in_reach = wanted.start..prefetch.end;
+        self.loaded
+            .borrow_mut()
+            .retain(|index, _| in_reach.contains(index));
+        self.requested.retain(|index| in_reach.contains(index));
+
+        for index in wanted.clone() {
+            if self.active.contains(&index) {
+                continue;
+            }
+            let data = self.loaded.borrow_mut().remove(&index);
+            match data {
+                Some(data) => {
+                    self.active.push(index);
+                    events.push(ChunkEvent::Entered {
+                        index,
+                        origin: self.window.origin(index),
+                        data,
+                    });
+                }
+                None => self.request(index, true),
+            }
+        }
+        self.active.sort_unstable();
+
+        for index in prefetch {
+            self.request(index, false);
+        }
+        events
+    }
+
+    // Anything not urgent waits for an idle period
+    fn request(&mut self, index: usize, urgent: bool) {
+        if self.requested.contains(&index) {
+            return;
+        }
+        self.requested.push(index);
+        let path = format!("{}{}.json", self.prefix, index);
+        let loaded = Rc::clone(&self.loaded);
+        let fetch = async move {
+            match platform::load_json::<T>(&path).await {
+                Ok(data) => {
+                    loaded.borrow_mut().insert(index, data);
+                }
+                Err(err) => {
+                    log!("Error loading chunk {} {:#?}", path, err);
+                }
+            }
+        };
+        if urgent {
+            platform::spawn_local(fetch);
+        } else {
+            idle::run_when_idle(move || platform::spawn_local(fetch));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_chunks_either_side_of_the_camera() {
+        let window = ChunkWindow::new(600.0, 1, 10);
+
+        assert_eq!(window.around(0.0), 0..2);
+        assert_eq!(window.around(1300.0), 1..4);
+        assert_eq!(window.around(5500.0), 8..10);
+    }
+
+    #[test]
+    fn is_empty_past_the_end_of_the_level() {
+        let window = ChunkWindow::new(600.0, 1, 3);
+
+        assert!(window.around(6000.0).is_empty());
+    }
+
+    #[test]
+    fn chunks_start_a_width_apart() {
+        let window = ChunkWindow::new(600.0, 1, 3);
+
+        assert_eq!(window.origin(2), 1200.0);
+    }
+}
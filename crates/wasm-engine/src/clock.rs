@@ -0,0 +1,112 @@
+// Works out how far another machine's clock is from ours, so clients racing
+// the same ghost or sharing a session can agree on which tick it is. It's
+// the usual ping exchange: send the local time, have the other end reply with
+// its own, and assume the reply was made halfway through the round trip. The
+// quickest round trips are the least skewed by one-way delays, so only those
+// go into the estimate.
+//
+// This doesn't send anything itself. Whatever carries the game's messages
+// stamps its pings with platform::now and hands each reply to pong_received.
+use std::collections::VecDeque;
+
+// How many of the latest exchanges to choose from
+const SAMPLES: usize = 8;
+// How many of the quickest of those to average
+const BEST: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    round_trip: f64,
+    offset: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct ClockSync {
+    samples: VecDeque<Sample>,
+}
+
+impl ClockSync {
+    // The reply to a ping sent at sent, stamped remote by the other end and
+    // arriving back at received
+    pub fn pong_received(&mut self, sent: f64, remote: f64, received: f64) {
+        if received < sent {
+            return;
+        }
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            round_trip: received - sent,
+            offset: remote - (sent + received) / 2.0,
+        });
+    }
+
+    pub fn is_synced(&self) -> bool {
+        !self.samples.is_empty()
+    }
+
+    // Add to local time to get the other end's. None until a pong arrives.
+    pub fn offset(&self) -> Option<f64> {
+        let mut quickest: Vec<Sample> = self.samples.iter().copied().collect();
+        quickest.sort_by(|a, b| a.round_trip.partial_cmp(&b.round_trip).unwrap());
+        quickest.truncate(BEST);
+        match quickest.len() {
+            0 => None,
+            count => Some(quickest.iter().map(|sample| sample.offset).sum::<f64>() / count as f64),
+        }
+    }
+
+    pub fn round_trip(&self) -> Option<f64> {
+        self.samples
+            .iter()
+            .map(|sample| sample.round_trip)
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+    }
+
+    pub fn remote_time(&self, local: f64) -> Option<f64> {
+        self.offset().map(|offset| local + offset)
+    }
+
+    // The tick the other end is on, for a simulation that started at start
+    // by its clock and steps every timestep milliseconds
+    pub fn remote_tick(&self, local: f64, start: f64, timestep: f32) -> Option<u64> {
+        self.remote_time(local)
+            .map(|remote| ((remote - start).max(0.0) / f64::from(timestep)) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assumes_the_reply_came_halfway_through_the_round_trip() {
+        let mut sync = ClockSync::default();
+        sync.pong_received(1000.0, 5050.0, 1100.0);
+
+        assert_eq!(sync.offset(), Some(4000.0));
+        assert_eq!(sync.round_trip(), Some(100.0));
+    }
+
+    #[test]
+    fn trusts_the_quickest_round_trips() {
+        let mut sync = ClockSync::default();
+        sync.pong_received(0.0, 4010.0, 20.0);
+        sync.pong_received(100.0, 4110.0, 120.0);
+        sync.pong_received(200.0, 4210.0, 220.0);
+        // Held up on the way back, so it looks like the clocks are further
+        // apart than they are
+        sync.pong_received(300.0, 4310.0, 900.0);
+
+        assert_eq!(sync.offset(), Some(4000.0));
+    }
+
+    #[test]
+    fn counts_ticks_on_the_other_clock() {
+        let mut sync = ClockSync::default();
+        sync.pong_received(0.0, 1010.0, 20.0);
+
+        assert_eq!(sync.remote_tick(500.0, 1000.0, 50.0), Some(10));
+        assert_eq!(ClockSync::default().remote_tick(500.0, 1000.0, 50.0), None);
+    }
+}
@@ -0,0 +1,157 @@
+// Settings read from the page's query string, so a configuration can be
+// shared as a URL, e.g. ?debug&seed=42&level=arena&muted. The game loop
+// builder picks out the ones it knows, and hands the rest of what it found to
+// the game as LaunchOptions.
+use std::{collections::HashMap, str::FromStr};
+
+#[derive(Debug, Default, Clone)]
+pub struct QueryParams {
+    params: HashMap<String, String>,
+}
+
+impl QueryParams {
+    // Takes the query string with or without its leading ?
+    pub fn parse(query: &str) -> Self {
+        let params = query
+            .trim_start_matches('?')
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                let key = decode(parts.next().unwrap_or_default());
+                let value = decode(parts.next().unwrap_or_default());
+                (key, value)
+            })
+            .collect();
+        QueryParams { params }
+    }
+
+    // None when the parameter is missing or doesn't parse as a T
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.params.get(key).and_then(|value| value.parse().ok())
+    }
+
+    // A flag is on when it's given without a value, as in ?muted, or with
+    // anything but 0 or false
+    pub fn flag(&self, key: &str) -> bool {
+        match self.params.get(key).map(String::as_str) {
+            Some("0") | Some("false") | None => false,
+            Some(_) => true,
+        }
+    }
+}
+
+// Undoes the form encoding browsers use in query strings. Anything that isn't
+// a valid escape is left as it was.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = match bytes[index] {
+            b'%' => text
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match (bytes[index], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', None) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, None) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// What a game might want to start differently. The engine doesn't use these
+// itself; see Game::configure.
+#[derive(Debug, Default, Clone)]
+pub struct LaunchOptions {
+    // ?seed=42, for a game with a random layout
+    pub seed: Option<u64>,
+    // ?level=arena, where to start
+    pub start_level: Option<String>,
+    // ?muted
+    pub muted: bool,
+    // ?spectate, to watch a run rather than play. It's the other player's
+    // live with ?server, and otherwise the best run saved on this machine.
+    pub spectate: bool,
+    // ?difficulty=2, for a game with difficulty levels
+    pub difficulty: Option<u8>,
+    // ?coop, for a second player on the same keyboard, or on another
+    // machine with ?server
+    pub coop: bool,
+    // ?player=2, which player this end is in co-op over a server. Both ends
+    // need the same ?seed.
+    pub player: Option<u8>,
+    // ?clips, to keep recording the canvas so the last few seconds can be
+    // saved as a video
+    pub clips: bool,
+    // ?server=wss://example.com/room, a relay to race, watch or play co-op
+    // with someone else through
+    pub server: Option<String>,
+    // ?name=Rhb, who the other player sees chat from
+    pub name: Option<String>,
+}
+
+impl LaunchOptions {
+    pub fn from_query(params: &QueryParams) -> Self {
+        LaunchOptions {
+            seed: params.get("seed"),
+            start_level: params.get("level"),
+            muted: params.flag("muted"),
+            spectate: params.flag("spectate"),
+            difficulty: params.get("difficulty"),
+            coop: params.flag("coop"),
+            player: params.get("player"),
+            clips: params.flag("clips"),
+            server: params.get("server"),
+            name: params.get("name"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_typed_values_and_flags() {
+        let params = QueryParams::parse("?seed=42&debug&muted=0&level=boss%20arena");
+
+        assert_eq!(params.get::<u64>("seed"), Some(42));
+        assert!(params.flag("debug"));
+        assert!(!params.flag("muted"));
+        assert_eq!(params.get::<String>("level").as_deref(), Some("boss arena"));
+    }
+
+    #[test]
+    fn a_value_of_the_wrong_type_is_missing() {
+        let params = QueryParams::parse("seed=abc");
+
+        assert_eq!(params.get::<u64>("seed"), None);
+    }
+
+    #[test]
+    fn launch_options_come_from_the_query() {
+        let options = LaunchOptions::from_query(&QueryParams::parse("seed=7&muted"));
+
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.start_level, None);
+        assert!(options.muted);
+        assert!(!options.spectate);
+        assert_eq!(options.difficulty, None);
+        assert!(!options.coop);
+        assert!(!options.clips);
+        assert_eq!(options.server, None);
+    }
+}
@@ -0,0 +1,172 @@
+// Sequences that play out over many frames, like a cutscene, written as
+// straight-line async code. They run on the game loop rather than the browser's
+// executor: every future is polled once per fixed update, so waiting is
+// measured in game time and stops when the game does.
+//
+// A coroutine can't hold on to the game across an await, so it changes things
+// through its Script, and the updates are handed back by tick() for the game
+// to apply, the same as the scheduler and tweens.
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::task::noop_waker;
+
+use crate::{engine::FRAME_SIZE, tween::Update};
+
+// A coroutine's handle on the game loop
+pub struct Script<C> {
+    timestep: f32,
+    clock: Rc<Cell<u64>>,
+    updates: Rc<RefCell<Vec<Update<C>>>>,
+}
+
+impl<C> Clone for Script<C> {
+    fn clone(&self) -> Self {
+        Script {
+            timestep: self.timestep,
+            clock: Rc::clone(&self.clock),
+            updates: Rc::clone(&self.updates),
+        }
+    }
+}
+
+impl<C: 'static> Script<C> {
+    // Queues a change to the game, applied at the end of this update
+    pub fn run(&self, update: impl FnOnce(&mut C) + 'static) {
+        self.updates.borrow_mut().push(Box::new(update));
+    }
+
+    pub fn next_frame(&self) -> WaitUntil {
+        self.frames(1)
+    }
+
+    pub fn frames(&self, frames: u64) -> WaitUntil {
+        WaitUntil {
+            clock: Rc::clone(&self.clock),
+            tick: self.clock.get() + frames,
+        }
+    }
+
+    // Rounded to the nearest update
+    pub fn seconds(&self, seconds: f32) -> WaitUntil {
+        self.frames((seconds * 1000.0 / self.timestep).round() as u64)
+    }
+}
+
+pub struct WaitUntil {
+    clock: Rc<Cell<u64>>,
+    tick: u64,
+}
+
+impl Future for WaitUntil {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.clock.get() >= self.tick {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+pub struct Coroutines<C> {
+    script: Script<C>,
+    running: Vec<Pin<Box<dyn Future<Output = ()>>>>,
+}
+
+impl<C: 'static> Coroutines<C> {
+    // The timestep is the length of one update in milliseconds
+    pub fn new(timestep: f32) -> Self {
+        Coroutines {
+            script: Script {
+                timestep,
+                clock: Rc::new(Cell::new(0)),
+                updates: Rc::new(RefCell::new(vec![])),
+            },
+            running: vec![],
+        }
+    }
+
+    pub fn script(&self) -> Script<C> {
+        self.script.clone()
+    }
+
+    // Starts on the next tick
+    pub fn spawn(&mut self, coroutine: impl Future<Output = ()> + 'static) {
+        self.running.push(Box::pin(coroutine));
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    // Moves the clock on one update and polls every coroutine, then returns
+    // the updates they queued
+    pub fn tick(&mut self) -> Vec<Update<C>> {
+        self.script.clock.set(self.script.clock.get() + 1);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut running = vec![];
+        for mut coroutine in self.running.drain(..) {
+            if coroutine.as_mut().poll(&mut cx).is_pending() {
+                running.push(coroutine);
+            }
+        }
+        self.running = running;
+        self.script.updates.borrow_mut().drain(..).collect()
+    }
+
+    // Ticks and applies the updates, for coroutines that aren't part of the
+    // context
+    pub fn run(&mut self, context: &mut C) {
+        for update in self.tick() {
+            update(context);
+        }
+    }
+}
+
+impl<C: 'static> Default for Coroutines<C> {
+    fn default() -> Self {
+        Coroutines::new(FRAME_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_then_carries_on() {
+        let mut coroutines = Coroutines::new(10.0);
+        let script = coroutines.script();
+        let mut log: Vec<&str> = vec![];
+
+        coroutines.spawn(async move {
+            script.run(|log: &mut Vec<&str>| log.push("start"));
+            script.seconds(0.03).await;
+            script.run(|log| log.push("waited"));
+            script.next_frame().await;
+            script.run(|log| log.push("done"));
+        });
+
+        coroutines.run(&mut log);
+        assert_eq!(log, vec!["start"]);
+
+        coroutines.run(&mut log);
+        coroutines.run(&mut log);
+        assert_eq!(log, vec!["start"]);
+
+        coroutines.run(&mut log);
+        assert_eq!(log, vec!["start", "waited"]);
+
+        coroutines.run(&mut log);
+        assert_eq!(log, vec!["start", "waited", "done"]);
+        assert!(coroutines.is_idle());
+    }
+}
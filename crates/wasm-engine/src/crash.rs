@@ -0,0 +1,132 @@
+// Sends panics and game loop errors to a server, so crashes in players'
+// browsers get seen. Each report has the message, the JavaScript stack, and
+// the summary and seed the game last gave. Panics can happen anywhere, so the
+// reporter lives in a thread local the panic hook can reach.
+use std::{cell::RefCell, panic};
+
+use serde::Serialize;
+
+use crate::platform;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub message: String,
+    pub stack: Option<String>,
+    pub state: String,
+    pub seed: Option<u64>,
+}
+
+struct Reporter {
+    endpoint: String,
+    enabled: bool,
+    state: String,
+    seed: Option<u64>,
+}
+
+impl Reporter {
+    fn report(&self, message: &str, stack: Option<String>) -> Option<CrashReport> {
+        if !self.enabled {
+            return None;
+        }
+        Some(CrashReport {
+            message: message.to_string(),
+            stack,
+            state: self.state.clone(),
+            seed: self.seed,
+        })
+    }
+}
+
+thread_local! {
+    static REPORTER: RefCell<Option<Reporter>> = const { RefCell::new(None) };
+}
+
+// Reports every panic to the endpoint from now on. Whatever panic hook was
+// set before, like console_error_panic_hook, still runs afterwards.
+pub fn install(endpoint: &str) {
+    REPORTER.with(|reporter| {
+        *reporter.borrow_mut() = Some(Reporter {
+            endpoint: endpoint.to_string(),
+            enabled: true,
+            state: String::new(),
+            seed: None,
+        })
+    });
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        report(&info.to_string());
+        previous(info);
+    }));
+}
+
+// For players who opt out. Reports are on once installed.
+pub fn set_enabled(enabled: bool) {
+    with_reporter(|reporter| reporter.enabled = enabled);
+}
+
+// A short summary of the game's state, and the seed its layout came from,
+// sent with any report after this
+pub fn set_context(state: &str, seed: Option<u64>) {
+    with_reporter(|reporter| {
+        reporter.state = state.to_string();
+        reporter.seed = seed;
+    });
+}
+
+// Does nothing until install has been called, or while reports are off
+pub fn report(message: &str) {
+    with_reporter(|reporter| {
+        if let Some(report) = reporter.report(message, platform::stack_trace()) {
+            let sent = serde_json::to_string(&report)
+                .map_err(anyhow::Error::from)
+                .and_then(|body| platform::send_beacon(&reporter.endpoint, &body));
+            if let Err(err) = sent {
+                log!("Could not send crash report {:#?}", err);
+            }
+        }
+    });
+}
+
+// A panic while the reporter is borrowed can't use it, and mustn't panic
+// again
+fn with_reporter(f: impl FnOnce(&mut Reporter)) {
+    let _ = REPORTER.try_with(|reporter| {
+        if let Ok(mut reporter) = reporter.try_borrow_mut() {
+            if let Some(reporter) = reporter.as_mut() {
+                f(reporter);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reporter(enabled: bool) -> Reporter {
+        Reporter {
+            endpoint: "/crash".to_string(),
+            enabled,
+            state: "running, 120m".to_string(),
+            seed: Some(42),
+        }
+    }
+
+    #[test]
+    fn reports_carry_the_last_context() {
+        let report = reporter(true)
+            .report("index out of bounds", None)
+            .expect("No report made");
+
+        assert_eq!(report.state, "running, 120m");
+        assert_eq!(report.seed, Some(42));
+    }
+
+    #[test]
+    fn opting_out_stops_reports() {
+        assert!(reporter(false)
+            .report("index out of bounds", None)
+            .is_none());
+    }
+}
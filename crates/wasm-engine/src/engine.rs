@@ -0,0 +1,1677 @@
+use crate::{
+    analytics::{Analytics, NoAnalytics},
+    audio,
+    config::LaunchOptions,
+    crash,
+    events::{EngineEvent, EventBus},
+    input::{Action, Gamepads, InputState, Touches},
+    mask::Mask,
+    platform::{
+        self, Bitmap, Canvas, Context, FrameCallback, ImageSource, InputEvent, InputSource,
+    },
+    viewport::{Camera, Transform, Viewport},
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::channel::oneshot::{self, channel};
+use serde::Deserialize;
+use std::{cell::RefCell, collections::HashMap, marker::PhantomData, rc::Rc};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl Point {
+    // Part of the way to another point, where 0.0 is this one and 1.0 is the
+    // other, for drawing between two updates
+    pub fn lerp(&self, to: &Point, factor: f32) -> Point {
+        Point {
+            x: self.x + ((to.x - self.x) as f32 * factor).round() as i16,
+            y: self.y + ((to.y - self.y) as f32 * factor).round() as i16,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vector {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    #[serde(rename(deserialize = "w"))]
+    pub width: f32,
+    #[serde(rename(deserialize = "h"))]
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn intersects(&self, rect: &Rect) -> bool {
+        self.x < (rect.x + rect.width)
+            && self.x + self.width > rect.x
+            && self.y < (rect.y + rect.height)
+            && self.y + self.height > rect.y
+    }
+
+    // The part of both, if they overlap at all
+    pub fn intersection(&self, rect: &Rect) -> Option<Rect> {
+        if !self.intersects(rect) {
+            return None;
+        }
+        let x = self.x.max(rect.x);
+        let y = self.y.max(rect.y);
+        Some(Rect {
+            x,
+            y,
+            width: (self.x + self.width).min(rect.x + rect.width) - x,
+            height: (self.y + self.height).min(rect.y + rect.height) - y,
+        })
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Cell {
+    frame: Rect,
+    // Where the trimmed frame sat in the sprite before it was packed
+    sprite_source_size: Rect,
+    source_size: Size,
+}
+
+#[derive(Deserialize, Clone)]
+struct Size {
+    #[serde(rename(deserialize = "w"))]
+    width: f32,
+    #[serde(rename(deserialize = "h"))]
+    height: f32,
+}
+
+// The point on a sprite that the position it's drawn at refers to. It's a
+// point on the whole sprite, before the packer trimmed it, so frames trimmed
+// to different sizes still line up with each other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    TopLeft,
+    // The middle of the bottom edge, where a character stands
+    FeetCenter,
+}
+
+impl Anchor {
+    // Where the top left of the trimmed frame goes, from the anchor point
+    fn frame_offset(&self, cell: &Cell) -> Vector {
+        let origin = match self {
+            Anchor::TopLeft => Vector { x: 0.0, y: 0.0 },
+            Anchor::FeetCenter => Vector {
+                x: cell.source_size.width / 2.0,
+                y: cell.source_size.height,
+            },
+        };
+        Vector {
+            x: cell.sprite_source_size.x - origin.x,
+            y: cell.sprite_source_size.y - origin.y,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Sheet {
+    frames: HashMap<String, Cell>,
+}
+
+#[derive(Clone)]
+pub struct Image {
+    image: Bitmap,
+    position: Point,
+    // Where it was before the last move
+    previous: Point,
+}
+
+impl Image {
+    pub fn new(image: Bitmap, position: Point) -> Self {
+        Image {
+            image,
+            position,
+            previous: position,
+        }
+    }
+
+    // The interpolation factor is how far between the last two updates to
+    // draw it, where 1.0 is where it is now
+    pub fn draw(&self, renderer: &Renderer, interpolation: f32) {
+        renderer.draw_whole_image(
+            &self.image,
+            &self.previous.lerp(&self.position, interpolation),
+        );
+    }
+
+    pub fn move_horizontally(&mut self, distance: i16) {
+        self.previous = self.position;
+        // Far enough off screen, it stays at the edge rather than wrapping
+        self.position.x = self.position.x.saturating_add(distance);
+    }
+
+    pub fn x(&self) -> i16 {
+        self.position.x
+    }
+
+    // Puts it straight there, without drawing it in between
+    pub fn set_x(&mut self, x: i16) {
+        self.position.x = x;
+        self.previous = self.position;
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x as f32,
+            y: self.position.y as f32,
+            width: self.image.width() as f32,
+            height: self.image.height() as f32,
+        }
+    }
+
+    // Of the whole image, to line up with its bounding box
+    pub fn mask(&self, cell: u32) -> Result<Mask> {
+        Mask::load(
+            &self.image,
+            &Rect {
+                x: 0.0,
+                y: 0.0,
+                width: self.image.width() as f32,
+                height: self.image.height() as f32,
+            },
+            cell,
+        )
+    }
+}
+
+// An image drawn twice, side by side, that wraps round as it scrolls so the
+// screen is never left with a gap. The screen mustn't be wider than the image.
+pub struct TilingBackground {
+    image: Bitmap,
+    y: i16,
+    // Where the first copy is drawn, always between -width and 0
+    offset: i16,
+}
+
+impl TilingBackground {
+    pub fn new(image: Bitmap, y: i16) -> Self {
+        TilingBackground {
+            image,
+            y,
+            offset: 0,
+        }
+    }
+
+    fn width(&self) -> i16 {
+        self.image.width() as i16
+    }
+
+    pub fn move_horizontally(&mut self, distance: i16) {
+        self.offset = wrap(self.offset + distance, self.width());
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        for x in [self.offset, self.offset + self.width()].iter() {
+            renderer.draw_whole_image(&self.image, &Point { x: *x, y: self.y });
+        }
+    }
+}
+
+// Brings x back between -width and 0, whichever way it's scrolled
+fn wrap(x: i16, width: i16) -> i16 {
+    if width <= 0 {
+        0
+    } else {
+        -(-x).rem_euclid(width)
+    }
+}
+
+// The animations in a sprite sheet, which games implement on an enum listing
+// every one, so a misspelt animation doesn't compile. An animation's frames
+// are the cells named "<name> (1).png", "<name> (2).png" and so on.
+pub trait AnimationSet: Copy + 'static {
+    // In the order of their indexes
+    fn all() -> &'static [Self];
+    fn name(self) -> &'static str;
+    // Its place in all()
+    fn index(self) -> usize;
+}
+
+// Each animation's frames are looked up once, when it's made, so drawing a
+// frame is just indexing. An animation with no frames in the sheet panics
+// here, while loading, rather than the first time it's drawn.
+#[derive(Clone)]
+pub struct Animation<A: AnimationSet> {
+    sheet: Rc<SpriteSheet>,
+    frames: Vec<Vec<Sprite>>,
+    // A mask for every frame, for the games that ask for them
+    masks: Option<Vec<Vec<Mask>>>,
+    animations: PhantomData<A>,
+}
+
+impl<A: AnimationSet> Animation<A> {
+    pub fn new(sheet: Rc<SpriteSheet>) -> Self {
+        let frames = A::all()
+            .iter()
+            .map(|animation| {
+                let sprites: Vec<Sprite> = (1..)
+                    .map(|frame| format!("{} ({}).png", animation.name(), frame))
+                    .take_while(|frame_name| sheet.get_frame(frame_name).is_some())
+                    .map(|frame_name| sheet.sprite(&frame_name))
+                    .collect();
+                if sprites.is_empty() {
+                    panic!("No animation named {}", animation.name());
+                }
+                sprites
+            })
+            .collect();
+
+        Animation {
+            sheet,
+            frames,
+            masks: None,
+            animations: PhantomData,
+        }
+    }
+
+    // Works out every frame's mask up front, in cells this many pixels a side
+    pub fn with_masks(mut self, cell: u32) -> Result<Self> {
+        let masks = self
+            .frames
+            .iter()
+            .map(|sprites| {
+                sprites
+                    .iter()
+                    .map(|sprite| self.sheet.mask(sprite, cell))
+                    .collect::<Result<Vec<Mask>>>()
+            })
+            .collect::<Result<Vec<Vec<Mask>>>>()?;
+        self.masks = Some(masks);
+        Ok(self)
+    }
+
+    // Lines up with bounding_box_for. None unless it was made with_masks.
+    pub fn mask_for(&self, animation: A, frame: &i16) -> Option<&Mask> {
+        self.masks
+            .as_ref()
+            .map(|masks| &masks[animation.index()][*frame as usize])
+    }
+
+    pub fn draw(&self, renderer: &Renderer, animation: A, frame: &i16, position: &Point) {
+        let sprite = &self.frames[animation.index()][*frame as usize];
+        self.sheet.draw_sprite(renderer, sprite, position);
+    }
+
+    pub fn frame_count(&self, animation: A) -> usize {
+        self.frames[animation.index()].len()
+    }
+
+    pub fn bounding_box_for(&self, animation: A, frame: &i16) -> Rect {
+        self.frames[animation.index()][*frame as usize].bounding_box()
+    }
+}
+
+// A frame of a sprite sheet, ready to draw without looking it up by name
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    frame: Rect,
+    // From the anchor point to the frame's top left
+    offset: Vector,
+}
+
+impl Sprite {
+    // Relative to the anchor point
+    pub fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.offset.x,
+            y: self.offset.y,
+            width: self.frame.width,
+            height: self.frame.height,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SpriteSheet {
+    image: Bitmap,
+    sheet: Sheet,
+    anchor: Anchor,
+}
+
+impl SpriteSheet {
+    pub fn new(image: Bitmap, sheet: Sheet) -> Self {
+        SpriteSheet {
+            image,
+            sheet,
+            anchor: Anchor::TopLeft,
+        }
+    }
+
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    fn get_frame(&self, name: &str) -> Option<&Cell> {
+        self.sheet.frames.get(name)
+    }
+
+    // Looks the cell up for drawing with draw_sprite. Do this when loading,
+    // so a missing cell is found straight away rather than mid-game.
+    pub fn sprite(&self, cell_name: &str) -> Sprite {
+        let cell = self
+            .get_frame(cell_name)
+            .unwrap_or_else(|| panic!("Cell {} not found", cell_name));
+        Sprite {
+            frame: cell.frame.clone(),
+            offset: self.anchor.frame_offset(cell),
+        }
+    }
+
+    // Relative to the anchor point
+    pub fn bounding_box_for(&self, cell_name: &str) -> Rect {
+        self.sprite(cell_name).bounding_box()
+    }
+
+    // Lines up with the sprite's bounding box
+    pub fn mask(&self, sprite: &Sprite, cell: u32) -> Result<Mask> {
+        Mask::load(&self.image, &sprite.frame, cell)
+    }
+
+    pub fn draw(&self, renderer: &Renderer, entry: &str, position: &Point) {
+        self.draw_sprite(renderer, &self.sprite(entry), position);
+    }
+
+    pub fn draw_sprite(&self, renderer: &Renderer, sprite: &Sprite, position: &Point) {
+        renderer.draw_image(
+            &self.image,
+            &sprite.frame,
+            &Rect {
+                x: position.x as f32 + sprite.offset.x,
+                y: position.y as f32 + sprite.offset.y,
+                width: sprite.frame.width,
+                height: sprite.frame.height,
+            },
+        );
+    }
+
+    // Stretched to fill the destination, whatever the sprite's own size
+    pub fn draw_sprite_in(&self, renderer: &Renderer, sprite: &Sprite, destination: &Rect) {
+        renderer.draw_image(&self.image, &sprite.frame, destination);
+    }
+}
+
+// Sprite sheets by name, each fetched and parsed once and then shared by
+// everything drawn from it. The sheet called "tiles" is tiles.json and
+// tiles.png.
+//
+// Nothing is released on its own unless there's a budget. Only sheets that
+// nothing else holds can be released, since anything still drawing from one
+// keeps its image alive anyway.
+#[derive(Default)]
+pub struct SpriteSheets {
+    sheets: HashMap<(String, Anchor), CachedSheet>,
+    // In bytes of decoded image. Going over it releases the least recently
+    // loaded sheets that aren't in use.
+    budget: Option<usize>,
+    loads: u64,
+}
+
+struct CachedSheet {
+    sheet: Rc<SpriteSheet>,
+    bytes: usize,
+    last_loaded: u64,
+}
+
+impl CachedSheet {
+    fn is_unused(&self) -> bool {
+        Rc::strong_count(&self.sheet) == 1
+    }
+}
+
+impl SpriteSheets {
+    pub fn with_budget(mut self, bytes: usize) -> Self {
+        self.budget = Some(bytes);
+        self
+    }
+
+    pub async fn load(&mut self, name: &str, anchor: Anchor) -> Result<Rc<SpriteSheet>> {
+        self.loads += 1;
+        let key = (name.to_string(), anchor);
+        if let Some(cached) = self.sheets.get_mut(&key) {
+            cached.last_loaded = self.loads;
+            return Ok(Rc::clone(&cached.sheet));
+        }
+        let sheet = platform::load_json(&format!("{}.json", name)).await?;
+        let image = load_image(&format!("{}.png", name)).await?;
+        // Decoded, the image is four bytes a pixel whatever the PNG's size
+        let bytes = (image.width() * image.height() * 4) as usize;
+        let sheet = Rc::new(SpriteSheet::new(image, sheet).with_anchor(anchor));
+        self.sheets.insert(
+            key,
+            CachedSheet {
+                sheet: Rc::clone(&sheet),
+                bytes,
+                last_loaded: self.loads,
+            },
+        );
+        self.enforce_budget();
+        Ok(sheet)
+    }
+
+    // Forgets the sheet, so it's released once nothing is drawing from it
+    // and loaded again if it's asked for again
+    pub fn unload(&mut self, name: &str, anchor: Anchor) -> bool {
+        self.sheets.remove(&(name.to_string(), anchor)).is_some()
+    }
+
+    // For between levels, once the last level's things are gone. Returns how
+    // many were released.
+    pub fn unload_unused(&mut self) -> usize {
+        let before = self.sheets.len();
+        self.sheets.retain(|_, cached| !cached.is_unused());
+        before - self.sheets.len()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.sheets.values().map(|cached| cached.bytes).sum()
+    }
+
+    fn enforce_budget(&mut self) {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let unused = self
+            .sheets
+            .iter()
+            .filter(|(_, cached)| cached.is_unused())
+            .map(|(key, cached)| (key.clone(), cached.last_loaded, cached.bytes))
+            .collect();
+        for key in evictions(unused, self.bytes(), budget) {
+            self.sheets.remove(&key);
+        }
+    }
+}
+
+// Which of the unused entries, given as (key, last loaded, bytes), to drop to
+// get under the budget, oldest first. It can still be over afterwards if
+// what's in use is more than the budget.
+fn evictions<K>(mut unused: Vec<(K, u64, usize)>, mut total: usize, budget: usize) -> Vec<K> {
+    unused.sort_by_key(|(_, last_loaded, _)| *last_loaded);
+    unused
+        .into_iter()
+        .take_while(|(_, _, bytes)| {
+            let over = total > budget;
+            total = total.saturating_sub(*bytes);
+            over
+        })
+        .map(|(key, _, _)| key)
+        .collect()
+}
+
+// What was drawn, recorded so tests can check it
+#[cfg(any(test, feature = "testing"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCall {
+    Clear,
+    Image,
+    Rect(String),
+    Zoom(f32),
+    Alpha(f32),
+    Filter(String),
+    Circle(String),
+    Text(String),
+    Fill(String),
+}
+
+// An image held back while y-sorting, with the alpha and filter it was drawn
+// with
+struct QueuedImage {
+    image: Bitmap,
+    frame: Rect,
+    destination: Rect,
+    alpha: f32,
+    filter: String,
+}
+
+// The order to draw in so lower baselines go on top, keeping the order they
+// were drawn in where baselines are level
+fn baseline_order(destinations: &[Rect]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..destinations.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (&destinations[*a], &destinations[*b]);
+        (a.y + a.height)
+            .partial_cmp(&(b.y + b.height))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    order
+}
+
+// How image destinations land on the canvas. Through a camera or a scaled
+// viewport, a whole world unit is rarely a whole canvas pixel.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Positioning {
+    // Exactly where they're asked for, blended across pixels, which keeps
+    // slow camera moves smooth
+    SubPixel,
+    // Rounded to whole canvas pixels, which keeps pixel art crisp
+    Snapped,
+}
+
+// The destination moved to the nearest canvas pixels under the transform
+fn snap_to_pixels(destination: &Rect, transform: &Transform) -> Rect {
+    let (left, top) = transform.apply(destination.x, destination.y);
+    let (right, bottom) = transform.apply(
+        destination.x + destination.width,
+        destination.y + destination.height,
+    );
+    let (left, top) = transform.unapply(left.round(), top.round());
+    let (right, bottom) = transform.unapply(right.round(), bottom.round());
+    Rect {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    }
+}
+
+pub struct Renderer {
+    context: Context,
+    debug: DebugFlags,
+    viewport: Option<Viewport>,
+    camera: std::cell::Cell<Option<Camera>>,
+    // Images waiting for end_y_sort, while y-sorting
+    y_sorted: RefCell<Option<Vec<QueuedImage>>>,
+    positioning: std::cell::Cell<Positioning>,
+    #[cfg(any(test, feature = "testing"))]
+    calls: RefCell<Vec<DrawCall>>,
+}
+
+impl Renderer {
+    pub fn new(context: Context) -> Self {
+        Renderer {
+            context,
+            debug: DebugFlags::default(),
+            viewport: None,
+            camera: std::cell::Cell::new(None),
+            y_sorted: RefCell::new(None),
+            positioning: std::cell::Cell::new(Positioning::SubPixel),
+            #[cfg(any(test, feature = "testing"))]
+            calls: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn with_debug(mut self, debug: DebugFlags) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn debug(&self) -> DebugFlags {
+        self.debug
+    }
+
+    // Without a viewport the game draws straight onto canvas pixels
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    fn canvas_size(&self) -> (f32, f32) {
+        let (width, height) = self.context.size();
+        (width as f32, height as f32)
+    }
+
+    // Without a viewport, the design resolution is the canvas
+    fn viewport(&self) -> Viewport {
+        self.viewport.unwrap_or_else(|| {
+            let (width, height) = self.canvas_size();
+            Viewport::new(width, height)
+        })
+    }
+
+    // From world units all the way to canvas pixels
+    fn world_transform(&self) -> Transform {
+        let viewport = self.viewport();
+        let (width, height) = self.canvas_size();
+        let camera = self
+            .camera
+            .get()
+            .unwrap_or_else(|| Camera::fixed(&viewport));
+        camera
+            .transform(&viewport)
+            .then(&viewport.transform(width, height))
+    }
+
+    // Draws everything afterwards through the camera, until the next frame
+    // or the next call
+    pub fn set_camera(&self, camera: Camera) {
+        self.camera.set(Some(camera));
+        self.apply_transform();
+    }
+
+    fn apply_transform(&self) {
+        self.context.set_transform(&self.world_transform());
+    }
+
+    pub fn world_to_screen(&self, point: Vector) -> Vector {
+        let (x, y) = self.world_transform().apply(point.x, point.y);
+        Vector { x, y }
+    }
+
+    pub fn screen_to_world(&self, point: Vector) -> Vector {
+        let (x, y) = self.world_transform().unapply(point.x, point.y);
+        Vector { x, y }
+    }
+
+    // Converts a pointer position from an event's client coordinates into
+    // the world, undoing the canvas's CSS size, the viewport and the camera
+    pub fn pointer_to_world(&self, client_x: f32, client_y: f32) -> Vector {
+        self.screen_to_world(self.context.client_to_canvas(Vector {
+            x: client_x,
+            y: client_y,
+        }))
+    }
+
+    // Resizing the canvas resets the context, so the game loop sets the
+    // viewport up again at the start of every frame, with the camera back
+    // where it started and sub-pixel positioning
+    pub fn begin_frame(&self) {
+        if let Some(viewport) = &self.viewport {
+            if viewport.is_high_dpi() {
+                self.context.match_device_pixels();
+            }
+            self.context.set_smoothing(!viewport.is_pixel_art());
+        }
+        self.camera.set(None);
+        self.positioning.set(Positioning::SubPixel);
+        self.apply_transform();
+    }
+
+    // For the images drawn afterwards, so each layer of a scene can choose
+    pub fn set_positioning(&self, positioning: Positioning) {
+        self.positioning.set(positioning);
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub fn calls(&self) -> Vec<DrawCall> {
+        self.calls.borrow().clone()
+    }
+
+    // The canvas as RGBA bytes, row by row, with its width and height
+    #[cfg(any(test, feature = "testing"))]
+    pub fn pixels(&self) -> (u32, u32, Vec<u8>) {
+        self.context.pixels()
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    fn record(&self, call: DrawCall) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    pub fn clear(&self, rect: &Rect) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Clear);
+        self.context.clear_rect(rect);
+    }
+
+    // Clears every pixel of the canvas, whatever its size and however it's
+    // currently zoomed, then fills it with color if there is one
+    pub fn clear_all(&self, color: Option<&str>) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Clear);
+        let (width, height) = self.canvas_size();
+        let all = Rect {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        };
+
+        self.context.save();
+        self.context.set_transform(&Transform::IDENTITY);
+        self.context.clear_rect(&all);
+        if let Some(color) = color {
+            self.context.fill_rect(color, &all);
+        }
+        self.context.restore();
+    }
+
+    // Until end_y_sort, images are held back and then drawn in order of their
+    // bottom edge, so something standing lower on the screen overlaps what's
+    // behind it. Everything else still draws straight away.
+    pub fn begin_y_sort(&self) {
+        self.y_sorted.replace(Some(vec![]));
+    }
+
+    pub fn end_y_sort(&self) {
+        let queued = match self.y_sorted.borrow_mut().take() {
+            Some(queued) => queued,
+            None => return,
+        };
+        let alpha = self.context.alpha();
+        let filter = self.context.filter();
+        let destinations: Vec<Rect> = queued
+            .iter()
+            .map(|queued| queued.destination.clone())
+            .collect();
+        for index in baseline_order(&destinations) {
+            let queued = &queued[index];
+            self.context.set_alpha(queued.alpha);
+            self.context.set_filter(&queued.filter);
+            self.draw_image_now(&queued.image, &queued.frame, &queued.destination);
+        }
+        self.context.set_alpha(alpha);
+        self.context.set_filter(&filter);
+    }
+
+    pub fn draw_image(&self, image: &Bitmap, frame: &Rect, destination: &Rect) {
+        let destination = match self.positioning.get() {
+            Positioning::SubPixel => destination.clone(),
+            Positioning::Snapped => snap_to_pixels(destination, &self.world_transform()),
+        };
+        if let Some(queued) = self.y_sorted.borrow_mut().as_mut() {
+            queued.push(QueuedImage {
+                image: image.clone(),
+                frame: frame.clone(),
+                destination,
+                alpha: self.context.alpha(),
+                filter: self.context.filter(),
+            });
+            return;
+        }
+        self.draw_image_now(image, frame, &destination);
+    }
+
+    // Already positioned and past any y-sorting
+    fn draw_image_now(&self, image: &Bitmap, frame: &Rect, destination: &Rect) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Image);
+        self.context.draw_image(image, frame, destination);
+    }
+
+    pub fn draw_whole_image(&self, image: &Bitmap, position: &Point) {
+        if self.y_sorted.borrow().is_some() || self.positioning.get() == Positioning::Snapped {
+            let (width, height) = (image.width() as f32, image.height() as f32);
+            self.draw_image(
+                image,
+                &Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width,
+                    height,
+                },
+                &Rect {
+                    x: position.x.into(),
+                    y: position.y.into(),
+                    width,
+                    height,
+                },
+            );
+            return;
+        }
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Image);
+        self.context.draw_whole_image(image, position);
+    }
+
+    pub fn draw_rect(&self, color: &str, rect: &Rect) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Rect(color.to_string()));
+        self.context.stroke_rect(color, rect);
+    }
+
+    // Scales everything drawn afterwards about the center of the design
+    // resolution, with a camera that otherwise doesn't move. A zoom of 1.0
+    // resets to drawing at normal size.
+    pub fn zoom(&self, zoom: f32) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Zoom(zoom));
+        self.set_camera(Camera {
+            zoom,
+            ..Camera::fixed(&self.viewport())
+        });
+    }
+
+    // How opaque everything drawn afterwards is, from 0.0 to 1.0
+    pub fn set_alpha(&self, alpha: f32) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Alpha(alpha));
+        self.context.set_alpha(alpha);
+    }
+
+    // A CSS filter over everything drawn after it, e.g. "hue-rotate(90deg)",
+    // until it's set back to "none"
+    pub fn set_filter(&self, filter: &str) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Filter(filter.to_string()));
+        self.context.set_filter(filter);
+    }
+
+    pub fn draw_circle(&self, color: &str, center: &Point, radius: f32) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Circle(color.to_string()));
+        self.context.stroke_circle(color, center, radius);
+    }
+
+    pub fn draw_text(&self, text: &str, position: &Point, color: &str, size: u8) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Text(text.to_string()));
+        self.context.fill_text(text, position, color, size);
+    }
+
+    pub fn fill_rect(&self, color: &str, rect: &Rect) {
+        #[cfg(any(test, feature = "testing"))]
+        self.record(DrawCall::Fill(color.to_string()));
+        self.context.fill_rect(color, rect);
+    }
+
+    // Shows the frame, on platforms where drawing isn't shown as it happens
+    pub fn end_frame(&self) {
+        self.context.present();
+    }
+}
+
+// Saves the frame on screen as a PNG, e.g. for bug reports
+pub fn save_screenshot(filename: &str) -> Result<()> {
+    platform::save_screenshot(filename)
+}
+
+pub async fn load_image(source: &str) -> Result<Bitmap> {
+    platform::load_image(source).await
+}
+
+// Sixty Frames per second, converted to a frame length in milliseconds
+pub const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
+const CANVAS_ID: &str = "canvas";
+// A quarter of a second of updates. Any more in one frame, from a breakpoint or
+// a throttled tab, and the game would lurch forward while the player can't
+// react.
+const MAX_UPDATES_PER_FRAME: u32 = 15;
+
+#[async_trait(?Send)]
+pub trait Game {
+    type Action: Action;
+
+    // Called on the first scene before it's initialized, with whatever the
+    // game loop builder was told to start with
+    fn configure(&mut self, _options: &LaunchOptions) {}
+    async fn initialize(&mut self) -> Result<Box<dyn Game<Action = Self::Action>>>;
+    // Turns this frame's keys, gamepads and touches into the game's actions,
+    // through whatever bindings the game uses
+    fn read_input(
+        &self,
+        keys: &KeyState,
+        gamepads: &Gamepads,
+        touches: &Touches,
+        input: &mut InputState<Self::Action>,
+    );
+    fn update(&mut self, input: &InputState<Self::Action>);
+    fn draw(&self, context: &Renderer);
+    // Called before the frame's updates for each engine event since the last
+    // frame
+    fn handle_event(&mut self, _event: &EngineEvent) {}
+    // Checked after each frame's updates. A scene returned here is
+    // initialized in the background, and then takes over from this one on
+    // the same canvas and input, e.g. going from an attract mode demo to the
+    // real game. This one keeps running until then.
+    fn replacement(&mut self) -> Option<Box<dyn Game<Action = Self::Action>>> {
+        None
+    }
+    // How loud to play every sound, from 0.0 for silent to 1.0 for full,
+    // checked after each frame's updates
+    fn volume(&self) -> f32 {
+        1.0
+    }
+}
+
+// Engine diagnostics, all off by default. Games can read these from the
+// renderer to switch on their own debug drawing.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DebugFlags {
+    // Draws the frame rate in the top left corner
+    pub show_fps: bool,
+    // Logs whenever a frame hits the update limit and game time is dropped
+    pub log_dropped_time: bool,
+    // For games that report their collisions, to draw and log them
+    pub show_collisions: bool,
+}
+
+pub struct GameLoop {
+    last_frame: f64,
+    accumulated_delta: f32,
+}
+
+// Where a scene that has finished initializing waits for the next frame
+type SceneSlot<A> = Rc<RefCell<Option<Box<dyn Game<Action = A>>>>>;
+
+// What a handle shares with the running loop. The loop's closure is kept here
+// rather than forgotten, so stopping can drop it, and the game with it.
+struct LoopState {
+    closure: RefCell<Option<FrameCallback>>,
+    // The pending frame, to cancel
+    frame: std::cell::Cell<Option<i32>>,
+    running: std::cell::Cell<bool>,
+    // Set on resume, so the time spent paused isn't owed to updates
+    resumed: std::cell::Cell<bool>,
+}
+
+impl LoopState {
+    fn request_frame(&self) -> Result<()> {
+        if !self.running.get() {
+            return Ok(());
+        }
+        if let Some(closure) = self.closure.borrow().as_ref() {
+            self.frame.set(Some(platform::request_frame(closure)?));
+        }
+        Ok(())
+    }
+
+    fn cancel_frame(&self) {
+        if let Some(frame) = self.frame.take() {
+            if let Err(err) = platform::cancel_frame(frame) {
+                log!("Could not cancel frame {:#?}", err);
+            }
+        }
+    }
+}
+
+impl GameLoop {
+    pub fn builder<G: Game + 'static>(initial_scene: G) -> GameLoopBuilder<G> {
+        GameLoopBuilder::new(initial_scene)
+    }
+
+    // Adds a frame's time to what's owed to updates, keeping no more than
+    // max_updates can use, and returns what was dropped in milliseconds
+    fn accumulate(&mut self, frame_length: f32, timestep: f32, max_updates: u32) -> f32 {
+        self.accumulated_delta += frame_length;
+        let limit = timestep * (max_updates as f32 + 1.0);
+        let dropped = (self.accumulated_delta - limit).max(0.0);
+        self.accumulated_delta -= dropped;
+        dropped
+    }
+
+    // Starts the game with the default configuration
+    pub async fn start<G: Game + 'static>(game: G) -> Result<GameLoopHandle> {
+        GameLoop::builder(game).start().await
+    }
+}
+
+// Configures the game loop before it starts. Everything but the initial scene
+// has a default: a canvas with the id "canvas", sixty updates per second, at
+// most fifteen updates per frame, and no analytics.
+pub struct GameLoopBuilder<G: Game + 'static> {
+    initial_scene: G,
+    canvas_id: String,
+    timestep: f32,
+    clear_color: Option<String>,
+    debug: DebugFlags,
+    max_updates_per_frame: u32,
+    viewport: Option<Viewport>,
+    analytics: Rc<dyn Analytics>,
+    launch_options: LaunchOptions,
+}
+
+impl<G: Game + 'static> GameLoopBuilder<G> {
+    pub fn new(initial_scene: G) -> Self {
+        GameLoopBuilder {
+            initial_scene,
+            canvas_id: CANVAS_ID.to_string(),
+            timestep: FRAME_SIZE,
+            clear_color: None,
+            debug: DebugFlags::default(),
+            max_updates_per_frame: MAX_UPDATES_PER_FRAME,
+            viewport: None,
+            analytics: Rc::new(NoAnalytics),
+            launch_options: LaunchOptions::default(),
+        }
+    }
+
+    // The id of the canvas element to draw to and take keyboard input from
+    pub fn canvas(mut self, id: &str) -> Self {
+        self.canvas_id = id.to_string();
+        self
+    }
+
+    // The length of one update in milliseconds
+    pub fn fixed_timestep(mut self, milliseconds: f32) -> Self {
+        self.timestep = milliseconds;
+        self
+    }
+
+    // Fills the canvas with this color before every draw. Without it the game
+    // is responsible for clearing.
+    pub fn clear_color(mut self, color: &str) -> Self {
+        self.clear_color = Some(color.to_string());
+        self
+    }
+
+    pub fn debug(mut self, debug: DebugFlags) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    // Stops a slow frame, or a tab coming back from the background, from
+    // running a burst of updates to catch up. Time past the limit is dropped,
+    // and the game hears about it as EngineEvent::Lag.
+    pub fn max_updates_per_frame(mut self, max: u32) -> Self {
+        self.max_updates_per_frame = max;
+        self
+    }
+
+    // Draws at the viewport's design resolution, scaled onto the canvas
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    // The loop reports the session starting. Games share the same analytics
+    // to report the rest.
+    pub fn analytics(mut self, analytics: Rc<dyn Analytics>) -> Self {
+        self.analytics = analytics;
+        self
+    }
+
+    // Reads the page's query string. ?debug turns on the frame rate and the
+    // dropped time log, ?collisions the collision view, and seed, level and
+    // muted go to the game as launch options.
+    pub fn configure_from_url(mut self) -> Self {
+        let params = match platform::query_params() {
+            Ok(params) => params,
+            Err(err) => {
+                log!("Could not read the query string {:#?}", err);
+                return self;
+            }
+        };
+        if params.flag("debug") {
+            self.debug.show_fps = true;
+            self.debug.log_dropped_time = true;
+        }
+        if params.flag("collisions") {
+            self.debug.show_collisions = true;
+        }
+        self.launch_options = LaunchOptions::from_query(&params);
+        self
+    }
+
+    pub async fn start(self) -> Result<GameLoopHandle> {
+        let (context, mut input_source) = platform::open_window(&self.canvas_id)?;
+        let mut event_bus = EventBus::listen()?;
+        audio::unlock_on_gesture()?;
+        let mut initial_scene = self.initial_scene;
+        initial_scene.configure(&self.launch_options);
+        let mut game = initial_scene.initialize().await?;
+        self.analytics.session_start();
+
+        let mut game_loop = GameLoop {
+            last_frame: platform::now()?,
+            accumulated_delta: 0.0,
+        };
+
+        let mut renderer = Renderer::new(context).with_debug(self.debug);
+        if let Some(viewport) = self.viewport {
+            renderer = renderer.with_viewport(viewport);
+        }
+        let timestep = self.timestep;
+        let clear_color = self.clear_color;
+        let max_updates = self.max_updates_per_frame;
+        let debug = self.debug;
+
+        let state = Rc::new(LoopState {
+            closure: RefCell::new(None),
+            frame: std::cell::Cell::new(None),
+            running: std::cell::Cell::new(true),
+            resumed: std::cell::Cell::new(false),
+        });
+        let loop_state = Rc::clone(&state);
+
+        // A replacement scene, once it has initialized
+        let next_scene: SceneSlot<G::Action> = Rc::new(RefCell::new(None));
+
+        let mut keystate = KeyState::new();
+        let mut touches = Touches::default();
+        let mut input = InputState::<G::Action>::new();
+        *state.closure.borrow_mut() = Some(platform::frame_callback(move |perf: f64| {
+            let state = &loop_state;
+            state.frame.set(None);
+            if !state.running.get() {
+                return;
+            }
+            if state.resumed.replace(false) {
+                game_loop.last_frame = perf;
+            }
+            if let Some(next) = next_scene.borrow_mut().take() {
+                game = next;
+            }
+            process_input(&mut keystate, &mut touches, input_source.poll(), perf);
+            let gamepads = Gamepads::poll();
+            for event in event_bus.drain() {
+                if debug.show_collisions {
+                    if let EngineEvent::Collision(collision) = &event {
+                        log!(
+                            "{} touched {}: {} over {:?}",
+                            collision.subject,
+                            collision.other,
+                            collision.resolution,
+                            collision.overlap
+                        );
+                    }
+                }
+                game.handle_event(&event);
+            }
+
+            let frame_length = (perf - game_loop.last_frame) as f32;
+            let dropped = game_loop.accumulate(frame_length, timestep, max_updates);
+            if dropped > 0.0 {
+                if debug.log_dropped_time {
+                    log!("Dropped {}ms of game time", dropped);
+                }
+                // Before the updates, so a game that pauses on lag doesn't
+                // run them
+                game.handle_event(&EngineEvent::Lag { dropped });
+            }
+            while game_loop.accumulated_delta > timestep {
+                game.read_input(&keystate, &gamepads, &touches, &mut input);
+                game.update(&input);
+                touches.end_update();
+                game_loop.accumulated_delta -= timestep;
+            }
+            audio::set_volume(game.volume());
+            if let Some(mut scene) = game.replacement() {
+                let next_scene = Rc::clone(&next_scene);
+                platform::spawn_local(async move {
+                    match scene.initialize().await {
+                        Ok(next) => *next_scene.borrow_mut() = Some(next),
+                        Err(err) => {
+                            log!("Could not initialize the next scene {:#?}", err);
+                        }
+                    }
+                });
+            }
+            game_loop.last_frame = perf;
+
+            renderer.begin_frame();
+            if let Some(color) = &clear_color {
+                renderer.clear_all(Some(color));
+            }
+            game.draw(&renderer);
+            if debug.show_fps && frame_length > 0.0 {
+                renderer.draw_text(
+                    &format!("{:.0} fps", 1000.0 / frame_length),
+                    &Point { x: 10, y: 20 },
+                    "#ff00ff",
+                    14,
+                );
+            }
+            renderer.end_frame();
+
+            if let Err(err) = state.request_frame() {
+                crash::report(&format!("GameLoop stopped: {:#?}", err));
+            }
+        }));
+
+        state.request_frame()?;
+        Ok(GameLoopHandle { state })
+    }
+}
+
+// Returned by a started game loop, to pause or stop it. Dropping the handle
+// leaves the loop running.
+#[derive(Clone)]
+pub struct GameLoopHandle {
+    state: Rc<LoopState>,
+}
+
+impl GameLoopHandle {
+    pub fn is_running(&self) -> bool {
+        self.state.running.get()
+    }
+
+    // Cancels the next frame. Called from inside the game, the loop finishes
+    // the frame it's on first.
+    pub fn pause(&self) {
+        self.state.running.set(false);
+        self.state.cancel_frame();
+    }
+
+    // Carries on from where the loop was paused. Does nothing once stopped.
+    pub fn resume(&self) {
+        if self.is_running() || self.state.closure.borrow().is_none() {
+            return;
+        }
+        self.state.running.set(true);
+        self.state.resumed.set(true);
+        if let Err(err) = self.state.request_frame() {
+            log!("Could not resume game loop {:#?}", err);
+        }
+    }
+
+    // Pauses for good, and drops the loop along with the game it was running
+    pub fn stop(&self) {
+        self.pause();
+        self.state.closure.borrow_mut().take();
+    }
+}
+
+// Every key that's down, with the time in milliseconds it went down. Times
+// are on the same clock as the frame time, which is when the keys were
+// last processed.
+pub struct KeyState {
+    pressed_keys: HashMap<String, f64>,
+    now: f64,
+}
+
+impl KeyState {
+    fn new() -> Self {
+        return KeyState {
+            pressed_keys: HashMap::new(),
+            now: 0.0,
+        };
+    }
+
+    pub fn is_pressed(&self, code: &str) -> bool {
+        self.pressed_keys.contains_key(code)
+    }
+
+    // The codes of every key that's down, in no particular order
+    pub fn pressed(&self) -> impl Iterator<Item = &str> {
+        self.pressed_keys.keys().map(String::as_str)
+    }
+
+    // How many milliseconds the key has been held, for charging up moves
+    pub fn held_for(&self, code: &str) -> Option<f32> {
+        self.pressed_keys
+            .get(code)
+            .map(|pressed_at| (self.now - pressed_at).max(0.0) as f32)
+    }
+
+    // Auto-repeat sends keydown again while the key is held, which shouldn't
+    // restart the hold
+    fn set_pressed(&mut self, code: &str, timestamp: f64) {
+        if !self.is_pressed(code) {
+            self.pressed_keys.insert(code.into(), timestamp);
+            KEY_WAITERS.with(|waiters| {
+                for waiter in waiters.borrow_mut().drain(..) {
+                    let _ = waiter.send(code.to_string());
+                }
+            });
+        }
+    }
+}
+
+thread_local! {
+    // Everything waiting on next_key_press
+    static KEY_WAITERS: RefCell<Vec<oneshot::Sender<String>>> = const { RefCell::new(Vec::new()) };
+}
+
+// The code of the next key to go down, for screens like rebinding controls
+// that wait for any key. Auto-repeats don't count, and it's heard when the
+// game loop next processes the keys.
+pub async fn next_key_press() -> Result<String> {
+    let (sender, receiver) = channel();
+    KEY_WAITERS.with(|waiters| waiters.borrow_mut().push(sender));
+    receiver
+        .await
+        .map_err(|err| anyhow!("Stopped waiting for a key {:#?}", err))
+}
+
+impl KeyState {
+    fn set_released(&mut self, code: &str) {
+        self.pressed_keys.remove(code);
+    }
+}
+
+// Keys go to the key state and everything else to the touches
+fn process_input(keys: &mut KeyState, touches: &mut Touches, events: Vec<InputEvent>, now: f64) {
+    keys.now = now;
+    for event in events {
+        match event {
+            InputEvent::KeyUp { code } => keys.set_released(&code),
+            InputEvent::KeyDown { repeat: true, .. } => {}
+            InputEvent::KeyDown { code, time, .. } => keys.set_pressed(&code, time),
+            InputEvent::PointerDown(position, time) => touches.press(position, time),
+            InputEvent::PointerUp(position, time) => touches.release(position, time),
+            InputEvent::PointerCancel => touches.cancel(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{Action, InputDevice, InputState, Step},
+        testing,
+    };
+    use futures::FutureExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    enum TestAction {
+        Jump,
+    }
+
+    impl Action for TestAction {
+        fn all() -> &'static [Self] {
+            &[TestAction::Jump]
+        }
+    }
+
+    struct Space<'a>(&'a KeyState);
+
+    impl InputDevice<TestAction> for Space<'_> {
+        fn value(&self, _action: TestAction) -> f32 {
+            if self.0.is_pressed("Space") {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    }
+
+    // Counts its updates, and how many of them had jump held and newly pressed
+    #[derive(Default)]
+    struct Counter {
+        updates: u32,
+        jumping: u32,
+        jumps: u32,
+    }
+
+    #[async_trait(?Send)]
+    impl Game for Counter {
+        type Action = TestAction;
+
+        async fn initialize(&mut self) -> Result<Box<dyn Game<Action = TestAction>>> {
+            Ok(Box::new(Counter::default()))
+        }
+
+        fn read_input(
+            &self,
+            keys: &KeyState,
+            _gamepads: &Gamepads,
+            _touches: &Touches,
+            input: &mut InputState<TestAction>,
+        ) {
+            input.update(&[&Space(keys)]);
+        }
+
+        fn update(&mut self, input: &InputState<TestAction>) {
+            self.updates += 1;
+            if input.is_pressed(TestAction::Jump) {
+                self.jumping += 1;
+            }
+            if input.just_pressed(TestAction::Jump) {
+                self.jumps += 1;
+            }
+        }
+
+        fn draw(&self, renderer: &Renderer) {
+            renderer.clear_all(None);
+            renderer.draw_text(
+                &self.updates.to_string(),
+                &Point { x: 0, y: 0 },
+                "#000000",
+                12,
+            );
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn sorts_by_the_bottom_edge() {
+        let rect = |y, height| Rect {
+            x: 0.0,
+            y,
+            width: 10.0,
+            height,
+        };
+
+        let order = baseline_order(&[rect(100.0, 50.0), rect(0.0, 20.0), rect(130.0, 20.0)]);
+
+        assert_eq!(order, vec![1, 0, 2]);
+    }
+
+    #[wasm_bindgen_test]
+    fn stands_trimmed_frames_on_their_feet() {
+        let cell = Cell {
+            frame: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 70.0,
+                height: 115.0,
+            },
+            sprite_source_size: Rect {
+                x: 58.0,
+                y: 8.0,
+                width: 70.0,
+                height: 115.0,
+            },
+            source_size: Size {
+                width: 160.0,
+                height: 136.0,
+            },
+        };
+
+        assert_eq!(
+            Anchor::FeetCenter.frame_offset(&cell),
+            Vector {
+                x: -22.0,
+                y: -128.0
+            }
+        );
+        assert_eq!(
+            Anchor::TopLeft.frame_offset(&cell),
+            Vector { x: 58.0, y: 8.0 }
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn snaps_edges_to_whole_pixels() {
+        let transform = Transform {
+            scale_x: 2.0,
+            scale_y: 2.0,
+            offset_x: 0.25,
+            offset_y: 0.25,
+        };
+        let destination = Rect {
+            x: 10.0,
+            y: 3.0,
+            width: 5.0,
+            height: 5.0,
+        };
+
+        let snapped = snap_to_pixels(&destination, &transform);
+
+        assert_eq!(
+            (snapped.x, snapped.y, snapped.width, snapped.height),
+            (9.875, 2.875, 5.0, 5.0)
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn intersects_only_where_both_cover() {
+        let rect = |x, y| Rect {
+            x,
+            y,
+            width: 10.0,
+            height: 10.0,
+        };
+
+        assert_eq!(
+            rect(0.0, 0.0).intersection(&rect(6.0, -4.0)),
+            Some(Rect {
+                x: 6.0,
+                y: 0.0,
+                width: 4.0,
+                height: 6.0,
+            })
+        );
+        assert_eq!(rect(0.0, 0.0).intersection(&rect(10.0, 0.0)), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn evicts_the_oldest_unused_sheets_until_under_budget() {
+        let unused = vec![("boss", 3, 400), ("tiles", 1, 300), ("coins", 2, 200)];
+
+        assert_eq!(evictions(unused.clone(), 1000, 600), vec!["tiles", "coins"]);
+        assert_eq!(evictions(unused, 500, 600), Vec::<&str>::new());
+    }
+
+    // The golden frames are the browser's, fetched from the test server
+    #[cfg(not(feature = "native"))]
+    #[wasm_bindgen_test]
+    async fn fills_rects_like_the_golden_frame() {
+        let renderer = testing::hidden_canvas();
+        renderer.begin_frame();
+        renderer.clear_all(Some("#000000"));
+        renderer.fill_rect(
+            "#ff0000",
+            &Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 120.0,
+                height: 60.0,
+            },
+        );
+        renderer.fill_rect(
+            "#0000ff",
+            &Rect {
+                x: 300.0,
+                y: 300.0,
+                width: 60.0,
+                height: 120.0,
+            },
+        );
+
+        testing::assert_matches_golden(&renderer, "/tests/golden/blocks.json", 2).await;
+    }
+
+    #[wasm_bindgen_test]
+    fn lerps_part_of_the_way() {
+        let from = Point { x: 0, y: 100 };
+
+        let point = from.lerp(&Point { x: 10, y: 50 }, 0.5);
+
+        assert_eq!((point.x, point.y), (5, 75));
+    }
+
+    #[wasm_bindgen_test]
+    fn drops_time_past_the_update_limit() {
+        let mut game_loop = GameLoop {
+            last_frame: 0.0,
+            accumulated_delta: 0.0,
+        };
+
+        let dropped = game_loop.accumulate(1000.0, 10.0, 5);
+
+        assert_eq!(dropped, 940.0);
+        assert_eq!(game_loop.accumulated_delta, 60.0);
+    }
+
+    #[wasm_bindgen_test]
+    fn runs_one_update_per_frame() {
+        let mut counter = Counter::default();
+
+        testing::run_updates(&mut counter, vec![], 10);
+
+        assert_eq!(counter.updates, 10);
+    }
+
+    #[wasm_bindgen_test]
+    fn scripted_actions_are_held_for_their_frames() {
+        let mut counter = Counter::default();
+
+        testing::run_updates(
+            &mut counter,
+            vec![
+                Step {
+                    action: TestAction::Jump,
+                    start: 2,
+                    frames: 3,
+                },
+                Step {
+                    action: TestAction::Jump,
+                    start: 8,
+                    frames: 1,
+                },
+            ],
+            10,
+        );
+
+        assert_eq!(counter.jumping, 4);
+        assert_eq!(counter.jumps, 2);
+    }
+
+    #[wasm_bindgen_test]
+    fn holding_a_key_counts_from_the_first_keydown() {
+        let mut keys = KeyState::new();
+
+        keys.set_pressed("Space", 100.0);
+        keys.set_pressed("Space", 200.0);
+        keys.now = 350.0;
+
+        assert_eq!(keys.held_for("Space"), Some(250.0));
+        assert_eq!(keys.held_for("KeyF"), None);
+
+        keys.set_released("Space");
+
+        assert_eq!(keys.held_for("Space"), None);
+    }
+
+    #[test]
+    fn releasing_a_key_lets_the_next_press_start_a_new_hold() {
+        let mut keys = KeyState::new();
+        keys.set_released("Space");
+        keys.set_pressed("Space", 100.0);
+        keys.set_pressed("KeyF", 100.0);
+
+        keys.set_released("Space");
+        keys.set_pressed("Space", 300.0);
+        keys.now = 400.0;
+
+        assert_eq!(keys.held_for("Space"), Some(100.0));
+        assert_eq!(keys.held_for("KeyF"), Some(300.0));
+    }
+
+    #[test]
+    fn lists_pressed_keys_and_hears_the_next_one() {
+        let mut keys = KeyState::new();
+        keys.set_pressed("Space", 100.0);
+        let mut next = Box::pin(next_key_press());
+        assert!((&mut next).now_or_never().is_none());
+
+        keys.set_pressed("Space", 200.0);
+        keys.set_pressed("KeyF", 300.0);
+
+        let mut pressed: Vec<&str> = keys.pressed().collect();
+        pressed.sort_unstable();
+        assert_eq!(pressed, vec!["KeyF", "Space"]);
+        assert_eq!(next.now_or_never().unwrap().unwrap(), "KeyF");
+    }
+
+    #[wasm_bindgen_test]
+    fn backgrounds_wrap_whichever_way_they_scroll() {
+        assert_eq!(wrap(-1004, 1000), -4);
+        assert_eq!(wrap(-1000, 1000), 0);
+        assert_eq!(wrap(6, 1000), -994);
+        assert_eq!(wrap(-300, 1000), -300);
+    }
+
+    #[wasm_bindgen_test]
+    fn records_draw_calls() {
+        let mut counter = Counter::default();
+        let renderer = testing::hidden_canvas();
+
+        testing::run_updates(&mut counter, vec![], 3);
+        counter.draw(&renderer);
+
+        assert_eq!(
+            renderer.calls(),
+            vec![DrawCall::Clear, DrawCall::Text("3".to_string())]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+// Things that happen outside the game loop, like a gamepad being plugged in or
+// the network dropping.
+// The browser's listeners queue them up, and the game loop hands them to the
+// game at the start of the next frame. Games can queue their own with emit.
+use anyhow::Result;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use std::cell::RefCell;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::GamepadEvent;
+
+use crate::{browser, engine::Rect};
+
+thread_local! {
+    static SENDER: RefCell<Option<UnboundedSender<EngineEvent>>> = const { RefCell::new(None) };
+}
+
+// Two things that touched during an update, for diagnosing collisions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub subject: &'static str,
+    pub other: &'static str,
+    // Where their bounding boxes overlapped, before it was resolved
+    pub overlap: Rect,
+    // What the game did about it, like landing or getting hurt
+    pub resolution: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+    GamepadConnected { id: String },
+    GamepadDisconnected { id: String },
+    Online,
+    Offline,
+    // The game loop skipped this many milliseconds of game time rather than
+    // run a burst of updates to catch up
+    Lag { dropped: f32 },
+    Collision(Collision),
+}
+
+// Queues an event for the start of the next frame. It's dropped when there's
+// no game loop running to take it.
+pub fn emit(event: EngineEvent) {
+    SENDER.with(|sender| {
+        if let Some(sender) = sender.borrow().as_ref() {
+            let _ = sender.unbounded_send(event);
+        }
+    });
+}
+
+pub struct EventBus {
+    receiver: UnboundedReceiver<EngineEvent>,
+}
+
+impl EventBus {
+    pub(crate) fn listen() -> Result<Self> {
+        let (sender, receiver) = unbounded();
+        // Natively there are no gamepads or network changes to hear about
+        if !cfg!(feature = "native") {
+            listen_to_window(&sender)?;
+        }
+        SENDER.with(|emitter| emitter.replace(Some(sender)));
+        Ok(EventBus { receiver })
+    }
+
+    // Everything queued since the last frame
+    pub(crate) fn drain(&mut self) -> Vec<EngineEvent> {
+        let mut events = vec![];
+        while let Ok(event) = self.receiver.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+}
+
+fn listen_to_window(sender: &UnboundedSender<EngineEvent>) -> Result<()> {
+    for &(name, connected) in &[("gamepadconnected", true), ("gamepaddisconnected", false)] {
+        let sender = sender.clone();
+        let listener = browser::closure_wrap(Box::new(move |event: JsValue| {
+            let id = event
+                .dyn_into::<GamepadEvent>()
+                .ok()
+                .and_then(|event| event.gamepad())
+                .map(|gamepad| gamepad.id())
+                .unwrap_or_default();
+            let event = if connected {
+                EngineEvent::GamepadConnected { id }
+            } else {
+                EngineEvent::GamepadDisconnected { id }
+            };
+            let _ = sender.unbounded_send(event);
+        }) as Box<dyn FnMut(JsValue)>);
+        browser::add_window_listener(name, &listener)?;
+        listener.forget();
+    }
+
+    for &(name, online) in &[("online", true), ("offline", false)] {
+        let sender = sender.clone();
+        let listener = browser::closure_wrap(Box::new(move |_event: JsValue| {
+            let event = if online {
+                EngineEvent::Online
+            } else {
+                EngineEvent::Offline
+            };
+            let _ = sender.unbounded_send(event);
+        }) as Box<dyn FnMut(JsValue)>);
+        browser::add_window_listener(name, &listener)?;
+        listener.forget();
+    }
+    Ok(())
+}
@@ -0,0 +1,153 @@
+// 24.8 fixed point for gameplay kinematics, so replays and ghost races land on
+// the same pixel wherever they're run. Every value is a whole number of 256ths
+// of a pixel, so adding them is exact, and an f32 only ever comes in rounded
+// to that grid. Positions stay in whole pixels, with the 256ths they've moved
+// short of the next pixel carried over to later updates instead of being
+// truncated away.
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+use crate::engine::Vector;
+
+const FRACTION_BITS: u32 = 8;
+const ONE: i32 = 1 << FRACTION_BITS;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+
+    // Rounded to the nearest 256th
+    pub fn from_f32(value: f32) -> Self {
+        Fixed((value * ONE as f32).round() as i32)
+    }
+
+    pub fn from_int(value: i16) -> Self {
+        Fixed(i32::from(value) << FRACTION_BITS)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / ONE as f32
+    }
+
+    // Rounded down, so -0.5 is -1
+    pub fn floor(self) -> i16 {
+        (self.0 >> FRACTION_BITS).clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        *self = *self - other;
+    }
+}
+
+// An f32 moved onto the grid, for values like velocities that are worked out
+// with f32 maths but have to be stored exactly
+pub fn quantize(value: f32) -> f32 {
+    Fixed::from_f32(value).to_f32()
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FixedVector {
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+impl FixedVector {
+    pub fn from_vector(vector: Vector) -> Self {
+        FixedVector {
+            x: Fixed::from_f32(vector.x),
+            y: Fixed::from_f32(vector.y),
+        }
+    }
+
+    pub fn to_vector(self) -> Vector {
+        Vector {
+            x: self.x.to_f32(),
+            y: self.y.to_f32(),
+        }
+    }
+}
+
+impl AddAssign for FixedVector {
+    fn add_assign(&mut self, other: FixedVector) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+// What's left over of a whole pixel position moving at a fractional speed
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SubPixel(Fixed);
+
+impl SubPixel {
+    // The whole pixels to move this update, keeping the rest for later ones
+    pub fn step(&mut self, velocity: f32) -> i16 {
+        let moved = self.0 + Fixed::from_f32(velocity);
+        let whole = moved.floor();
+        self.0 = moved - Fixed::from_int(whole);
+        whole
+    }
+
+    // For when the position is set outright, like landing on a platform
+    pub fn clear(&mut self) {
+        self.0 = Fixed::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_onto_the_grid() {
+        assert_eq!(quantize(0.1), 26.0 / 256.0);
+        assert_eq!(Fixed::from_f32(-1.5).floor(), -2);
+        assert_eq!(
+            Fixed::from_int(3) - Fixed::from_f32(0.5),
+            Fixed::from_f32(2.5)
+        );
+    }
+
+    #[test]
+    fn slow_speeds_add_up_to_whole_pixels() {
+        let mut forwards = SubPixel::default();
+        let mut backwards = SubPixel::default();
+
+        let moved: i16 = (0..8).map(|_| forwards.step(0.25)).sum();
+        let moved_back: i16 = (0..8).map(|_| backwards.step(-0.25)).sum();
+
+        assert_eq!((moved, moved_back), (2, -2));
+    }
+}
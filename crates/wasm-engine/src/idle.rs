@@ -0,0 +1,109 @@
+// Work that can wait until the browser has time to spare between frames, so
+// it doesn't eat into the frame budget: fetching what's coming up next,
+// decoding images for the next level, rebuilding anything derived from an
+// asset. Tasks run in the order they were queued, as many as fit in each idle
+// period, and anything left waits for the next one.
+use std::{cell::RefCell, collections::VecDeque};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser::{self, IdleClosure},
+    platform,
+};
+
+// With less time than this left, the rest waits for the next idle period
+const MINIMUM_TIME_LEFT: f64 = 1.0;
+// How long work can wait in milliseconds if the browser is never idle
+const TIMEOUT: u32 = 2000;
+
+type Task = Box<dyn FnOnce()>;
+
+#[derive(Default)]
+struct IdleQueue {
+    tasks: VecDeque<Task>,
+    // Made once and kept for good, since it reschedules itself from inside
+    // its own call when there's work left over
+    callback: Option<IdleClosure>,
+    pending: bool,
+}
+
+thread_local! {
+    static QUEUE: RefCell<IdleQueue> = RefCell::new(IdleQueue::default());
+}
+
+pub fn run_when_idle(task: impl FnOnce() + 'static) {
+    QUEUE.with(|queue| queue.borrow_mut().tasks.push_back(Box::new(task)));
+    schedule();
+}
+
+fn schedule() {
+    let requested: Result<()> = QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        if queue.pending || queue.tasks.is_empty() {
+            return Ok(());
+        }
+        if cfg!(feature = "native") {
+            return Err(anyhow!("No idle callbacks outside the browser"));
+        }
+        let callback = queue.callback.get_or_insert_with(|| {
+            browser::create_idle_closure(|deadline| {
+                QUEUE.with(|queue| queue.borrow_mut().pending = false);
+                run_tasks(|| {
+                    deadline.did_timeout() || deadline.time_remaining() > MINIMUM_TIME_LEFT
+                });
+                schedule();
+            })
+        });
+        browser::request_idle_callback(callback, TIMEOUT)?;
+        queue.pending = true;
+        Ok(())
+    });
+    // Without idle callbacks, as in Safari or natively, the work is only put
+    // off until this frame is done
+    if requested.is_err() {
+        platform::spawn_local(async {
+            run_tasks(|| true);
+        });
+    }
+}
+
+// The queue isn't borrowed while a task runs, so tasks can queue more
+fn run_tasks(has_time: impl Fn() -> bool) {
+    while has_time() {
+        let task = QUEUE.with(|queue| queue.borrow_mut().tasks.pop_front());
+        match task {
+            Some(task) => task(),
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn runs_tasks_in_order_while_there_is_time() {
+        let ran = Rc::new(RefCell::new(vec![]));
+        for task in 0..3 {
+            let ran = Rc::clone(&ran);
+            QUEUE.with(|queue| {
+                queue
+                    .borrow_mut()
+                    .tasks
+                    .push_back(Box::new(move || ran.borrow_mut().push(task)))
+            });
+        }
+        let checks = Cell::new(0);
+
+        run_tasks(|| {
+            checks.set(checks.get() + 1);
+            checks.get() <= 2
+        });
+
+        assert_eq!(*ran.borrow(), vec![0, 1]);
+        assert_eq!(QUEUE.with(|queue| queue.borrow().tasks.len()), 1);
+    }
+}
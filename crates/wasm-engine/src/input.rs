@@ -0,0 +1,452 @@
+use std::{collections::HashMap, hash::Hash};
+
+use wasm_bindgen::JsCast;
+use web_sys::GamepadButton;
+
+use crate::{browser, engine::Vector};
+
+// A value at or above this counts as the action being pressed
+const PRESS_THRESHOLD: f32 = 0.5;
+// Stick movement below this is treated as noise
+const DEADZONE: f32 = 0.25;
+// In CSS pixels. A finger moving less than TAP_DISTANCE, and lifted within
+// TAP_MS milliseconds, tapped. One moving at least SWIPE_DISTANCE, mostly up
+// or down, swiped.
+const TAP_DISTANCE: f32 = 15.0;
+const TAP_MS: f64 = 300.0;
+const SWIPE_DISTANCE: f32 = 40.0;
+
+// The set of things a game's player can do, independent of which keys or
+// buttons do them. Games implement this on an enum listing every action.
+pub trait Action: Copy + Eq + Hash + 'static {
+    fn all() -> &'static [Self];
+}
+
+// Anything that can drive an action, reporting how far it's held from 0.0
+// to 1.0. Digital inputs are either 0.0 or 1.0.
+pub trait InputDevice<A: Action> {
+    fn value(&self, action: A) -> f32;
+}
+
+pub struct Pad {
+    buttons: Vec<f32>,
+    axes: Vec<f32>,
+}
+
+impl Pad {
+    pub fn button(&self, index: usize) -> f32 {
+        self.buttons.get(index).copied().unwrap_or(0.0)
+    }
+
+    // How far the stick is pushed in the direction of sign, ignoring the
+    // deadzone
+    pub fn axis(&self, index: usize, sign: f32) -> f32 {
+        let value = self.axes.get(index).copied().unwrap_or(0.0) * sign;
+        if value < DEADZONE {
+            0.0
+        } else {
+            value.min(1.0)
+        }
+    }
+}
+
+// A snapshot of every connected gamepad. Buttons and axes are numbered by the
+// standard mapping, and games decide what each one does.
+pub struct Gamepads {
+    pads: Vec<Pad>,
+}
+
+impl Gamepads {
+    pub fn poll() -> Self {
+        let pads = browser::gamepads()
+            .unwrap_or_default()
+            .iter()
+            .map(|gamepad| Pad {
+                buttons: gamepad
+                    .buttons()
+                    .iter()
+                    .map(|button| {
+                        button
+                            .dyn_into::<GamepadButton>()
+                            .map(|button| button.value() as f32)
+                            .unwrap_or(0.0)
+                    })
+                    .collect(),
+                axes: gamepad
+                    .axes()
+                    .iter()
+                    .map(|axis| axis.as_f64().unwrap_or(0.0) as f32)
+                    .collect(),
+            })
+            .collect();
+
+        Gamepads { pads }
+    }
+
+    // The strongest reading across every pad
+    pub fn value(&self, read: impl Fn(&Pad) -> f32) -> f32 {
+        self.pads.iter().map(read).fold(0.0, f32::max)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Gesture {
+    Tap,
+    SwipeUp,
+    SwipeDown,
+}
+
+impl Gesture {
+    // None for anything in between, like a slow press or a sideways swipe
+    fn from_stroke(from: Vector, to: Vector, milliseconds: f64) -> Option<Self> {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        if dy.abs() >= SWIPE_DISTANCE && dy.abs() > dx.abs() {
+            Some(if dy < 0.0 {
+                Gesture::SwipeUp
+            } else {
+                Gesture::SwipeDown
+            })
+        } else if dx.hypot(dy) < TAP_DISTANCE && milliseconds <= TAP_MS {
+            Some(Gesture::Tap)
+        } else {
+            None
+        }
+    }
+}
+
+// Touches and mouse clicks on the canvas, boiled down to gestures. A gesture
+// is seen by the first update after the finger lifts, and only that one, so
+// games read it like a button that was pressed for one update.
+#[derive(Default)]
+pub struct Touches {
+    // Where and when the finger went down, while it's down
+    down: Option<(Vector, f64)>,
+    gesture: Option<Gesture>,
+}
+
+impl Touches {
+    pub fn gesture(&self) -> Option<Gesture> {
+        self.gesture
+    }
+
+    pub fn is_touching(&self) -> bool {
+        self.down.is_some()
+    }
+
+    pub(crate) fn press(&mut self, position: Vector, time: f64) {
+        self.down = Some((position, time));
+    }
+
+    pub(crate) fn release(&mut self, position: Vector, time: f64) {
+        if let Some((from, pressed_at)) = self.down.take() {
+            self.gesture = Gesture::from_stroke(from, position, time - pressed_at).or(self.gesture);
+        }
+    }
+
+    // The browser took the touch back, e.g. for a system gesture
+    pub(crate) fn cancel(&mut self) {
+        self.down = None;
+    }
+
+    pub(crate) fn end_update(&mut self) {
+        self.gesture = None;
+    }
+}
+
+// Every device merged into the state of each action, for this update and the
+// one before it
+pub struct InputState<A: Action> {
+    current: HashMap<A, f32>,
+    previous: HashMap<A, f32>,
+}
+
+impl<A: Action> InputState<A> {
+    pub fn new() -> Self {
+        InputState {
+            current: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, devices: &[&dyn InputDevice<A>]) {
+        let current = A::all()
+            .iter()
+            .map(|action| {
+                let value = devices
+                    .iter()
+                    .map(|device| device.value(*action))
+                    .fold(0.0, f32::max);
+                (*action, value)
+            })
+            .collect();
+        self.previous = std::mem::replace(&mut self.current, current);
+    }
+
+    pub fn value(&self, action: A) -> f32 {
+        self.current.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn is_pressed(&self, action: A) -> bool {
+        self.value(action) >= PRESS_THRESHOLD
+    }
+
+    pub fn just_pressed(&self, action: A) -> bool {
+        self.is_pressed(action)
+            && self.previous.get(&action).copied().unwrap_or(0.0) < PRESS_THRESHOLD
+    }
+}
+
+impl<A: Action> Default for InputState<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One action held down for a number of frames, starting at a given frame
+pub struct Step<A: Action> {
+    pub action: A,
+    pub start: u32,
+    pub frames: u32,
+}
+
+// Plays back a list of steps in place of the DOM events, advancing one frame
+// per update, so scenarios can be replayed in tests.
+pub struct ScriptedInput<A: Action> {
+    steps: Vec<Step<A>>,
+    frame: u32,
+}
+
+impl<A: Action> ScriptedInput<A> {
+    pub fn new(steps: Vec<Step<A>>) -> Self {
+        ScriptedInput { steps, frame: 0 }
+    }
+
+    pub fn advance(&mut self) {
+        self.frame += 1;
+    }
+}
+
+impl<A: Action> InputDevice<A> for ScriptedInput<A> {
+    fn value(&self, action: A) -> f32 {
+        let held = self.steps.iter().any(|step| {
+            step.action == action && (step.start..step.start + step.frames).contains(&self.frame)
+        });
+        if held {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// A sequence of steps that have to be pressed in order, each within a number
+// of updates of the one before. A step is every action pressed together, like
+// down and forward at once for a quarter circle.
+pub struct Combo<A: Action, E: Copy> {
+    steps: Vec<Vec<A>>,
+    window: u32,
+    event: E,
+}
+
+impl<A: Action, E: Copy> Combo<A, E> {
+    pub fn new(steps: Vec<Vec<A>>, window: u32, event: E) -> Self {
+        Combo {
+            steps,
+            window,
+            event,
+        }
+    }
+
+    // The step is done when all of its actions are down and at least one of
+    // them has just gone down
+    fn step_done(&self, step: usize, input: &InputState<A>) -> bool {
+        let actions = &self.steps[step];
+        actions.iter().all(|action| input.is_pressed(*action))
+            && actions.iter().any(|action| input.just_pressed(*action))
+    }
+
+    // Pressing anything else starts the combo over, apart from the actions
+    // still held from the step before
+    fn broken(&self, step: usize, input: &InputState<A>) -> bool {
+        A::all().iter().any(|action| {
+            input.just_pressed(*action)
+                && !self.steps[step].contains(action)
+                && (step == 0 || !self.steps[step - 1].contains(action))
+        })
+    }
+}
+
+struct Progress {
+    step: usize,
+    frames: u32,
+}
+
+// Watches the input for combos, and returns the events for any completed on
+// this update
+pub struct ComboRecognizer<A: Action, E: Copy> {
+    combos: Vec<(Combo<A, E>, Progress)>,
+}
+
+impl<A: Action, E: Copy> ComboRecognizer<A, E> {
+    pub fn new(combos: Vec<Combo<A, E>>) -> Self {
+        ComboRecognizer {
+            combos: combos
+                .into_iter()
+                .map(|combo| (combo, Progress { step: 0, frames: 0 }))
+                .collect(),
+        }
+    }
+
+    pub fn update(&mut self, input: &InputState<A>) -> Vec<E> {
+        let mut events = vec![];
+        for (combo, progress) in self.combos.iter_mut() {
+            progress.frames += 1;
+            if progress.step > 0
+                && (progress.frames > combo.window || combo.broken(progress.step, input))
+            {
+                progress.step = 0;
+            }
+
+            if combo.step_done(progress.step, input) {
+                progress.step += 1;
+                progress.frames = 0;
+                if progress.step == combo.steps.len() {
+                    events.push(combo.event);
+                    progress.step = 0;
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    enum TestAction {
+        Down,
+        Forward,
+        Jump,
+    }
+
+    impl Action for TestAction {
+        fn all() -> &'static [Self] {
+            &[TestAction::Down, TestAction::Forward, TestAction::Jump]
+        }
+    }
+
+    fn step(action: TestAction, start: u32, frames: u32) -> Step<TestAction> {
+        Step {
+            action,
+            start,
+            frames,
+        }
+    }
+
+    fn events(steps: Vec<Step<TestAction>>, frames: u32) -> Vec<u32> {
+        let mut recognizer = ComboRecognizer::new(vec![Combo::new(
+            vec![
+                vec![TestAction::Down],
+                vec![TestAction::Down, TestAction::Forward],
+                vec![TestAction::Jump],
+            ],
+            10,
+            1,
+        )]);
+        let mut script = ScriptedInput::new(steps);
+        let mut input = InputState::new();
+        let mut events = vec![];
+        for _ in 0..frames {
+            input.update(&[&script]);
+            events.extend(recognizer.update(&input));
+            script.advance();
+        }
+        events
+    }
+
+    #[test]
+    fn recognizes_a_quarter_circle_and_jump() {
+        let events = events(
+            vec![
+                step(TestAction::Down, 0, 6),
+                step(TestAction::Forward, 4, 6),
+                step(TestAction::Jump, 8, 2),
+            ],
+            20,
+        );
+
+        assert_eq!(events, vec![1]);
+    }
+
+    #[test]
+    fn steps_too_far_apart_miss_the_window() {
+        let events = events(
+            vec![
+                step(TestAction::Down, 0, 6),
+                step(TestAction::Forward, 4, 6),
+                step(TestAction::Jump, 20, 2),
+            ],
+            30,
+        );
+
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn a_wrong_step_starts_over() {
+        let events = events(
+            vec![
+                step(TestAction::Down, 0, 6),
+                step(TestAction::Jump, 3, 1),
+                step(TestAction::Forward, 4, 6),
+                step(TestAction::Jump, 8, 2),
+            ],
+            20,
+        );
+
+        assert!(events.is_empty());
+    }
+
+    fn at(x: f32, y: f32) -> Vector {
+        Vector { x, y }
+    }
+
+    #[test]
+    fn tells_taps_from_swipes() {
+        assert_eq!(
+            Gesture::from_stroke(at(100.0, 100.0), at(104.0, 103.0), 120.0),
+            Some(Gesture::Tap)
+        );
+        assert_eq!(
+            Gesture::from_stroke(at(100.0, 300.0), at(110.0, 200.0), 200.0),
+            Some(Gesture::SwipeUp)
+        );
+        assert_eq!(
+            Gesture::from_stroke(at(100.0, 100.0), at(90.0, 180.0), 200.0),
+            Some(Gesture::SwipeDown)
+        );
+        // Held too long to be a tap, and sideways isn't a swipe
+        assert_eq!(
+            Gesture::from_stroke(at(100.0, 100.0), at(100.0, 100.0), 800.0),
+            None
+        );
+        assert_eq!(
+            Gesture::from_stroke(at(100.0, 100.0), at(200.0, 110.0), 200.0),
+            None
+        );
+    }
+
+    #[test]
+    fn a_gesture_lasts_one_update() {
+        let mut touches = Touches::default();
+        touches.press(at(0.0, 200.0), 0.0);
+        assert!(touches.is_touching());
+        touches.release(at(0.0, 100.0), 150.0);
+
+        assert_eq!(touches.gesture(), Some(Gesture::SwipeUp));
+        touches.end_update();
+        assert_eq!(touches.gesture(), None);
+    }
+}
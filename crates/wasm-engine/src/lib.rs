@@ -0,0 +1,47 @@
+// A small engine for 2D games drawn on an HTML canvas: a fixed timestep game
+// loop, sprite sheets and animation, keyboard and gamepad input mapped to the
+// game's own actions, and the platform services (assets, storage, the clock)
+// a game needs from the browser, and audio once the player lets it play.
+// With the `native` feature the same games run in a desktop window instead.
+//
+// Games implement engine::Game and hand it to engine::GameLoop::start from
+// inside spawn_local. Natively, main then calls platform::run. See examples/
+// for the smallest one that runs.
+
+#[macro_use]
+pub mod platform;
+pub mod analytics;
+pub mod audio;
+pub mod behavior;
+// Natively, browser is only used by the modules that stay web only, and its
+// functions return errors there
+#[cfg_attr(feature = "native", allow(dead_code))]
+mod browser;
+pub mod chat;
+pub mod chunks;
+pub mod clock;
+pub mod config;
+pub mod coroutine;
+pub mod crash;
+pub mod engine;
+pub mod events;
+pub mod fixed;
+pub mod idle;
+pub mod input;
+pub mod mask;
+pub mod net;
+pub mod offline;
+pub mod path;
+pub mod recorder;
+pub mod rewind;
+pub mod rollback;
+pub mod scheduler;
+pub mod spawner;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tween;
+pub mod ui;
+pub mod viewport;
+pub mod wake_lock;
+
+pub use platform::spawn_local;
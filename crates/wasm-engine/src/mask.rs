@@ -0,0 +1,153 @@
+// Which parts of a sprite are solid, for collisions that bounding boxes get
+// wrong, like the corners of a round rock. A mask is worked out once, when
+// the sprite is loaded, from the alpha of its pixels, in square cells of a few
+// pixels each so it stays small. It's only the narrow phase: check that the
+// bounding boxes overlap first, then ask the mask.
+use anyhow::Result;
+
+use crate::{
+    engine::Rect,
+    platform::{Bitmap, ImageSource},
+};
+
+// Pixels more transparent than this don't count as solid
+const ALPHA_THRESHOLD: u8 = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    columns: usize,
+    rows: usize,
+    // In pixels a side
+    cell: u32,
+    solid: Vec<bool>,
+}
+
+impl Mask {
+    // From RGBA bytes, row by row. A cell is solid when any pixel in it is.
+    pub fn from_alpha(width: u32, height: u32, rgba: &[u8], cell: u32) -> Self {
+        let cell = cell.max(1);
+        let columns = width.div_ceil(cell) as usize;
+        let rows = height.div_ceil(cell) as usize;
+        let mut solid = vec![false; columns * rows];
+        for y in 0..height {
+            for x in 0..width {
+                let alpha = rgba
+                    .get(((y * width + x) * 4 + 3) as usize)
+                    .copied()
+                    .unwrap_or(0);
+                if alpha >= ALPHA_THRESHOLD {
+                    solid[(y / cell) as usize * columns + (x / cell) as usize] = true;
+                }
+            }
+        }
+        Mask {
+            columns,
+            rows,
+            cell,
+            solid,
+        }
+    }
+
+    // For the part of the image inside frame, drawn at its own size
+    pub fn load(image: &Bitmap, frame: &Rect, cell: u32) -> Result<Self> {
+        let pixels = image.pixels(frame)?;
+        Ok(Mask::from_alpha(
+            frame.width as u32,
+            frame.height as u32,
+            &pixels,
+            cell,
+        ))
+    }
+
+    // With the mask's top left at that of bounds, whether a solid cell
+    // overlaps the rect
+    pub fn overlaps_rect(&self, bounds: &Rect, rect: &Rect) -> bool {
+        let overlap = match bounds.intersection(rect) {
+            Some(overlap) => overlap,
+            None => return false,
+        };
+        let cell = self.cell as f32;
+        let first_column = ((overlap.x - bounds.x) / cell).floor().max(0.0) as usize;
+        let first_row = ((overlap.y - bounds.y) / cell).floor().max(0.0) as usize;
+        let last_column = ((overlap.x + overlap.width - bounds.x) / cell).ceil() as usize;
+        let last_row = ((overlap.y + overlap.height - bounds.y) / cell).ceil() as usize;
+        (first_row..last_row.min(self.rows)).any(|row| {
+            (first_column..last_column.min(self.columns))
+                .any(|column| self.solid[row * self.columns + column])
+        })
+    }
+
+    // Both masks placed at the top left of their bounds
+    pub fn overlaps(&self, bounds: &Rect, other: &Mask, other_bounds: &Rect) -> bool {
+        if !bounds.intersects(other_bounds) {
+            return false;
+        }
+        let cell = self.cell as f32;
+        (0..self.rows).any(|row| {
+            (0..self.columns).any(|column| {
+                self.solid[row * self.columns + column]
+                    && other.overlaps_rect(
+                        other_bounds,
+                        &Rect {
+                            x: bounds.x + column as f32 * cell,
+                            y: bounds.y + row as f32 * cell,
+                            width: cell,
+                            height: cell,
+                        },
+                    )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A square with only its top left quarter solid
+    fn corner(size: u32) -> Vec<u8> {
+        (0..size * size)
+            .flat_map(|pixel| {
+                let (x, y) = (pixel % size, pixel / size);
+                let alpha = if x < size / 2 && y < size / 2 { 255 } else { 0 };
+                vec![0, 0, 0, alpha]
+            })
+            .collect()
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn downsamples_alpha_into_cells() {
+        let mask = Mask::from_alpha(8, 8, &corner(8), 2);
+
+        assert_eq!((mask.columns, mask.rows), (4, 4));
+        assert_eq!(mask.solid.iter().filter(|solid| **solid).count(), 4);
+    }
+
+    #[test]
+    fn only_the_solid_part_collides() {
+        let mask = Mask::from_alpha(8, 8, &corner(8), 2);
+        let bounds = rect(100.0, 100.0, 8.0, 8.0);
+
+        assert!(mask.overlaps_rect(&bounds, &rect(96.0, 96.0, 6.0, 6.0)));
+        assert!(!mask.overlaps_rect(&bounds, &rect(105.0, 105.0, 10.0, 10.0)));
+        assert!(!mask.overlaps_rect(&bounds, &rect(0.0, 0.0, 10.0, 10.0)));
+    }
+
+    #[test]
+    fn masks_collide_where_both_are_solid() {
+        let mask = Mask::from_alpha(8, 8, &corner(8), 2);
+        let bounds = rect(0.0, 0.0, 8.0, 8.0);
+
+        assert!(mask.overlaps(&bounds, &mask, &rect(2.0, 2.0, 8.0, 8.0)));
+        assert!(!mask.overlaps(&bounds, &mask, &rect(5.0, 5.0, 8.0, 8.0)));
+    }
+}
@@ -0,0 +1,278 @@
+// Connects two players through a relay server over a WebSocket, for racing,
+// spectating and chat. The server only has to pass each message on to the
+// other client in the same room. Both ends ping each other and answer each
+// other's pings, and stamp the states they send with their own clock, so the
+// ClockSync here estimates the other player's clock rather than the server's.
+//
+// Messages are JSON text frames. What arrives is queued until the game's next
+// update, like input, and what the game sends goes out on that update too.
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, WebSocket};
+
+use crate::{
+    browser,
+    chat::{Chat, ChatMessage},
+    clock::ClockSync,
+    platform,
+};
+
+const PING_MS: f64 = 1000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Message<S> {
+    Ping { sent: f64 },
+    // Sent is the ping's, and time is when it was answered
+    Pong { sent: f64, time: f64 },
+    Chat { message: ChatMessage },
+    // What the game shares every update, like where its player is
+    State { time: f64, state: S },
+}
+
+// Everything about a session apart from the socket, so it can be tested
+// without one. Times are in milliseconds from platform::now.
+struct Peer<S> {
+    clock: ClockSync,
+    chat: Chat,
+    // With the time the other end sent them, by its clock
+    states: VecDeque<(f64, S)>,
+    last_ping: Option<f64>,
+    outbox: Vec<Message<S>>,
+}
+
+impl<S> Peer<S> {
+    fn new(name: &str) -> Self {
+        Peer {
+            clock: ClockSync::default(),
+            chat: Chat::new(name),
+            states: VecDeque::new(),
+            last_ping: None,
+            outbox: vec![],
+        }
+    }
+
+    fn receive(&mut self, message: Message<S>, now: f64) {
+        match message {
+            Message::Ping { sent } => self.outbox.push(Message::Pong { sent, time: now }),
+            Message::Pong { sent, time } => self.clock.pong_received(sent, time, now),
+            Message::Chat { message } => self.chat.receive(message),
+            Message::State { time, state } => self.states.push_back((time, state)),
+        }
+    }
+
+    fn tick(&mut self, now: f64) {
+        if !matches!(self.last_ping, Some(last) if now - last < PING_MS) {
+            self.last_ping = Some(now);
+            self.outbox.push(Message::Ping { sent: now });
+        }
+    }
+
+    // The states that arrived, oldest first, leaving out any sent longer
+    // than max_age ago. Until the clocks are synced there's no telling, so
+    // everything is kept.
+    fn states(&mut self, now: f64, max_age: f64) -> Vec<S> {
+        let remote_now = self.clock.remote_time(now);
+        self.states
+            .drain(..)
+            .filter(
+                |(time, _)| !matches!(remote_now, Some(remote_now) if remote_now - time > max_age),
+            )
+            .map(|(_, state)| state)
+            .collect()
+    }
+}
+
+struct Connection {
+    socket: WebSocket,
+    received: UnboundedReceiver<String>,
+    _on_message: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Connection {
+    fn open(url: &str) -> Result<Self> {
+        if cfg!(feature = "native") {
+            return Err(anyhow!("No WebSockets outside the browser"));
+        }
+        let socket =
+            WebSocket::new(url).map_err(|err| anyhow!("Error opening WebSocket {:#?}", err))?;
+        let (sender, received) = unbounded();
+        let on_message = browser::closure_wrap(Box::new(move |event: JsValue| {
+            if let Some(text) = event
+                .dyn_into::<MessageEvent>()
+                .ok()
+                .and_then(|event| event.data().as_string())
+            {
+                let _ = sender.unbounded_send(text);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(Connection {
+            socket,
+            received,
+            _on_message: on_message,
+        })
+    }
+
+    fn is_open(&self) -> bool {
+        self.socket.ready_state() == WebSocket::OPEN
+    }
+
+    fn send(&self, text: &str) -> Result<()> {
+        self.socket
+            .send_with_str(text)
+            .map_err(|err| anyhow!("Error sending on WebSocket {:#?}", err))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.socket.close();
+    }
+}
+
+pub struct Session<S> {
+    connection: Connection,
+    peer: Peer<S>,
+}
+
+impl<S: Serialize + DeserializeOwned> Session<S> {
+    // Name is who chat messages from this end are from
+    pub fn connect(url: &str, name: &str) -> Result<Self> {
+        Ok(Session {
+            connection: Connection::open(url)?,
+            peer: Peer::new(name),
+        })
+    }
+
+    // Call once per update. Messages the game sent since the last one are
+    // held until the socket opens.
+    pub fn update(&mut self) -> Result<()> {
+        let now = platform::now()?;
+        while let Ok(text) = self.connection.received.try_recv() {
+            match serde_json::from_str(&text) {
+                Ok(message) => self.peer.receive(message, now),
+                Err(err) => {
+                    log!("Could not read message {} {:#?}", text, err);
+                }
+            }
+        }
+        if !self.connection.is_open() {
+            return Ok(());
+        }
+
+        self.peer.tick(now);
+        for message in self.peer.outbox.drain(..) {
+            self.connection.send(&serde_json::to_string(&message)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn send_state(&mut self, state: S) -> Result<()> {
+        let time = platform::now()?;
+        self.peer.outbox.push(Message::State { time, state });
+        Ok(())
+    }
+
+    // Fails if the message is empty or the player's sending too quickly
+    pub fn send_chat(&mut self, text: &str) -> Result<()> {
+        let message = self.peer.chat.send(text, platform::now()?)?;
+        self.peer.outbox.push(Message::Chat { message });
+        Ok(())
+    }
+
+    // The states the other end sent since the last call, leaving out any
+    // older than max_age milliseconds by its clock
+    pub fn states(&mut self, max_age: f64) -> Result<Vec<S>> {
+        Ok(self.peer.states(platform::now()?, max_age))
+    }
+
+    pub fn chat(&self) -> &Chat {
+        &self.peer.chat
+    }
+
+    pub fn clock(&self) -> &ClockSync {
+        &self.peer.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_pings_with_the_time() {
+        let mut peer: Peer<u32> = Peer::new("rhb");
+
+        peer.receive(Message::Ping { sent: 10.0 }, 500.0);
+
+        assert_eq!(
+            peer.outbox,
+            vec![Message::Pong {
+                sent: 10.0,
+                time: 500.0
+            }]
+        );
+    }
+
+    #[test]
+    fn pings_once_a_second() {
+        let mut peer: Peer<u32> = Peer::new("rhb");
+
+        for now in (0..2500).step_by(100) {
+            peer.tick(f64::from(now));
+        }
+
+        assert_eq!(peer.outbox.len(), 3);
+    }
+
+    #[test]
+    fn drops_states_too_old_by_the_other_clock() {
+        let mut peer = Peer::new("rhb");
+        // The other clock is 1000ms ahead
+        peer.receive(
+            Message::Pong {
+                sent: 0.0,
+                time: 1010.0,
+            },
+            20.0,
+        );
+        peer.receive(
+            Message::State {
+                time: 900.0,
+                state: 1,
+            },
+            100.0,
+        );
+        peer.receive(
+            Message::State {
+                time: 1090.0,
+                state: 2,
+            },
+            100.0,
+        );
+
+        assert_eq!(peer.states(100.0, 50.0), vec![2]);
+    }
+
+    #[test]
+    fn messages_round_trip_as_json() {
+        let message: Message<u32> = Message::State {
+            time: 1.5,
+            state: 7,
+        };
+
+        let json = serde_json::to_string(&message).unwrap();
+
+        assert_eq!(json, r#"{"type":"state","time":1.5,"state":7}"#);
+        assert_eq!(
+            serde_json::from_str::<Message<u32>>(&json).unwrap(),
+            message
+        );
+    }
+}
@@ -0,0 +1,44 @@
+// Lets the game start without a network. The game lists its assets in a
+// manifest, and once that's loaded a service worker is asked to cache them
+// all, so the next visit can be served from the cache. The page has to
+// register a worker that understands the "precache" message; see
+// static/sw.js in the game for one.
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::platform;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssetManifest {
+    // Paths relative to the page
+    pub assets: Vec<String>,
+}
+
+// Loads the manifest and hands its assets to the service worker. Caching
+// happens in the background, and a failure there is only logged, since the
+// game runs fine without it.
+pub async fn load_manifest(path: &str) -> Result<AssetManifest> {
+    let manifest: AssetManifest = platform::load_json(path).await?;
+    platform::precache(&manifest.assets)?;
+    Ok(manifest)
+}
+
+// Whether the browser thinks there's a network. Games hear about changes
+// through EngineEvent::Online and EngineEvent::Offline.
+pub fn is_online() -> bool {
+    platform::is_online().unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_asset_list() {
+        let manifest: AssetManifest =
+            serde_json::from_str(r#"{"assets": ["index.html", "rhb.png"]}"#)
+                .expect("Could not parse manifest");
+
+        assert_eq!(manifest.assets, vec!["index.html", "rhb.png"]);
+    }
+}
@@ -0,0 +1,144 @@
+// Moves something along a list of waypoints at a fixed speed, one fixed step
+// per update. Waypoints are relative to wherever the owner started, and
+// update() returns how far it moved this step, so a moving platform can carry
+// whatever is standing on it and a camera can pan by the same amount.
+use crate::engine::Point;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathMode {
+    // Stops at the last waypoint
+    Once,
+    // Goes from the last waypoint straight back to the first
+    Loop,
+    // Turns around at either end and retraces its steps
+    PingPong,
+}
+
+#[derive(Clone)]
+pub struct Path {
+    waypoints: Vec<Point>,
+    speed: i16,
+    mode: PathMode,
+    next: usize,
+    forward: bool,
+    finished: bool,
+    offset: Point,
+}
+
+impl Path {
+    // Speed is in pixels per update along each axis. Paths loop unless given
+    // another mode.
+    pub fn new(waypoints: Vec<Point>, speed: i16) -> Self {
+        Path {
+            waypoints,
+            speed,
+            mode: PathMode::Loop,
+            next: 0,
+            forward: true,
+            finished: false,
+            offset: Point { x: 0, y: 0 },
+        }
+    }
+
+    pub fn mode(mut self, mode: PathMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // How far from the start the path has moved
+    pub fn offset(&self) -> Point {
+        self.offset
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn update(&mut self) -> Point {
+        let target = match self.waypoints.get(self.next) {
+            Some(target) if !self.finished => *target,
+            _ => return Point { x: 0, y: 0 },
+        };
+
+        let delta = Point {
+            x: (target.x - self.offset.x).clamp(-self.speed, self.speed),
+            y: (target.y - self.offset.y).clamp(-self.speed, self.speed),
+        };
+        self.offset.x += delta.x;
+        self.offset.y += delta.y;
+
+        if self.offset.x == target.x && self.offset.y == target.y {
+            self.advance();
+        }
+        delta
+    }
+
+    fn advance(&mut self) {
+        let last = self.waypoints.len() - 1;
+        match self.mode {
+            PathMode::Once if self.next == last => self.finished = true,
+            PathMode::Once => self.next += 1,
+            PathMode::Loop => self.next = (self.next + 1) % self.waypoints.len(),
+            PathMode::PingPong if last == 0 => {}
+            PathMode::PingPong => {
+                if self.next == last {
+                    self.forward = false;
+                } else if self.next == 0 {
+                    self.forward = true;
+                }
+                self.next = if self.forward {
+                    self.next + 1
+                } else {
+                    self.next - 1
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(mut path: Path, updates: usize) -> Vec<(i16, i16)> {
+        (0..updates)
+            .map(|_| {
+                path.update();
+                (path.offset().x, path.offset().y)
+            })
+            .collect()
+    }
+
+    fn waypoints() -> Vec<Point> {
+        vec![
+            Point { x: 0, y: 0 },
+            Point { x: 10, y: 0 },
+            Point { x: 10, y: 10 },
+        ]
+    }
+
+    #[test]
+    fn loops_back_to_the_first_waypoint() {
+        let offsets = offsets(Path::new(waypoints(), 10), 5);
+
+        assert_eq!(offsets, vec![(0, 0), (10, 0), (10, 10), (0, 0), (10, 0)]);
+    }
+
+    #[test]
+    fn ping_pong_retraces_its_steps() {
+        let offsets = offsets(Path::new(waypoints(), 10).mode(PathMode::PingPong), 5);
+
+        assert_eq!(offsets, vec![(0, 0), (10, 0), (10, 10), (10, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn once_stops_at_the_end() {
+        let mut path = Path::new(waypoints(), 5).mode(PathMode::Once);
+        for _ in 0..10 {
+            path.update();
+        }
+
+        assert_eq!((path.offset().x, path.offset().y), (10, 10));
+        assert!(path.is_finished());
+    }
+}
@@ -0,0 +1,131 @@
+// Everything the engine needs from whatever it's running on: a window to draw
+// in and take input from, timing frames, decoding images, loading assets,
+// storage, the clock, and a few facts about the player. The web backend is
+// the default, and building with the `native` feature swaps in one that opens
+// a desktop window with winit, draws into it in software and reads from the
+// filesystem, so the same game code can be run and debugged natively.
+//
+// Each backend provides the same names: a Context the renderer draws on, the
+// Bitmap images it draws, the Input the game loop reads, and functions for the
+// rest. The traits here are what the engine relies on them to do.
+//
+// Sound, gamepads, the HTML UI layer and networking are still web only, and
+// natively they stay silent or fail with an error.
+
+// Straight taken from https://rustwasm.github.io/book/game-of-life/debugging.html,
+// and printed natively
+#[macro_export]
+macro_rules! log {
+    ( $( $t:tt )* ) => {
+        $crate::platform::log(&format!( $( $t )* ));
+    }
+}
+
+#[cfg(feature = "native")]
+mod native;
+#[cfg(feature = "native")]
+mod raster;
+#[cfg(not(feature = "native"))]
+mod web;
+
+use anyhow::Result;
+
+use crate::{
+    engine::{Point, Rect, Vector},
+    viewport::Transform,
+};
+
+#[cfg(feature = "native")]
+pub use self::native::*;
+#[cfg(not(feature = "native"))]
+pub use self::web::*;
+
+// A decoded image, cheap to clone so everything drawn from it can share it
+pub trait ImageSource: Clone {
+    // In pixels
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    // The part inside frame as RGBA bytes, row by row
+    fn pixels(&self, frame: &Rect) -> Result<Vec<u8>>;
+}
+
+// What the renderer draws on, like the canvas's 2D context. Everything is
+// drawn through the current transform, and colors are CSS colors.
+pub trait Canvas {
+    type Image: ImageSource;
+
+    // In pixels
+    fn size(&self) -> (u32, u32);
+    // Resizes to however many device pixels it's shown at, so it stays sharp
+    // on high DPI screens
+    fn match_device_pixels(&self);
+    // From where a pointer event says it was to canvas pixels
+    fn client_to_canvas(&self, point: Vector) -> Vector;
+    fn set_transform(&self, transform: &Transform);
+    // Off for pixel art, so scaled images aren't blurred
+    fn set_smoothing(&self, smoothing: bool);
+    // The transform, alpha and filter, to put back with restore
+    fn save(&self);
+    fn restore(&self);
+    fn alpha(&self) -> f32;
+    // How opaque everything drawn afterwards is, from 0.0 to 1.0
+    fn set_alpha(&self, alpha: f32);
+    fn filter(&self) -> String;
+    fn set_filter(&self, filter: &str);
+    fn clear_rect(&self, rect: &Rect);
+    fn fill_rect(&self, color: &str, rect: &Rect);
+    fn stroke_rect(&self, color: &str, rect: &Rect);
+    fn stroke_circle(&self, color: &str, center: &Point, radius: f32);
+    // From the left end of the baseline, in bold
+    fn fill_text(&self, text: &str, position: &Point, color: &str, size: u8);
+    // The frame of the image, stretched over the destination
+    fn draw_image(&self, image: &Self::Image, frame: &Rect, destination: &Rect);
+
+    fn draw_whole_image(&self, image: &Self::Image, position: &Point) {
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        self.draw_image(
+            image,
+            &Rect {
+                x: 0.0,
+                y: 0.0,
+                width,
+                height,
+            },
+            &Rect {
+                x: position.x.into(),
+                y: position.y.into(),
+                width,
+                height,
+            },
+        );
+    }
+
+    // Shows the frame, for backends where that doesn't happen by itself
+    fn present(&self) {}
+    // RGBA bytes row by row, with the width and height
+    fn pixels(&self) -> (u32, u32, Vec<u8>);
+}
+
+// What the player did with the keyboard, mouse or touch screen. Key codes are
+// the browser's KeyboardEvent.code names, like "ArrowLeft" or "KeyA", and
+// times are in milliseconds on the same clock as now.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    KeyDown {
+        code: String,
+        repeat: bool,
+        time: f64,
+    },
+    KeyUp {
+        code: String,
+    },
+    // Where, in client coordinates, and when
+    PointerDown(Vector, f64),
+    PointerUp(Vector, f64),
+    PointerCancel,
+}
+
+pub trait InputSource {
+    // Everything since the last call, oldest first
+    fn poll(&mut self) -> Vec<InputEvent>;
+}
@@ -0,0 +1,614 @@
+// The desktop backend. The game runs in a window from winit, which owns the
+// main thread once run is called, so frames, input and spawned tasks all come
+// from its event loop. Drawing is done in software by raster and copied to the
+// window with softbuffer, and images are decoded with the image crate.
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    env, fs,
+    io::ErrorKind,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use futures::{
+    executor::{LocalPool, LocalSpawner},
+    task::LocalSpawnExt,
+    Future,
+};
+use serde::de::DeserializeOwned;
+use softbuffer::Surface;
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, MouseButton, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::PhysicalKey,
+    window::{Window, WindowBuilder},
+};
+
+use super::{
+    raster::{self, Pixmap},
+    Canvas, ImageSource, InputEvent, InputSource,
+};
+use crate::{
+    config::QueryParams,
+    engine::{Point, Rect, Vector},
+    viewport::Transform,
+};
+
+// Relative to the directory the game is run from
+const ASSET_DIR: &str = "static";
+const SAVE_DIR: &str = "saves";
+// What the canvas on the page starts at. The window can be resized, and the
+// viewport scales the game to fit.
+const WINDOW_SIZE: (f64, f64) = (600.0, 600.0);
+// requestAnimationFrame's usual rate
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+thread_local! {
+    // Spawning only needs the spawner, so tasks can spawn more while the pool
+    // is running them
+    static TASKS: (RefCell<LocalPool>, LocalSpawner) = {
+        let pool = LocalPool::new();
+        let spawner = pool.spawner();
+        (RefCell::new(pool), spawner)
+    };
+    // The frame asked for and its id
+    static FRAME: RefCell<Option<(i32, FrameCallback)>> = const { RefCell::new(None) };
+    static NEXT_FRAME_ID: Cell<i32> = const { Cell::new(1) };
+    // Kept here between open_window and run
+    static WINDOW: RefCell<Option<(EventLoop<()>, Rc<Window>)>> = const { RefCell::new(None) };
+    // What the player did since the game loop last polled
+    static INPUT: RefCell<VecDeque<InputEvent>> = const { RefCell::new(VecDeque::new()) };
+}
+
+pub async fn load_json<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let json = fs::read_to_string(asset_path(path))?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+// Each key is saved to its own file
+pub fn get_item(key: &str) -> Result<Option<String>> {
+    match fs::read_to_string(save_path(key)) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(anyhow!("Error reading {} {:#?}", key, err)),
+    }
+}
+
+pub fn set_item(key: &str, value: &str) -> Result<()> {
+    fs::create_dir_all(SAVE_DIR)?;
+    fs::write(save_path(key), value).map_err(|err| anyhow!("Error writing {} {:#?}", key, err))
+}
+
+// In milliseconds
+pub fn now() -> Result<f64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64() * 1000.0)
+}
+
+// Today's date in UTC as (year, month, day), with January as month 1
+pub fn utc_date() -> (u32, u32, u32) {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+    civil_from_days(days)
+}
+
+// From LANG, e.g. es_ES.UTF-8 becomes es-ES like navigator.language
+pub fn language() -> Result<String> {
+    let lang = env::var("LANG")?;
+    let locale = lang.split('.').next().unwrap_or(&lang);
+    Ok(locale.replace('_', "-"))
+}
+
+// The QUERY environment variable stands in for the query string, e.g.
+// QUERY="seed=42&muted"
+pub fn query_params() -> Result<QueryParams> {
+    Ok(QueryParams::parse(&env::var("QUERY").unwrap_or_default()))
+}
+
+// The first argument stands in for the URL hash, e.g. #daily
+pub fn location_hash() -> Result<String> {
+    Ok(env::args().nth(1).unwrap_or_default())
+}
+
+pub fn page_url() -> Result<String> {
+    Err(anyhow!("There's no page URL outside the browser"))
+}
+
+// Assets are always on disk
+pub fn is_online() -> Result<bool> {
+    Ok(true)
+}
+
+pub fn precache(_assets: &[String]) -> Result<()> {
+    Ok(())
+}
+
+// There's nowhere to report to outside the browser, so beacons are dropped
+pub fn send_beacon(_url: &str, _body: &str) -> Result<()> {
+    Ok(())
+}
+
+pub fn stack_trace() -> Option<String> {
+    None
+}
+
+// Printed to stdout
+pub fn log(message: &str) {
+    println!("{}", message);
+}
+
+// Run on the main thread between frames
+pub fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    TASKS.with(|(_, spawner)| {
+        if let Err(err) = spawner.spawn_local(future) {
+            log!("Could not spawn task {:#?}", err);
+        }
+    });
+}
+
+pub fn save_screenshot(_filename: &str) -> Result<()> {
+    Err(anyhow!("Screenshots are only saved in the browser"))
+}
+
+// Frames come from the event loop, at most as often as requestAnimationFrame
+// would call them, with the time from now
+#[derive(Clone)]
+pub struct FrameCallback(Rc<RefCell<dyn FnMut(f64)>>);
+
+pub fn frame_callback(f: impl FnMut(f64) + 'static) -> FrameCallback {
+    FrameCallback(Rc::new(RefCell::new(f)))
+}
+
+// Returns the id to cancel the frame with. Only the latest frame asked for is
+// called.
+pub fn request_frame(callback: &FrameCallback) -> Result<i32> {
+    let id = NEXT_FRAME_ID.with(|next| next.replace(next.get() + 1));
+    FRAME.with(|frame| frame.replace(Some((id, callback.clone()))));
+    Ok(id)
+}
+
+pub fn cancel_frame(id: i32) -> Result<()> {
+    FRAME.with(|frame| {
+        let mut frame = frame.borrow_mut();
+        if matches!(&*frame, Some((pending, _)) if *pending == id) {
+            *frame = None;
+        }
+    });
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct Bitmap(Rc<Pixmap>);
+
+impl ImageSource for Bitmap {
+    fn width(&self) -> u32 {
+        self.0.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.0.height()
+    }
+
+    fn pixels(&self, frame: &Rect) -> Result<Vec<u8>> {
+        Ok(self.0.crop(frame))
+    }
+}
+
+// Read from the asset directory, like the JSON
+pub async fn load_image(source: &str) -> Result<Bitmap> {
+    let bytes = fs::read(asset_path(source))
+        .map_err(|err| anyhow!("Error loading {} {:#?}", source, err))?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|err| anyhow!("Error decoding {} {:#?}", source, err))?
+        .into_rgba8();
+    let pixmap = Pixmap::from_rgba(image.width(), image.height(), image.into_raw())
+        .ok_or_else(|| anyhow!("Error decoding {}", source))?;
+    Ok(Bitmap(Rc::new(pixmap)))
+}
+
+// What save and restore keep
+#[derive(Clone)]
+struct DrawState {
+    transform: Transform,
+    alpha: f32,
+    filter: String,
+}
+
+struct Output {
+    window: Rc<Window>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+}
+
+// Draws into a pixmap the size of the window, which is copied to the window
+// when the frame's presented. The pixmap follows the window's size from the
+// frame after it changes.
+pub struct Context {
+    // None when offscreen
+    output: Option<RefCell<Output>>,
+    pixmap: RefCell<Pixmap>,
+    state: RefCell<DrawState>,
+    saved: RefCell<Vec<DrawState>>,
+}
+
+impl Context {
+    // Drawing only into its pixels, for tests
+    pub fn offscreen(width: u32, height: u32) -> Self {
+        Context {
+            output: None,
+            pixmap: RefCell::new(Pixmap::new(width, height)),
+            state: RefCell::new(DrawState {
+                transform: Transform::IDENTITY,
+                alpha: 1.0,
+                filter: "none".to_string(),
+            }),
+            saved: RefCell::new(vec![]),
+        }
+    }
+
+    fn for_window(window: Rc<Window>) -> Result<Self> {
+        let display = softbuffer::Context::new(Rc::clone(&window))
+            .map_err(|err| anyhow!("Error opening display {:#?}", err))?;
+        let surface = Surface::new(&display, Rc::clone(&window))
+            .map_err(|err| anyhow!("Error creating surface {:#?}", err))?;
+        let size = window.inner_size();
+        Ok(Context {
+            output: Some(RefCell::new(Output { window, surface })),
+            ..Context::offscreen(size.width, size.height)
+        })
+    }
+
+    // In pixels
+    fn to_device(&self, rect: &Rect) -> Rect {
+        let transform = self.state.borrow().transform;
+        let (left, top) = transform.apply(rect.x, rect.y);
+        let (right, bottom) = transform.apply(rect.x + rect.width, rect.y + rect.height);
+        Rect {
+            x: left.min(right),
+            y: top.min(bottom),
+            width: (right - left).abs(),
+            height: (bottom - top).abs(),
+        }
+    }
+
+    fn fill(&self, color: &str, rect: &Rect) {
+        if let Some(color) = raster::parse_color(color) {
+            let alpha = self.state.borrow().alpha;
+            self.pixmap
+                .borrow_mut()
+                .fill(&self.to_device(rect), color, alpha);
+        }
+    }
+
+    fn show(&self, output: &mut Output) -> Result<()> {
+        let pixmap = self.pixmap.borrow();
+        let (width, height) = match (
+            NonZeroU32::new(pixmap.width()),
+            NonZeroU32::new(pixmap.height()),
+        ) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Ok(()),
+        };
+        let surface = &mut output.surface;
+        surface
+            .resize(width, height)
+            .map_err(|err| anyhow!("Error resizing surface {:#?}", err))?;
+        let mut buffer = surface
+            .buffer_mut()
+            .map_err(|err| anyhow!("Error getting surface buffer {:#?}", err))?;
+        for (pixel, rgba) in buffer.iter_mut().zip(pixmap.data().chunks_exact(4)) {
+            *pixel = u32::from(rgba[0]) << 16 | u32::from(rgba[1]) << 8 | u32::from(rgba[2]);
+        }
+        buffer
+            .present()
+            .map_err(|err| anyhow!("Error presenting surface {:#?}", err))
+    }
+}
+
+impl Canvas for Context {
+    type Image = Bitmap;
+
+    fn size(&self) -> (u32, u32) {
+        let pixmap = self.pixmap.borrow();
+        (pixmap.width(), pixmap.height())
+    }
+
+    // The window's size is already in device pixels
+    fn match_device_pixels(&self) {}
+
+    // Cursor positions are already in window pixels
+    fn client_to_canvas(&self, point: Vector) -> Vector {
+        point
+    }
+
+    fn set_transform(&self, transform: &Transform) {
+        self.state.borrow_mut().transform = *transform;
+    }
+
+    // Images are always sampled from the nearest pixel
+    fn set_smoothing(&self, _smoothing: bool) {}
+
+    fn save(&self) {
+        let state = self.state.borrow().clone();
+        self.saved.borrow_mut().push(state);
+    }
+
+    fn restore(&self) {
+        if let Some(state) = self.saved.borrow_mut().pop() {
+            *self.state.borrow_mut() = state;
+        }
+    }
+
+    fn alpha(&self) -> f32 {
+        self.state.borrow().alpha
+    }
+
+    fn set_alpha(&self, alpha: f32) {
+        self.state.borrow_mut().alpha = alpha;
+    }
+
+    // Kept so it reads back, but not drawn with
+    fn filter(&self) -> String {
+        self.state.borrow().filter.clone()
+    }
+
+    fn set_filter(&self, filter: &str) {
+        self.state.borrow_mut().filter = filter.to_string();
+    }
+
+    fn clear_rect(&self, rect: &Rect) {
+        self.pixmap.borrow_mut().clear(&self.to_device(rect));
+    }
+
+    fn fill_rect(&self, color: &str, rect: &Rect) {
+        self.fill(color, rect);
+    }
+
+    // One unit wide, centered on the edges like the canvas's lines
+    fn stroke_rect(&self, color: &str, rect: &Rect) {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = *rect;
+        let edges = [
+            (x - 0.5, y - 0.5, width + 1.0, 1.0),
+            (x - 0.5, y + height - 0.5, width + 1.0, 1.0),
+            (x - 0.5, y + 0.5, 1.0, height - 1.0),
+            (x + width - 0.5, y + 0.5, 1.0, height - 1.0),
+        ];
+        for &(x, y, width, height) in edges.iter() {
+            self.fill(
+                color,
+                &Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+            );
+        }
+    }
+
+    fn stroke_circle(&self, color: &str, center: &Point, radius: f32) {
+        let steps = (std::f32::consts::PI * 2.0 * radius).ceil().max(8.0) as u32;
+        for step in 0..steps {
+            let angle = std::f32::consts::PI * 2.0 * step as f32 / steps as f32;
+            self.fill(
+                color,
+                &Rect {
+                    x: f32::from(center.x) + radius * angle.cos() - 0.5,
+                    y: f32::from(center.y) + radius * angle.sin() - 0.5,
+                    width: 1.0,
+                    height: 1.0,
+                },
+            );
+        }
+    }
+
+    // In the built-in font, at roughly the cap height a sans-serif font would
+    // have at that size
+    fn fill_text(&self, text: &str, position: &Point, color: &str, size: u8) {
+        let dot = f32::from(size) / 7.0;
+        let top = f32::from(position.y) - dot * f32::from(raster::GLYPH_HEIGHT);
+        for (index, character) in text.chars().enumerate() {
+            let glyph = match raster::glyph(character) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let left =
+                f32::from(position.x) + index as f32 * dot * f32::from(raster::GLYPH_WIDTH + 1);
+            for row in 0..raster::GLYPH_HEIGHT {
+                for column in 0..raster::GLYPH_WIDTH {
+                    if raster::is_lit(glyph, column, row) {
+                        self.fill(
+                            color,
+                            &Rect {
+                                x: left + dot * f32::from(column),
+                                y: top + dot * f32::from(row),
+                                width: dot,
+                                height: dot,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_image(&self, image: &Bitmap, frame: &Rect, destination: &Rect) {
+        let alpha = self.state.borrow().alpha;
+        self.pixmap
+            .borrow_mut()
+            .draw(&image.0, frame, &self.to_device(destination), alpha);
+    }
+
+    fn present(&self) {
+        let mut output = match &self.output {
+            Some(output) => output.borrow_mut(),
+            None => return,
+        };
+        if let Err(err) = self.show(&mut output) {
+            log!("Could not show the frame {:#?}", err);
+        }
+        let size = output.window.inner_size();
+        if (size.width, size.height) != self.size() {
+            *self.pixmap.borrow_mut() = Pixmap::new(size.width, size.height);
+        }
+    }
+
+    fn pixels(&self) -> (u32, u32, Vec<u8>) {
+        let pixmap = self.pixmap.borrow();
+        (pixmap.width(), pixmap.height(), pixmap.data().to_vec())
+    }
+}
+
+// Queued by the event loop
+pub struct Input;
+
+impl InputSource for Input {
+    fn poll(&mut self) -> Vec<InputEvent> {
+        INPUT.with(|input| input.borrow_mut().drain(..).collect())
+    }
+}
+
+// A window titled after the program, which shows once run is called. There's
+// only ever one, so the canvas id isn't needed.
+pub fn open_window(_canvas_id: &str) -> Result<(Context, Input)> {
+    let event_loop =
+        EventLoop::new().map_err(|err| anyhow!("Error creating event loop {:#?}", err))?;
+    let window = WindowBuilder::new()
+        .with_title(title())
+        .with_inner_size(LogicalSize::new(WINDOW_SIZE.0, WINDOW_SIZE.1))
+        .build(&event_loop)
+        .map_err(|err| anyhow!("Error opening window {:#?}", err))?;
+    let window = Rc::new(window);
+    let context = Context::for_window(Rc::clone(&window))?;
+    WINDOW.with(|opened| opened.replace(Some((event_loop, window))));
+    Ok((context, Input))
+}
+
+// Runs the tasks spawned so far, which should start the game loop, then the
+// window it opened until it's closed. Natively, main calls this after the
+// game's start function, and it doesn't return until the game's over.
+pub fn run() -> Result<()> {
+    run_tasks();
+    let (event_loop, window) = WINDOW
+        .with(|opened| opened.borrow_mut().take())
+        .ok_or_else(|| anyhow!("No window was opened to run"))?;
+    let mut cursor = Vector { x: 0.0, y: 0.0 };
+    let mut last_frame = Instant::now();
+    event_loop
+        .run(move |event, target| match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => target.exit(),
+                WindowEvent::RedrawRequested => {
+                    last_frame = Instant::now();
+                    run_frame();
+                }
+                WindowEvent::KeyboardInput { event, .. } => {
+                    if let PhysicalKey::Code(code) = event.physical_key {
+                        // winit's key names are the browser's
+                        let code = format!("{:?}", code);
+                        queue_input(match event.state {
+                            ElementState::Pressed => InputEvent::KeyDown {
+                                code,
+                                repeat: event.repeat,
+                                time: now().unwrap_or_default(),
+                            },
+                            ElementState::Released => InputEvent::KeyUp { code },
+                        });
+                    }
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    cursor = Vector {
+                        x: position.x as f32,
+                        y: position.y as f32,
+                    };
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    let time = now().unwrap_or_default();
+                    queue_input(match state {
+                        ElementState::Pressed => InputEvent::PointerDown(cursor, time),
+                        ElementState::Released => InputEvent::PointerUp(cursor, time),
+                    });
+                }
+                _ => {}
+            },
+            Event::AboutToWait => {
+                run_tasks();
+                let next_frame = last_frame + FRAME_TIME;
+                if Instant::now() >= next_frame && FRAME.with(|frame| frame.borrow().is_some()) {
+                    window.request_redraw();
+                }
+                target.set_control_flow(ControlFlow::WaitUntil(next_frame));
+            }
+            _ => {}
+        })
+        .map_err(|err| anyhow!("Error running event loop {:#?}", err))
+}
+
+fn run_tasks() {
+    TASKS.with(|(pool, _)| pool.borrow_mut().run_until_stalled());
+}
+
+fn run_frame() {
+    let pending = FRAME.with(|frame| frame.borrow_mut().take());
+    if let Some((_, callback)) = pending {
+        let now = now().unwrap_or_default();
+        (callback.0.borrow_mut())(now);
+    }
+}
+
+fn queue_input(event: InputEvent) {
+    INPUT.with(|input| input.borrow_mut().push_back(event));
+}
+
+fn title() -> String {
+    env::args()
+        .next()
+        .as_ref()
+        .and_then(|program| Path::new(program).file_stem())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "game".to_string())
+}
+
+fn asset_path(path: &str) -> PathBuf {
+    PathBuf::from(ASSET_DIR).join(path)
+}
+
+fn save_path(key: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIR).join(format!("{}.json", key))
+}
+
+// Howard Hinnant's days to civil date algorithm, for dates after 1970
+fn civil_from_days(days: i64) -> (u32, u32, u32) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as u32, month as u32, day as u32)
+}
@@ -0,0 +1,328 @@
+// Drawing in software, for the native backend, which has a window to show
+// pixels in but nothing like the canvas to draw them with. It only does what
+// the renderer asks of a canvas, in canvas pixels: filling and clearing rects,
+// and images scaled without rotation. Images are sampled from the nearest
+// pixel, which suits pixel art. Text comes from a small built-in font whose
+// dots the backend fills as rects.
+use crate::engine::Rect;
+
+// RGBA, with alpha that isn't premultiplied
+pub type Color = [u8; 4];
+
+pub const GLYPH_WIDTH: u8 = 3;
+pub const GLYPH_HEIGHT: u8 = 5;
+
+pub struct Pixmap {
+    width: u32,
+    height: u32,
+    // RGBA bytes, row by row
+    data: Vec<u8>,
+}
+
+impl Pixmap {
+    // Transparent black all over
+    pub fn new(width: u32, height: u32) -> Self {
+        Pixmap {
+            width,
+            height,
+            data: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    // Fails if there aren't four bytes for every pixel
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Option<Self> {
+        if data.len() != (width * height * 4) as usize {
+            return None;
+        }
+        Some(Pixmap {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    // The part inside frame, as RGBA bytes row by row, with anything outside
+    // the pixmap left transparent
+    pub fn crop(&self, frame: &Rect) -> Vec<u8> {
+        let (width, height) = (frame.width as i64, frame.height as i64);
+        let mut cropped = Vec::with_capacity((width.max(0) * height.max(0) * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = self
+                    .index(frame.x as i64 + x, frame.y as i64 + y)
+                    .map(|index| self.color_at(index))
+                    .unwrap_or_default();
+                cropped.extend_from_slice(&pixel);
+            }
+        }
+        cropped
+    }
+
+    pub fn clear(&mut self, rect: &Rect) {
+        let (columns, rows) = self.covered(rect);
+        for y in rows {
+            for x in columns.clone() {
+                let index = self.unchecked_index(x, y);
+                self.data[index..index + 4].copy_from_slice(&[0; 4]);
+            }
+        }
+    }
+
+    pub fn fill(&mut self, rect: &Rect, color: Color, alpha: f32) {
+        let (columns, rows) = self.covered(rect);
+        for y in rows {
+            for x in columns.clone() {
+                self.blend(self.unchecked_index(x, y), color, alpha);
+            }
+        }
+    }
+
+    // The frame of the image stretched over the destination
+    pub fn draw(&mut self, image: &Pixmap, frame: &Rect, destination: &Rect, alpha: f32) {
+        if destination.width <= 0.0 || destination.height <= 0.0 {
+            return;
+        }
+        let (columns, rows) = self.covered(destination);
+        let scale_x = frame.width / destination.width;
+        let scale_y = frame.height / destination.height;
+        let last_x = (frame.x + frame.width).ceil() as i64 - 1;
+        let last_y = (frame.y + frame.height).ceil() as i64 - 1;
+        for y in rows {
+            let source_y = frame.y + (y as f32 + 0.5 - destination.y) * scale_y;
+            let source_y = (source_y.floor() as i64).clamp(frame.y as i64, last_y);
+            for x in columns.clone() {
+                let source_x = frame.x + (x as f32 + 0.5 - destination.x) * scale_x;
+                let source_x = (source_x.floor() as i64).clamp(frame.x as i64, last_x);
+                if let Some(source) = image.index(source_x, source_y) {
+                    let color = image.color_at(source);
+                    self.blend(self.unchecked_index(x, y), color, alpha);
+                }
+            }
+        }
+    }
+
+    // The pixels whose centers are inside the rect, as a canvas fills them
+    fn covered(&self, rect: &Rect) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let clamp = |value: f32, max: u32| value.round().clamp(0.0, max as f32) as u32;
+        (
+            clamp(rect.x, self.width)..clamp(rect.x + rect.width, self.width),
+            clamp(rect.y, self.height)..clamp(rect.y + rect.height, self.height),
+        )
+    }
+
+    fn index(&self, x: i64, y: i64) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width.into() || y >= self.height.into() {
+            return None;
+        }
+        Some(self.unchecked_index(x as u32, y as u32))
+    }
+
+    fn unchecked_index(&self, x: u32, y: u32) -> usize {
+        ((y * self.width + x) * 4) as usize
+    }
+
+    fn color_at(&self, index: usize) -> Color {
+        [
+            self.data[index],
+            self.data[index + 1],
+            self.data[index + 2],
+            self.data[index + 3],
+        ]
+    }
+
+    // Source over, like the canvas's default compositing
+    fn blend(&mut self, index: usize, color: Color, alpha: f32) {
+        let coverage = f32::from(color[3]) / 255.0 * alpha.clamp(0.0, 1.0);
+        if coverage <= 0.0 {
+            return;
+        }
+        let below = f32::from(self.data[index + 3]) / 255.0;
+        let out = coverage + below * (1.0 - coverage);
+        for (channel, &over) in self.data[index..index + 3].iter_mut().zip(&color[..3]) {
+            let over = f32::from(over) * coverage;
+            let under = f32::from(*channel) * below * (1.0 - coverage);
+            *channel = ((over + under) / out).round() as u8;
+        }
+        self.data[index + 3] = (out * 255.0).round() as u8;
+    }
+}
+
+// The CSS colors games use: #rgb, #rrggbb, #rrggbbaa, rgb() and rgba()
+pub fn parse_color(css: &str) -> Option<Color> {
+    let css = css.trim();
+    if let Some(hex) = css.strip_prefix('#') {
+        let digit = |index: usize| u8::from_str_radix(hex.get(index..index + 1)?, 16).ok();
+        let pair = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+        return match hex.len() {
+            3 => Some([digit(0)? * 17, digit(1)? * 17, digit(2)? * 17, 255]),
+            6 => Some([pair(0)?, pair(2)?, pair(4)?, 255]),
+            8 => Some([pair(0)?, pair(2)?, pair(4)?, pair(6)?]),
+            _ => None,
+        };
+    }
+    let arguments = css
+        .strip_prefix("rgba(")
+        .or_else(|| css.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let parts: Vec<f32> = arguments
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    match parts.as_slice() {
+        [red, green, blue] => Some([channel(*red), channel(*green), channel(*blue), 255]),
+        [red, green, blue, alpha] => Some([
+            channel(*red),
+            channel(*green),
+            channel(*blue),
+            channel(alpha * 255.0),
+        ]),
+        _ => None,
+    }
+}
+
+// Which of a character's dots are lit, three to a row and five rows from the
+// top, with the first dot in the highest bit. Lower case letters are drawn as
+// capitals, and anything else missing as a gap.
+pub fn glyph(character: char) -> Option<u16> {
+    let rows: [u8; 5] = match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b110, 0b001, 0b010, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        _ => return None,
+    };
+    Some(
+        rows.iter()
+            .fold(0, |dots, row| (dots << GLYPH_WIDTH) | u16::from(*row)),
+    )
+}
+
+// Whether the dot in that column and row of the glyph is lit
+pub fn is_lit(glyph: u16, column: u8, row: u8) -> bool {
+    let from_last = (GLYPH_HEIGHT - 1 - row) * GLYPH_WIDTH + (GLYPH_WIDTH - 1 - column);
+    glyph & (1 << from_last) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_css_colors_games_use() {
+        assert_eq!(parse_color("#fff"), Some([255, 255, 255, 255]));
+        assert_eq!(parse_color("#d9482b"), Some([0xd9, 0x48, 0x2b, 255]));
+        assert_eq!(parse_color("rgba(0, 0, 0, 0.5)"), Some([0, 0, 0, 128]));
+        assert_eq!(parse_color("tomato"), None);
+    }
+
+    #[test]
+    fn blends_over_what_is_there() {
+        let mut pixmap = Pixmap::new(2, 1);
+        let all = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 2.0,
+            height: 1.0,
+        };
+        pixmap.fill(&all, [255, 255, 255, 255], 1.0);
+
+        pixmap.fill(&all, [0, 0, 0, 255], 0.5);
+
+        assert_eq!(&pixmap.data()[..4], &[128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn stretches_images_from_the_nearest_pixel() {
+        let image = Pixmap::from_rgba(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+        let mut pixmap = Pixmap::new(4, 1);
+
+        pixmap.draw(
+            &image,
+            &Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 2.0,
+                height: 1.0,
+            },
+            &Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 4.0,
+                height: 1.0,
+            },
+            1.0,
+        );
+
+        let reds: Vec<u8> = pixmap.data().chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(reds, vec![255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn lights_glyph_dots_from_the_top_left() {
+        let one = glyph('1').unwrap();
+
+        assert!(is_lit(one, 1, 0));
+        assert!(!is_lit(one, 0, 0));
+        assert!(is_lit(one, 0, 4));
+        assert_eq!(glyph('a'), glyph('A'));
+    }
+}
@@ -0,0 +1,454 @@
+use std::{cell::RefCell, rc::Rc, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot::channel,
+    },
+    Future,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement};
+
+use super::{Canvas, ImageSource, InputEvent, InputSource};
+use crate::{
+    browser,
+    config::QueryParams,
+    engine::{Point, Rect, Vector},
+    viewport::Transform,
+};
+
+// Fetched relative to the page
+pub async fn load_json<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let json = browser::fetch_json(path).await?;
+    serde_wasm_bindgen::from_value(json)
+        .map_err(|err| anyhow!("Error deserializing {} {:#?}", path, err))
+}
+
+pub fn get_item(key: &str) -> Result<Option<String>> {
+    browser::get_item(key)
+}
+
+pub fn set_item(key: &str, value: &str) -> Result<()> {
+    browser::set_item(key, value)
+}
+
+// In milliseconds
+pub fn now() -> Result<f64> {
+    browser::now()
+}
+
+pub fn utc_date() -> (u32, u32, u32) {
+    browser::utc_date()
+}
+
+pub fn language() -> Result<String> {
+    browser::language()
+}
+
+// Without the hash
+pub fn page_url() -> Result<String> {
+    browser::page_url()
+}
+
+pub fn is_online() -> Result<bool> {
+    browser::is_online()
+}
+
+#[derive(Serialize)]
+struct Precache<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    assets: &'a [String],
+}
+
+// Asks the service worker to cache the assets, in the background
+pub fn precache(assets: &[String]) -> Result<()> {
+    let message = serde_wasm_bindgen::to_value(&Precache {
+        kind: "precache",
+        assets,
+    })
+    .map_err(|err| anyhow!("Error serializing precache message {:#?}", err))?;
+    browser::spawn_local(async move {
+        if let Err(err) = browser::post_message(&message).await {
+            log!("Could not precache assets {:#?}", err);
+        }
+    });
+    Ok(())
+}
+
+pub fn send_beacon(url: &str, body: &str) -> Result<()> {
+    browser::send_beacon(url, body)
+}
+
+pub fn stack_trace() -> Option<String> {
+    browser::stack_trace()
+}
+
+pub fn query_params() -> Result<QueryParams> {
+    browser::query_params()
+}
+
+pub fn location_hash() -> Result<String> {
+    browser::location_hash()
+}
+
+pub fn log(message: &str) {
+    web_sys::console::log_1(&message.into());
+}
+
+pub fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    browser::spawn_local(future);
+}
+
+// Downloads the frame on screen as a PNG, e.g. for bug reports
+pub fn save_screenshot(filename: &str) -> Result<()> {
+    let png = browser::canvas_to_png()?;
+    browser::download(&png, filename)
+}
+
+// Frames come from requestAnimationFrame, with the time it gives in
+// milliseconds
+pub type FrameCallback = browser::LoopClosure;
+
+pub fn frame_callback(f: impl FnMut(f64) + 'static) -> FrameCallback {
+    browser::create_raf_closure(f)
+}
+
+// Returns the id to cancel the frame with
+pub fn request_frame(callback: &FrameCallback) -> Result<i32> {
+    browser::request_animation_frame(callback)
+}
+
+pub fn cancel_frame(id: i32) -> Result<()> {
+    browser::cancel_animation_frame(id)
+}
+
+#[derive(Clone)]
+pub struct Bitmap(HtmlImageElement);
+
+impl ImageSource for Bitmap {
+    fn width(&self) -> u32 {
+        self.0.natural_width()
+    }
+
+    fn height(&self) -> u32 {
+        self.0.natural_height()
+    }
+
+    fn pixels(&self, frame: &Rect) -> Result<Vec<u8>> {
+        browser::image_pixels(&self.0, frame)
+    }
+}
+
+// Fetched relative to the page, and decoded by the browser
+pub async fn load_image(source: &str) -> Result<Bitmap> {
+    let image = browser::new_image()?;
+
+    let (complete_tx, complete_rx) = channel::<Result<()>>();
+    let success_tx = Rc::new(Mutex::new(Some(complete_tx)));
+    let error_tx = Rc::clone(&success_tx);
+    let success_callback = browser::closure_once(move || {
+        if let Some(success_tx) = success_tx.lock().ok().and_then(|mut opt| opt.take()) {
+            let _ = success_tx.send(Ok(()));
+        }
+    });
+
+    let error_callback: Closure<dyn FnMut(JsValue)> = browser::closure_once(move |err| {
+        if let Some(error_tx) = error_tx.lock().ok().and_then(|mut opt| opt.take()) {
+            let _ = error_tx.send(Err(anyhow!("Error Loading Image: {:#?}", err)));
+        }
+    });
+
+    image.set_onload(Some(success_callback.as_ref().unchecked_ref()));
+    image.set_onerror(Some(error_callback.as_ref().unchecked_ref()));
+    image.set_src(source);
+
+    complete_rx.await??;
+
+    Ok(Bitmap(image))
+}
+
+// The canvas's 2D context
+pub struct Context(CanvasRenderingContext2d);
+
+impl Context {
+    pub fn new(context: CanvasRenderingContext2d) -> Self {
+        Context(context)
+    }
+
+    fn canvas(&self) -> HtmlCanvasElement {
+        self.0
+            .canvas()
+            .expect("Renderer has no canvas! Unrecoverable error.")
+    }
+}
+
+impl Canvas for Context {
+    type Image = Bitmap;
+
+    fn size(&self) -> (u32, u32) {
+        let canvas = self.canvas();
+        (canvas.width(), canvas.height())
+    }
+
+    fn match_device_pixels(&self) {
+        let canvas = self.canvas();
+        let ratio = browser::device_pixel_ratio().unwrap_or(1.0);
+        let width = (canvas.client_width() as f64 * ratio).round() as u32;
+        let height = (canvas.client_height() as f64 * ratio).round() as u32;
+        if canvas.width() != width || canvas.height() != height {
+            canvas.set_width(width);
+            canvas.set_height(height);
+        }
+    }
+
+    // Undoing the canvas's CSS size
+    fn client_to_canvas(&self, point: Vector) -> Vector {
+        let canvas = self.canvas();
+        let bounds = canvas.get_bounding_client_rect();
+        Vector {
+            x: (point.x - bounds.left() as f32) * canvas.width() as f32 / bounds.width() as f32,
+            y: (point.y - bounds.top() as f32) * canvas.height() as f32 / bounds.height() as f32,
+        }
+    }
+
+    fn set_transform(&self, transform: &Transform) {
+        self.0
+            .set_transform(
+                transform.scale_x.into(),
+                0.0,
+                0.0,
+                transform.scale_y.into(),
+                transform.offset_x.into(),
+                transform.offset_y.into(),
+            )
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn set_smoothing(&self, smoothing: bool) {
+        self.0.set_image_smoothing_enabled(smoothing);
+    }
+
+    fn save(&self) {
+        self.0.save();
+    }
+
+    fn restore(&self) {
+        self.0.restore();
+    }
+
+    fn alpha(&self) -> f32 {
+        self.0.global_alpha() as f32
+    }
+
+    fn set_alpha(&self, alpha: f32) {
+        self.0.set_global_alpha(alpha.into());
+    }
+
+    fn filter(&self) -> String {
+        self.0.filter()
+    }
+
+    fn set_filter(&self, filter: &str) {
+        self.0.set_filter(filter);
+    }
+
+    fn clear_rect(&self, rect: &Rect) {
+        self.0.clear_rect(
+            rect.x.into(),
+            rect.y.into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+    }
+
+    fn fill_rect(&self, color: &str, rect: &Rect) {
+        self.0.set_fill_style(&JsValue::from_str(color));
+        self.0.fill_rect(
+            rect.x.into(),
+            rect.y.into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+    }
+
+    fn stroke_rect(&self, color: &str, rect: &Rect) {
+        self.0.set_stroke_style(&JsValue::from_str(color));
+        self.0.begin_path();
+        self.0.rect(
+            rect.x.into(),
+            rect.y.into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+        self.0.stroke();
+    }
+
+    fn stroke_circle(&self, color: &str, center: &Point, radius: f32) {
+        self.0.set_stroke_style(&JsValue::from_str(color));
+        self.0.begin_path();
+        self.0
+            .arc(
+                center.x.into(),
+                center.y.into(),
+                radius.into(),
+                0.0,
+                std::f64::consts::PI * 2.0,
+            )
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+        self.0.stroke();
+    }
+
+    fn fill_text(&self, text: &str, position: &Point, color: &str, size: u8) {
+        self.0.set_font(&format!("bold {}px sans-serif", size));
+        self.0.set_fill_style(&JsValue::from_str(color));
+        self.0
+            .fill_text(text, position.x.into(), position.y.into())
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn draw_image(&self, image: &Bitmap, frame: &Rect, destination: &Rect) {
+        self.0
+            .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                &image.0,
+                frame.x.into(),
+                frame.y.into(),
+                frame.width.into(),
+                frame.height.into(),
+                destination.x.into(),
+                destination.y.into(),
+                destination.width.into(),
+                destination.height.into(),
+            )
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn draw_whole_image(&self, image: &Bitmap, position: &Point) {
+        self.0
+            .draw_image_with_html_image_element(&image.0, position.x.into(), position.y.into())
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn pixels(&self) -> (u32, u32, Vec<u8>) {
+        let (width, height) = self.size();
+        let image_data = self
+            .0
+            .get_image_data(0.0, 0.0, width.into(), height.into())
+            .expect("Could not read the canvas pixels");
+        (image_data.width(), image_data.height(), image_data.data().0)
+    }
+}
+
+pub struct Input {
+    receiver: UnboundedReceiver<InputEvent>,
+}
+
+impl InputSource for Input {
+    fn poll(&mut self) -> Vec<InputEvent> {
+        let mut events = vec![];
+        while let Ok(event) = self.receiver.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+}
+
+// The canvas with the given id, with the keyboard and, alongside it, touches
+// and clicks on it
+pub fn open_window(canvas_id: &str) -> Result<(Context, Input)> {
+    let canvas = browser::canvas_with_id(canvas_id)?;
+    let (sender, receiver) = unbounded();
+    listen_for_keys(&canvas, sender.clone());
+    listen_for_pointers(&canvas, sender)?;
+    Ok((
+        Context::new(browser::context_for(&canvas)?),
+        Input { receiver },
+    ))
+}
+
+fn listen_for_keys(canvas: &HtmlCanvasElement, sender: UnboundedSender<InputEvent>) {
+    let keydown_sender = Rc::new(RefCell::new(sender));
+    let keyup_sender = Rc::clone(&keydown_sender);
+    let onkeydown = browser::closure_wrap(Box::new(move |event: web_sys::KeyboardEvent| {
+        let _ = keydown_sender
+            .borrow_mut()
+            .unbounded_send(InputEvent::KeyDown {
+                code: event.code(),
+                repeat: event.repeat(),
+                time: event.time_stamp(),
+            });
+    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+
+    let onkeyup = browser::closure_wrap(Box::new(move |event: web_sys::KeyboardEvent| {
+        let _ = keyup_sender
+            .borrow_mut()
+            .unbounded_send(InputEvent::KeyUp { code: event.code() });
+    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+
+    canvas.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
+    canvas.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
+    onkeydown.forget();
+    onkeyup.forget();
+}
+
+// Touches come from the touch events, which are stopped there so the page
+// doesn't scroll or zoom under the game. Pointer events only bring the mouse
+// and pens, since a touch fires those as well.
+fn listen_for_pointers(
+    canvas: &HtmlCanvasElement,
+    sender: UnboundedSender<InputEvent>,
+) -> Result<()> {
+    for &name in ["touchstart", "touchend", "touchcancel"].iter() {
+        let sender = sender.clone();
+        let listener = browser::closure_wrap(Box::new(move |event: web_sys::TouchEvent| {
+            event.prevent_default();
+            let touch = match event.changed_touches().get(0) {
+                Some(touch) => touch,
+                None => return,
+            };
+            let position = Vector {
+                x: touch.client_x() as f32,
+                y: touch.client_y() as f32,
+            };
+            let press = match name {
+                "touchstart" => InputEvent::PointerDown(position, event.time_stamp()),
+                "touchend" => InputEvent::PointerUp(position, event.time_stamp()),
+                _ => InputEvent::PointerCancel,
+            };
+            let _ = sender.unbounded_send(press);
+        }) as Box<dyn FnMut(web_sys::TouchEvent)>);
+        canvas
+            .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+            .map_err(|err| anyhow!("Error adding {} listener {:#?}", name, err))?;
+        listener.forget();
+    }
+    for &name in ["pointerdown", "pointerup"].iter() {
+        let sender = sender.clone();
+        let listener = browser::closure_wrap(Box::new(move |event: web_sys::PointerEvent| {
+            if event.pointer_type() == "touch" {
+                return;
+            }
+            let position = Vector {
+                x: event.client_x() as f32,
+                y: event.client_y() as f32,
+            };
+            let press = if name == "pointerdown" {
+                InputEvent::PointerDown(position, event.time_stamp())
+            } else {
+                InputEvent::PointerUp(position, event.time_stamp())
+            };
+            let _ = sender.unbounded_send(press);
+        }) as Box<dyn FnMut(web_sys::PointerEvent)>);
+        canvas
+            .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+            .map_err(|err| anyhow!("Error adding {} listener {:#?}", name, err))?;
+        listener.forget();
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+// Keeps the last few seconds of the canvas as video, so something that just
+// happened can be saved as a clip after the fact. The browser's MediaRecorder
+// hands a recording over a second at a time, and only the first of those
+// chunks has the WebM header, so chunks from the middle of a recording can't
+// be played on their own. Instead a fresh recording is started every so
+// often, the previous one is kept, and the one before that is stopped. A clip
+// is the whole of the older recording, so it always starts cleanly and covers
+// between one and two lengths of the time asked for.
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{
+    Blob, BlobEvent, BlobPropertyBag, MediaRecorder, MediaRecorderOptions, MediaStream, Url,
+};
+
+use crate::browser;
+
+const CHUNK_MS: i32 = 1000;
+const MIME_TYPE: &str = "video/webm";
+// The recording a clip is saved from, and the one that takes over from it
+const RECORDINGS_KEPT: usize = 2;
+
+// When to start a new recording and which ones to keep, by the time each
+// started
+struct Rotation<T> {
+    recordings: VecDeque<(f64, T)>,
+    length_ms: f64,
+}
+
+impl<T> Rotation<T> {
+    fn new(length_ms: f64) -> Self {
+        Rotation {
+            recordings: VecDeque::new(),
+            length_ms,
+        }
+    }
+
+    fn is_due(&self, now: f64) -> bool {
+        match self.recordings.back() {
+            Some((started, _)) => now - started >= self.length_ms,
+            None => true,
+        }
+    }
+
+    fn push(&mut self, now: f64, recording: T) {
+        self.recordings.push_back((now, recording));
+        while self.recordings.len() > RECORDINGS_KEPT {
+            self.recordings.pop_front();
+        }
+    }
+
+    // The longest running recording
+    fn oldest(&self) -> Option<&T> {
+        self.recordings.front().map(|(_, recording)| recording)
+    }
+}
+
+// One MediaRecorder from start to stop, with everything it's handed over
+struct Recording {
+    recorder: MediaRecorder,
+    chunks: Rc<RefCell<Vec<Blob>>>,
+    _on_data: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Recording {
+    fn start(stream: &MediaStream) -> Result<Self> {
+        let recorder = MediaRecorder::new_with_media_stream_and_media_recorder_options(
+            stream,
+            MediaRecorderOptions::new().mime_type(MIME_TYPE),
+        )
+        .map_err(|err| anyhow!("Error creating MediaRecorder {:#?}", err))?;
+
+        let chunks = Rc::new(RefCell::new(vec![]));
+        let on_data = {
+            let chunks = Rc::clone(&chunks);
+            browser::closure_wrap(Box::new(move |event: JsValue| {
+                if let Some(data) = event
+                    .dyn_into::<BlobEvent>()
+                    .ok()
+                    .and_then(|event| event.data())
+                {
+                    chunks.borrow_mut().push(data);
+                }
+            }) as Box<dyn FnMut(JsValue)>)
+        };
+        recorder.set_ondataavailable(Some(on_data.as_ref().unchecked_ref()));
+        recorder
+            .start_with_time_slice(CHUNK_MS)
+            .map_err(|err| anyhow!("Error starting MediaRecorder {:#?}", err))?;
+
+        Ok(Recording {
+            recorder,
+            chunks,
+            _on_data: on_data,
+        })
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        let _ = self.recorder.stop();
+    }
+}
+
+pub struct ClipRecorder {
+    stream: MediaStream,
+    rotation: Rotation<Recording>,
+}
+
+impl ClipRecorder {
+    // Starts recording the canvas straight away, keeping at least the last
+    // number of seconds given once it's been running that long
+    pub fn start(seconds: u32) -> Result<Self> {
+        let stream = browser::canvas()?
+            .capture_stream()
+            .map_err(|err| anyhow!("Error capturing canvas stream {:#?}", err))?;
+        let mut recorder = ClipRecorder {
+            stream,
+            rotation: Rotation::new(f64::from(seconds) * 1000.0),
+        };
+        recorder.update()?;
+        Ok(recorder)
+    }
+
+    // Starts the next recording when it's time to, which stops the oldest.
+    // Call it every frame.
+    pub fn update(&mut self) -> Result<()> {
+        let now = browser::now()?;
+        if self.rotation.is_due(now) {
+            let recording = Recording::start(&self.stream)?;
+            self.rotation.push(now, recording);
+        }
+        Ok(())
+    }
+
+    // Downloads the older recording so far as a WebM video. Recording
+    // carries on.
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let recording = self
+            .rotation
+            .oldest()
+            .ok_or_else(|| anyhow!("Nothing recorded yet"))?;
+        let parts = js_sys::Array::new();
+        for chunk in recording.chunks.borrow().iter() {
+            parts.push(chunk);
+        }
+        let clip = Blob::new_with_blob_sequence_and_options(
+            &parts,
+            BlobPropertyBag::new().type_(MIME_TYPE),
+        )
+        .map_err(|err| anyhow!("Error creating clip {:#?}", err))?;
+
+        // The URL is left alive, since revoking it straight away can cancel
+        // the download
+        let url = Url::create_object_url_with_blob(&clip)
+            .map_err(|err| anyhow!("Error creating clip URL {:#?}", err))?;
+        browser::download(&url, filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_from_a_recording_at_least_the_length_asked_for() {
+        let mut rotation = Rotation::new(15_000.0);
+
+        for now in (0..=40_000).step_by(1000) {
+            let now = f64::from(now);
+            if rotation.is_due(now) {
+                rotation.push(now, now);
+            }
+        }
+
+        assert_eq!(rotation.recordings.len(), 2);
+        assert_eq!(rotation.oldest(), Some(&15_000.0));
+    }
+}
@@ -0,0 +1,89 @@
+// The last few seconds of a game's state, one snapshot per update, so play
+// can be stepped back to just before something went wrong and watched again.
+// What a snapshot holds is up to the game; it should be cheap to copy, since
+// one is taken every update.
+use std::collections::VecDeque;
+
+use crate::engine::FRAME_SIZE;
+
+pub struct Rewind<T> {
+    snapshots: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> Rewind<T> {
+    // Seconds of game time, at one snapshot per fixed update
+    pub fn new(seconds: f32) -> Self {
+        Rewind::with_capacity(ticks(seconds))
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Rewind {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record(&mut self, snapshot: T) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    // Goes back the given number of seconds, or as far as it can, and forgets
+    // everything after. None when nothing has been recorded.
+    pub fn rewind(&mut self, seconds: f32) -> Option<T> {
+        self.rewind_ticks(ticks(seconds))
+    }
+
+    pub fn rewind_ticks(&mut self, ticks: usize) -> Option<T> {
+        let keep = self.snapshots.len().saturating_sub(ticks).max(1);
+        self.snapshots.truncate(keep);
+        self.snapshots.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+fn ticks(seconds: f32) -> usize {
+    (seconds * 1000.0 / FRAME_SIZE).round() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_latest_snapshots() {
+        let mut rewind = Rewind::with_capacity(3);
+        (0..5).for_each(|tick| rewind.record(tick));
+
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.rewind_ticks(0), Some(4));
+    }
+
+    #[test]
+    fn goes_back_and_forgets_what_came_after() {
+        let mut rewind = Rewind::with_capacity(10);
+        (0..10).for_each(|tick| rewind.record(tick));
+
+        assert_eq!(rewind.rewind_ticks(3), Some(6));
+        assert_eq!(rewind.len(), 6);
+    }
+
+    #[test]
+    fn stops_at_the_oldest_snapshot() {
+        let mut rewind = Rewind::with_capacity(10);
+        (0..4).for_each(|tick| rewind.record(tick));
+
+        assert_eq!(rewind.rewind_ticks(20), Some(0));
+        assert_eq!(rewind.rewind_ticks(1), None);
+    }
+}
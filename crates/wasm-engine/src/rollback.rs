@@ -0,0 +1,223 @@
+// Rollback for networked play. A simulation that's a pure function of its
+// state and every player's input can run ahead on a guess at what the other
+// players pressed, and when their real input turns up late, go back to the
+// tick it was for and run forward again. Only recent ticks are kept, so input
+// older than that can't be taken back any more.
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+
+pub trait Simulation {
+    // Kept in memory, so it only has to be cloned
+    type State: Clone;
+    // Default is what's guessed for a player nothing has been heard from
+    type Input: Clone + PartialEq + Default;
+
+    // One fixed update, with an input for each player in turn
+    fn step(state: &Self::State, inputs: &[Self::Input]) -> Self::State;
+}
+
+struct Frame<S: Simulation> {
+    tick: u64,
+    // The state before this tick's step
+    state: S::State,
+    inputs: Vec<S::Input>,
+    confirmed: Vec<bool>,
+}
+
+pub struct Rollback<S: Simulation> {
+    state: S::State,
+    tick: u64,
+    players: usize,
+    frames: VecDeque<Frame<S>>,
+    capacity: usize,
+}
+
+impl<S: Simulation> Rollback<S> {
+    // Capacity is how many ticks back remote input can still arrive for
+    pub fn new(state: S::State, players: usize, capacity: usize) -> Self {
+        Rollback {
+            state,
+            tick: 0,
+            players,
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn state(&self) -> &S::State {
+        &self.state
+    }
+
+    // The next tick to be stepped
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    // Steps once with the local player's input. Everyone else is guessed to
+    // still be pressing what they were last.
+    pub fn advance(&mut self, player: usize, input: S::Input) {
+        self.advance_local(vec![(player, input)]);
+    }
+
+    // The same, for when more than one player is on this end, like sharing a
+    // keyboard
+    pub fn advance_local(&mut self, local: Vec<(usize, S::Input)>) {
+        let mut inputs = self.predicted_inputs();
+        let mut confirmed = vec![false; self.players];
+        for (player, input) in local {
+            inputs[player] = input;
+            confirmed[player] = true;
+        }
+
+        let next = S::step(&self.state, &inputs);
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Frame {
+            tick: self.tick,
+            state: std::mem::replace(&mut self.state, next),
+            inputs,
+            confirmed,
+        });
+        self.tick += 1;
+    }
+
+    // Input that arrived from another player. If it isn't what was guessed,
+    // everything from its tick on is simulated again, and this returns true.
+    pub fn confirm(&mut self, player: usize, tick: u64, input: S::Input) -> Result<bool> {
+        let oldest = self.frames.front().map_or(self.tick, |frame| frame.tick);
+        if tick < oldest || tick >= self.tick {
+            return Err(anyhow!(
+                "No tick {} to confirm input for, only {} to {}",
+                tick,
+                oldest,
+                self.tick
+            ));
+        }
+        let index = (tick - oldest) as usize;
+        let frame = &mut self.frames[index];
+        frame.confirmed[player] = true;
+        if frame.inputs[player] == input {
+            return Ok(false);
+        }
+        frame.inputs[player] = input;
+        self.resimulate(index, player);
+        Ok(true)
+    }
+
+    // A change from outside the simulation, like part of the level that's
+    // finished loading. It's made to the kept states as well as the current
+    // one, so rolling back doesn't undo it.
+    pub fn amend(&mut self, change: impl Fn(&mut S::State)) {
+        for frame in self.frames.iter_mut() {
+            change(&mut frame.state);
+        }
+        change(&mut self.state);
+    }
+
+    // Carries on from a state that didn't come from stepping, like one
+    // rewound to. Nothing can be rolled back from before it.
+    pub fn reset(&mut self, state: S::State) {
+        self.state = state;
+        self.frames.clear();
+    }
+
+    // Later guesses for the player were based on the wrong input, so they're
+    // guessed again on the way
+    fn resimulate(&mut self, from: usize, player: usize) {
+        let mut state = self.frames[from].state.clone();
+        for index in from..self.frames.len() {
+            if index > from && !self.frames[index].confirmed[player] {
+                self.frames[index].inputs[player] = self.frames[index - 1].inputs[player].clone();
+            }
+            let frame = &mut self.frames[index];
+            frame.state = state;
+            state = S::step(&frame.state, &frame.inputs);
+        }
+        self.state = state;
+    }
+
+    fn predicted_inputs(&self) -> Vec<S::Input> {
+        match self.frames.back() {
+            Some(frame) => frame.inputs.clone(),
+            None => vec![S::Input::default(); self.players],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each player's input is how far they move that tick
+    struct Walk;
+
+    impl Simulation for Walk {
+        type State = Vec<i32>;
+        type Input = i32;
+
+        fn step(state: &Vec<i32>, inputs: &[i32]) -> Vec<i32> {
+            state.iter().zip(inputs).map(|(x, dx)| x + dx).collect()
+        }
+    }
+
+    #[test]
+    fn guesses_the_other_player_keeps_doing_the_same() {
+        let mut rollback = Rollback::<Walk>::new(vec![0, 0], 2, 10);
+        rollback.advance(0, 1);
+        rollback.confirm(1, 0, 3).unwrap();
+        rollback.advance(0, 1);
+        rollback.advance(0, 1);
+
+        assert_eq!(rollback.state(), &vec![3, 9]);
+    }
+
+    #[test]
+    fn simulates_again_from_late_input() {
+        let mut rollback = Rollback::<Walk>::new(vec![0, 0], 2, 10);
+        (0..4).for_each(|_| rollback.advance(0, 1));
+
+        assert!(rollback.confirm(1, 2, 5).unwrap());
+        assert_eq!(rollback.state(), &vec![4, 10]);
+        assert!(!rollback.confirm(1, 2, 5).unwrap());
+    }
+
+    #[test]
+    fn keeps_confirmed_input_when_simulating_again() {
+        let mut rollback = Rollback::<Walk>::new(vec![0, 0], 2, 10);
+        (0..3).for_each(|_| rollback.advance(0, 1));
+        rollback.confirm(1, 2, 1).unwrap();
+        rollback.confirm(1, 0, 2).unwrap();
+
+        assert_eq!(rollback.state(), &vec![3, 5]);
+    }
+
+    #[test]
+    fn input_older_than_the_history_is_too_late() {
+        let mut rollback = Rollback::<Walk>::new(vec![0, 0], 2, 2);
+        (0..5).for_each(|_| rollback.advance(0, 1));
+
+        assert!(rollback.confirm(1, 1, 1).is_err());
+        assert!(rollback.confirm(1, 5, 1).is_err());
+    }
+
+    #[test]
+    fn steps_every_local_player_together() {
+        let mut rollback = Rollback::<Walk>::new(vec![0, 0], 2, 10);
+        rollback.advance_local(vec![(0, 1), (1, 2)]);
+
+        assert_eq!(rollback.state(), &vec![1, 2]);
+        assert!(!rollback.confirm(1, 0, 2).unwrap());
+    }
+
+    #[test]
+    fn amendments_survive_a_rollback() {
+        let mut rollback = Rollback::<Walk>::new(vec![0, 0], 2, 10);
+        (0..3).for_each(|_| rollback.advance(0, 1));
+        rollback.amend(|state| state[0] += 100);
+        rollback.confirm(1, 0, 1).unwrap();
+
+        assert_eq!(rollback.state(), &vec![103, 3]);
+    }
+}
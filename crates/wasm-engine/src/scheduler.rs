@@ -0,0 +1,206 @@
+// Timers that run on the game's fixed timestep rather than the wall clock, so
+// they pause with the game and replay exactly in tests. Callbacks are given
+// whatever context the game ticks the scheduler with, usually the object that
+// owns it.
+use std::rc::Rc;
+
+use crate::engine::FRAME_SIZE;
+
+pub type Callback<C> = Rc<dyn Fn(&mut C)>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimerHandle(u32);
+
+struct Timer<C> {
+    handle: TimerHandle,
+    due: u64,
+    // Set for timers that go off every so many ticks
+    repeat: Option<u64>,
+    callback: Callback<C>,
+}
+
+pub struct Scheduler<C> {
+    timestep: f32,
+    tick: u64,
+    next_handle: u32,
+    timers: Vec<Timer<C>>,
+}
+
+// Derived, these would need the context to be Clone too, when it's only the
+// callbacks' argument. A copy shares the callbacks and goes on separately.
+impl<C> Clone for Timer<C> {
+    fn clone(&self) -> Self {
+        Timer {
+            handle: self.handle,
+            due: self.due,
+            repeat: self.repeat,
+            callback: Rc::clone(&self.callback),
+        }
+    }
+}
+
+impl<C> Clone for Scheduler<C> {
+    fn clone(&self) -> Self {
+        Scheduler {
+            timestep: self.timestep,
+            tick: self.tick,
+            next_handle: self.next_handle,
+            timers: self.timers.clone(),
+        }
+    }
+}
+
+impl<C> Scheduler<C> {
+    // The timestep is the length of one tick in milliseconds
+    pub fn new(timestep: f32) -> Self {
+        Scheduler {
+            timestep,
+            tick: 0,
+            next_handle: 0,
+            timers: vec![],
+        }
+    }
+
+    // Milliseconds are rounded to the nearest tick, and are always at least
+    // one
+    fn ticks(&self, milliseconds: f32) -> u64 {
+        ((milliseconds / self.timestep).round() as u64).max(1)
+    }
+
+    fn schedule(&mut self, delay: u64, repeat: Option<u64>, callback: Callback<C>) -> TimerHandle {
+        let handle = TimerHandle(self.next_handle);
+        self.next_handle += 1;
+        self.timers.push(Timer {
+            handle,
+            due: self.tick + delay,
+            repeat,
+            callback,
+        });
+        handle
+    }
+
+    pub fn after(&mut self, milliseconds: f32, callback: impl Fn(&mut C) + 'static) -> TimerHandle {
+        let delay = self.ticks(milliseconds);
+        self.schedule(delay, None, Rc::new(callback))
+    }
+
+    pub fn every(&mut self, milliseconds: f32, callback: impl Fn(&mut C) + 'static) -> TimerHandle {
+        let period = self.ticks(milliseconds);
+        self.schedule(period, Some(period), Rc::new(callback))
+    }
+
+    // Cancelling a timer that's already gone off does nothing
+    pub fn cancel(&mut self, handle: TimerHandle) {
+        self.timers.retain(|timer| timer.handle != handle);
+    }
+
+    pub fn is_pending(&self, handle: TimerHandle) -> bool {
+        self.timers.iter().any(|timer| timer.handle == handle)
+    }
+
+    // Milliseconds until the timer next goes off
+    pub fn remaining(&self, handle: TimerHandle) -> Option<f32> {
+        self.timers
+            .iter()
+            .find(|timer| timer.handle == handle)
+            .map(|timer| (timer.due - self.tick) as f32 * self.timestep)
+    }
+
+    // Moves on one fixed step, and returns the callbacks that are due, in the
+    // order they were due. The caller runs them, so a scheduler can be ticked
+    // while it's a field of the context its callbacks need.
+    pub fn tick(&mut self) -> Vec<Callback<C>> {
+        self.tick += 1;
+        let now = self.tick;
+        let mut due: Vec<(u64, Callback<C>)> = vec![];
+        for timer in self.timers.iter_mut().filter(|timer| timer.due <= now) {
+            due.push((timer.due, Rc::clone(&timer.callback)));
+            if let Some(period) = timer.repeat {
+                timer.due += period;
+            }
+        }
+        self.timers
+            .retain(|timer| timer.repeat.is_some() || timer.due > now);
+        due.sort_by_key(|(due, _)| *due);
+        due.into_iter().map(|(_, callback)| callback).collect()
+    }
+
+    // Ticks and runs the due callbacks, for a scheduler that isn't part of
+    // the context
+    pub fn run(&mut self, context: &mut C) {
+        for callback in self.tick() {
+            callback(context);
+        }
+    }
+}
+
+impl<C> Default for Scheduler<C> {
+    fn default() -> Self {
+        Scheduler::new(FRAME_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn after_goes_off_once_on_the_tick_it_is_due() {
+        let mut scheduler = Scheduler::new(10.0);
+        let mut fired = vec![];
+
+        scheduler.after(30.0, |fired: &mut Vec<u32>| fired.push(1));
+        for tick in 1..=6 {
+            scheduler.run(&mut fired);
+            if tick == 2 {
+                assert!(fired.is_empty());
+            }
+        }
+
+        assert_eq!(fired, vec![1]);
+    }
+
+    #[test]
+    fn every_repeats_until_cancelled() {
+        let mut scheduler = Scheduler::new(10.0);
+        let mut count = 0;
+
+        let handle = scheduler.every(20.0, |count: &mut u32| *count += 1);
+        for _ in 0..6 {
+            scheduler.run(&mut count);
+        }
+        scheduler.cancel(handle);
+        for _ in 0..6 {
+            scheduler.run(&mut count);
+        }
+
+        assert_eq!(count, 3);
+        assert!(!scheduler.is_pending(handle));
+    }
+
+    #[test]
+    fn reports_the_time_remaining() {
+        let mut scheduler: Scheduler<()> = Scheduler::new(10.0);
+
+        let handle = scheduler.after(50.0, |_| {});
+        scheduler.tick();
+        scheduler.tick();
+
+        assert_eq!(scheduler.remaining(handle), Some(30.0));
+    }
+
+    #[test]
+    fn a_copy_keeps_time_on_its_own() {
+        let mut scheduler = Scheduler::new(10.0);
+        let mut fired = vec![];
+
+        scheduler.after(20.0, |fired: &mut Vec<u32>| fired.push(1));
+        scheduler.run(&mut fired);
+        let mut copy = scheduler.clone();
+        copy.run(&mut fired);
+        scheduler.run(&mut fired);
+
+        assert_eq!(fired, vec![1, 1]);
+        assert!(copy.tick().is_empty());
+    }
+}
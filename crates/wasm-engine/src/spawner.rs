@@ -0,0 +1,184 @@
+// Emits things into the world over time from wave definitions, usually loaded
+// from a level's JSON, rather than placing each one by hand. What's spawned is
+// up to the game: a wave's kind is any type it can deserialize, and the
+// spawner only decides when and where.
+use serde::Deserialize;
+
+use crate::engine::{Point, FRAME_SIZE};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "rule")]
+pub enum Placement {
+    // Every spawn in the same place
+    Fixed { x: i16, y: i16 },
+    // Each spawn moved on from the one before by dx and dy
+    Row { x: i16, y: i16, dx: i16, dy: i16 },
+    // Takes each height in turn, starting over at the end
+    Heights { x: i16, heights: Vec<i16> },
+}
+
+impl Placement {
+    fn position(&self, index: u32) -> Point {
+        match self {
+            Placement::Fixed { x, y } => Point { x: *x, y: *y },
+            Placement::Row { x, y, dx, dy } => Point {
+                x: x + dx * index as i16,
+                y: y + dy * index as i16,
+            },
+            Placement::Heights { x, heights } => Point {
+                x: *x,
+                y: heights
+                    .get(index as usize % heights.len().max(1))
+                    .copied()
+                    .unwrap_or(0),
+            },
+        }
+    }
+}
+
+// Start and interval are in milliseconds of game time
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Wave<K> {
+    pub kind: K,
+    #[serde(default)]
+    pub start: f32,
+    #[serde(default)]
+    pub interval: f32,
+    pub count: u32,
+    pub position: Placement,
+}
+
+pub struct Spawn<K> {
+    pub kind: K,
+    pub position: Point,
+}
+
+#[derive(Clone)]
+pub struct Spawner<K> {
+    timestep: f32,
+    tick: u64,
+    // Each wave and how many it has spawned so far
+    waves: Vec<(Wave<K>, u32)>,
+}
+
+// Rounded to the nearest tick, like the scheduler
+fn ticks(milliseconds: f32, timestep: f32) -> u64 {
+    (milliseconds / timestep).round() as u64
+}
+
+impl<K: Clone> Spawner<K> {
+    pub fn new(waves: Vec<Wave<K>>) -> Self {
+        Spawner::with_timestep(waves, FRAME_SIZE)
+    }
+
+    // The timestep is the length of one tick in milliseconds
+    pub fn with_timestep(waves: Vec<Wave<K>>, timestep: f32) -> Self {
+        Spawner {
+            timestep,
+            tick: 0,
+            waves: waves.into_iter().map(|wave| (wave, 0)).collect(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.waves
+            .iter()
+            .all(|(wave, spawned)| *spawned >= wave.count)
+    }
+
+    // Moves on one fixed step, and returns everything due to spawn on it, in
+    // the order the waves were defined. Anything due at 0ms spawns on the
+    // first tick.
+    pub fn tick(&mut self) -> Vec<Spawn<K>> {
+        self.tick += 1;
+        let (now, timestep) = (self.tick, self.timestep);
+        let mut spawns = vec![];
+        for (wave, spawned) in self.waves.iter_mut() {
+            let start = ticks(wave.start, timestep);
+            let interval = ticks(wave.interval, timestep).max(1);
+            while *spawned < wave.count && start + (*spawned as u64) * interval < now {
+                spawns.push(Spawn {
+                    kind: wave.kind.clone(),
+                    position: wave.position.position(*spawned),
+                });
+                *spawned += 1;
+            }
+        }
+        spawns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waves() -> Vec<Wave<&'static str>> {
+        vec![
+            Wave {
+                kind: "coin",
+                start: 0.0,
+                interval: 20.0,
+                count: 3,
+                position: Placement::Row {
+                    x: 600,
+                    y: 400,
+                    dx: 50,
+                    dy: 0,
+                },
+            },
+            Wave {
+                kind: "shield",
+                start: 30.0,
+                interval: 0.0,
+                count: 1,
+                position: Placement::Fixed { x: 600, y: 300 },
+            },
+        ]
+    }
+
+    #[test]
+    fn spawns_each_wave_on_its_interval() {
+        let mut spawner = Spawner::with_timestep(waves(), 10.0);
+        let spawned: Vec<Vec<&str>> = (0..6)
+            .map(|_| spawner.tick().iter().map(|spawn| spawn.kind).collect())
+            .collect();
+
+        assert_eq!(
+            spawned,
+            vec![
+                vec!["coin"],
+                vec![],
+                vec!["coin"],
+                vec!["shield"],
+                vec!["coin"],
+                vec![]
+            ]
+        );
+        assert!(spawner.is_finished());
+    }
+
+    #[test]
+    fn places_by_the_wave_rule() {
+        let placement = Placement::Heights {
+            x: 600,
+            heights: vec![300, 450],
+        };
+
+        let heights: Vec<i16> = (0..3).map(|index| placement.position(index).y).collect();
+
+        assert_eq!(heights, vec![300, 450, 300]);
+    }
+
+    #[test]
+    fn reads_waves_from_json() {
+        let waves: Vec<Wave<String>> = serde_json::from_str(
+            r#"[{"kind": "bones", "count": 2, "interval": 100,
+                 "position": {"rule": "fixed", "x": 600, "y": 450}}]"#,
+        )
+        .expect("Could not parse waves");
+
+        assert_eq!(waves[0].kind, "bones");
+        assert_eq!(waves[0].start, 0.0);
+    }
+}
@@ -0,0 +1,255 @@
+// Helpers for running a game in wasm-bindgen-test, without a game loop. Games
+// turn these on by depending on the engine with the `testing` feature in
+// their dev-dependencies.
+#[cfg(not(feature = "native"))]
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "native"))]
+use crate::browser;
+use crate::{
+    engine::{Game, Renderer},
+    input::{Action, InputState, ScriptedInput, Step},
+    platform,
+};
+
+// Golden frames are compared in blocks of this many pixels square
+const GOLDEN_BLOCK: u32 = 60;
+
+#[cfg(not(feature = "native"))]
+fn append_html(selector: &str, html: &str) {
+    browser::document()
+        .and_then(|document| {
+            document
+                .query_selector(selector)
+                .map_err(|err| anyhow!("Error finding {} {:#?}", selector, err))?
+                .ok_or_else(|| anyhow!("No {} element found", selector))
+        })
+        .and_then(|element| {
+            element
+                .insert_adjacent_html("beforeend", html)
+                .map_err(|err| anyhow!("Error appending to {} {:#?}", selector, err))
+        })
+        .expect("Could not set up the test page");
+}
+
+// A renderer for a canvas that's on the page, so it draws like the real
+// one, but isn't shown. Every test shares the same canvas.
+#[cfg(not(feature = "native"))]
+pub fn hidden_canvas() -> Renderer {
+    if browser::canvas().is_err() {
+        append_html(
+            "body",
+            "<canvas id=\"canvas\" width=\"600\" height=\"600\" hidden></canvas>",
+        );
+    }
+    Renderer::new(platform::Context::new(
+        browser::context().expect("No context for the test canvas"),
+    ))
+}
+
+// Natively the canvas is drawn in software, with nothing to show it
+#[cfg(feature = "native")]
+pub fn hidden_canvas() -> Renderer {
+    Renderer::new(platform::Context::offscreen(600, 600))
+}
+
+// The test runner serves the crate root, so point relative asset requests at
+// the static directory
+#[cfg(not(feature = "native"))]
+pub fn serve_static_assets() {
+    let served = browser::document()
+        .ok()
+        .and_then(|document| document.query_selector("base").ok().flatten())
+        .is_some();
+    if !served {
+        append_html("head", "<base href=\"/static/\">");
+    }
+}
+
+// Natively assets are read from static/ under the directory tests run in,
+// which is already the crate root
+#[cfg(feature = "native")]
+pub fn serve_static_assets() {}
+
+// Feeds the script in for the given number of fixed updates, calling update
+// with the input for each one
+pub fn play<A: Action>(steps: Vec<Step<A>>, frames: u32, mut update: impl FnMut(&InputState<A>)) {
+    let mut script = ScriptedInput::new(steps);
+    let mut input = InputState::new();
+    for _ in 0..frames {
+        input.update(&[&script]);
+        update(&input);
+        script.advance();
+    }
+}
+
+pub fn run_updates<A: Action>(game: &mut dyn Game<Action = A>, steps: Vec<Step<A>>, frames: u32) {
+    play(steps, frames, |input| game.update(input));
+}
+
+// A frame boiled down to the average color of each block of pixels. That's
+// small enough to keep as a reference, and forgiving of the few pixels of
+// anti-aliasing that differ between browsers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameSignature {
+    width: u32,
+    height: u32,
+    block: u32,
+    // RGBA, a row of blocks at a time
+    colors: Vec<[u8; 4]>,
+}
+
+// A block whose color is further from the reference than the tolerance
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockDifference {
+    // The block's top left, in canvas pixels
+    pub x: u32,
+    pub y: u32,
+    pub actual: [u8; 4],
+    pub expected: [u8; 4],
+}
+
+impl FrameSignature {
+    pub fn capture(renderer: &Renderer) -> Self {
+        let (width, height, pixels) = renderer.pixels();
+        FrameSignature::from_pixels(&pixels, width, height, GOLDEN_BLOCK)
+    }
+
+    pub fn from_pixels(pixels: &[u8], width: u32, height: u32, block: u32) -> Self {
+        let columns = width.div_ceil(block);
+        let rows = height.div_ceil(block);
+        let mut sums = vec![[0u32; 4]; (columns * rows) as usize];
+        let mut counts = vec![0u32; sums.len()];
+        for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            let cell = ((y / block) * columns + x / block) as usize;
+            for channel in 0..4 {
+                sums[cell][channel] += u32::from(pixel[channel]);
+            }
+            counts[cell] += 1;
+        }
+
+        let colors = sums
+            .iter()
+            .zip(counts)
+            .map(|(sum, count)| {
+                let mut color = [0; 4];
+                for channel in 0..4 {
+                    color[channel] = (sum[channel] / count.max(1)) as u8;
+                }
+                color
+            })
+            .collect();
+        FrameSignature {
+            width,
+            height,
+            block,
+            colors,
+        }
+    }
+
+    // Every block where any channel is more than tolerance away from the
+    // reference's
+    pub fn differences(&self, reference: &FrameSignature, tolerance: u8) -> Vec<BlockDifference> {
+        let columns = self.width.div_ceil(self.block);
+        self.colors
+            .iter()
+            .zip(reference.colors.iter())
+            .enumerate()
+            .filter(|(_, (actual, expected))| {
+                actual
+                    .iter()
+                    .zip(expected.iter())
+                    .any(|(actual, expected)| {
+                        (i16::from(*actual) - i16::from(*expected)).abs() > i16::from(tolerance)
+                    })
+            })
+            .map(|(index, (actual, expected))| BlockDifference {
+                x: index as u32 % columns * self.block,
+                y: index as u32 / columns * self.block,
+                actual: *actual,
+                expected: *expected,
+            })
+            .collect()
+    }
+}
+
+// Compares what's on the renderer's canvas with a reference saved from a
+// frame that looked right. The reference is fetched from the page, so under
+// wasm-pack test a path like /tests/golden/start.json is in the crate. When
+// there's no reference yet, or the frame differs, the panic message has the
+// frame's signature to save as the new one.
+pub async fn assert_matches_golden(renderer: &Renderer, reference: &str, tolerance: u8) {
+    let actual = FrameSignature::capture(renderer);
+    let signature = serde_json::to_string(&actual).unwrap_or_default();
+    let expected: FrameSignature = match platform::load_json(reference).await {
+        Ok(expected) => expected,
+        Err(err) => panic!(
+            "Could not load the golden frame {} {:#?}\nIf this frame is right, save it there: {}",
+            reference, err, signature
+        ),
+    };
+    assert_eq!(
+        (actual.width, actual.height, actual.block),
+        (expected.width, expected.height, expected.block),
+        "{} is for a different size of canvas",
+        reference
+    );
+
+    let differences = actual.differences(&expected, tolerance);
+    assert!(
+        differences.is_empty(),
+        "{} blocks differ from {}: {:#?}\nThis frame is: {}",
+        differences.len(),
+        reference,
+        differences,
+        signature
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 4x2 image, red on the left half and blue on the right
+    fn halves() -> Vec<u8> {
+        let mut pixels = vec![];
+        for _ in 0..2 {
+            pixels.extend_from_slice(&[255, 0, 0, 255, 255, 0, 0, 255]);
+            pixels.extend_from_slice(&[0, 0, 255, 255, 0, 0, 255, 255]);
+        }
+        pixels
+    }
+
+    #[test]
+    fn averages_each_block() {
+        let signature = FrameSignature::from_pixels(&halves(), 4, 2, 2);
+
+        assert_eq!(signature.colors, vec![[255, 0, 0, 255], [0, 0, 255, 255]]);
+    }
+
+    #[test]
+    fn reports_only_blocks_past_the_tolerance() {
+        let reference = FrameSignature::from_pixels(&halves(), 4, 2, 2);
+        let mut pixels = halves();
+        pixels[0] = 251;
+
+        let differences = FrameSignature::from_pixels(&pixels, 4, 2, 2).differences(&reference, 4);
+
+        assert_eq!(differences, vec![]);
+
+        pixels[4] = 0;
+        let differences = FrameSignature::from_pixels(&pixels, 4, 2, 2).differences(&reference, 4);
+
+        assert_eq!(
+            differences,
+            vec![BlockDifference {
+                x: 0,
+                y: 0,
+                actual: [190, 0, 0, 255],
+                expected: [255, 0, 0, 255],
+            }]
+        );
+    }
+}
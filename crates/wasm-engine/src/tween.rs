@@ -0,0 +1,250 @@
+// Animates a value from one number to another over time, on the fixed
+// timestep. Tweens don't hold on to what they animate. They're given a setter,
+// and like the scheduler, ticking them hands back the updates for the caller
+// to apply to its context.
+use std::rc::Rc;
+
+use crate::engine::FRAME_SIZE;
+
+pub type Setter<C> = Rc<dyn Fn(&mut C, f32)>;
+pub type Update<C> = Box<dyn FnOnce(&mut C)>;
+pub type OnComplete<C> = Rc<dyn Fn(&mut C)>;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    // Starts slowly and speeds up
+    EaseIn,
+    // Starts quickly and slows down into the end
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    // Maps progress from 0.0 to 1.0 onto how far along the value is
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            }
+        }
+    }
+}
+
+pub struct Tween<C> {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+    setter: Setter<C>,
+    on_complete: Option<OnComplete<C>>,
+    next: Option<Box<Tween<C>>>,
+}
+
+impl<C> Tween<C> {
+    pub fn new(
+        from: f32,
+        to: f32,
+        milliseconds: f32,
+        setter: impl Fn(&mut C, f32) + 'static,
+    ) -> Self {
+        Tween {
+            from,
+            to,
+            duration: milliseconds,
+            elapsed: 0.0,
+            easing: Easing::Linear,
+            setter: Rc::new(setter),
+            on_complete: None,
+            next: None,
+        }
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn on_complete(mut self, on_complete: impl Fn(&mut C) + 'static) -> Self {
+        self.on_complete = Some(Rc::new(on_complete));
+        self
+    }
+
+    // Starts next as soon as this one finishes. Chaining onto a tween that
+    // already has a next one chains onto the end.
+    pub fn then(mut self, next: Tween<C>) -> Self {
+        self.next = Some(Box::new(match self.next.take() {
+            Some(current) => current.then(next),
+            None => next,
+        }));
+        self
+    }
+
+    fn value(&self) -> f32 {
+        let t = if self.duration <= 0.0 {
+            1.0
+        } else {
+            (self.elapsed / self.duration).min(1.0)
+        };
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TweenHandle(u32);
+
+pub struct Tweens<C> {
+    timestep: f32,
+    next_handle: u32,
+    running: Vec<(TweenHandle, Tween<C>)>,
+}
+
+impl<C: 'static> Tweens<C> {
+    // The timestep is the length of one tick in milliseconds
+    pub fn new(timestep: f32) -> Self {
+        Tweens {
+            timestep,
+            next_handle: 0,
+            running: vec![],
+        }
+    }
+
+    // The handle stays the same through the whole chain
+    pub fn start(&mut self, tween: Tween<C>) -> TweenHandle {
+        let handle = TweenHandle(self.next_handle);
+        self.next_handle += 1;
+        self.running.push((handle, tween));
+        handle
+    }
+
+    // Stops where it is, without completing
+    pub fn cancel(&mut self, handle: TweenHandle) {
+        self.running.retain(|(running, _)| *running != handle);
+    }
+
+    pub fn is_running(&self, handle: TweenHandle) -> bool {
+        self.running.iter().any(|(running, _)| *running == handle)
+    }
+
+    // Moves every tween on one fixed step, and returns the updates to apply:
+    // the new values, then the completion callbacks of any that finished
+    pub fn tick(&mut self) -> Vec<Update<C>> {
+        let mut updates: Vec<Update<C>> = vec![];
+        let mut running = vec![];
+        for (handle, mut tween) in self.running.drain(..) {
+            tween.elapsed += self.timestep;
+            let setter = Rc::clone(&tween.setter);
+            let value = tween.value();
+            updates.push(Box::new(move |context| setter(context, value)));
+
+            if !tween.is_finished() {
+                running.push((handle, tween));
+                continue;
+            }
+            if let Some(on_complete) = tween.on_complete.take() {
+                updates.push(Box::new(move |context| on_complete(context)));
+            }
+            if let Some(next) = tween.next.take() {
+                running.push((handle, *next));
+            }
+        }
+        self.running = running;
+        updates
+    }
+
+    // Ticks and applies the updates, for tweens that aren't part of the
+    // context
+    pub fn run(&mut self, context: &mut C) {
+        for update in self.tick() {
+            update(context);
+        }
+    }
+}
+
+impl<C: 'static> Default for Tweens<C> {
+    fn default() -> Self {
+        Tweens::new(FRAME_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eases_every_curve_from_start_to_end() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ]
+        .iter()
+        {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn animates_a_value_then_completes() {
+        let mut tweens = Tweens::new(10.0);
+        let mut state = (0.0, false);
+
+        tweens.start(
+            Tween::new(0.0, 100.0, 40.0, |state: &mut (f32, bool), value| {
+                state.0 = value
+            })
+            .on_complete(|state| state.1 = true),
+        );
+        tweens.run(&mut state);
+
+        assert_eq!(state, (25.0, false));
+
+        for _ in 0..3 {
+            tweens.run(&mut state);
+        }
+
+        assert_eq!(state, (100.0, true));
+    }
+
+    #[test]
+    fn chained_tweens_run_one_after_another() {
+        let mut tweens = Tweens::new(10.0);
+        let mut value = 0.0;
+
+        let handle = tweens.start(
+            Tween::new(0.0, 10.0, 20.0, |value: &mut f32, new| *value = new).then(Tween::new(
+                10.0,
+                0.0,
+                20.0,
+                |value: &mut f32, new| *value = new,
+            )),
+        );
+        tweens.run(&mut value);
+        tweens.run(&mut value);
+
+        assert_eq!(value, 10.0);
+        assert!(tweens.is_running(handle));
+
+        tweens.run(&mut value);
+        tweens.run(&mut value);
+
+        assert_eq!(value, 0.0);
+        assert!(!tweens.is_running(handle));
+    }
+}
@@ -0,0 +1,81 @@
+// A layer of HTML over the canvas, for the few things a canvas can't do, like
+// a real button the browser will open a share sheet from. The page needs an
+// empty <div id="ui"> positioned over the canvas.
+use anyhow::{anyhow, Result};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Event, HtmlInputElement};
+
+use crate::browser;
+
+// Replaces whatever was shown before
+pub fn show(html: &str) -> Result<()> {
+    browser::hide_ui()?;
+    browser::draw_ui(html)
+}
+
+pub fn hide() -> Result<()> {
+    browser::hide_ui()
+}
+
+// Receives once for every click on the element with the given id
+pub fn on_click(id: &str) -> Result<UnboundedReceiver<()>> {
+    let element = browser::find_html_element_by_id(id)?;
+    let (sender, receiver) = unbounded();
+    let on_click = browser::closure_wrap(Box::new(move || {
+        let _ = sender.unbounded_send(());
+    }) as Box<dyn FnMut()>);
+    element.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+    on_click.forget();
+    Ok(receiver)
+}
+
+// Receives what's typed into the input with the given id each time the form
+// around it is submitted, e.g. with enter, and clears it for the next
+pub fn on_submit(form_id: &str, input_id: &str) -> Result<UnboundedReceiver<String>> {
+    let input = browser::find_html_element_by_id(input_id)?
+        .dyn_into::<HtmlInputElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlInputElement", element))?;
+    let (sender, receiver) = unbounded();
+    let on_submit = browser::closure_wrap(Box::new(move |event: Event| {
+        // Or the page reloads
+        event.prevent_default();
+        let _ = sender.unbounded_send(input.value());
+        input.set_value("");
+    }) as Box<dyn FnMut(Event)>);
+    browser::find_html_element_by_id(form_id)?
+        .set_onsubmit(Some(on_submit.as_ref().unchecked_ref()));
+    on_submit.forget();
+    Ok(receiver)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Shared {
+    Shared,
+    // There was no share sheet, so the text and URL were copied instead
+    Copied,
+}
+
+// Browsers only open the share sheet soon after a click, so this should be
+// called on the update the click is received
+pub async fn share(title: &str, text: &str, url: &str) -> Result<Shared> {
+    if let Some(promise) = browser::share(title, text, url)? {
+        JsFuture::from(promise)
+            .await
+            .map_err(|err| anyhow!("Error sharing {:#?}", err))?;
+        return Ok(Shared::Shared);
+    }
+
+    JsFuture::from(browser::write_to_clipboard(&format!("{} {}", text, url))?)
+        .await
+        .map_err(|err| anyhow!("Error copying to the clipboard {:#?}", err))?;
+    Ok(Shared::Copied)
+}
+
+pub async fn copy(text: &str) -> Result<()> {
+    JsFuture::from(browser::write_to_clipboard(text)?)
+        .await
+        .map_err(|err| anyhow!("Error copying to the clipboard {:#?}", err))?;
+    Ok(())
+}
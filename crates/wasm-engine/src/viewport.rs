@@ -0,0 +1,289 @@
+// The game draws at a fixed design resolution, and the viewport maps that onto
+// whatever size the canvas actually is. A camera sits in front of that,
+// mapping the game's world onto the design resolution, so there are three
+// coordinate spaces:
+//
+//   world  --camera-->  design  --viewport-->  canvas pixels
+//
+// Game code only ever works in the first two.
+use crate::engine::Vector;
+
+// Scale and offset from one coordinate space to the next
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        scale_x: 1.0,
+        scale_y: 1.0,
+        offset_x: 0.0,
+        offset_y: 0.0,
+    };
+
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.scale_x + self.offset_x,
+            y * self.scale_y + self.offset_y,
+        )
+    }
+
+    pub fn unapply(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.offset_x) / self.scale_x,
+            (y - self.offset_y) / self.scale_y,
+        )
+    }
+
+    // This transform followed by outer, as one transform
+    pub fn then(&self, outer: &Transform) -> Transform {
+        Transform {
+            scale_x: self.scale_x * outer.scale_x,
+            scale_y: self.scale_y * outer.scale_y,
+            offset_x: self.offset_x * outer.scale_x + outer.offset_x,
+            offset_y: self.offset_y * outer.scale_y + outer.offset_y,
+        }
+    }
+}
+
+// The world point drawn at the center of the design resolution, and how far
+// in it's zoomed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera {
+    pub center: Vector,
+    pub zoom: f32,
+}
+
+impl Camera {
+    // A camera that shows the world exactly as the design resolution, with
+    // world and design units the same
+    pub fn fixed(viewport: &Viewport) -> Self {
+        Camera {
+            center: Vector {
+                x: viewport.width / 2.0,
+                y: viewport.height / 2.0,
+            },
+            zoom: 1.0,
+        }
+    }
+
+    pub fn transform(&self, viewport: &Viewport) -> Transform {
+        Transform {
+            scale_x: self.zoom,
+            scale_y: self.zoom,
+            offset_x: viewport.width / 2.0 - self.center.x * self.zoom,
+            offset_y: viewport.height / 2.0 - self.center.y * self.zoom,
+        }
+    }
+}
+
+// What to do when the canvas isn't the same shape as the design resolution
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ViewportPolicy {
+    // Scales each axis separately to cover the canvas exactly, distorting
+    // the picture
+    Stretch,
+    // Scales until one axis fits, with bars along the other
+    Fit,
+    // Scales until both axes are covered, cropping whatever falls off the
+    // edges
+    Fill,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Viewport {
+    width: f32,
+    height: f32,
+    policy: ViewportPolicy,
+    pixel_art: bool,
+    high_dpi: bool,
+}
+
+impl Viewport {
+    pub fn new(width: f32, height: f32) -> Self {
+        Viewport {
+            width,
+            height,
+            policy: ViewportPolicy::Fit,
+            pixel_art: false,
+            high_dpi: false,
+        }
+    }
+
+    pub fn policy(mut self, policy: ViewportPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Turns off image smoothing and only scales by whole numbers, so every
+    // sprite pixel covers the same square of canvas pixels. Fit rounds the
+    // scale down and Fill rounds it up. Stretch can't keep pixels square, so
+    // it only loses the smoothing.
+    pub fn pixel_art(mut self) -> Self {
+        self.pixel_art = true;
+        self
+    }
+
+    // Sizes the canvas's backing store to its displayed size times the
+    // device pixel ratio, so drawing is sharp on high density screens. The
+    // page has to give the canvas its displayed size with CSS, or it will
+    // grow every frame.
+    pub fn high_dpi(mut self) -> Self {
+        self.high_dpi = true;
+        self
+    }
+
+    pub fn is_high_dpi(&self) -> bool {
+        self.high_dpi
+    }
+
+    pub fn is_pixel_art(&self) -> bool {
+        self.pixel_art
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    // Scales the design resolution by the policy, and centers it
+    pub fn transform(&self, canvas_width: f32, canvas_height: f32) -> Transform {
+        let scale_x = canvas_width / self.width;
+        let scale_y = canvas_height / self.height;
+        let scale = match (self.policy, self.pixel_art) {
+            (ViewportPolicy::Stretch, _) => {
+                return Transform {
+                    scale_x,
+                    scale_y,
+                    offset_x: 0.0,
+                    offset_y: 0.0,
+                }
+            }
+            (ViewportPolicy::Fit, false) => scale_x.min(scale_y),
+            (ViewportPolicy::Fit, true) => scale_x.min(scale_y).floor().max(1.0),
+            (ViewportPolicy::Fill, false) => scale_x.max(scale_y),
+            (ViewportPolicy::Fill, true) => scale_x.max(scale_y).ceil(),
+        };
+
+        Transform {
+            scale_x: scale,
+            scale_y: scale,
+            offset_x: (canvas_width - self.width * scale) / 2.0,
+            offset_y: (canvas_height - self.height * scale) / 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_and_centers_the_design_resolution() {
+        let transform = Viewport::new(600.0, 600.0).transform(1200.0, 900.0);
+
+        assert_eq!(
+            transform,
+            Transform {
+                scale_x: 1.5,
+                scale_y: 1.5,
+                offset_x: 150.0,
+                offset_y: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn pixel_art_only_scales_by_whole_numbers() {
+        let transform = Viewport::new(300.0, 200.0)
+            .pixel_art()
+            .transform(1000.0, 700.0);
+
+        assert_eq!(
+            transform,
+            Transform {
+                scale_x: 3.0,
+                scale_y: 3.0,
+                offset_x: 50.0,
+                offset_y: 50.0,
+            }
+        );
+    }
+
+    #[test]
+    fn fill_crops_the_longer_side() {
+        let transform = Viewport::new(600.0, 600.0)
+            .policy(ViewportPolicy::Fill)
+            .transform(1200.0, 900.0);
+
+        assert_eq!(
+            transform,
+            Transform {
+                scale_x: 2.0,
+                scale_y: 2.0,
+                offset_x: 0.0,
+                offset_y: -150.0,
+            }
+        );
+    }
+
+    #[test]
+    fn stretch_covers_the_canvas_exactly() {
+        let transform = Viewport::new(600.0, 600.0)
+            .policy(ViewportPolicy::Stretch)
+            .transform(1200.0, 900.0);
+
+        assert_eq!(
+            transform,
+            Transform {
+                scale_x: 2.0,
+                scale_y: 1.5,
+                offset_x: 0.0,
+                offset_y: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn converts_canvas_pixels_back_to_design_units() {
+        let transform = Viewport::new(600.0, 600.0).transform(1200.0, 900.0);
+
+        assert_eq!(transform.unapply(150.0, 0.0), (0.0, 0.0));
+        assert_eq!(transform.unapply(600.0, 450.0), (300.0, 300.0));
+    }
+
+    #[test]
+    fn world_to_canvas_round_trips_through_camera_and_viewport() {
+        let viewport = Viewport::new(600.0, 600.0);
+        let camera = Camera {
+            center: Vector {
+                x: 1000.0,
+                y: 300.0,
+            },
+            zoom: 2.0,
+        };
+        let transform = camera
+            .transform(&viewport)
+            .then(&viewport.transform(1200.0, 900.0));
+
+        assert_eq!(transform.apply(1000.0, 300.0), (600.0, 450.0));
+        assert_eq!(transform.unapply(600.0, 450.0), (1000.0, 300.0));
+    }
+
+    #[test]
+    fn a_fixed_camera_leaves_world_and_design_units_the_same() {
+        let viewport = Viewport::new(600.0, 600.0);
+
+        assert_eq!(
+            Camera::fixed(&viewport).transform(&viewport),
+            Transform::IDENTITY
+        );
+    }
+}
@@ -0,0 +1,64 @@
+// Keeps a phone's screen on while the game wants it, since a run played by
+// touch can go a long time between the taps the phone counts as activity.
+// The browser lets go of the lock by itself whenever the page is hidden, and
+// holding it again on the way back is up to the game.
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{browser, platform};
+
+#[derive(Default)]
+pub struct WakeLock {
+    wanted: Rc<Cell<bool>>,
+    sentinel: Rc<RefCell<Option<JsValue>>>,
+}
+
+impl WakeLock {
+    // Cheap to call every update, as it only asks the browser when wanted
+    // changes. Browsers without a wake lock just let the screen dim.
+    pub fn hold(&mut self, wanted: bool) {
+        if self.wanted.replace(wanted) == wanted {
+            return;
+        }
+        if !wanted {
+            if let Some(sentinel) = self.sentinel.borrow_mut().take() {
+                release(&sentinel);
+            }
+            return;
+        }
+
+        let request = match browser::request_wake_lock() {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(err) => {
+                log!("Could not request a wake lock {:#?}", err);
+                return;
+            }
+        };
+        let wanted = Rc::clone(&self.wanted);
+        let held = Rc::clone(&self.sentinel);
+        platform::spawn_local(async move {
+            match JsFuture::from(request).await {
+                // It was let go of while the request was on its way
+                Ok(sentinel) if !wanted.get() => release(&sentinel),
+                Ok(sentinel) => {
+                    held.replace(Some(sentinel));
+                }
+                Err(err) => {
+                    log!("Could not hold a wake lock {:#?}", err);
+                }
+            }
+        });
+    }
+}
+
+fn release(sentinel: &JsValue) {
+    if let Err(err) = browser::release_wake_lock(sentinel) {
+        log!("Could not release the wake lock {:#?}", err);
+    }
+}
@@ -1 +1,5 @@
-import("../pkg/index.js").catch(console.error);
+if ("serviceWorker" in navigator) {
+  navigator.serviceWorker.register("sw.js").catch(console.error);
+}
+
+import(/* webpackChunkName: "game" */ "../pkg/index.js").catch(console.error);
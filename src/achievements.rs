@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{Point, Rect, Renderer},
+    i18n::I18n,
+    platform,
+};
+
+const STORAGE_KEY: &str = "walk_the_dog_achievements";
+const TOAST_FRAMES: u16 = 180;
+
+// What the player has done so far, in the current run and over all runs.
+pub struct Progress {
+    pub distance: u32,
+    pub near_misses: u32,
+    pub lifetime_coins: u32,
+}
+
+struct Achievement {
+    id: &'static str,
+    name: &'static str,
+    unlocked_by: fn(&Progress) -> bool,
+}
+
+const ACHIEVEMENTS: [Achievement; 3] = [
+    Achievement {
+        id: "marathon",
+        name: "achievements.marathon",
+        unlocked_by: |progress| progress.distance >= 1000,
+    },
+    Achievement {
+        id: "collector",
+        name: "achievements.collector",
+        unlocked_by: |progress| progress.lifetime_coins >= 50,
+    },
+    Achievement {
+        id: "daredevil",
+        name: "achievements.daredevil",
+        unlocked_by: |progress| progress.near_misses >= 10,
+    },
+];
+
+#[derive(Serialize, Deserialize, Default)]
+struct SavedAchievements {
+    unlocked: Vec<String>,
+}
+
+struct Toast {
+    text: &'static str,
+    frames: u16,
+}
+
+pub struct Achievements {
+    saved: SavedAchievements,
+    toasts: Vec<Toast>,
+}
+
+impl Achievements {
+    pub fn load() -> Self {
+        let saved = platform::get_item(STORAGE_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Achievements {
+            saved,
+            toasts: vec![],
+        }
+    }
+
+    pub fn update(&mut self, progress: &Progress) {
+        let newly_unlocked: Vec<&Achievement> = ACHIEVEMENTS
+            .iter()
+            .filter(|achievement| !self.is_unlocked(achievement.id))
+            .filter(|achievement| (achievement.unlocked_by)(progress))
+            .collect();
+
+        for achievement in newly_unlocked.iter() {
+            self.saved.unlocked.push(achievement.id.to_string());
+            self.toasts.push(Toast {
+                text: achievement.name,
+                frames: TOAST_FRAMES,
+            });
+        }
+        if !newly_unlocked.is_empty() {
+            self.save();
+        }
+
+        if let Some(toast) = self.toasts.first_mut() {
+            toast.frames = toast.frames.saturating_sub(1);
+            if toast.frames == 0 {
+                self.toasts.remove(0);
+            }
+        }
+    }
+
+    // Toasts are shown one at a time, in the order they were unlocked
+    pub fn draw_toast(&self, renderer: &Renderer, i18n: &I18n) {
+        if let Some(toast) = self.toasts.first() {
+            renderer.fill_rect(
+                "rgba(0, 0, 0, 0.7)",
+                &Rect {
+                    x: 100.0,
+                    y: 520.0,
+                    width: 400.0,
+                    height: 56.0,
+                },
+            );
+            renderer.draw_text(
+                i18n.get("achievements.unlocked"),
+                &Point { x: 115, y: 542 },
+                "#ffd700",
+                16,
+            );
+            renderer.draw_text(
+                i18n.get(toast.text),
+                &Point { x: 115, y: 566 },
+                "#ffffff",
+                16,
+            );
+        }
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.saved.unlocked.iter().any(|unlocked| unlocked == id)
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.saved) {
+            Ok(json) => {
+                if let Err(err) = platform::set_item(STORAGE_KEY, &json) {
+                    log!("Could not save achievements {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not serialize achievements {:#?}", err);
+            }
+        }
+    }
+}
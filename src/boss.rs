@@ -0,0 +1,245 @@
+use std::rc::Rc;
+
+use crate::{
+    engine::{Point, Rect, Renderer},
+    world,
+};
+use wasm_engine::behavior::{action, condition, selector, sequence, Node, Status};
+
+const ATTACK_FRAMES: u16 = 240;
+const VULNERABLE_FRAMES: u16 = 120;
+const SHOT_INTERVAL: u16 = 60;
+const ENRAGED_SHOT_INTERVAL: u16 = 35;
+const SHOT_SPEED: i16 = -6;
+const LOW_SHOT_Y: i16 = 550;
+const HIGH_SHOT_Y: i16 = 450;
+const BOSS_WIDTH: f32 = 120.0;
+const BOSS_HEIGHT: f32 = 160.0;
+
+#[derive(Copy, Clone)]
+enum Phase {
+    Attacking(u16),
+    Vulnerable(u16),
+    Defeated,
+}
+
+#[derive(Clone)]
+pub enum BossEvent {
+    Fire(BossShot),
+    OpenUp,
+}
+
+// Whole pixels at a whole pixel speed, so shots need no fixed point
+#[derive(Clone)]
+pub struct BossShot {
+    position: Point,
+}
+
+impl BossShot {
+    pub fn update(&mut self) {
+        self.position.x = world::shift(self.position.x, SHOT_SPEED);
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        renderer.fill_rect("#d9482b", &self.bounding_box());
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.into(),
+            y: self.position.y.into(),
+            width: 30.0,
+            height: 20.0,
+        }
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.position.x < -30
+    }
+}
+
+// The boss cycles between attacking, when it fires shots RHB has to jump or
+// slide past, and a vulnerable window when it can be hit. Once it's down to
+// half health it enrages, firing faster and mixing in high shots.
+#[derive(Clone)]
+pub struct Boss {
+    position: Point,
+    health: u8,
+    max_health: u8,
+    phase: Phase,
+    shots_fired: u16,
+    brain: Rc<Node<Boss>>,
+    // What this update's decision was, handed back from update()
+    event: Option<BossEvent>,
+}
+
+// Each update the boss takes the first thing it can do: nothing once it's
+// beaten, wait out a vulnerable window, open up when the attack runs out, or
+// keep attacking, firing whenever a shot is due.
+fn brain() -> Node<Boss> {
+    selector(vec![
+        condition(Boss::is_defeated),
+        sequence(vec![condition(Boss::is_vulnerable), action(Boss::recover)]),
+        sequence(vec![condition(Boss::attack_over), action(Boss::open_up)]),
+        sequence(vec![
+            selector(vec![
+                sequence(vec![condition(Boss::shot_due), action(Boss::fire_shot)]),
+                action(|_| Status::Success),
+            ]),
+            action(Boss::keep_attacking),
+        ]),
+    ])
+}
+
+impl Boss {
+    pub fn new(position: Point, health: u8) -> Self {
+        Boss {
+            position,
+            health,
+            max_health: health,
+            phase: Phase::Attacking(ATTACK_FRAMES),
+            shots_fired: 0,
+            brain: Rc::new(brain()),
+            event: None,
+        }
+    }
+
+    pub fn update(&mut self) -> Option<BossEvent> {
+        let brain = Rc::clone(&self.brain);
+        brain.tick(self);
+        self.event.take()
+    }
+
+    pub fn hit(&mut self) -> bool {
+        if !self.is_vulnerable() {
+            return false;
+        }
+
+        self.health = self.health.saturating_sub(1);
+        self.phase = if self.health == 0 {
+            Phase::Defeated
+        } else {
+            Phase::Attacking(ATTACK_FRAMES)
+        };
+        true
+    }
+
+    pub fn is_vulnerable(&self) -> bool {
+        matches!(self.phase, Phase::Vulnerable(_))
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        matches!(self.phase, Phase::Defeated)
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let color = match self.phase {
+            Phase::Attacking(_) => "#5b1a1a",
+            Phase::Vulnerable(frames) if frames % 10 < 5 => "#ffd23f",
+            Phase::Vulnerable(_) => "#5b1a1a",
+            Phase::Defeated => return,
+        };
+        renderer.fill_rect(color, &self.bounding_box());
+    }
+
+    pub fn draw_health_bar(&self, renderer: &Renderer) {
+        let bar = Rect {
+            x: 150.0,
+            y: 20.0,
+            width: 300.0,
+            height: 16.0,
+        };
+        renderer.fill_rect("#333333", &bar);
+        renderer.fill_rect(
+            "#d9482b",
+            &Rect {
+                width: bar.width * self.health as f32 / self.max_health as f32,
+                ..bar
+            },
+        );
+        renderer.draw_rect("#ffffff", &bar);
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.into(),
+            y: self.position.y.into(),
+            width: BOSS_WIDTH,
+            height: BOSS_HEIGHT,
+        }
+    }
+
+    fn recover(&mut self) -> Status {
+        self.phase = match self.phase {
+            Phase::Vulnerable(0) => Phase::Attacking(ATTACK_FRAMES),
+            Phase::Vulnerable(frames) => Phase::Vulnerable(frames - 1),
+            phase => phase,
+        };
+        Status::Running
+    }
+
+    fn attack_over(&self) -> bool {
+        matches!(self.phase, Phase::Attacking(0))
+    }
+
+    fn open_up(&mut self) -> Status {
+        self.phase = Phase::Vulnerable(VULNERABLE_FRAMES);
+        self.event = Some(BossEvent::OpenUp);
+        Status::Success
+    }
+
+    fn shot_due(&self) -> bool {
+        match self.phase {
+            Phase::Attacking(frames) => frames % self.shot_interval() == 0,
+            _ => false,
+        }
+    }
+
+    fn fire_shot(&mut self) -> Status {
+        self.event = Some(BossEvent::Fire(self.fire()));
+        Status::Success
+    }
+
+    fn keep_attacking(&mut self) -> Status {
+        if let Phase::Attacking(frames) = self.phase {
+            self.phase = Phase::Attacking(frames - 1);
+        }
+        Status::Running
+    }
+
+    fn is_enraged(&self) -> bool {
+        self.health * 2 <= self.max_health
+    }
+
+    fn shot_interval(&self) -> u16 {
+        if self.is_enraged() {
+            ENRAGED_SHOT_INTERVAL
+        } else {
+            SHOT_INTERVAL
+        }
+    }
+
+    fn fire(&mut self) -> BossShot {
+        self.shots_fired += 1;
+        let y = if self.is_enraged() && self.shots_fired.is_multiple_of(2) {
+            HIGH_SHOT_Y
+        } else {
+            LOW_SHOT_Y
+        };
+
+        BossShot {
+            position: Point {
+                x: self.position.x,
+                y,
+            },
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use wasm_engine::config::QueryParams;
+
+const KEY: &str = "c";
+const RADIX: u64 = 36;
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+// A run to challenge a friend to: the same layout at the same difficulty. It
+// goes in the URL fragment as #c=e.2n9c.1, which is the mode (e for endless,
+// d for a daily challenge), the seed in base 36, and the difficulty.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub daily: bool,
+    pub seed: u64,
+    pub difficulty: u8,
+}
+
+impl Challenge {
+    // None unless the whole fragment makes sense. The difficulty can be left
+    // off, for the easiest.
+    pub fn parse(hash: &str) -> Option<Self> {
+        let value: String = QueryParams::parse(hash.trim_start_matches('#')).get(KEY)?;
+        let mut parts = value.split('.');
+        let daily = match parts.next()? {
+            "e" => false,
+            "d" => true,
+            _ => return None,
+        };
+        let seed = u64::from_str_radix(parts.next()?, RADIX as u32).ok()?;
+        let difficulty = match parts.next() {
+            Some(difficulty) => difficulty.parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Challenge {
+            daily,
+            seed,
+            difficulty,
+        })
+    }
+
+    pub fn fragment(&self) -> String {
+        let mode = if self.daily { "d" } else { "e" };
+        format!(
+            "#{}={}.{}.{}",
+            KEY,
+            mode,
+            base36(self.seed),
+            self.difficulty
+        )
+    }
+}
+
+fn base36(mut value: u64) -> String {
+    let mut digits = vec![];
+    loop {
+        digits.push(DIGITS[(value % RADIX) as usize]);
+        value /= RADIX;
+        if value == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_the_round_trip_through_a_fragment() {
+        let challenge = Challenge {
+            daily: false,
+            seed: u64::MAX,
+            difficulty: 2,
+        };
+
+        assert_eq!(Challenge::parse(&challenge.fragment()), Some(challenge));
+    }
+
+    #[test]
+    fn is_compact() {
+        let challenge = Challenge {
+            daily: true,
+            seed: 20261015,
+            difficulty: 0,
+        };
+
+        assert_eq!(challenge.fragment(), "#c=d.c29hz.0");
+    }
+
+    #[test]
+    fn rejects_fragments_that_dont_make_sense() {
+        assert_eq!(
+            Challenge::parse("#c=e.zz"),
+            Some(Challenge {
+                daily: false,
+                seed: 36 * 36 - 1,
+                difficulty: 0,
+            })
+        );
+        assert_eq!(Challenge::parse("#c=x.zz.1"), None);
+        assert_eq!(Challenge::parse("#c=e.zz.1.2"), None);
+        assert_eq!(Challenge::parse("#seed=42"), None);
+    }
+}
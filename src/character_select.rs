@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use wasm_engine::input::{Gamepads, Touches};
+
+use crate::{
+    achievements::Achievements,
+    controls::{self, Action, InputState, Profile},
+    engine::{Anchor, Animation, Game, KeyState, Point, Renderer, SpriteSheets},
+    game::{Start, WalkTheDog},
+    i18n::I18n,
+    red_hat_boy::RhbAnimation,
+    skins::{Skin, Wardrobe, SKINS},
+    stats::Stats,
+};
+
+const BACKGROUND_COLOR: &str = "#2c3e50";
+const PREVIEW_SPACING: i16 = 180;
+const PREVIEW_LEFT: i16 = 120;
+const PREVIEW_FEET: i16 = 380;
+// Updates per frame of the preview animation, like RHB's own
+const PREVIEW_RATE: u16 = 3;
+
+// Shown before a run starts, with every skin running in place. The one
+// chosen is what the game starts with, and it's saved for next time.
+pub struct CharacterSelect {
+    // One for each skin, in the same order
+    previews: Vec<Animation<RhbAnimation>>,
+    wardrobe: Wardrobe,
+    frame: u16,
+    i18n: I18n,
+    achievements: Achievements,
+    lifetime_coins: u32,
+    // Taken once the player has chosen
+    start: Option<Start>,
+    chosen: Option<&'static Skin>,
+}
+
+impl CharacterSelect {
+    pub async fn load(start: Start) -> Result<Self> {
+        let mut sheets = SpriteSheets::default();
+        let mut previews = vec![];
+        for skin in SKINS.iter() {
+            previews.push(Animation::new(
+                sheets
+                    .load(skin.character.sheet, Anchor::FeetCenter)
+                    .await?,
+            ));
+        }
+        let mut wardrobe = Wardrobe::default();
+        wardrobe.show(Skin::load());
+
+        Ok(CharacterSelect {
+            previews,
+            wardrobe,
+            frame: 0,
+            i18n: I18n::load().await?,
+            achievements: Achievements::load(),
+            lifetime_coins: Stats::load().lifetime.coins,
+            start: Some(start),
+            chosen: None,
+        })
+    }
+
+    fn is_unlocked(&self, skin: &Skin) -> bool {
+        skin.is_unlocked(&self.achievements, self.lifetime_coins)
+    }
+}
+
+#[async_trait(?Send)]
+impl Game for CharacterSelect {
+    type Action = Action;
+
+    async fn initialize(&mut self) -> Result<Box<dyn Game<Action = Action>>> {
+        Err(anyhow!("CharacterSelect is already loaded!"))
+    }
+
+    fn read_input(
+        &self,
+        keys: &KeyState,
+        gamepads: &Gamepads,
+        touches: &Touches,
+        input: &mut InputState,
+    ) {
+        let profile = self.start.as_ref().map_or(Profile::Default, Start::profile);
+        controls::read(keys, gamepads, touches, profile, input);
+    }
+
+    fn update(&mut self, input: &InputState) {
+        self.frame = self.frame.wrapping_add(1);
+        if self.chosen.is_some() {
+            return;
+        }
+        if input.just_pressed(Action::Up) {
+            self.wardrobe.move_highlight(-1);
+        }
+        if input.just_pressed(Action::Down) {
+            self.wardrobe.move_highlight(1);
+        }
+        let skin = self.wardrobe.highlighted();
+        if input.just_pressed(Action::Jump) && self.is_unlocked(skin) {
+            skin.save();
+            self.chosen = Some(skin);
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.clear_all(Some(BACKGROUND_COLOR));
+        renderer.draw_text(
+            self.i18n.get("select.title"),
+            &Point { x: 120, y: 100 },
+            "#ffffff",
+            32,
+        );
+        for (column, (skin, preview)) in SKINS.iter().zip(self.previews.iter()).enumerate() {
+            let x = PREVIEW_LEFT + column as i16 * PREVIEW_SPACING;
+            let unlocked = self.is_unlocked(skin);
+            // Only the highlighted skin runs, the rest stand still
+            let (animation, frame) = if skin.id == self.wardrobe.highlighted().id {
+                let count = preview.frame_count(RhbAnimation::Run) as u16;
+                (RhbAnimation::Run, (self.frame / PREVIEW_RATE) % count)
+            } else {
+                (RhbAnimation::Idle, 0)
+            };
+            match (unlocked, skin.filter) {
+                (false, _) => renderer.set_filter("brightness(0.2)"),
+                (true, Some(filter)) => renderer.set_filter(filter),
+                (true, None) => {}
+            }
+            preview.draw(
+                renderer,
+                animation,
+                &(frame as i16),
+                &Point { x, y: PREVIEW_FEET },
+            );
+            renderer.set_filter("none");
+
+            let color = if skin.id == self.wardrobe.highlighted().id {
+                "#ffd700"
+            } else {
+                "#ffffff"
+            };
+            renderer.draw_text(
+                self.i18n.get(skin.name),
+                &Point {
+                    x: x - 40,
+                    y: PREVIEW_FEET + 40,
+                },
+                color,
+                18,
+            );
+            if !unlocked {
+                renderer.draw_text(
+                    self.i18n.get(skin.hint),
+                    &Point {
+                        x: x - 60,
+                        y: PREVIEW_FEET + 60,
+                    },
+                    "#aaaaaa",
+                    10,
+                );
+            }
+        }
+        renderer.draw_text(
+            self.i18n.get("select.hint"),
+            &Point { x: 120, y: 540 },
+            "#ffffff",
+            16,
+        );
+    }
+
+    fn replacement(&mut self) -> Option<Box<dyn Game<Action = Action>>> {
+        let skin = self.chosen?;
+        let start = self.start.take()?;
+        Some(Box::new(WalkTheDog::Starting(start, skin)))
+    }
+}
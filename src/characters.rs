@@ -0,0 +1,30 @@
+// Who's running. Each character has a sprite sheet and physics of their own,
+// and maybe a special move, and skins dress one of them up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Special {
+    None,
+    // Runs straight through breakable obstacles, without having to slide
+    Smash,
+}
+
+#[derive(Debug)]
+pub struct Character {
+    // rhb is rhb.json and rhb.png
+    pub sheet: &'static str,
+    // Loaded into a PhysicsConfig
+    pub physics: &'static str,
+    pub special: Special,
+}
+
+pub const RHB: Character = Character {
+    sheet: "rhb",
+    physics: "physics.json",
+    special: Special::None,
+};
+
+// Slower and jumps lower, but nothing breakable gets in his way
+pub const HEAVY: Character = Character {
+    sheet: "rhb",
+    physics: "physics_heavy.json",
+    special: Special::Smash,
+};
@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use wasm_engine::{
+    engine::KeyState,
+    input::{self, Gamepads, Gesture, InputDevice, Touches},
+};
+
+use crate::platform;
+
+const PROFILE_KEY: &str = "walk_the_dog_profile";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Run,
+    Jump,
+    Slide,
+    Throw,
+    Up,
+    Down,
+    Settings,
+    Screenshot,
+    SaveClip,
+    Rewind,
+    Wardrobe,
+    // Player two, in co-op
+    PartnerRun,
+    PartnerJump,
+    PartnerSlide,
+    // On to the next line of dialog
+    Skip,
+}
+
+pub type InputState = input::InputState<Action>;
+
+const ACTIONS: [Action; 15] = [
+    Action::Run,
+    Action::Jump,
+    Action::Slide,
+    Action::Throw,
+    Action::Up,
+    Action::Down,
+    Action::Settings,
+    Action::Screenshot,
+    Action::SaveClip,
+    Action::Rewind,
+    Action::Wardrobe,
+    Action::PartnerRun,
+    Action::PartnerJump,
+    Action::PartnerSlide,
+    Action::Skip,
+];
+
+impl input::Action for Action {
+    fn all() -> &'static [Self] {
+        &ACTIONS
+    }
+}
+
+enum Binding {
+    Keys(&'static [&'static str]),
+    // Held down for as long as the profile is in use
+    Always,
+}
+
+// A named set of keyboard bindings for every action. Gamepads always use the
+// standard mapping.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Profile {
+    Default,
+    LeftHanded,
+    // RHB runs on his own and the one button jumps, for playing on a phone
+    OneButton,
+}
+
+// Which buttons the game's prompts name
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Glyphs {
+    Keyboard,
+    Gamepad,
+}
+
+pub const PROFILES: [Profile; 3] = [Profile::Default, Profile::LeftHanded, Profile::OneButton];
+
+impl Profile {
+    pub fn load() -> Self {
+        platform::get_item(PROFILE_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or(Profile::Default)
+    }
+
+    pub fn save(&self) {
+        match serde_json::to_string(self) {
+            Ok(json) => {
+                if let Err(err) = platform::set_item(PROFILE_KEY, &json) {
+                    log!("Could not save binding profile {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not serialize binding profile {:#?}", err);
+            }
+        }
+    }
+
+    // The i18n key for the profile's name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Default => "profile.default",
+            Profile::LeftHanded => "profile.left_handed",
+            Profile::OneButton => "profile.one_button",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Profile::Default => Profile::LeftHanded,
+            Profile::LeftHanded => Profile::OneButton,
+            Profile::OneButton => Profile::Default,
+        }
+    }
+
+    // On to the next profile, saved for next time
+    pub fn switch(&mut self) {
+        *self = self.next();
+        self.save();
+    }
+
+    // What the action's bound to on the keyboard, with nothing for actions
+    // that are always held
+    #[cfg(test)]
+    pub fn keys(&self, action: Action) -> &'static [&'static str] {
+        match self.binding(action) {
+            Binding::Keys(codes) => codes,
+            Binding::Always => &[],
+        }
+    }
+
+    fn binding(&self, action: Action) -> Binding {
+        match (self, action) {
+            (_, Action::Settings) => Binding::Keys(&["Escape"]),
+            (_, Action::Screenshot) => Binding::Keys(&["F2"]),
+            (_, Action::SaveClip) => Binding::Keys(&["F3"]),
+            (_, Action::Rewind) => Binding::Keys(&["F4"]),
+            (_, Action::Wardrobe) => Binding::Keys(&["KeyC"]),
+            (_, Action::PartnerRun) => Binding::Keys(&["KeyD"]),
+            (_, Action::PartnerJump) => Binding::Keys(&["KeyW"]),
+            (_, Action::PartnerSlide) => Binding::Keys(&["KeyS"]),
+            (_, Action::Skip) => Binding::Keys(&["Enter"]),
+            (Profile::Default, Action::Run) => Binding::Keys(&["ArrowRight"]),
+            (Profile::Default, Action::Jump) => Binding::Keys(&["Space"]),
+            (Profile::Default, Action::Slide) => Binding::Keys(&["ArrowDown"]),
+            (Profile::Default, Action::Throw) => Binding::Keys(&["KeyF"]),
+            (Profile::Default, Action::Up) => Binding::Keys(&["ArrowUp"]),
+            (Profile::Default, Action::Down) => Binding::Keys(&["ArrowDown"]),
+            (Profile::LeftHanded, Action::Run) => Binding::Keys(&["KeyD"]),
+            (Profile::LeftHanded, Action::Jump) => Binding::Keys(&["Space"]),
+            (Profile::LeftHanded, Action::Slide) => Binding::Keys(&["KeyS"]),
+            (Profile::LeftHanded, Action::Throw) => Binding::Keys(&["KeyE"]),
+            (Profile::LeftHanded, Action::Up) => Binding::Keys(&["KeyW"]),
+            (Profile::LeftHanded, Action::Down) => Binding::Keys(&["KeyS"]),
+            (Profile::OneButton, Action::Run) => Binding::Always,
+            (Profile::OneButton, Action::Jump) => Binding::Keys(&["Space"]),
+            (Profile::OneButton, _) => Binding::Keys(&[]),
+        }
+    }
+}
+
+// The keyboard, read through a binding profile
+struct Keyboard<'a> {
+    keys: &'a KeyState,
+    profile: Profile,
+}
+
+impl InputDevice<Action> for Keyboard<'_> {
+    fn value(&self, action: Action) -> f32 {
+        let pressed = match self.profile.binding(action) {
+            Binding::Keys(codes) => codes.iter().any(|code| self.keys.is_pressed(code)),
+            Binding::Always => true,
+        };
+        if pressed {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Gamepads always use the standard mapping, whatever the profile
+struct StandardMapping<'a>(&'a Gamepads);
+
+impl InputDevice<Action> for StandardMapping<'_> {
+    fn value(&self, action: Action) -> f32 {
+        self.0.value(|pad| match action {
+            Action::Run => pad.button(15).max(pad.axis(0, 1.0)),
+            Action::Jump => pad.button(0),
+            Action::Slide => pad.button(1),
+            Action::Throw => pad.button(2),
+            Action::Up => pad.button(12).max(pad.axis(1, -1.0)),
+            Action::Down => pad.button(13).max(pad.axis(1, 1.0)),
+            Action::Settings => pad.button(9),
+            Action::Wardrobe => pad.button(3),
+            Action::Screenshot => pad.button(8),
+            Action::Skip => pad.button(5),
+            Action::SaveClip
+            | Action::Rewind
+            | Action::PartnerRun
+            | Action::PartnerJump
+            | Action::PartnerSlide => 0.0,
+        })
+    }
+}
+
+// On a phone: swipe up to jump, swipe down to slide and tap to run. A gesture
+// holds its action for a single update.
+struct TouchGestures<'a>(&'a Touches);
+
+impl InputDevice<Action> for TouchGestures<'_> {
+    fn value(&self, action: Action) -> f32 {
+        let gesture = match action {
+            Action::Run => Gesture::Tap,
+            Action::Jump => Gesture::SwipeUp,
+            Action::Slide => Gesture::SwipeDown,
+            _ => return 0.0,
+        };
+        if self.0.gesture() == Some(gesture) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Player two is on WASD when sharing the keyboard, which some profiles use
+// too, so player one is moved onto the arrows for as long as that lasts
+pub fn keyboard_profile(profile: Profile, sharing_keyboard: bool) -> Profile {
+    if sharing_keyboard {
+        Profile::Default
+    } else {
+        profile
+    }
+}
+
+pub fn read(
+    keys: &KeyState,
+    gamepads: &Gamepads,
+    touches: &Touches,
+    profile: Profile,
+    input: &mut InputState,
+) {
+    input.update(&[
+        &Keyboard { keys, profile },
+        &StandardMapping(gamepads),
+        &TouchGestures(touches),
+    ]);
+}
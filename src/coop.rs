@@ -0,0 +1,48 @@
+use crate::red_hat_boy::RHB_POSITION;
+
+// Live players are kept between these, in screen pixels
+pub const FRAME_LEFT: i16 = 40;
+pub const FRAME_RIGHT: i16 = 420;
+// The camera closes the gap between the players' midpoint and RHB's usual
+// spot by a fraction of it each update
+const CAMERA_PULL: i16 = 20;
+
+// How fast the world scrolls past with more than one player, from each live
+// player's screen x and walking speed. It follows their average speed, and
+// drifts to keep the pair centred where RHB usually stands. Players then
+// move across the screen by however much faster or slower they are.
+pub fn camera_speed(players: &[(i16, i16)]) -> i16 {
+    if players.is_empty() {
+        return 0;
+    }
+    let count = players.len() as i16;
+    let speed: i16 = players.iter().map(|(_, speed)| speed).sum::<i16>() / count;
+    let midpoint: i16 = players.iter().map(|(x, _)| x).sum::<i16>() / count;
+    (speed + (midpoint - RHB_POSITION) / CAMERA_PULL).max(0)
+}
+
+// Where a live player at screen x is kept to, so neither runs off the screen
+pub fn framed(x: i16) -> i16 {
+    x.clamp(FRAME_LEFT, FRAME_RIGHT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_average_speed() {
+        let players = [(RHB_POSITION - 20, 2), (RHB_POSITION + 20, 6)];
+
+        assert_eq!(camera_speed(&players), 4);
+    }
+
+    #[test]
+    fn catches_up_with_players_ahead_of_centre() {
+        let centred = camera_speed(&[(RHB_POSITION, 4), (RHB_POSITION, 4)]);
+        let ahead = camera_speed(&[(RHB_POSITION + 200, 4), (RHB_POSITION + 200, 4)]);
+
+        assert!(ahead > centred);
+        assert_eq!(camera_speed(&[]), 0);
+    }
+}
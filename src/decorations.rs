@@ -0,0 +1,102 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::engine::{Rect, Renderer};
+
+// The foreground scrolls this much faster than the world, so it looks closer
+const PARALLAX: f32 = 1.2;
+const GROUND: f32 = 600.0;
+const GAP_MIN: f32 = 80.0;
+const GAP_MAX: f32 = 260.0;
+// Decorations are made just past the right edge of the screen, and dropped
+// once they're past the left
+const SPAWN_X: f32 = 700.0;
+const CULL_X: f32 = -100.0;
+
+#[derive(Copy, Clone)]
+enum Kind {
+    GrassTuft,
+    Bush,
+    FencePost,
+}
+
+struct Decoration {
+    kind: Kind,
+    x: f32,
+}
+
+impl Decoration {
+    fn draw(&self, renderer: &Renderer) {
+        let (color, blades): (&str, &[(f32, f32, f32)]) = match self.kind {
+            Kind::GrassTuft => (
+                "#3f8f29",
+                &[(0.0, 4.0, 14.0), (5.0, 4.0, 22.0), (10.0, 4.0, 12.0)],
+            ),
+            Kind::Bush => ("#2e6b1f", &[(0.0, 60.0, 22.0), (12.0, 36.0, 34.0)]),
+            Kind::FencePost => ("#7a5230", &[(0.0, 10.0, 48.0)]),
+        };
+        for (x, width, height) in blades.iter() {
+            renderer.fill_rect(
+                color,
+                &Rect {
+                    x: self.x + x,
+                    y: GROUND - height,
+                    width: *width,
+                    height: *height,
+                },
+            );
+        }
+    }
+}
+
+// Grass, bushes and fence posts drawn in front of RHB for depth. They're only
+// scenery, so nothing collides with them, and they're made up as the world
+// scrolls rather than placed.
+pub struct Foreground {
+    decorations: Vec<Decoration>,
+    rng: StdRng,
+    // Where the next decoration goes
+    next_x: f32,
+}
+
+impl Foreground {
+    pub fn new(rng: StdRng) -> Self {
+        let mut foreground = Foreground {
+            decorations: vec![],
+            rng,
+            next_x: 0.0,
+        };
+        foreground.generate();
+        foreground
+    }
+
+    fn generate(&mut self) {
+        while self.next_x < SPAWN_X {
+            let kind = match self.rng.gen_range(0..3) {
+                0 => Kind::GrassTuft,
+                1 => Kind::Bush,
+                _ => Kind::FencePost,
+            };
+            self.decorations.push(Decoration {
+                kind,
+                x: self.next_x,
+            });
+            self.next_x += self.rng.gen_range(GAP_MIN..GAP_MAX);
+        }
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        let distance = x as f32 * PARALLAX;
+        for decoration in self.decorations.iter_mut() {
+            decoration.x += distance;
+        }
+        self.next_x += distance;
+        self.decorations.retain(|decoration| decoration.x > CULL_X);
+        self.generate();
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.decorations
+            .iter()
+            .for_each(|decoration| decoration.draw(renderer));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+use wasm_engine::scheduler::{Scheduler, TimerHandle};
+
+use crate::{
+    controls::{Action, InputState},
+    engine::{Anchor, Point, Rect, Renderer, Sprite, SpriteSheet, SpriteSheets},
+    i18n::I18n,
+    platform,
+};
+
+const BOX_COLOR: &str = "rgba(0, 0, 0, 0.7)";
+const BOX: Rect = Rect {
+    x: 40.0,
+    y: 440.0,
+    width: 520.0,
+    height: 120.0,
+};
+const PORTRAIT_SIZE: f32 = 96.0;
+const PADDING: f32 = 12.0;
+
+// One text box, shown for duration milliseconds unless it's skipped
+#[derive(Deserialize, Debug, Clone)]
+pub struct Line {
+    // A key of speakers
+    pub speaker: String,
+    // The i18n key for what's said
+    pub text: String,
+    pub duration: f32,
+}
+
+// Whose portrait goes by the text, as a cell of one of the sprite sheets
+#[derive(Deserialize, Debug, Clone)]
+pub struct Speaker {
+    // The i18n key for their name
+    pub name: String,
+    pub sheet: String,
+    pub cell: String,
+}
+
+// Everything in dialog.json: who can speak, and what each script has them say
+#[derive(Deserialize, Debug, Default)]
+pub struct Scripts {
+    pub speakers: HashMap<String, Speaker>,
+    pub scripts: HashMap<String, Vec<Line>>,
+}
+
+struct Portrait {
+    sheet: Rc<SpriteSheet>,
+    sprite: Sprite,
+}
+
+// Plays scripts a line at a time, each timed on the game's own clock, so the
+// lines pause with the game. Skip moves straight on to the next line.
+pub struct Dialog {
+    scripts: Scripts,
+    portraits: HashMap<String, Portrait>,
+    lines: VecDeque<Line>,
+    timers: Scheduler<Dialog>,
+    // For the line being shown
+    timer: Option<TimerHandle>,
+}
+
+impl Dialog {
+    pub fn new(scripts: Scripts) -> Self {
+        Dialog {
+            scripts,
+            portraits: HashMap::new(),
+            lines: VecDeque::new(),
+            timers: Scheduler::default(),
+            timer: None,
+        }
+    }
+
+    // With every speaker's portrait looked up, so one that's missing from
+    // its sheet is found while loading
+    pub async fn load(sheets: &mut SpriteSheets) -> Result<Self> {
+        let mut dialog = Dialog::new(platform::load_json("dialog.json").await?);
+        for (id, speaker) in dialog.scripts.speakers.iter() {
+            let sheet = sheets.load(&speaker.sheet, Anchor::TopLeft).await?;
+            let sprite = sheet.sprite(&speaker.cell);
+            dialog
+                .portraits
+                .insert(id.clone(), Portrait { sheet, sprite });
+        }
+        Ok(dialog)
+    }
+
+    // Whatever was playing stops. A script that doesn't exist is logged and
+    // nothing plays.
+    pub fn play(&mut self, script: &str) {
+        match self.scripts.scripts.get(script) {
+            Some(lines) => {
+                self.lines = lines.iter().cloned().collect();
+                self.show_line();
+            }
+            None => {
+                log!("No dialog script named {}", script);
+            }
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    pub fn update(&mut self, input: &InputState) {
+        for update in self.timers.tick() {
+            update(self);
+        }
+        if self.is_playing() && input.just_pressed(Action::Skip) {
+            self.next_line();
+        }
+    }
+
+    pub fn next_line(&mut self) {
+        self.lines.pop_front();
+        self.show_line();
+    }
+
+    fn show_line(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            self.timers.cancel(timer);
+        }
+        if let Some(line) = self.lines.front() {
+            self.timer = Some(
+                self.timers
+                    .after(line.duration, |dialog: &mut Dialog| dialog.next_line()),
+            );
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer, i18n: &I18n) {
+        let line = match self.lines.front() {
+            Some(line) => line,
+            None => return,
+        };
+        renderer.fill_rect(BOX_COLOR, &BOX);
+        let mut text_x = BOX.x + PADDING;
+        if let Some(portrait) = self.portraits.get(&line.speaker) {
+            // Fitted into a square, keeping its shape
+            let size = portrait.sprite.bounding_box();
+            let scale = PORTRAIT_SIZE / size.width.max(size.height);
+            portrait.sheet.draw_sprite_in(
+                renderer,
+                &portrait.sprite,
+                &Rect {
+                    x: text_x,
+                    y: BOX.y + PADDING,
+                    width: size.width * scale,
+                    height: size.height * scale,
+                },
+            );
+            text_x += PORTRAIT_SIZE + PADDING;
+        }
+        if let Some(speaker) = self.scripts.speakers.get(&line.speaker) {
+            renderer.draw_text(
+                i18n.get(&speaker.name),
+                &Point {
+                    x: text_x as i16,
+                    y: (BOX.y + PADDING * 2.0) as i16,
+                },
+                "#ffd700",
+                16,
+            );
+        }
+        renderer.draw_text(
+            i18n.get(&line.text),
+            &Point {
+                x: text_x as i16,
+                y: (BOX.y + PADDING * 4.0) as i16,
+            },
+            "#ffffff",
+            18,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialog() -> Dialog {
+        let scripts = serde_json::from_str(
+            r#"{
+                "speakers": {},
+                "scripts": {
+                    "intro": [
+                        {"speaker": "rhb", "text": "first", "duration": 100},
+                        {"speaker": "rhb", "text": "second", "duration": 100}
+                    ]
+                }
+            }"#,
+        )
+        .expect("Could not parse scripts");
+        Dialog::new(scripts)
+    }
+
+    fn text(dialog: &Dialog) -> Option<&str> {
+        dialog.lines.front().map(|line| line.text.as_str())
+    }
+
+    #[test]
+    fn each_line_shows_for_its_duration() {
+        let mut dialog = dialog();
+        let input = InputState::new();
+        dialog.play("intro");
+
+        // 100 milliseconds is six updates at sixty a second
+        for _ in 0..5 {
+            dialog.update(&input);
+        }
+        assert_eq!(text(&dialog), Some("first"));
+        dialog.update(&input);
+        assert_eq!(text(&dialog), Some("second"));
+        for _ in 0..6 {
+            dialog.update(&input);
+        }
+        assert!(!dialog.is_playing());
+    }
+
+    #[test]
+    fn skipping_a_line_restarts_the_clock_for_the_next() {
+        let mut dialog = dialog();
+        let input = InputState::new();
+        dialog.play("intro");
+        for _ in 0..5 {
+            dialog.update(&input);
+        }
+
+        dialog.next_line();
+        dialog.update(&input);
+
+        assert_eq!(text(&dialog), Some("second"));
+    }
+}
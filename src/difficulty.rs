@@ -0,0 +1,230 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::platform;
+
+// How the random parts of a run are generated at one point along it
+#[derive(Deserialize, Copy, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Difficulty {
+    // In meters
+    pub distance: f32,
+    // The space, in pixels, between one coin slot or generated platform and
+    // the next
+    pub gap_min: f32,
+    pub gap_max: f32,
+    // The chance, from 0.0 to 1.0, that a slot has a coin in it
+    pub coin_density: f32,
+    #[serde(default)]
+    pub obstacle_weights: ObstacleWeights,
+}
+
+// The kinds of platform the generator lays out past the boss, which level
+// chunks name too
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ObstacleKind {
+    Platform,
+    Moving,
+    Crumbling,
+    Spring,
+    Conveyor,
+}
+
+// How often each kind of obstacle is generated, relative to the others. A
+// kind with no weight never turns up.
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ObstacleWeights {
+    pub platform: f32,
+    pub moving: f32,
+    pub crumbling: f32,
+    pub spring: f32,
+    pub conveyor: f32,
+}
+
+impl ObstacleWeights {
+    fn weighted(&self) -> [(ObstacleKind, f32); 5] {
+        [
+            (ObstacleKind::Platform, self.platform),
+            (ObstacleKind::Moving, self.moving),
+            (ObstacleKind::Crumbling, self.crumbling),
+            (ObstacleKind::Spring, self.spring),
+            (ObstacleKind::Conveyor, self.conveyor),
+        ]
+    }
+
+    // The kind a roll from 0.0 up to 1.0 lands on, or None when nothing has
+    // any weight
+    pub fn pick(&self, roll: f32) -> Option<ObstacleKind> {
+        let weighted = self.weighted();
+        let total: f32 = weighted.iter().map(|(_, weight)| weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut remaining = roll * total;
+        weighted
+            .iter()
+            .filter(|(_, weight)| *weight > 0.0)
+            .find(|(_, weight)| {
+                remaining -= weight;
+                remaining < 0.0
+            })
+            .or_else(|| weighted.iter().rev().find(|(_, weight)| *weight > 0.0))
+            .map(|(kind, _)| *kind)
+    }
+
+    fn lerp(&self, to: &ObstacleWeights, t: f32) -> ObstacleWeights {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        ObstacleWeights {
+            platform: lerp(self.platform, to.platform),
+            moving: lerp(self.moving, to.moving),
+            crumbling: lerp(self.crumbling, to.crumbling),
+            spring: lerp(self.spring, to.spring),
+            conveyor: lerp(self.conveyor, to.conveyor),
+        }
+    }
+}
+
+// Difficulty settings at points along a run, loaded from difficulty.json so
+// the game can be balanced without a rebuild. Settings between two points are
+// interpolated, and the first and last points hold before and after them.
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct DifficultyCurve {
+    points: Vec<Difficulty>,
+}
+
+impl DifficultyCurve {
+    pub async fn load() -> Result<Self> {
+        let curve: DifficultyCurve = platform::load_json("difficulty.json").await?;
+        curve.validated()
+    }
+
+    // The generators step along the run by the gap until they reach the end,
+    // so a gap that isn't positive would keep them going forever
+    fn validated(mut self) -> Result<Self> {
+        if self.points.is_empty() {
+            return Err(anyhow!("difficulty.json has no points"));
+        }
+        for point in self.points.iter() {
+            let numbers = [point.distance, point.gap_min, point.gap_max];
+            if !numbers.iter().all(|number| number.is_finite()) {
+                return Err(anyhow!("A difficulty point isn't all numbers"));
+            }
+            if point.gap_min <= 0.0 {
+                return Err(anyhow!(
+                    "gapMin at {}m must be more than 0, not {}",
+                    point.distance,
+                    point.gap_min
+                ));
+            }
+            if point.gap_max < point.gap_min {
+                return Err(anyhow!(
+                    "gapMax at {}m must be at least gapMin, not {}",
+                    point.distance,
+                    point.gap_max
+                ));
+            }
+            if !(0.0..=1.0).contains(&point.coin_density) {
+                return Err(anyhow!(
+                    "coinDensity at {}m must be from 0 to 1, not {}",
+                    point.distance,
+                    point.coin_density
+                ));
+            }
+        }
+        self.points
+            .sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        Ok(self)
+    }
+
+    pub fn at(&self, distance: f32) -> Difficulty {
+        let after = self
+            .points
+            .iter()
+            .position(|point| point.distance > distance);
+        match after {
+            Some(0) => self.points[0],
+            None => self.points[self.points.len() - 1],
+            Some(index) => {
+                let (from, to) = (&self.points[index - 1], &self.points[index]);
+                let t = (distance - from.distance) / (to.distance - from.distance);
+                let lerp = |a: f32, b: f32| a + (b - a) * t;
+                Difficulty {
+                    distance,
+                    gap_min: lerp(from.gap_min, to.gap_min),
+                    gap_max: lerp(from.gap_max, to.gap_max),
+                    coin_density: lerp(from.coin_density, to.coin_density),
+                    obstacle_weights: from.obstacle_weights.lerp(&to.obstacle_weights, t),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_in_proportion_to_the_weights() {
+        let weights = ObstacleWeights {
+            platform: 3.0,
+            spring: 1.0,
+            ..ObstacleWeights::default()
+        };
+
+        let picks: Vec<Option<ObstacleKind>> = [0.0, 0.5, 0.74, 0.75, 0.99]
+            .iter()
+            .map(|roll| weights.pick(*roll))
+            .collect();
+
+        assert_eq!(
+            picks,
+            vec![
+                Some(ObstacleKind::Platform),
+                Some(ObstacleKind::Platform),
+                Some(ObstacleKind::Platform),
+                Some(ObstacleKind::Spring),
+                Some(ObstacleKind::Spring),
+            ]
+        );
+        assert_eq!(ObstacleWeights::default().pick(0.5), None);
+    }
+
+    #[test]
+    fn weights_blend_between_points() {
+        let curve: DifficultyCurve = serde_json::from_str(
+            r#"[
+                { "distance": 0, "gapMin": 0, "gapMax": 0, "coinDensity": 0,
+                  "obstacleWeights": { "platform": 2 } },
+                { "distance": 100, "gapMin": 0, "gapMax": 0, "coinDensity": 0,
+                  "obstacleWeights": { "platform": 0, "crumbling": 2 } }
+            ]"#,
+        )
+        .unwrap();
+
+        let halfway = curve.at(50.0).obstacle_weights;
+
+        assert_eq!((halfway.platform, halfway.crumbling), (1.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_gaps_the_generators_would_never_get_past() {
+        let curve = |json: &str| -> DifficultyCurve { serde_json::from_str(json).unwrap() };
+
+        let no_gap = curve(r#"[{ "distance": 0, "gapMin": 0, "gapMax": 0, "coinDensity": 0.5 }]"#);
+        let backwards =
+            curve(r#"[{ "distance": 0, "gapMin": 80, "gapMax": 40, "coinDensity": 0.5 }]"#);
+        let too_dense =
+            curve(r#"[{ "distance": 0, "gapMin": 40, "gapMax": 80, "coinDensity": 1.5 }]"#);
+        let fine = curve(r#"[{ "distance": 0, "gapMin": 40, "gapMax": 40, "coinDensity": 1 }]"#);
+
+        assert!(no_gap.validated().is_err());
+        assert!(backwards.validated().is_err());
+        assert!(too_dense.validated().is_err());
+        assert!(fine.validated().is_ok());
+    }
+}
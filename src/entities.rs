@@ -0,0 +1,103 @@
+use std::ops::RangeBounds;
+
+use crate::engine::Renderer;
+
+// Something that looks after itself once it's made: it updates, draws,
+// scrolls with the world and goes away when it's done, without the game
+// knowing what it is. Anything the game checks collisions against keeps its
+// own list instead.
+pub trait Entity {
+    fn update(&mut self);
+    fn draw(&self, renderer: &Renderer);
+    fn move_horizontally(&mut self, x: i16);
+    fn is_finished(&self) -> bool;
+}
+
+// Every entity in the world, in draw order. Lower priorities draw first, and
+// entities with the same priority draw in the order they were added.
+#[derive(Default)]
+pub struct Entities {
+    entities: Vec<(i32, Box<dyn Entity>)>,
+}
+
+impl Entities {
+    pub fn add(&mut self, priority: i32, entity: impl Entity + 'static) {
+        let index = self
+            .entities
+            .iter()
+            .position(|(other, _)| *other > priority)
+            .unwrap_or(self.entities.len());
+        self.entities.insert(index, (priority, Box::new(entity)));
+    }
+
+    // Updates everything, then drops whatever has finished
+    pub fn update(&mut self) {
+        for (_, entity) in self.entities.iter_mut() {
+            entity.update();
+        }
+        self.entities.retain(|(_, entity)| !entity.is_finished());
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        for (_, entity) in self.entities.iter_mut() {
+            entity.move_horizontally(x);
+        }
+    }
+
+    // Draws only the priorities in range, so the game can draw some layers
+    // behind its own things and some in front
+    pub fn draw(&self, renderer: &Renderer, priorities: impl RangeBounds<i32>) {
+        self.entities
+            .iter()
+            .filter(|(priority, _)| priorities.contains(priority))
+            .for_each(|(_, entity)| entity.draw(renderer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Countdown(u8);
+
+    impl Entity for Countdown {
+        fn update(&mut self) {
+            self.0 = self.0.saturating_sub(1);
+        }
+
+        fn draw(&self, _renderer: &Renderer) {}
+
+        fn move_horizontally(&mut self, _x: i16) {}
+
+        fn is_finished(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    #[test]
+    fn keeps_entities_in_priority_order() {
+        let mut entities = Entities::default();
+        entities.add(10, Countdown(1));
+        entities.add(0, Countdown(2));
+        entities.add(10, Countdown(3));
+
+        let priorities: Vec<i32> = entities
+            .entities
+            .iter()
+            .map(|(priority, _)| *priority)
+            .collect();
+
+        assert_eq!(priorities, vec![0, 10, 10]);
+    }
+
+    #[test]
+    fn drops_finished_entities() {
+        let mut entities = Entities::default();
+        entities.add(0, Countdown(1));
+        entities.add(0, Countdown(2));
+
+        entities.update();
+
+        assert_eq!(entities.entities.len(), 1);
+    }
+}
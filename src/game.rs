@@ -1,50 +1,378 @@
+use std::{collections::VecDeque, rc::Rc};
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use wasm_engine::{
+    analytics::Analytics,
+    audio::{self, Music, Sound},
+    chunks::{ChunkEvent, ChunkStream, ChunkWindow},
+    config::LaunchOptions,
+    coroutine::{Coroutines, Script},
+    crash,
+    events::{self, Collision, EngineEvent},
+    fixed::{Fixed, FixedVector},
+    input::{Combo, ComboRecognizer, Gamepads, Touches},
+    mask::Mask,
+    net::Session,
+    offline,
+    path::{Path, PathMode},
+    recorder::ClipRecorder,
+    rewind::Rewind,
+    rollback::{Rollback, Simulation},
+    scheduler::{Scheduler, TimerHandle},
+    spawner::{Spawn, Spawner, Wave},
+    tween::{Easing, Tween, TweenHandle, Tweens},
+    ui::{self, Shared},
+    wake_lock::WakeLock,
+};
 
 use crate::{
-    browser,
-    engine::{self, Animation, Game, Image, KeyState, Point, Rect, Renderer, SpriteSheet, Vector},
+    achievements::{self, Achievements},
+    boss::{Boss, BossEvent, BossShot},
+    challenge::Challenge,
+    character_select::CharacterSelect,
+    characters::Special,
+    controls::{self, Action, Glyphs, InputState, Profile},
+    coop,
+    decorations::Foreground,
+    dialog::Dialog,
+    difficulty::{DifficultyCurve, ObstacleKind},
+    engine::{
+        self, Anchor, Animation, Game, Image, KeyState, Point, Positioning, Rect, Renderer, Sprite,
+        SpriteSheet, SpriteSheets, TilingBackground, Vector,
+    },
+    entities::{Entities, Entity},
+    ghost::{self, Ghost},
+    high_scores::HighScores,
+    i18n::I18n,
+    intro::Intro,
+    platform,
+    red_hat_boy::{Grounding, PhysicsConfig, RedHatBoyStateMachine, RhbAnimation, RHB_POSITION},
+    skins::{Skin, Wardrobe, SKINS},
+    spectator::Spectator,
+    stats::{DeathCause, Stats, Tally},
+    tiles::TileLayout,
+    tutorial::{Lesson, Tutorial},
+    world::{self, Camera},
 };
 
-const GRAVITY: f32 = 1.0;
 const FLOOR: i16 = 600;
-const IDLE_ANIMATION: &str = "Idle";
-const RUNNING_ANIMATION: &str = "Run";
-const JUMPING_ANIMATION: &str = "Jump";
-const SLIDING_ANIMATION: &str = "Slide";
-const DEAD_ANIMATION: &str = "Dead";
-const RUNNING_SPEED: i16 = 4;
-const JUMP_VELOCITY: f32 = -25.0;
-const TERMINAL_VELOCITY: f32 = 20.0;
-const BACKGROUND_WIDTH: i16 = 1000;
-const RHB_POSITION: i16 = 100;
+const CRUMBLE_DELAY: u8 = 30;
+const CRUMBLE_RESPAWN: u16 = 180;
+const SPRING_VELOCITY: f32 = -35.0;
+const CONVEYOR_SPEED: i16 = -2;
+const SLOPE_TOLERANCE: f32 = 20.0;
+// RHB's feet are the bottom of his frame, across the middle of it, so a toe
+// over the edge of a platform doesn't hold him up
+const FEET_WIDTH: f32 = 0.5;
+const FEET_HEIGHT: f32 = 8.0;
+// How far into a platform's top his feet can sink and still land on it,
+// rather than hitting its side. It's more than he can fall in one update.
+const LANDING_TOLERANCE: f32 = 25.0;
+const PORTAL_PAN_SPEED: i16 = 40;
+const SHATTER_PARTICLES: i16 = 12;
+const PARTICLE_LIFE: u8 = 40;
+const STARTING_BONES: u8 = 3;
+const THROW_COOLDOWN: u8 = 20;
+const THROW_VELOCITY: Vector = Vector { x: 10.0, y: -8.0 };
+const BONE_GRAVITY: f32 = 0.5;
+// A jump within this many updates of starting a slide is a slide jump, which
+// goes higher than a jump from running
+const TRICK_WINDOW: u32 = 12;
+const SLIDE_JUMP_VELOCITY: f32 = -32.0;
+const BOSS_HEALTH: u8 = 4;
+// The boss fight starts, and the world stops scrolling, once the boss reaches this x
+const ARENA_X: i16 = 450;
+const BOSS_X: i16 = 7000;
+const PORTAL_MARKER_COLOR: &str = "#8e44ad";
+const BOSS_MARKER_COLOR: &str = "#d9482b";
+const MAX_HEALTH: u8 = 3;
+const INVULNERABLE_MS: f32 = 1500.0;
+// RHB flickers on and off this often while he's invulnerable
+const BLINK_MS: f32 = 66.0;
+const SHIELD_COLOR: &str = "#4fc3f7";
+const MAGNET_COLOR: &str = "#e53935";
+const MAGNET_MS: f32 = 10000.0;
+const MAGNET_RADIUS: f32 = 250.0;
+const MAGNET_PULL: f32 = 8.0;
+const COIN_RADIUS: f32 = 10.0;
+const BOOST_COLOR: &str = "#66bb6a";
+const BOOST_MS: f32 = 5000.0;
+const BOOST_MULTIPLIER: f32 = 1.75;
+// How far the camera zooms out during a speed boost, and how quickly
+const BOOST_ZOOM: f32 = 0.92;
+const ZOOM_RATE: f32 = 0.005;
+const POPUP_LIFE: u8 = 45;
+const POPUP_RISE: f32 = 1.5;
+const NOTICE_FRAMES: u16 = 150;
+const SCORE_ROLL_MS: f32 = 400.0;
+// A beat after RHB hits the ground before the results come up
+const RESULTS_DELAY: f32 = 0.5;
+const HIGH_SCORES_DELAY: f32 = 0.5;
+const COIN_SCORE: u32 = 100;
+const NEAR_MISS_SCORE: u32 = 250;
+// Passing within this many pixels of an obstacle without touching it counts as a near miss
+const NEAR_MISS_MARGIN: f32 = 20.0;
+// Obstacle masks are in cells this many pixels a side
+const MASK_CELL: u32 = 4;
+const PIXELS_PER_METER: f32 = 10.0;
+const HIGH_SCORES_KEY: &str = "walk_the_dog_high_scores";
+const GHOST_KEY: &str = "walk_the_dog_ghost";
+const SCREENSHOT_FILE: &str = "walk-the-dog.png";
+const CLIP_FILE: &str = "walk-the-dog.webm";
+const CLIP_SECONDS: u32 = 15;
+const REWIND_SECONDS: f32 = 2.0;
+// Everything a service worker should cache to play offline
+const ASSET_MANIFEST: &str = "assets.json";
+// What analytics calls the boss arena
+const ARENA_LEVEL: &str = "boss_arena";
+const CRASH_REPORTS_KEY: &str = "walk_the_dog_crash_reports";
+// How often crash reports get a fresh summary of the game
+const CRASH_CONTEXT_MS: f32 = 1000.0;
+const LAG_PAUSE_MS: f32 = 500.0;
+// Leaves a little headroom for effects over the music
+const VOLUME: f32 = 0.8;
+// Entities draw in priority order. Those from FRONT_PRIORITY up draw in front
+// of the platforms and the foreground, and the rest just after RHB.
+const PARTICLE_PRIORITY: i32 = 0;
+const FRONT_PRIORITY: i32 = 100;
+const POPUP_PRIORITY: i32 = FRONT_PRIORITY;
+// A link with #seed=123 plays the layout for that seed
+const SEED_HASH: &str = "#seed=";
+const SHARE_BUTTONS: &str = "<button id='share' style='margin: 480px 0 0 200px'>{share}</button>\
+     <button id='challenge' style='margin-left: 10px'>{challenge}</button>";
+// Under the share buttons, when there's another player to talk to
+const CHAT_FORM: &str = "<form id='chat' style='margin: 10px 0 0 200px'>\
+     <input id='chat-text' maxlength='120' placeholder='{placeholder}'></form>";
+const CHAT_POSITION: Point = Point { x: 10, y: 60 };
+const CHAT_WIDTH: f32 = 300.0;
+// Each difficulty level starts this many meters further along the curve
+const DIFFICULTY_STEP: f32 = 100.0;
+const GHOST_ALPHA: f32 = 0.35;
+// With ?server, where the other racer was this long ago by their clock is too
+// stale to draw, and a co-op partner's moves too late to roll back for
+const REMOTE_MAX_AGE: f64 = 500.0;
+// As many updates as REMOTE_MAX_AGE covers
+const ROLLBACK_TICKS: usize = 30;
+// Who chat is from without ?name
+const DEFAULT_NAME: &str = "RHB";
+const CONTACTS_SHOWN: usize = 5;
+const CONTACT_COLOR: &str = "#ff00ff";
+// Where the run of coins starts and ends, in pixels from the start
+const COINS_START: f32 = 1300.0;
+const COINS_END: f32 = 3300.0;
+const COIN_HEIGHTS: [f32; 3] = [300.0, 380.0, 450.0];
+// Where the generated platforms start and end, past the boss
+const PLATFORMS_START: f32 = 7600.0;
+const PLATFORMS_END: f32 = 12600.0;
+const PLATFORM_HEIGHTS: [i16; 3] = [350, 400, 420];
+const SPRING_Y: i16 = 472;
+// After the generated platforms, the rest of the level is authored, and too
+// long to keep in memory all at once. It's streamed from chunks/<index>.json,
+// a chunk either side of the camera.
+const CHUNKS_START: i64 = 13000;
+const CHUNK_WIDTH: f32 = 1200.0;
+const CHUNK_PREFIX: &str = "chunks/";
+// Platform shapes, as rows of cells in tiles.json from the top
+const LONG_PLATFORM: &[&[&str]] = &[&["13.png", "14.png", "15.png"]];
+const SHORT_PLATFORM: &[&[&str]] = &[&["13.png", "15.png"]];
+const CRUMBLING_BLOCK: &[&[&str]] = &[&["17.png"]];
+// The crumbling block stands on less than the height of its sprite
+const CRUMBLING_HEIGHT: f32 = 90.0;
+const SPRING_BLOCK: &[&[&str]] = &[&["3.png"]];
+// Platforms further behind than this are dropped. It's as far as a rewind can
+// scroll back: an update for every snapshot, each at the fastest the world
+// ever scrolls, which is a portal's pan.
+const DESPAWN_X: f32 =
+    -(REWIND_SECONDS * 1000.0 / engine::FRAME_SIZE + 1.0) * PORTAL_PAN_SPEED as f32;
+// Platforms are drawn this far either side of the screen, for the zoom
+const VIEW_WIDTH: f32 = 600.0;
+const CULL_MARGIN: f32 = 100.0;
+// Player two starts this far behind RHB, and is tinted to tell them apart
+const PARTNER_OFFSET: i16 = -60;
+const PARTNER_FILTER: &str = "hue-rotate(200deg)";
+const PARTNER_HEART_COLOR: &str = "#2479e0";
+
+// In a daily challenge every player gets the same layout for the day, and
+// scores go in a separate table for that day. Endless runs get a random
+// layout, unless a shared link names the seed, or ?seed=N does.
+#[derive(Copy, Clone)]
+enum Mode {
+    Endless(u64),
+    Daily(u64),
+}
+
+impl Mode {
+    fn from_location(seed: Option<u64>) -> Self {
+        let hash = platform::location_hash().unwrap_or_default();
+        if let Some(challenge) = Challenge::parse(&hash) {
+            return if challenge.daily {
+                Mode::Daily(challenge.seed)
+            } else {
+                Mode::Endless(challenge.seed)
+            };
+        }
+        if hash == "#daily" {
+            let (year, month, day) = platform::utc_date();
+            return Mode::Daily((year * 10000 + month * 100 + day).into());
+        }
+        match hash.strip_prefix(SEED_HASH).map(str::parse) {
+            Some(Ok(seed)) => Mode::Endless(seed),
+            _ => Mode::Endless(seed.unwrap_or_else(|| StdRng::from_entropy().gen())),
+        }
+    }
+
+    fn seed(&self) -> u64 {
+        match self {
+            Mode::Endless(seed) | Mode::Daily(seed) => *seed,
+        }
+    }
+
+    fn is_daily(&self) -> bool {
+        matches!(self, Mode::Daily(_))
+    }
+
+    fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed())
+    }
+
+    // Anything saved for a layout is kept separately for each daily challenge
+    fn storage_key(&self, key: &str) -> String {
+        match self {
+            Mode::Endless(_) => key.to_string(),
+            Mode::Daily(seed) => format!("{}_daily_{}", key, seed),
+        }
+    }
+}
 
 pub enum WalkTheDog {
-    Loading,
+    Loading(Rc<dyn Analytics>, LaunchOptions),
+    // Once the intro is over, the first time the game is played
+    Choosing(Start),
+    // Once a skin's been chosen on the character select scene
+    Starting(Start, &'static Skin),
     Loaded(WalkTheDogGame),
 }
 
+// What a run starts with, worked out from the launch options and the URL
+// before the character select scene. It's handed from scene to scene until
+// the game starts, so the binding profile is loaded once, here, and whichever
+// scene holds it reads the keyboard through it.
+pub struct Start {
+    analytics: Rc<dyn Analytics>,
+    mode: Mode,
+    difficulty: u8,
+    spectate: bool,
+    coop: bool,
+    player: Option<u8>,
+    muted: bool,
+    clips: bool,
+    server: Option<String>,
+    name: Option<String>,
+    profile: Profile,
+}
+
+impl Start {
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+}
+
 impl WalkTheDog {
-    pub fn new() -> Self {
-        WalkTheDog::Loading {}
+    pub fn new(analytics: Rc<dyn Analytics>) -> Self {
+        WalkTheDog::Loading(analytics, LaunchOptions::default())
     }
 }
 
 #[async_trait(?Send)]
 impl Game for WalkTheDog {
-    async fn initialize(&mut self) -> Result<Box<dyn Game>> {
+    type Action = Action;
+
+    fn configure(&mut self, options: &LaunchOptions) {
+        if let WalkTheDog::Loading(_, launch_options) = self {
+            *launch_options = options.clone();
+        }
+    }
+
+    async fn initialize(&mut self) -> Result<Box<dyn Game<Action = Action>>> {
         match self {
-            WalkTheDog::Loading => {
-                let game = WalkTheDogGame::initialize().await?;
+            WalkTheDog::Loading(analytics, options) => {
+                // A challenge link's difficulty wins over ?difficulty
+                let difficulty = Challenge::parse(&platform::location_hash().unwrap_or_default())
+                    .map(|challenge| challenge.difficulty)
+                    .or(options.difficulty)
+                    .unwrap_or(0);
+                let start = Start {
+                    analytics: Rc::clone(analytics),
+                    mode: Mode::from_location(options.seed),
+                    difficulty,
+                    spectate: options.spectate,
+                    coop: options.coop,
+                    player: options.player,
+                    muted: options.muted,
+                    clips: options.clips,
+                    server: options.server.clone(),
+                    name: options.name.clone(),
+                    profile: Profile::load(),
+                };
+                // Spectators watch someone else's run, so there's nothing to
+                // choose
+                if start.spectate {
+                    let game = WalkTheDogGame::initialize(start, Skin::load()).await?;
+                    return Ok(Box::new(WalkTheDog::Loaded(game)));
+                }
+                if !Intro::is_seen() {
+                    return Ok(Box::new(Intro::load(start).await?));
+                }
+                Ok(Box::new(CharacterSelect::load(start).await?))
+            }
+            WalkTheDog::Choosing(start) => {
+                let start = Start {
+                    analytics: Rc::clone(&start.analytics),
+                    server: start.server.clone(),
+                    name: start.name.clone(),
+                    ..*start
+                };
+                Ok(Box::new(CharacterSelect::load(start).await?))
+            }
+            WalkTheDog::Starting(start, skin) => {
+                let start = Start {
+                    analytics: Rc::clone(&start.analytics),
+                    server: start.server.clone(),
+                    name: start.name.clone(),
+                    ..*start
+                };
+                let game = WalkTheDogGame::initialize(start, skin).await?;
                 Ok(Box::new(WalkTheDog::Loaded(game)))
             }
             WalkTheDog::Loaded(_) => Err(anyhow!("WalkTheDog already loaded!")),
         }
     }
 
-    fn update(&mut self, keystate: &KeyState) {
+    fn read_input(
+        &self,
+        keys: &KeyState,
+        gamepads: &Gamepads,
+        touches: &Touches,
+        input: &mut InputState,
+    ) {
+        let profile = match self {
+            WalkTheDog::Loaded(game) => controls::keyboard_profile(
+                game.profile,
+                game.world().partner.is_some() && game.remote.is_none(),
+            ),
+            WalkTheDog::Choosing(start) | WalkTheDog::Starting(start, _) => start.profile,
+            WalkTheDog::Loading(..) => Profile::Default,
+        };
+        controls::read(keys, gamepads, touches, profile, input);
+    }
+
+    fn update(&mut self, input: &InputState) {
         match self {
-            WalkTheDog::Loaded(game) => game.update(keystate),
+            WalkTheDog::Loaded(game) => game.update(input),
             _ => {}
         }
     }
@@ -55,202 +383,3105 @@ impl Game for WalkTheDog {
             _ => {}
         }
     }
+
+    fn handle_event(&mut self, event: &EngineEvent) {
+        match self {
+            WalkTheDog::Loaded(game) => game.handle_event(event),
+            _ => {}
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        match self {
+            WalkTheDog::Loaded(game) if game.muted => 0.0,
+            _ => VOLUME,
+        }
+    }
 }
 
-struct Platform {
-    sheet: SpriteSheet,
-    bounding_box: Rect,
+#[derive(Copy, Clone)]
+enum Crumble {
+    Solid,
+    Shaking(u8),
+    Collapsed(u16),
+}
+
+impl Crumble {
+    fn stood_on(self) -> Self {
+        match self {
+            Crumble::Solid => Crumble::Shaking(CRUMBLE_DELAY),
+            _ => self,
+        }
+    }
+
+    fn update(self) -> Self {
+        match self {
+            Crumble::Solid => Crumble::Solid,
+            Crumble::Shaking(0) => Crumble::Collapsed(CRUMBLE_RESPAWN),
+            Crumble::Shaking(frames) => Crumble::Shaking(frames - 1),
+            Crumble::Collapsed(0) => Crumble::Solid,
+            Crumble::Collapsed(frames) => Crumble::Collapsed(frames - 1),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Water {
+    sheet: Rc<SpriteSheet>,
+    sprite: Sprite,
     position: Point,
-    sprites: Vec<String>,
+    tiles: i16,
 }
 
-impl Platform {
+impl Water {
     fn draw(&self, renderer: &Renderer) {
-        for (pos, sprite) in self.sprites.iter().enumerate() {
+        for tile in 0..self.tiles {
             let position = Point {
-                x: self.position.x + (pos as i16 * 128), // FIXME: Width shouldn't be hard coded (probably)
+                x: self.position.x + tile * 128,
                 y: self.position.y,
             };
-            self.sheet.draw(renderer, sprite, &position)
+            self.sheet.draw_sprite(renderer, &self.sprite, &position);
         }
     }
 
     fn move_horizontally(&mut self, x: i16) {
-        self.position.x += x;
+        self.position.x = world::shift(self.position.x, x);
     }
 
     fn bounding_box(&self) -> Rect {
         Rect {
             x: self.position.x.into(),
             y: self.position.y.into(),
-            width: self.bounding_box.width,
-            height: self.bounding_box.height,
+            width: (self.tiles * 128).into(),
+            height: 128.0,
         }
     }
 }
 
-pub struct WalkTheDogGame {
-    backgrounds: Vec<Image>,
-    rock: Image,
-    rhb: RedHatBoy,
-    platforms: Vec<Platform>,
-    velocity: i16,
+// A volume of wind that pushes anything inside it. A negative x is a
+// headwind and a negative y is an updraft.
+#[derive(Clone)]
+struct ForceZone {
+    position: Point,
+    width: i16,
+    height: i16,
+    force: Vector,
 }
 
-impl WalkTheDogGame {
-    async fn initialize() -> Result<WalkTheDogGame> {
-        let background = Image::new(engine::load_image("BG.png").await?, Point { x: 0, y: 0 });
-        let background_2 = Image::new(
-            engine::load_image("BG.png").await?,
-            Point {
-                x: BACKGROUND_WIDTH,
-                y: 0,
+impl ForceZone {
+    fn draw(&self, renderer: &Renderer) {
+        renderer.draw_rect("#a0d8ff", &self.bounding_box());
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.into(),
+            y: self.position.y.into(),
+            width: self.width.into(),
+            height: self.height.into(),
+        }
+    }
+}
+
+// Reaching one of these shows its tutorial prompt, if the player hasn't
+// seen it before. They're laid out in level.json, from the floor up.
+#[derive(Clone, Deserialize)]
+struct TutorialZone {
+    x: i16,
+    width: i16,
+    lesson: Lesson,
+}
+
+impl TutorialZone {
+    fn move_horizontally(&mut self, x: i16) {
+        self.x = world::shift(self.x, x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.x.into(),
+            y: 0.0,
+            width: self.width.into(),
+            height: FLOOR.into(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Obstacle {
+    image: Image,
+    // Hits only count where the image is solid, when there is one
+    mask: Option<Mask>,
+    breakable: bool,
+    broken: bool,
+    // Whether RHB is within the near miss margin, and whether he has touched
+    // the obstacle since he got there
+    grazing: bool,
+    touched: bool,
+}
+
+impl Obstacle {
+    fn draw(&self, renderer: &Renderer, interpolation: f32) {
+        if !self.broken {
+            self.image.draw(renderer, interpolation);
+        }
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.image.move_horizontally(x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.image.bounding_box()
+    }
+
+    fn hits(&self, rect: &Rect) -> bool {
+        let bounding_box = self.bounding_box();
+        match &self.mask {
+            Some(mask) => mask.overlaps_rect(&bounding_box, rect),
+            None => bounding_box.intersects(rect),
+        }
+    }
+
+    fn near_miss_box(&self) -> Rect {
+        let bounding_box = self.bounding_box();
+        Rect {
+            x: bounding_box.x - NEAR_MISS_MARGIN,
+            y: bounding_box.y - NEAR_MISS_MARGIN,
+            width: bounding_box.width + NEAR_MISS_MARGIN * 2.0,
+            height: bounding_box.height + NEAR_MISS_MARGIN * 2.0,
+        }
+    }
+
+    fn shatter(&mut self) -> Vec<Particle> {
+        self.broken = true;
+
+        let bounding_box = self.bounding_box();
+        (0..SHATTER_PARTICLES)
+            .map(|i| Particle {
+                position: Vector {
+                    x: bounding_box.x + bounding_box.width / 2.0,
+                    y: bounding_box.y + bounding_box.height / 2.0,
+                },
+                velocity: Vector {
+                    x: (i - SHATTER_PARTICLES / 2) as f32,
+                    y: -8.0 - (i % 4) as f32 * 2.0,
+                },
+                life: PARTICLE_LIFE,
+            })
+            .collect()
+    }
+}
+
+// What the combos in the input turn into
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Trick {
+    SlideJump,
+}
+
+fn tricks() -> ComboRecognizer<Action, Trick> {
+    ComboRecognizer::new(vec![Combo::new(
+        vec![vec![Action::Slide], vec![Action::Jump]],
+        TRICK_WINDOW,
+        Trick::SlideJump,
+    )])
+}
+
+struct Sounds {
+    jump: Sound,
+    crash: Sound,
+    music: Sound,
+}
+
+impl Sounds {
+    async fn load() -> Result<Self> {
+        Ok(Sounds {
+            jump: audio::load_sound("jump.wav").await?,
+            crash: audio::load_sound("crash.wav").await?,
+            music: audio::load_sound("music.wav").await?,
+        })
+    }
+}
+
+// A message pinned to the top of the screen for a few seconds, e.g. when a
+// controller is plugged in
+struct Notice {
+    text: &'static str,
+    frames: u16,
+}
+
+impl Notice {
+    fn new(text: &'static str) -> Self {
+        Notice {
+            text,
+            frames: NOTICE_FRAMES,
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer, i18n: &I18n) {
+        renderer.fill_rect(
+            "rgba(0, 0, 0, 0.7)",
+            &Rect {
+                x: 180.0,
+                y: 10.0,
+                width: 240.0,
+                height: 30.0,
             },
         );
+        renderer.draw_text(i18n.get(self.text), &Point { x: 195, y: 31 }, "#ffffff", 16);
+    }
+}
 
-        let rock = Image::new(
-            engine::load_image("Stone.png").await?,
-            Point { x: 700, y: 546 },
+// Floating text, e.g. "+100", that drifts upwards and fades out
+struct Popup {
+    text: String,
+    position: Vector,
+    life: u8,
+}
+
+impl Popup {
+    fn new(text: &str, position: Vector) -> Self {
+        Popup {
+            text: text.to_string(),
+            position,
+            life: POPUP_LIFE,
+        }
+    }
+}
+
+impl Entity for Popup {
+    fn update(&mut self) {
+        self.position.y -= POPUP_RISE;
+        self.life = self.life.saturating_sub(1);
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let alpha = self.life as f32 / POPUP_LIFE as f32;
+        renderer.draw_text(
+            &self.text,
+            &Point {
+                x: self.position.x as i16,
+                y: self.position.y as i16,
+            },
+            &format!("rgba(255, 255, 255, {})", alpha),
+            20,
         );
+    }
 
-        let json = browser::fetch_json("rhb.json").await?;
-        let sheet = json.into_serde()?;
-        let image = engine::load_image("rhb.png").await?;
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x += x as f32;
+    }
 
-        let rhb = RedHatBoy::new(Animation::new(
-            SpriteSheet::new(image, sheet),
-            vec![
-                IDLE_ANIMATION,
-                RUNNING_ANIMATION,
-                JUMPING_ANIMATION,
-                SLIDING_ANIMATION,
-                DEAD_ANIMATION,
-            ],
-        ));
+    fn is_finished(&self) -> bool {
+        self.life == 0
+    }
+}
+
+#[derive(Clone)]
+struct Particle {
+    position: Vector,
+    velocity: Vector,
+    life: u8,
+}
 
-        let json = browser::fetch_json("tiles.json").await?;
-        let sheet = json.into_serde()?;
-        let image = engine::load_image("tiles.png").await?;
-        let platform_sheet = SpriteSheet::new(image, sheet);
-
-        let first_platform = Platform {
-            sheet: platform_sheet,
-            bounding_box: Rect {
-                x: 0.0,
-                y: 0.0,
-                width: 384.0,
-                height: 90.0,
+impl Entity for Particle {
+    fn update(&mut self) {
+        self.velocity.y += 1.0;
+        self.position.x += self.velocity.x;
+        self.position.y += self.velocity.y;
+        self.life = self.life.saturating_sub(1);
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.fill_rect(
+            "#8a8a8a",
+            &Rect {
+                x: self.position.x,
+                y: self.position.y,
+                width: 6.0,
+                height: 6.0,
             },
-            sprites: vec![
-                "13.png".to_string(),
-                "14.png".to_string(),
-                "15.png".to_string(),
-            ],
-            position: Point { x: 220, y: 350 },
-        };
+        );
+    }
 
-        Ok(WalkTheDogGame {
-            backgrounds: vec![background, background_2],
-            rock,
-            rhb,
-            platforms: vec![first_platform],
-            velocity: 0,
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x += x as f32;
+    }
+
+    fn is_finished(&self) -> bool {
+        self.life == 0
+    }
+}
+
+#[derive(Clone)]
+struct Bone {
+    position: FixedVector,
+    velocity: FixedVector,
+}
+
+impl Bone {
+    fn update(&mut self) {
+        self.velocity.y += Fixed::from_f32(BONE_GRAVITY);
+        self.position += self.velocity;
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.fill_rect("#f5f0e1", &self.bounding_box());
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x += Fixed::from_int(x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.to_f32(),
+            y: self.position.y.to_f32(),
+            width: 20.0,
+            height: 8.0,
+        }
+    }
+
+    fn is_spent(&self) -> bool {
+        self.position.y > Fixed::from_int(FLOOR) || self.position.x > Fixed::from_int(600)
+    }
+}
+
+// Hands out bones while there's ammo, no faster than once every
+// THROW_COOLDOWN frames so holding the key doesn't empty it instantly.
+#[derive(Clone)]
+struct BoneThrower {
+    ammo: u8,
+    cooldown: u8,
+}
+
+impl BoneThrower {
+    fn update(&mut self) {
+        self.cooldown = self.cooldown.saturating_sub(1);
+    }
+
+    fn throw(&mut self, from: Point, speed: f32) -> Option<Bone> {
+        if self.ammo == 0 || self.cooldown > 0 {
+            return None;
+        }
+
+        self.ammo -= 1;
+        self.cooldown = THROW_COOLDOWN;
+        Some(Bone {
+            position: FixedVector {
+                x: Fixed::from_int(from.x),
+                y: Fixed::from_int(from.y),
+            },
+            velocity: FixedVector::from_vector(Vector {
+                x: THROW_VELOCITY.x + speed,
+                y: THROW_VELOCITY.y,
+            }),
         })
     }
+}
+
+#[derive(Clone)]
+struct BonePickup {
+    position: Point,
+    amount: u8,
+}
+
+impl BonePickup {
+    fn draw(&self, renderer: &Renderer) {
+        renderer.draw_rect("#f5f0e1", &self.bounding_box());
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.into(),
+            y: self.position.y.into(),
+            width: 30.0,
+            height: 30.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Effect {
+    // Absorbs the next hit
+    Shield,
+    // Pulls nearby coins towards RHB
+    Magnet,
+    // Makes RHB run faster
+    SpeedBoost,
+}
+
+impl Effect {
+    // How many milliseconds the effect lasts, or None if it lasts until it's
+    // used up
+    fn duration(&self) -> Option<f32> {
+        match self {
+            Effect::Shield => None,
+            Effect::Magnet => Some(MAGNET_MS),
+            Effect::SpeedBoost => Some(BOOST_MS),
+        }
+    }
+}
+
+// What a level's waves can spawn
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Pickup {
+    Bones(u8),
+    PowerUp(Effect),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Level {
+    waves: Vec<Wave<Pickup>>,
+    tutorial_zones: Vec<TutorialZone>,
+    // How many chunk files there are
+    #[serde(default)]
+    chunks: usize,
+}
+
+// One chunk's worth of the streamed part of the level. Platforms are placed
+// from the chunk's left edge.
+#[derive(Deserialize)]
+struct LevelChunk {
+    platforms: Vec<ChunkPlatform>,
+}
+
+#[derive(Deserialize)]
+struct ChunkPlatform {
+    x: i16,
+    y: i16,
+    kind: ObstacleKind,
+}
+
+#[derive(Clone)]
+struct ActiveEffect {
+    effect: Effect,
+    expiry: Option<TimerHandle>,
+}
+
+#[derive(Clone)]
+struct Coin {
+    position: FixedVector,
+}
+
+impl Coin {
+    fn draw(&self, renderer: &Renderer) {
+        renderer.draw_circle(
+            "#ffd700",
+            &Point {
+                x: self.position.x.floor(),
+                y: self.position.y.floor(),
+            },
+            COIN_RADIUS,
+        );
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x += Fixed::from_int(x);
+    }
 
-    fn update(&mut self, keystate: &KeyState) {
-        if keystate.is_pressed("ArrowRight") {
-            self.rhb.run();
-            self.velocity = -RUNNING_SPEED;
+    // Each step is rounded onto the grid before it's taken, so the pull
+    // adds up the same way everywhere
+    fn move_towards(&mut self, target: &Vector, distance: f32) {
+        let position = self.position.to_vector();
+        let dx = target.x - position.x;
+        let dy = target.y - position.y;
+        let length = (dx * dx + dy * dy).sqrt();
+        if length <= distance {
+            self.position = FixedVector::from_vector(*target);
+        } else {
+            self.position += FixedVector::from_vector(Vector {
+                x: dx / length * distance,
+                y: dy / length * distance,
+            });
         }
+    }
+
+    fn distance_to(&self, point: &Vector) -> f32 {
+        let position = self.position.to_vector();
+        let dx = point.x - position.x;
+        let dy = point.y - position.y;
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    fn bounding_box(&self) -> Rect {
+        let position = self.position.to_vector();
+        Rect {
+            x: position.x - COIN_RADIUS,
+            y: position.y - COIN_RADIUS,
+            width: COIN_RADIUS * 2.0,
+            height: COIN_RADIUS * 2.0,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PowerUp {
+    position: Point,
+    effect: Effect,
+}
+
+impl PowerUp {
+    fn draw(&self, renderer: &Renderer) {
+        match self.effect {
+            Effect::Shield => renderer.draw_circle(SHIELD_COLOR, &self.center(), 15.0),
+            Effect::Magnet => renderer.draw_rect(MAGNET_COLOR, &self.bounding_box()),
+            Effect::SpeedBoost => renderer.fill_rect(BOOST_COLOR, &self.bounding_box()),
+        }
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    fn center(&self) -> Point {
+        Point {
+            x: self.position.x + 15,
+            y: self.position.y + 15,
+        }
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.into(),
+            y: self.position.y.into(),
+            width: 30.0,
+            height: 30.0,
+        }
+    }
+}
+
+// A one way portal. Both ends are in world space, so they scroll with
+// everything else.
+#[derive(Clone)]
+struct Portal {
+    entrance: Point,
+    exit: Point,
+}
+
+impl Portal {
+    fn draw(&self, renderer: &Renderer) {
+        renderer.draw_rect("#9b30ff", &Portal::bounding_box_at(&self.entrance));
+        renderer.draw_rect("#9b30ff", &Portal::bounding_box_at(&self.exit));
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.entrance.x = world::shift(self.entrance.x, x);
+        self.exit.x = world::shift(self.exit.x, x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Portal::bounding_box_at(&self.entrance)
+    }
+
+    fn bounding_box_at(position: &Point) -> Rect {
+        Rect {
+            x: position.x.into(),
+            y: position.y.into(),
+            width: 60.0,
+            height: 120.0,
+        }
+    }
+}
+
+// The camera following RHB through a portal, panning along a one-way path
+#[derive(Clone)]
+struct PortalPan {
+    path: Path,
+    distance: i16,
+}
+
+impl PortalPan {
+    fn remaining(&self) -> i16 {
+        self.distance - self.path.offset().x
+    }
+}
+
+#[derive(Clone)]
+struct Ladder {
+    position: Point,
+    height: i16,
+}
+
+impl Ladder {
+    fn draw(&self, renderer: &Renderer) {
+        renderer.draw_rect("#7a4a1f", &self.bounding_box());
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect {
+            x: self.position.x.into(),
+            y: self.position.y.into(),
+            width: 40.0,
+            height: self.height.into(),
+        }
+    }
+}
+
+// How far below the top of the platform its surface is at the left and right
+// edges. The surface is interpolated in between.
+#[derive(Copy, Clone)]
+struct Slope {
+    left: i16,
+    right: i16,
+}
+
+#[derive(Clone)]
+struct Platform {
+    sheet: Rc<SpriteSheet>,
+    bounding_box: Rect,
+    position: Point,
+    // Each tile, and where it goes from the platform's top left
+    tiles: Vec<(Point, Sprite)>,
+    path: Option<Path>,
+    last_move: Point,
+    crumble: Option<Crumble>,
+    bounce: Option<f32>,
+    conveyor: i16,
+    slope: Option<Slope>,
+}
+
+impl Platform {
+    // The shape is rows of cells in the sheet, from the top, and the
+    // platform's as big as they are laid out
+    fn new(sheet: Rc<SpriteSheet>, position: Point, shape: &[&[&str]]) -> Self {
+        let sprites: Vec<Vec<Sprite>> = shape
+            .iter()
+            .map(|row| row.iter().map(|cell| sheet.sprite(cell)).collect())
+            .collect();
+        let layout = TileLayout::new(
+            &sprites
+                .iter()
+                .map(|row| row.iter().map(Sprite::bounding_box).collect())
+                .collect::<Vec<_>>(),
+        );
+        Platform {
+            sheet,
+            bounding_box: layout.bounds,
+            position,
+            tiles: layout
+                .offsets
+                .into_iter()
+                .zip(sprites.into_iter().flatten())
+                .collect(),
+            path: None,
+            last_move: Point { x: 0, y: 0 },
+            crumble: None,
+            bounce: None,
+            conveyor: 0,
+            slope: None,
+        }
+    }
+
+    fn with_path(mut self, path: Path) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    fn with_height(mut self, height: f32) -> Self {
+        self.bounding_box.height = height;
+        self
+    }
+
+    fn crumbling(mut self) -> Self {
+        self.crumble = Some(Crumble::Solid);
+        self
+    }
+
+    fn with_bounce(mut self, velocity: f32) -> Self {
+        self.bounce = Some(velocity);
+        self
+    }
+
+    fn with_conveyor(mut self, speed: i16) -> Self {
+        self.conveyor = speed;
+        self
+    }
+
+    fn with_slope(mut self, slope: Slope) -> Self {
+        self.slope = Some(slope);
+        self
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let shake = match self.crumble {
+            Some(Crumble::Collapsed(_)) => return,
+            Some(Crumble::Shaking(frames)) if frames % 4 < 2 => 2,
+            Some(Crumble::Shaking(_)) => -2,
+            _ => 0,
+        };
+
+        let bounding_box = self.bounding_box();
+        let (left, top) = (bounding_box.x as i16, bounding_box.y as i16);
+        for (offset, sprite) in self.tiles.iter() {
+            let x = left + offset.x;
+            // Lower rows follow a slope down with the top one
+            let y = match self.surface_within(&bounding_box, x.into()) {
+                Some(surface) if self.slope.is_some() => surface as i16,
+                _ => top,
+            } + offset.y;
+            let position = Point { x: x + shake, y };
+            self.sheet.draw_sprite(renderer, sprite, &position)
+        }
+    }
+
+    fn update(&mut self) {
+        self.last_move = match self.path.as_mut() {
+            Some(path) => path.update(),
+            None => Point { x: 0, y: 0 },
+        };
+        self.crumble = self.crumble.map(Crumble::update);
+    }
+
+    // How far anything standing on the platform is moved this frame
+    fn carry(&self) -> Point {
+        Point {
+            x: self.last_move.x + self.conveyor,
+            y: self.last_move.y,
+        }
+    }
+
+    // The y coordinate of the platform's walkable surface at x, if x is over
+    // the platform at all
+    fn surface_at(&self, x: f32) -> Option<f32> {
+        self.surface_within(&self.bounding_box(), x)
+    }
+
+    // For when the bounding box is already to hand
+    fn surface_within(&self, bounding_box: &Rect, x: f32) -> Option<f32> {
+        if x < bounding_box.x || x > bounding_box.x + bounding_box.width {
+            return None;
+        }
+
+        let offset = match self.slope {
+            Some(slope) => {
+                let t = (x - bounding_box.x) / bounding_box.width;
+                slope.left as f32 + (slope.right - slope.left) as f32 * t
+            }
+            None => 0.0,
+        };
+        Some(bounding_box.y + offset)
+    }
+
+    fn stood_on(&mut self) {
+        self.crumble = self.crumble.map(Crumble::stood_on);
+    }
+
+    fn is_solid(&self) -> bool {
+        !matches!(self.crumble, Some(Crumble::Collapsed(_)))
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x = world::shift(self.position.x, x);
+    }
+
+    fn position(&self) -> Point {
+        match &self.path {
+            Some(path) => Point {
+                x: self.position.x + path.offset().x,
+                y: self.position.y + path.offset().y,
+            },
+            None => self.position,
+        }
+    }
+
+    fn bounding_box(&self) -> Rect {
+        let position = self.position();
+        Rect {
+            x: position.x.into(),
+            y: position.y.into(),
+            width: self.bounding_box.width,
+            height: self.bounding_box.height,
+        }
+    }
+}
+
+// Every platform in the world, updated, scrolled and drawn together. Each one
+// has an id that stays the same as others come and go, so RHB can keep track
+// of the one he's standing on. Platforms left far enough behind are dropped,
+// and the rest are only drawn when they're on screen.
+#[derive(Default, Clone)]
+struct Platforms {
+    platforms: Vec<(usize, Platform)>,
+    next_id: usize,
+}
+
+impl Platforms {
+    fn spawn(&mut self, platform: Platform) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.platforms.push((id, platform));
+        id
+    }
+
+    fn remove(&mut self, ids: &[usize]) {
+        self.platforms.retain(|(id, _)| !ids.contains(id));
+    }
+
+    // Copies over the platforms with those ids that aren't here, from a copy
+    // of the world that's scrolled velocity further along. New ids follow on
+    // from the other's.
+    fn keep_from<'a>(
+        &mut self,
+        other: &Platforms,
+        ids: impl Iterator<Item = &'a usize>,
+        velocity: i16,
+    ) {
+        for &id in ids {
+            if self.get(id).is_some() {
+                continue;
+            }
+            if let Some(platform) = other.get(id) {
+                let mut platform = platform.clone();
+                platform.move_horizontally(velocity);
+                self.platforms.push((id, platform));
+            }
+        }
+        self.next_id = self.next_id.max(other.next_id);
+    }
+
+    fn get(&self, id: usize) -> Option<&Platform> {
+        self.platforms
+            .iter()
+            .find(|(platform_id, _)| *platform_id == id)
+            .map(|(_, platform)| platform)
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut Platform)> {
+        self.platforms
+            .iter_mut()
+            .map(|(id, platform)| (*id, platform))
+    }
+
+    fn update(&mut self) {
+        self.platforms.retain(|(_, platform)| {
+            let bounding_box = platform.bounding_box();
+            bounding_box.x + bounding_box.width > DESPAWN_X
+        });
+        for (_, platform) in self.platforms.iter_mut() {
+            platform.update();
+        }
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        for (_, platform) in self.platforms.iter_mut() {
+            platform.move_horizontally(x);
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        for (_, platform) in self.platforms.iter() {
+            let bounding_box = platform.bounding_box();
+            let on_screen = bounding_box.x + bounding_box.width >= -CULL_MARGIN
+                && bounding_box.x <= VIEW_WIDTH + CULL_MARGIN;
+            if on_screen {
+                platform.draw(renderer);
+            }
+        }
+    }
+}
+
+pub struct WalkTheDogGame {
+    background: TilingBackground,
+    world: Rollback<Gameplay>,
+    // Which player this end plays, which is player one unless it's the
+    // second end of co-op over a server
+    seat: usize,
+    // In co-op over a server, the other end's player, and the moves it's sent
+    // for updates this end hasn't got to yet
+    remote: Option<usize>,
+    remote_moves: Vec<(u64, Moves)>,
+    tiles: Rc<SpriteSheet>,
+    chunks: ChunkStream<LevelChunk>,
+    // The platforms each streamed chunk spawned, to drop when it leaves
+    chunk_platforms: Vec<(usize, Vec<usize>)>,
+    tricks: ComboRecognizer<Action, Trick>,
+    foreground: Foreground,
+    zoom: f32,
+    // Particles, popups and anything else that looks after itself
+    entities: Entities,
+    // The HUD rolls up to the score rather than jumping
+    shown_score: f32,
+    score_roll: Option<(u32, TweenHandle)>,
+    tweens: Tweens<WalkTheDogGame>,
+    coroutines: Coroutines<WalkTheDogGame>,
+    game_over_screen: GameOverScreen,
+    stats: Stats,
+    high_scores: HighScores,
+    ghost: Ghost,
+    // With ?server, the other player, and where they were last
+    session: Option<Session<Remote>>,
+    rival: Option<ghost::Frame>,
+    // Where the other player's frames go when spectating them
+    feed: Option<UnboundedSender<ghost::Frame>>,
+    achievements: Achievements,
+    tutorial: Tutorial,
+    dialog: Dialog,
+    i18n: I18n,
+    // The settings scene pauses the game while it's open
+    settings_open: bool,
+    // Held while a run is going, so the screen stays on for touch play
+    wake_lock: WakeLock,
+    sounds: Sounds,
+    // Playing from the first step of a run until it's over
+    music: Option<Music>,
+    // From ?muted
+    muted: bool,
+    // So does the wardrobe
+    wardrobe: Wardrobe,
+    skin: &'static Skin,
+    profile: Profile,
+    gamepads_connected: u8,
+    notice: Option<Notice>,
+    // The last few seconds of play, for F3 to save. Not every browser can
+    // record the canvas, so this is None on those.
+    clips: Option<ClipRecorder>,
+    mode: Mode,
+    difficulty: u8,
+    // Clicks on the share and challenge buttons, once the game over screen
+    // has them
+    share_clicks: Option<UnboundedReceiver<()>>,
+    challenge_clicks: Option<UnboundedReceiver<()>>,
+    // What's typed into the chat box between runs
+    chat_entries: Option<UnboundedReceiver<String>>,
+    analytics: Rc<dyn Analytics>,
+    // Players can opt out of crash reports in the settings
+    crash_reports: bool,
+    timers: Scheduler<WalkTheDogGame>,
+    // The latest collisions RHB reported, newest last, for ?collisions
+    contacts: VecDeque<Collision>,
+    // For F4 to step back to, when a collision needs watching again
+    snapshots: Rewind<Snapshot>,
+    // With ?spectate, the best run plays back in place of RHB, and nothing
+    // is simulated
+    spectator: Option<Spectator>,
+}
+
+// A second RHB on the same keyboard, scoring for themselves. The camera keeps
+// both players in frame, and the run is only over once both are down.
+#[derive(Clone)]
+struct Partner {
+    rhb: RedHatBoy,
+    score: u32,
+}
+
+// What F4 puts back: the whole world as it was, with the run's stats to
+// match, so coins collected since are back where they were
+#[derive(Clone)]
+struct Snapshot {
+    world: WorldState,
+    run: Tally,
+}
+
+// What a player is pressing on one update, as far as the world's concerned.
+// In co-op over a server it's what's sent to the other end, and what's
+// guessed for a partner whose moves haven't arrived yet.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Moves {
+    run: bool,
+    jump: bool,
+    slide: bool,
+    // Jump pressed to finish the slide jump combo
+    slide_jump: bool,
+    throw: bool,
+    // Down less up, for ladders
+    climb: f32,
+}
+
+impl Moves {
+    fn player_one(input: &InputState, tricks: &[Trick]) -> Self {
+        Moves {
+            run: input.is_pressed(Action::Run),
+            jump: input.is_pressed(Action::Jump),
+            slide: input.is_pressed(Action::Slide),
+            slide_jump: tricks.contains(&Trick::SlideJump),
+            throw: input.is_pressed(Action::Throw),
+            climb: input.value(Action::Down) - input.value(Action::Up),
+        }
+    }
+
+    // Player two on the same keyboard only has the basics
+    fn player_two(input: &InputState) -> Self {
+        Moves {
+            run: input.is_pressed(Action::PartnerRun),
+            jump: input.is_pressed(Action::PartnerJump),
+            slide: input.is_pressed(Action::PartnerSlide),
+            ..Moves::default()
+        }
+    }
+}
+
+// What the other end of a session sends every update
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Remote {
+    // Where its player is, to race against or watch
+    Frame(ghost::Frame),
+    // In co-op, what its player pressed on its update number tick
+    Moves { tick: u64, moves: Moves },
+}
+
+// What happened during a step that the game has more to do about, like
+// playing a sound. Players are numbered from 0 for player one.
+#[derive(Clone)]
+enum WorldEvent {
+    Started(usize),
+    Jumped(usize),
+    Slid(usize),
+    NearMiss { player: usize, at: Vector },
+    Coin { player: usize, at: Vector },
+    Shattered(Vec<Particle>),
+    Collision(Collision),
+    ArenaReached,
+    // How far the world scrolled for running, leaving out portal pans
+    Ran(i16),
+    // Where a live player got to, in world space, before the world scrolled
+    Moved { player: usize, frame: ghost::Frame },
+}
+
+// Everything that plays out in the world, apart from what's only for show.
+// A step is a pure function of the state and each player's moves, so it can
+// go through Rollback. Sounds, stats, saving, analytics and the event bus are
+// left to the game, which finds out what happened from the events the step
+// leaves behind.
+//
+// It's only cloned, not serialized. RHB's timers and the boss's brain are
+// callbacks, and sprites share their sheets, so saving it to bytes waits on
+// those becoming plain data.
+#[derive(Clone)]
+struct WorldState {
+    rock: Obstacle,
+    rhb: RedHatBoy,
+    // With ?coop, player two
+    partner: Option<Partner>,
+    platforms: Platforms,
+    water: Vec<Water>,
+    ladders: Vec<Ladder>,
+    force_zones: Vec<ForceZone>,
+    tutorial_zones: Vec<TutorialZone>,
+    portals: Vec<Portal>,
+    pan: Option<PortalPan>,
+    bones: Vec<Bone>,
+    bone_thrower: BoneThrower,
+    bone_pickups: Vec<BonePickup>,
+    boss: Boss,
+    boss_shots: Vec<BossShot>,
+    power_ups: Vec<PowerUp>,
+    // Waves only move on while the world scrolls, so pickups don't pile up
+    // off screen while RHB stands still
+    spawner: Spawner<Pickup>,
+    coins: Vec<Coin>,
+    score: u32,
+    // Whether the boss arena has been reached yet
+    arena_reached: bool,
+    // Where the screen is along the whole run
+    camera: Camera,
+    // From the latest step only
+    events: Vec<WorldEvent>,
+}
+
+struct Gameplay;
+
+impl Simulation for Gameplay {
+    type State = WorldState;
+    type Input = Moves;
+
+    fn step(state: &WorldState, inputs: &[Moves]) -> WorldState {
+        let mut next = state.clone();
+        next.update(inputs);
+        next
+    }
+}
+
+impl WorldState {
+    fn update(&mut self, inputs: &[Moves]) {
+        self.events.clear();
+        self.rhb.killed_by = None;
+        if let Some(partner) = self.partner.as_mut() {
+            partner.rhb.killed_by = None;
+        }
+        let moves = inputs.first().copied().unwrap_or_default();
+
+        if moves.run && self.rhb.run() {
+            self.events.push(WorldEvent::Started(0));
+        }
+
+        if moves.jump && self.rhb.jump() {
+            self.events.push(WorldEvent::Jumped(0));
+        }
+
+        if moves.slide && self.rhb.slide() {
+            self.events.push(WorldEvent::Slid(0));
+        }
+
+        if moves.slide_jump && self.rhb.slide_jump(SLIDE_JUMP_VELOCITY) {
+            self.events.push(WorldEvent::Jumped(0));
+        }
+
+        self.bone_thrower.update();
+        if moves.throw && self.rhb.can_throw() {
+            let hand = self.rhb.hand();
+            let speed = self.rhb.walking_speed();
+            if let Some(bone) = self.bone_thrower.throw(hand, speed) {
+                self.bones.push(bone);
+            }
+        }
+
+        if self.on_ladder() {
+            if moves.climb < 0.0 || self.rhb.is_climbing() {
+                self.rhb.climb(moves.climb);
+            }
+        } else {
+            self.rhb.let_go();
+        }
+
+        self.platforms.update();
+
+        if let Some(platform) = self.rhb.grounded_on.and_then(|id| self.platforms.get(id)) {
+            self.rhb.ride(platform.carry());
+        }
+
+        if self
+            .water
+            .iter()
+            .any(|water| self.rhb.collides_with(&water.bounding_box()))
+        {
+            self.rhb.swim();
+        } else {
+            self.rhb.surface();
+        }
+
+        for zone in self.force_zones.iter() {
+            if self.rhb.collides_with(&zone.bounding_box()) {
+                self.rhb.push(zone.force);
+            }
+        }
+
+        self.rhb.update();
+
+        self.rhb.grounded_on = None;
+        self.rhb.grounding.begin();
+        for (id, platform) in self.platforms.iter_mut() {
+            if platform.is_solid()
+                && self
+                    .rhb
+                    .check_platform_collisions(platform, &mut self.events)
+            {
+                self.rhb.grounded_on = Some(id);
+                platform.stood_on();
+            }
+        }
+
+        if !self.rock.broken && self.rock.hits(&self.rhb.bounding_box()) {
+            let overlap = self
+                .rhb
+                .bounding_box()
+                .intersection(&self.rock.bounding_box());
+            if self.rock.breakable && self.rhb.can_smash() {
+                self.events.push(WorldEvent::Shattered(self.rock.shatter()));
+                self.events.extend(collision("rock", overlap, "smashed"));
+            } else {
+                let resolution = self.rhb.hit(DeathCause::Obstacle);
+                self.events.extend(collision("rock", overlap, resolution));
+            }
+            self.rock.touched = true;
+        }
+
+        if self.rhb.collides_with(&self.rock.near_miss_box()) {
+            self.rock.grazing = true;
+        } else if self.rock.grazing {
+            if !self.rock.touched && !self.rock.broken {
+                self.score += NEAR_MISS_SCORE;
+                self.events.push(WorldEvent::NearMiss {
+                    player: 0,
+                    at: self.rhb.center(),
+                });
+            }
+            self.rock.grazing = false;
+            self.rock.touched = false;
+        }
+
+        let rhb = &self.rhb;
+        let thrower = &mut self.bone_thrower;
+        self.bone_pickups.retain(|pickup| {
+            let collected = rhb.collides_with(&pickup.bounding_box());
+            if collected {
+                thrower.ammo = thrower.ammo.saturating_add(pickup.amount);
+            }
+            !collected
+        });
+
+        let rhb = &mut self.rhb;
+        self.power_ups.retain(|power_up| {
+            let collected = rhb.collides_with(&power_up.bounding_box());
+            if collected {
+                rhb.add_effect(power_up.effect);
+            }
+            !collected
+        });
+
+        if self.rhb.has_effect(Effect::Magnet) {
+            let center = self.rhb.center();
+            self.coins
+                .iter_mut()
+                .filter(|coin| coin.distance_to(&center) <= MAGNET_RADIUS)
+                .for_each(|coin| coin.move_towards(&center, MAGNET_PULL));
+        }
+
+        let rhb = &self.rhb;
+        let events = &mut self.events;
+        let before = self.coins.len();
+        self.coins.retain(|coin| {
+            let collected = rhb.collides_with(&coin.bounding_box());
+            if collected {
+                events.push(WorldEvent::Coin {
+                    player: 0,
+                    at: coin.position.to_vector(),
+                });
+            }
+            !collected
+        });
+        self.score += (before - self.coins.len()) as u32 * COIN_SCORE;
+
+        self.bones.iter_mut().for_each(Bone::update);
+        let rock = &mut self.rock;
+        let events = &mut self.events;
+        self.bones.retain(|bone| {
+            let hit = rock.breakable && !rock.broken && rock.hits(&bone.bounding_box());
+            if hit {
+                events.push(WorldEvent::Shattered(rock.shatter()));
+            }
+            !hit && !bone.is_spent()
+        });
+
+        if self.in_arena() {
+            if !self.arena_reached {
+                self.arena_reached = true;
+                self.events.push(WorldEvent::ArenaReached);
+            }
+            match self.boss.update() {
+                Some(BossEvent::Fire(shot)) => self.boss_shots.push(shot),
+                Some(BossEvent::OpenUp) if self.bone_thrower.ammo == 0 => {
+                    self.bone_pickups.push(BonePickup {
+                        position: Point {
+                            x: RHB_POSITION + 150,
+                            y: 450,
+                        },
+                        amount: 1,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        let boss = &mut self.boss;
+        self.bones
+            .retain(|bone| !(bone.bounding_box().intersects(&boss.bounding_box()) && boss.hit()));
+
+        self.boss_shots.iter_mut().for_each(BossShot::update);
+        let shot_overlap = self
+            .boss_shots
+            .iter()
+            .find_map(|shot| self.rhb.bounding_box().intersection(&shot.bounding_box()));
+        if shot_overlap.is_some() {
+            let resolution = self.rhb.hit(DeathCause::BossShot);
+            self.events
+                .extend(collision("boss shot", shot_overlap, resolution));
+        }
+        self.boss_shots.retain(|shot| !shot.is_spent());
+
+        if self.rhb.landing() {
+            self.rhb.land_on(FLOOR);
+        }
+
+        if self.pan.is_none() {
+            if let Some(portal) = self
+                .portals
+                .iter()
+                .find(|portal| self.rhb.collides_with(&portal.bounding_box()))
+            {
+                let distance = portal.exit.x - portal.entrance.x;
+                self.rhb.teleport(Point {
+                    x: distance,
+                    y: portal.exit.y - portal.entrance.y,
+                });
+                self.pan = Some(PortalPan {
+                    path: Path::new(vec![Point { x: distance, y: 0 }], PORTAL_PAN_SPEED)
+                        .mode(PathMode::Once),
+                    distance,
+                });
+            }
+        }
+
+        self.update_partner(inputs.get(1).copied().unwrap_or_default());
+
+        let mut scroll = self.scroll_speed();
+        self.events.push(WorldEvent::Ran(scroll));
+        if let Some(pan) = self.pan.as_mut() {
+            let step = pan.path.update().x;
+            self.rhb.ride(Point { x: -step, y: 0 });
+            if let Some(partner) = self.partner.as_mut() {
+                partner.rhb.ride(Point { x: -step, y: 0 });
+            }
+            scroll -= step;
+        }
+        if matches!(&self.pan, Some(pan) if pan.path.is_finished()) {
+            self.pan = None;
+        }
+        for player in 0..self.players() {
+            let rhb = self.player(player);
+            if !rhb.is_game_over() {
+                let frame = ghost::Frame {
+                    x: self.camera.world_x(rhb.position().x),
+                    y: rhb.position().y,
+                    animation: rhb.animation(),
+                    frame: rhb.frame() / 3,
+                };
+                self.events.push(WorldEvent::Moved { player, frame });
+            }
+        }
+        if scroll != 0 {
+            for spawn in self.spawner.tick() {
+                self.spawn(spawn);
+            }
+        }
+        if self.partner.is_some() {
+            self.move_players(scroll);
+        }
+        self.scroll(scroll);
+    }
+
+    // Player two gets the basics: running, jumping and sliding over the same
+    // platforms, water and wind, and coins of their own. Ladders, portals,
+    // pickups and bones are still player one's.
+    fn update_partner(&mut self, moves: Moves) {
+        let partner = match self.partner.as_mut() {
+            Some(partner) => partner,
+            None => return,
+        };
+        let rhb = &mut partner.rhb;
+        if moves.run && rhb.run() {
+            self.events.push(WorldEvent::Started(1));
+        }
+        if moves.jump && rhb.jump() {
+            self.events.push(WorldEvent::Jumped(1));
+        }
+        if moves.slide && rhb.slide() {
+            self.events.push(WorldEvent::Slid(1));
+        }
+
+        let platforms = &self.platforms;
+        if let Some(platform) = rhb.grounded_on.and_then(|id| platforms.get(id)) {
+            rhb.ride(platform.carry());
+        }
+        if self
+            .water
+            .iter()
+            .any(|water| rhb.collides_with(&water.bounding_box()))
+        {
+            rhb.swim();
+        } else {
+            rhb.surface();
+        }
+        for zone in self.force_zones.iter() {
+            if rhb.collides_with(&zone.bounding_box()) {
+                rhb.push(zone.force);
+            }
+        }
+
+        rhb.update();
+
+        rhb.grounded_on = None;
+        rhb.grounding.begin();
+        for (id, platform) in self.platforms.iter_mut() {
+            if platform.is_solid() && rhb.check_platform_collisions(platform, &mut self.events) {
+                rhb.grounded_on = Some(id);
+                platform.stood_on();
+            }
+        }
+
+        if !self.rock.broken && self.rock.hits(&rhb.bounding_box()) {
+            if self.rock.breakable && rhb.can_smash() {
+                self.events.push(WorldEvent::Shattered(self.rock.shatter()));
+            } else {
+                rhb.hit(DeathCause::Obstacle);
+            }
+        }
+
+        let events = &mut self.events;
+        let before = self.coins.len();
+        self.coins.retain(|coin| {
+            let collected = rhb.collides_with(&coin.bounding_box());
+            if collected {
+                events.push(WorldEvent::Coin {
+                    player: 1,
+                    at: coin.position.to_vector(),
+                });
+            }
+            !collected
+        });
+        partner.score += (before - self.coins.len()) as u32 * COIN_SCORE;
+
+        if self
+            .boss_shots
+            .iter()
+            .any(|shot| rhb.bounding_box().intersects(&shot.bounding_box()))
+        {
+            rhb.hit(DeathCause::BossShot);
+        }
+
+        if rhb.landing() {
+            rhb.land_on(FLOOR);
+        }
+    }
+
+    fn players(&self) -> usize {
+        if self.partner.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn player(&self, player: usize) -> &RedHatBoy {
+        match (player, &self.partner) {
+            (1, Some(partner)) => &partner.rhb,
+            _ => &self.rhb,
+        }
+    }
+
+    fn score_of(&self, player: usize) -> u32 {
+        match (player, &self.partner) {
+            (1, Some(partner)) => partner.score,
+            _ => self.score,
+        }
+    }
+
+    fn spawn(&mut self, spawn: Spawn<Pickup>) {
+        match spawn.kind {
+            Pickup::Bones(amount) => self.bone_pickups.push(BonePickup {
+                position: spawn.position,
+                amount,
+            }),
+            Pickup::PowerUp(effect) => self.power_ups.push(PowerUp {
+                position: spawn.position,
+                effect,
+            }),
+        }
+    }
+
+    // The platforms a chunk of the level brings in, placed from its left
+    // edge
+    fn spawn_chunk(&mut self, origin: f32, chunk: &LevelChunk, tiles: &Rc<SpriteSheet>) {
+        let left = self.camera.screen_x(CHUNKS_START + origin as i64);
+        for platform in chunk.platforms.iter() {
+            let position = Point {
+                x: world::shift(left, platform.x),
+                y: platform.y,
+            };
+            self.platforms
+                .spawn(generated_platform(platform.kind, tiles, position));
+        }
+    }
+
+    fn scroll(&mut self, velocity: i16) {
+        self.camera.scroll(velocity);
+        self.rock.move_horizontally(velocity);
+        self.platforms.move_horizontally(velocity);
+        for water in self.water.iter_mut() {
+            water.move_horizontally(velocity);
+        }
+        for ladder in self.ladders.iter_mut() {
+            ladder.move_horizontally(velocity);
+        }
+        for zone in self.force_zones.iter_mut() {
+            zone.move_horizontally(velocity);
+        }
+        for zone in self.tutorial_zones.iter_mut() {
+            zone.move_horizontally(velocity);
+        }
+        for portal in self.portals.iter_mut() {
+            portal.move_horizontally(velocity);
+        }
+        for bone in self.bones.iter_mut() {
+            bone.move_horizontally(velocity);
+        }
+        for pickup in self.bone_pickups.iter_mut() {
+            pickup.move_horizontally(velocity);
+        }
+        for power_up in self.power_ups.iter_mut() {
+            power_up.move_horizontally(velocity);
+        }
+        for coin in self.coins.iter_mut() {
+            coin.move_horizontally(velocity);
+        }
+        self.boss.move_horizontally(velocity);
+        for shot in self.boss_shots.iter_mut() {
+            shot.move_horizontally(velocity);
+        }
+    }
+
+    // The world scrolls past at RHB's speed, apart from in the boss arena,
+    // where it holds still. In co-op it goes at the camera's speed instead.
+    fn scroll_speed(&self) -> i16 {
+        if self.in_arena() {
+            0
+        } else if self.partner.is_some() {
+            -coop::camera_speed(&self.live_players())
+        } else {
+            -self.rhb.scroll_speed()
+        }
+    }
+
+    // Each live player's screen x and walking speed
+    fn live_players(&self) -> Vec<(i16, i16)> {
+        std::iter::once(&self.rhb)
+            .chain(self.partner.as_ref().map(|partner| &partner.rhb))
+            .filter(|rhb| !rhb.is_game_over())
+            .map(|rhb| (rhb.position().x, rhb.scroll_speed()))
+            .collect()
+    }
+
+    fn everyone_down(&self) -> bool {
+        self.live_players().is_empty()
+    }
+
+    // In co-op the world scrolls at the camera's speed, so each player moves
+    // across the screen by however much faster or slower than it they are,
+    // and live players are kept in frame. Crashed players are left behind.
+    fn move_players(&mut self, scroll: i16) {
+        for rhb in std::iter::once(&mut self.rhb)
+            .chain(self.partner.as_mut().map(|partner| &mut partner.rhb))
+        {
+            let x = rhb.position().x + rhb.scroll_speed() + scroll;
+            let x = if rhb.is_game_over() {
+                x
+            } else {
+                coop::framed(x)
+            };
+            rhb.ride(Point {
+                x: x - rhb.position().x,
+                y: 0,
+            });
+        }
+    }
+
+    fn in_arena(&self) -> bool {
+        !self.boss.is_defeated() && self.boss.bounding_box().x <= ARENA_X.into()
+    }
+
+    fn on_ladder(&self) -> bool {
+        self.ladders
+            .iter()
+            .any(|ladder| self.rhb.collides_with(&ladder.bounding_box()))
+    }
+}
+
+impl WalkTheDogGame {
+    async fn initialize(start: Start, skin: &'static Skin) -> Result<WalkTheDogGame> {
+        let Start {
+            analytics,
+            mode,
+            difficulty: difficulty_level,
+            spectate,
+            coop,
+            player,
+            muted,
+            clips,
+            server,
+            name,
+            profile,
+        } = start;
+        if let Err(err) = offline::load_manifest(ASSET_MANIFEST).await {
+            log!("Could not load the asset manifest {:#?}", err);
+        }
+        let mut rng = mode.rng();
+        let crash_reports = load_crash_reports();
+        crash::set_enabled(crash_reports);
+        let mut timers = Scheduler::default();
+        timers.every(CRASH_CONTEXT_MS, |game: &mut WalkTheDogGame| {
+            game.update_crash_context()
+        });
+        let physics: PhysicsConfig = platform::load_json(skin.character.physics).await?;
+        let level: Level = platform::load_json("level.json").await?;
+        let difficulty = DifficultyCurve::load().await?;
+
+        let background = TilingBackground::new(engine::load_image("BG.png").await?, 0);
+
+        let rock = Image::new(
+            engine::load_image("Stone.png").await?,
+            Point { x: 700, y: 546 },
+        );
+        // Without it the rock is just its bounding box, which is a little
+        // unfair at the rounded corners
+        let rock_mask = rock
+            .mask(MASK_CELL)
+            .map_err(|err| log!("Could not make the rock's mask {:#?}", err))
+            .ok();
+
+        let mut sheets = SpriteSheets::default();
+        let rhb_sheet = sheets
+            .load(skin.character.sheet, Anchor::FeetCenter)
+            .await?;
+        let rhb = RedHatBoy::new(
+            Animation::new(Rc::clone(&rhb_sheet)),
+            physics,
+            skin.character.special,
+        );
+        let dialog = Dialog::load(&mut sheets).await?;
+        let partner = if coop {
+            let mut rhb =
+                RedHatBoy::new(Animation::new(rhb_sheet), physics, skin.character.special);
+            rhb.teleport(Point {
+                x: PARTNER_OFFSET,
+                y: 0,
+            });
+            Some(Partner { rhb, score: 0 })
+        } else {
+            None
+        };
+
+        let platform_sheet = sheets.load("tiles", Anchor::TopLeft).await?;
+
+        let first_platform = Platform::new(
+            Rc::clone(&platform_sheet),
+            Point { x: 220, y: 350 },
+            LONG_PLATFORM,
+        );
+
+        let moving_platform = Platform::new(
+            Rc::clone(&platform_sheet),
+            Point { x: 900, y: 420 },
+            SHORT_PLATFORM,
+        )
+        .with_path(Path::new(
+            vec![Point { x: 0, y: -150 }, Point { x: 0, y: 0 }],
+            2,
+        ));
+
+        let crumbling_platform = Platform::new(
+            Rc::clone(&platform_sheet),
+            Point { x: 1300, y: 400 },
+            CRUMBLING_BLOCK,
+        )
+        .with_height(CRUMBLING_HEIGHT)
+        .crumbling();
+
+        let spring = Platform::new(
+            Rc::clone(&platform_sheet),
+            Point { x: 1600, y: 472 },
+            SPRING_BLOCK,
+        )
+        .with_bounce(SPRING_VELOCITY);
+
+        let conveyor = Platform::new(
+            Rc::clone(&platform_sheet),
+            Point { x: 1900, y: 350 },
+            LONG_PLATFORM,
+        )
+        .with_conveyor(CONVEYOR_SPEED);
+
+        let hill = Platform::new(
+            Rc::clone(&platform_sheet),
+            Point { x: 3400, y: 380 },
+            LONG_PLATFORM,
+        )
+        .with_slope(Slope {
+            left: 120,
+            right: 0,
+        });
+
+        let pool = Water {
+            sprite: platform_sheet.sprite("18.png"),
+            sheet: Rc::clone(&platform_sheet),
+            position: Point { x: 2500, y: 472 },
+            tiles: 3,
+        };
+
+        // Coins and the foreground are drawn from the seed first, so adding
+        // generated platforms didn't change them
+        let coins = generate_coins(&mut rng, &difficulty, difficulty_level);
+        let foreground = Foreground::new(StdRng::seed_from_u64(rng.gen()));
+        let mut layout = vec![
+            first_platform,
+            moving_platform,
+            crumbling_platform,
+            spring,
+            conveyor,
+            hill,
+        ];
+        layout.extend(generate_platforms(
+            &mut rng,
+            &difficulty,
+            difficulty_level,
+            &platform_sheet,
+        ));
+
+        let mut platforms = Platforms::default();
+        for platform in layout {
+            platforms.spawn(platform);
+        }
+
+        let ghost = Ghost::load(&mode.storage_key(GHOST_KEY));
+        let session = server.and_then(|url| {
+            Session::connect(&url, name.as_deref().unwrap_or(DEFAULT_NAME))
+                .map_err(|err| log!("Could not connect to {} {:#?}", url, err))
+                .ok()
+        });
+        // Spectators watch the other player when there is one, and the best
+        // run so far when there isn't
+        let (spectator, feed) = match (spectate, &session) {
+            (false, _) => (None, None),
+            (true, Some(_)) => {
+                let (feed, spectator) = Spectator::live();
+                (Some(spectator), Some(feed))
+            }
+            (true, None) => (Some(Spectator::replay(ghost.best_run())), None),
+        };
+        // In co-op over a server, each end plays one of the two players and
+        // is sent the other's moves
+        let online = coop && session.is_some();
+        let seat = if online && player == Some(2) { 1 } else { 0 };
+        let remote = if online { Some(1 - seat) } else { None };
+
+        let world = WorldState {
+            rock: Obstacle {
+                image: rock,
+                mask: rock_mask,
+                breakable: true,
+                broken: false,
+                grazing: false,
+                touched: false,
+            },
+            rhb,
+            partner,
+            platforms,
+            water: vec![pool],
+            ladders: vec![Ladder {
+                position: Point { x: 3000, y: 150 },
+                height: 450,
+            }],
+            force_zones: vec![
+                ForceZone {
+                    position: Point { x: 3900, y: 0 },
+                    width: 400,
+                    height: 600,
+                    force: Vector { x: -2.0, y: 0.0 },
+                },
+                ForceZone {
+                    position: Point { x: 4500, y: 0 },
+                    width: 300,
+                    height: 600,
+                    force: Vector { x: 0.0, y: -0.6 },
+                },
+            ],
+            tutorial_zones: level.tutorial_zones,
+            portals: vec![Portal {
+                entrance: Point { x: 5000, y: 480 },
+                exit: Point { x: 6200, y: 480 },
+            }],
+            pan: None,
+            bones: vec![],
+            bone_thrower: BoneThrower {
+                ammo: STARTING_BONES,
+                cooldown: 0,
+            },
+            bone_pickups: vec![],
+            boss: Boss::new(Point { x: BOSS_X, y: 440 }, BOSS_HEALTH),
+            boss_shots: vec![],
+            power_ups: vec![],
+            spawner: Spawner::new(level.waves),
+            coins,
+            score: 0,
+            arena_reached: false,
+            camera: Camera::default(),
+            events: vec![],
+        };
+        let players = world.players();
+
+        Ok(WalkTheDogGame {
+            background,
+            world: Rollback::new(world, players, ROLLBACK_TICKS),
+            seat,
+            remote,
+            remote_moves: vec![],
+            tiles: platform_sheet,
+            chunks: ChunkStream::new(CHUNK_PREFIX, ChunkWindow::new(CHUNK_WIDTH, 1, level.chunks)),
+            chunk_platforms: vec![],
+            tricks: tricks(),
+            foreground,
+            zoom: 1.0,
+            entities: Entities::default(),
+            shown_score: 0.0,
+            score_roll: None,
+            tweens: Tweens::default(),
+            coroutines: Coroutines::default(),
+            game_over_screen: GameOverScreen::Hidden,
+            stats: Stats::load(),
+            high_scores: HighScores::load(&mode.storage_key(HIGH_SCORES_KEY)),
+            ghost,
+            session,
+            rival: None,
+            feed,
+            achievements: Achievements::load(),
+            tutorial: Tutorial::load(),
+            dialog,
+            i18n: I18n::load().await?,
+            settings_open: false,
+            wake_lock: WakeLock::default(),
+            sounds: Sounds::load().await?,
+            music: None,
+            muted,
+            wardrobe: Wardrobe::default(),
+            skin,
+            profile,
+            gamepads_connected: 0,
+            notice: (!offline::is_online()).then(|| Notice::new("hud.offline")),
+            // Only recorded with ?clips, since it's costly to keep encoding
+            // video all session
+            clips: if clips {
+                ClipRecorder::start(CLIP_SECONDS)
+                    .map_err(|err| log!("Could not start recording clips {:#?}", err))
+                    .ok()
+            } else {
+                None
+            },
+            mode,
+            difficulty: difficulty_level,
+            share_clicks: None,
+            challenge_clicks: None,
+            chat_entries: None,
+            analytics,
+            crash_reports,
+            timers,
+            contacts: VecDeque::new(),
+            snapshots: Rewind::new(REWIND_SECONDS),
+            spectator,
+        })
+    }
+
+    fn handle_event(&mut self, event: &EngineEvent) {
+        match event {
+            EngineEvent::GamepadConnected { .. } => {
+                self.gamepads_connected += 1;
+                self.notice = Some(Notice::new("hud.gamepad_connected"));
+            }
+            EngineEvent::GamepadDisconnected { .. } => {
+                self.gamepads_connected = self.gamepads_connected.saturating_sub(1);
+                self.notice = Some(Notice::new("hud.gamepad_disconnected"));
+            }
+            EngineEvent::Online => self.notice = Some(Notice::new("hud.online")),
+            EngineEvent::Offline => self.notice = Some(Notice::new("hud.offline")),
+            // A long stall, like the tab being in the background, pauses the
+            // game so the player isn't dropped back in mid-jump
+            EngineEvent::Lag { dropped } if *dropped >= LAG_PAUSE_MS => {
+                if !self.local_player().is_game_over() {
+                    self.settings_open = true;
+                }
+            }
+            EngineEvent::Lag { .. } => {}
+            EngineEvent::Collision(collision) => {
+                self.contacts.push_back(collision.clone());
+                if self.contacts.len() > CONTACTS_SHOWN {
+                    self.contacts.pop_front();
+                }
+            }
+        }
+    }
+
+    fn roll_up_score(&mut self) {
+        let score = self.world().score;
+        let rolling_to = self.score_roll.map(|(score, _)| score).unwrap_or(0);
+        if score == rolling_to {
+            return;
+        }
+
+        if let Some((_, tween)) = self.score_roll {
+            self.tweens.cancel(tween);
+        }
+        let tween = self.tweens.start(
+            Tween::new(
+                self.shown_score,
+                score as f32,
+                SCORE_ROLL_MS,
+                |game: &mut WalkTheDogGame, score| game.shown_score = score,
+            )
+            .easing(Easing::EaseOut),
+        );
+        self.score_roll = Some((score, tween));
+    }
+
+    // What a crash report should say the player was doing
+    fn update_crash_context(&self) {
+        let arena = if self.world().in_arena() {
+            ", in the arena"
+        } else {
+            ""
+        };
+        let state = format!(
+            "{:?} at {}m, score {}{}",
+            self.world().rhb.animation(),
+            self.stats.run.distance as u32,
+            self.world().score,
+            arena
+        );
+        crash::set_context(&state, Some(self.mode.seed()));
+    }
+
+    fn show_share_button(&mut self) {
+        let mut html = SHARE_BUTTONS
+            .replace("{share}", self.i18n.get("share.button"))
+            .replace("{challenge}", self.i18n.get("share.challenge"));
+        if self.session.is_some() {
+            html.push_str(&CHAT_FORM.replace("{placeholder}", self.i18n.get("chat.placeholder")));
+        }
+        let clicks =
+            ui::show(&html).and_then(|_| Ok((ui::on_click("share")?, ui::on_click("challenge")?)));
+        match clicks {
+            Ok((share, challenge)) => {
+                self.share_clicks = Some(share);
+                self.challenge_clicks = Some(challenge);
+            }
+            Err(err) => log!("Could not show the share buttons {:#?}", err),
+        }
+        if self.session.is_some() {
+            match ui::on_submit("chat", "chat-text") {
+                Ok(entries) => self.chat_entries = Some(entries),
+                Err(err) => log!("Could not show the chat box {:#?}", err),
+            }
+        }
+    }
+
+    // A link to this exact run: the same mode, seed and difficulty
+    fn challenge_url(&self) -> Result<String> {
+        let challenge = Challenge {
+            daily: self.mode.is_daily(),
+            seed: self.mode.seed(),
+            difficulty: self.difficulty,
+        };
+        Ok(format!("{}{}", platform::page_url()?, challenge.fragment()))
+    }
+
+    fn copy_challenge(&mut self) {
+        let url = match self.challenge_url() {
+            Ok(url) => url,
+            Err(err) => {
+                log!("Could not get the page URL {:#?}", err);
+                return;
+            }
+        };
+        let script = self.coroutines.script();
+        self.coroutines.spawn(async move {
+            let copied = ui::copy(&url).await;
+            script.run(move |game: &mut WalkTheDogGame| match copied {
+                Ok(()) => game.notice = Some(Notice::new("share.challenge_copied")),
+                Err(err) => log!("Could not copy challenge link {:#?}", err),
+            });
+        });
+    }
+
+    // Shares a link to play the same layout. The share sheet opens
+    // asynchronously, so the result comes back through a coroutine.
+    fn share_score(&mut self) {
+        let url = match self.challenge_url() {
+            Ok(url) => url,
+            Err(err) => {
+                log!("Could not get the page URL {:#?}", err);
+                return;
+            }
+        };
+        let title = self.i18n.get("share.title").to_string();
+        let text = self
+            .i18n
+            .get("share.text")
+            .replace("{score}", &self.world().score_of(self.seat).to_string())
+            .replace("{seed}", &self.mode.seed().to_string());
+
+        let script = self.coroutines.script();
+        self.coroutines.spawn(async move {
+            let shared = ui::share(&title, &text, &url).await;
+            script.run(move |game: &mut WalkTheDogGame| match shared {
+                Ok(Shared::Copied) => game.notice = Some(Notice::new("share.copied")),
+                Ok(Shared::Shared) => {}
+                Err(err) => log!("Could not share score {:#?}", err),
+            });
+        });
+    }
+
+    // Prompts name controller buttons for as long as one is plugged in
+    fn glyphs(&self) -> Glyphs {
+        if self.gamepads_connected > 0 {
+            Glyphs::Gamepad
+        } else {
+            Glyphs::Keyboard
+        }
+    }
+
+    fn update(&mut self, input: &InputState) {
+        if let Some(notice) = self.notice.as_mut() {
+            notice.frames = notice.frames.saturating_sub(1);
+            if notice.frames == 0 {
+                self.notice = None;
+            }
+        }
+
+        if input.just_pressed(Action::Screenshot) {
+            if let Err(err) = engine::save_screenshot(SCREENSHOT_FILE) {
+                log!("Could not save screenshot {:#?}", err);
+            }
+        }
+        if let Some(Err(err)) = self.clips.as_mut().map(ClipRecorder::update) {
+            log!("Could not keep recording clips {:#?}", err);
+            self.clips = None;
+        }
+        if input.just_pressed(Action::SaveClip) {
+            if let Some(Err(err)) = self.clips.as_ref().map(|clips| clips.save(CLIP_FILE)) {
+                log!("Could not save clip {:#?}", err);
+            }
+        }
+        if input.just_pressed(Action::Rewind) {
+            self.rewind();
+        }
+        self.update_session();
+        if let Some(Ok(text)) = self.chat_entries.as_mut().map(|entries| entries.try_recv()) {
+            if let Some(Err(err)) = self
+                .session
+                .as_mut()
+                .map(|session| session.send_chat(&text))
+            {
+                log!("Could not send chat {:#?}", err);
+            }
+        }
+        if let Some(Ok(())) = self.share_clicks.as_mut().map(|clicks| clicks.try_recv()) {
+            self.share_score();
+        }
+        if let Some(Ok(())) = self
+            .challenge_clicks
+            .as_mut()
+            .map(|clicks| clicks.try_recv())
+        {
+            self.copy_challenge();
+        }
+        if input.just_pressed(Action::Settings) {
+            self.settings_open = !self.settings_open;
+        }
+        // Let go of while paused and once the run is over. The browser lets go
+        // when the page is hidden, and the stall from that pauses the game, so
+        // it's held again on the way back.
+        self.wake_lock
+            .hold(!self.settings_open && !self.wardrobe.open && !self.world().everyone_down());
+        if self.settings_open {
+            if input.just_pressed(Action::Jump) {
+                self.profile.switch();
+            }
+            if input.just_pressed(Action::Slide) {
+                self.crash_reports = !self.crash_reports;
+                crash::set_enabled(self.crash_reports);
+                save_crash_reports(self.crash_reports);
+            }
+            return;
+        }
+        if input.just_pressed(Action::Wardrobe) {
+            if self.wardrobe.open {
+                self.wardrobe.open = false;
+            } else {
+                self.wardrobe.show(self.skin);
+            }
+        }
+        if self.wardrobe.open {
+            self.update_wardrobe(input);
+            return;
+        }
+
+        if let Some(spectator) = self.spectator.as_mut() {
+            spectator.update();
+            if let Some(frame) = spectator.current().copied() {
+                self.scroll(world::saturate(
+                    self.world().camera.world_x(RHB_POSITION) - frame.x,
+                ));
+            }
+            return;
+        }
+
+        self.snapshots.record(self.snapshot());
+        // To hear whether anything hurt him this update
+        let health = self.local_player().health;
+        for update in self.tweens.tick() {
+            update(self);
+        }
+        for update in self.coroutines.tick() {
+            update(self);
+        }
+        for update in self.timers.tick() {
+            update(self);
+        }
+        self.roll_up_score();
+        self.tutorial.update(input);
+        self.dialog.update(input);
+        if let Some(zone) = self
+            .world()
+            .tutorial_zones
+            .iter()
+            .find(|zone| self.world().rhb.collides_with(&zone.bounding_box()))
+        {
+            let prompt = zone.lesson.prompt();
+            if self.tutorial.trigger(prompt) {
+                if let Some(script) = prompt.dialog {
+                    self.dialog.play(script);
+                }
+            }
+        }
+
+        let tricks = self.tricks.update(input);
+        let moves = Moves::player_one(input, &tricks);
+        let local = match self.remote {
+            Some(_) => vec![(self.seat, moves)],
+            None if self.world().partner.is_some() => {
+                vec![(0, moves), (1, Moves::player_two(input))]
+            }
+            None => vec![(0, moves)],
+        };
+        self.stream_chunks();
+        let camera = self.world().camera.x();
+        self.world.advance_local(local);
+        if self.remote.is_some() {
+            self.send_moves(moves);
+            self.confirm_remote_moves();
+        }
+        for event in self.world().events.clone() {
+            self.apply(event);
+        }
+        self.entities.update();
+
+        let target_zoom = if self.world().rhb.has_effect(Effect::SpeedBoost) {
+            BOOST_ZOOM
+        } else {
+            1.0
+        };
+        if self.zoom < target_zoom {
+            self.zoom = (self.zoom + ZOOM_RATE).min(target_zoom);
+        } else {
+            self.zoom = (self.zoom - ZOOM_RATE).max(target_zoom);
+        }
+
+        self.achievements.update(&achievements::Progress {
+            distance: self.stats.run.distance as u32,
+            near_misses: self.stats.run.near_misses,
+            lifetime_coins: self.lifetime_coins(),
+        });
+
+        if let Some(cause) = self.local_player().killed_by {
+            let score = self.world().score_of(self.seat);
+            self.stats.record_death(cause);
+            self.analytics.death(cause.name());
+            self.analytics
+                .run_end(score, self.stats.run.distance as u32);
+            self.high_scores.record(score);
+            self.ghost.finish(score);
+        }
+        if self.local_player().health < health {
+            audio::play(&self.sounds.crash);
+        }
+        if self.world().everyone_down() && self.game_over_screen == GameOverScreen::Hidden {
+            self.game_over_screen = GameOverScreen::Starting;
+            self.music = None;
+            self.coroutines
+                .spawn(game_over_sequence(self.coroutines.script()));
+        }
+        self.scroll_scenery(world::saturate(camera - self.world().camera.x()));
+    }
+
+    // The side effects of what happened in the world this update. Events from
+    // updates simulated again after a rollback aren't seen, so a sound or stat
+    // from one of those can be missed.
+    fn apply(&mut self, event: WorldEvent) {
+        let seat = self.seat;
+        let local = |player| player == seat;
+        match event {
+            WorldEvent::Started(player) if local(player) => {
+                self.analytics.run_start(self.mode.seed());
+                if self.music.is_none() {
+                    self.music = Some(audio::play_looping(&self.sounds.music));
+                }
+            }
+            WorldEvent::Jumped(player) => {
+                if local(player) {
+                    self.stats.run.jumps += 1;
+                }
+                audio::play(&self.sounds.jump);
+            }
+            WorldEvent::Slid(player) if local(player) => self.stats.run.slides += 1,
+            WorldEvent::NearMiss { player, at } => {
+                if local(player) {
+                    self.stats.run.near_misses += 1;
+                }
+                self.entities.add(
+                    POPUP_PRIORITY,
+                    Popup::new(self.i18n.get("popup.near_miss"), at),
+                );
+            }
+            WorldEvent::Coin { player, at } => {
+                if local(player) {
+                    self.stats.run.coins += 1;
+                }
+                self.entities
+                    .add(POPUP_PRIORITY, Popup::new(&format!("+{}", COIN_SCORE), at));
+            }
+            WorldEvent::Shattered(particles) => {
+                for particle in particles {
+                    self.entities.add(PARTICLE_PRIORITY, particle);
+                }
+            }
+            WorldEvent::Collision(collision) => events::emit(EngineEvent::Collision(collision)),
+            WorldEvent::ArenaReached => self.analytics.level_reached(ARENA_LEVEL),
+            WorldEvent::Ran(scroll) => {
+                self.stats.run.distance -= f64::from(scroll) / f64::from(PIXELS_PER_METER)
+            }
+            WorldEvent::Moved { player, frame } if local(player) => {
+                self.ghost.record(frame);
+                if let Some(Err(err)) = self
+                    .session
+                    .as_mut()
+                    .map(|session| session.send_state(Remote::Frame(frame)))
+                {
+                    log!("Could not send where RHB is {:#?}", err);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A skin with a sheet of its own is only loaded at the next start, until
+    // then RHB keeps the sheet he has with the new skin's filter. The same
+    // goes for a skin of another character, whose physics and special move
+    // wait for the next start too.
+    fn update_wardrobe(&mut self, input: &InputState) {
+        if input.just_pressed(Action::Up) {
+            self.wardrobe.move_highlight(-1);
+        }
+        if input.just_pressed(Action::Down) {
+            self.wardrobe.move_highlight(1);
+        }
+        let skin = self.wardrobe.highlighted();
+        if input.just_pressed(Action::Jump)
+            && skin.is_unlocked(&self.achievements, self.lifetime_coins())
+        {
+            self.skin = skin;
+            skin.save();
+        }
+    }
+
+    fn lifetime_coins(&self) -> u32 {
+        self.stats.lifetime.coins + self.stats.run.coins
+    }
+
+    fn world(&self) -> &WorldState {
+        self.world.state()
+    }
+
+    // The player on this end, whose run is the one recorded
+    fn local_player(&self) -> &RedHatBoy {
+        self.world().player(self.seat)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            world: self.world().clone(),
+            run: self.stats.run.clone(),
+        }
+    }
+
+    // Once the run is over its ending is already under way, so there's no
+    // going back from it. Nor is there in co-op over a server, where the
+    // other end would carry on from where it was.
+    fn rewind(&mut self) {
+        if self.world().rhb.is_game_over() || self.remote.is_some() {
+            return;
+        }
+        if let Some(Snapshot { mut world, run }) = self.snapshots.rewind(REWIND_SECONDS) {
+            let velocity = (self.world().camera.x() - world.camera.x()) as i16;
+            // The chunk stream won't send chunks again, so whatever streamed
+            // in since is kept
+            let streamed = self.chunk_platforms.iter().flat_map(|(_, ids)| ids);
+            world
+                .platforms
+                .keep_from(&self.world().platforms, streamed, velocity);
+            self.world.reset(world);
+            self.stats.run = run;
+            self.scroll_scenery(velocity);
+        }
+    }
+
+    // Keeps up with the other player, dropping the session if it fails
+    fn update_session(&mut self) {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return,
+        };
+        let received = match session
+            .update()
+            .and_then(|_| session.states(REMOTE_MAX_AGE))
+        {
+            Ok(received) => received,
+            Err(err) => {
+                log!("Lost the connection to the other player {:#?}", err);
+                self.session = None;
+                self.rival = None;
+                return;
+            }
+        };
+        for remote in received {
+            match (remote, &self.feed) {
+                (Remote::Frame(frame), Some(feed)) => {
+                    let _ = feed.unbounded_send(frame);
+                }
+                // A co-op partner is already in the world
+                (Remote::Frame(frame), None) if self.remote.is_none() => self.rival = Some(frame),
+                (Remote::Frame(_), None) => {}
+                (Remote::Moves { tick, moves }, _) => self.remote_moves.push((tick, moves)),
+            }
+        }
+    }
+
+    // For the update just stepped
+    fn send_moves(&mut self, moves: Moves) {
+        let tick = self.world.tick() - 1;
+        if let Some(Err(err)) = self
+            .session
+            .as_mut()
+            .map(|session| session.send_state(Remote::Moves { tick, moves }))
+        {
+            log!("Could not send moves {:#?}", err);
+        }
+    }
+
+    // The other end's moves are confirmed once this end has stepped the
+    // update they're for, rolling back if they weren't what was guessed.
+    // Any for updates too long ago to roll back to are lost.
+    fn confirm_remote_moves(&mut self) {
+        let remote = match self.remote {
+            Some(remote) => remote,
+            None => return,
+        };
+        let next = self.world.tick();
+        let (due, later): (Vec<_>, Vec<_>) = self
+            .remote_moves
+            .drain(..)
+            .partition(|(tick, _)| *tick < next);
+        self.remote_moves = later;
+        for (tick, moves) in due {
+            if let Err(err) = self.world.confirm(remote, tick, moves) {
+                log!("Could not roll back for the other player {:#?}", err);
+            }
+        }
+    }
+
+    fn stream_chunks(&mut self) {
+        // The chunk stream starts at the first chunk however far off it is,
+        // so it isn't told about the camera until it's nearly there
+        let level_x = self.world().camera.x() - CHUNKS_START;
+        if level_x < -(CHUNK_WIDTH as i64) {
+            return;
+        }
+
+        for event in self.chunks.update(level_x as f32) {
+            match event {
+                ChunkEvent::Entered {
+                    index,
+                    origin,
+                    data,
+                } => {
+                    // Every kept state has spawned the same platforms so
+                    // far, so these get the same ids in all of them
+                    let first = self.world().platforms.next_id;
+                    let tiles = &self.tiles;
+                    self.world
+                        .amend(|world| world.spawn_chunk(origin, &data, tiles));
+                    let ids = (first..self.world().platforms.next_id).collect();
+                    self.chunk_platforms.push((index, ids));
+                }
+                ChunkEvent::Left { index } => {
+                    let world = &mut self.world;
+                    self.chunk_platforms.retain(|(chunk, ids)| {
+                        let keep = *chunk != index;
+                        if !keep {
+                            world.amend(|world| world.platforms.remove(ids));
+                        }
+                        keep
+                    });
+                }
+            }
+        }
+    }
+
+    // Moves everything on screen, for when the world has to be somewhere
+    // without being stepped there
+    fn scroll(&mut self, velocity: i16) {
+        self.world.amend(|world| world.scroll(velocity));
+        self.scroll_scenery(velocity);
+    }
+
+    // What's only for show, once the world has scrolled
+    fn scroll_scenery(&mut self, velocity: i16) {
+        self.background.move_horizontally(velocity);
+        self.foreground.move_horizontally(velocity);
+        self.entities.move_horizontally(velocity);
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        // The game loop doesn't say how far it is between updates yet, so
+        // moving things are drawn where the last update left them
+        let interpolation = 1.0;
+        renderer.clear_all(None);
+
+        renderer.zoom(self.zoom);
+        self.background.draw(renderer);
+        // Sprites in the world overlap by how low they stand, until the
+        // platforms, which always go in front. They're kept on whole pixels
+        // while the camera zooms, where the background can blend.
+        renderer.set_positioning(Positioning::Snapped);
+        renderer.begin_y_sort();
+        self.world().rock.draw(renderer, interpolation);
+        self.world()
+            .water
+            .iter()
+            .for_each(|water| water.draw(renderer));
+        self.world()
+            .ladders
+            .iter()
+            .for_each(|ladder| ladder.draw(renderer));
+        self.world()
+            .force_zones
+            .iter()
+            .for_each(|zone| zone.draw(renderer));
+        self.world()
+            .portals
+            .iter()
+            .for_each(|portal| portal.draw(renderer));
+        match self.spectator.as_ref().and_then(Spectator::current) {
+            Some(frame) => self.draw_spectated(renderer, frame),
+            None => {
+                self.draw_ghost(renderer);
+                if let Some(filter) = self.skin.filter {
+                    renderer.set_filter(filter);
+                }
+                self.world().rhb.draw(renderer, interpolation);
+                renderer.set_filter("none");
+                if let Some(partner) = &self.world().partner {
+                    renderer.set_filter(PARTNER_FILTER);
+                    partner.rhb.draw(renderer, interpolation);
+                    renderer.set_filter("none");
+                }
+            }
+        }
+        self.entities.draw(renderer, ..FRONT_PRIORITY);
+        self.world()
+            .bones
+            .iter()
+            .for_each(|bone| bone.draw(renderer));
+        self.world()
+            .bone_pickups
+            .iter()
+            .for_each(|pickup| pickup.draw(renderer));
+        self.world()
+            .power_ups
+            .iter()
+            .for_each(|power_up| power_up.draw(renderer));
+        self.world()
+            .coins
+            .iter()
+            .for_each(|coin| coin.draw(renderer));
+        self.world().boss.draw(renderer);
+        self.world()
+            .boss_shots
+            .iter()
+            .for_each(|shot| shot.draw(renderer));
+        renderer.end_y_sort();
+
+        self.world().platforms.draw(renderer);
+        self.foreground.draw(renderer);
+        self.entities.draw(renderer, FRONT_PRIORITY..);
+        if renderer.debug().show_collisions {
+            self.draw_contacts(renderer);
+        }
+        renderer.set_positioning(Positioning::SubPixel);
+        renderer.zoom(1.0);
+
+        renderer.draw_text(
+            &format!("{}", self.shown_score.round() as u32),
+            &Point { x: 480, y: 36 },
+            "#ffffff",
+            24,
+        );
+
+        for heart in 0..self.world().rhb.health {
+            renderer.fill_rect(
+                "#e02424",
+                &Rect {
+                    x: 20.0 + heart as f32 * 24.0,
+                    y: 20.0,
+                    width: 16.0,
+                    height: 16.0,
+                },
+            );
+        }
+        if let Some(partner) = &self.world().partner {
+            self.draw_partner_status(renderer, partner);
+        }
+
+        if self.world().in_arena() {
+            self.world().boss.draw_health_bar(renderer);
+        } else if !self.world().boss.is_defeated() {
+            self.draw_progress(renderer);
+        }
+        self.achievements.draw_toast(renderer, &self.i18n);
+        self.tutorial.draw(renderer, &self.i18n, self.glyphs());
+        if let Some(session) = &self.session {
+            session.chat().draw(renderer, &CHAT_POSITION, CHAT_WIDTH);
+        }
+        self.dialog.draw(renderer, &self.i18n);
+        if let Some(notice) = &self.notice {
+            notice.draw(renderer, &self.i18n);
+        }
+
+        if self.game_over_screen >= GameOverScreen::Results {
+            self.stats.draw(renderer, &self.i18n);
+        }
+        if self.game_over_screen >= GameOverScreen::HighScores {
+            self.draw_high_scores(renderer);
+        }
+
+        if self.settings_open {
+            self.draw_settings(renderer);
+        }
+        if self.wardrobe.open {
+            self.draw_wardrobe(renderer);
+        }
+
+        if let Some(pan) = &self.world().pan {
+            let alpha = pan.remaining() as f32 / pan.distance as f32;
+            renderer.fill_rect(
+                &format!("rgba(255, 255, 255, {})", alpha),
+                &Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 600.0,
+                    height: 600.0,
+                },
+            );
+        }
+    }
+
+    // Player two's score and hearts, under player one's
+    fn draw_partner_status(&self, renderer: &Renderer, partner: &Partner) {
+        renderer.draw_text(
+            &format!("{} {}", self.i18n.get("coop.player_two"), partner.score),
+            &Point { x: 480, y: 64 },
+            "#ffffff",
+            18,
+        );
+        for heart in 0..partner.rhb.health {
+            renderer.fill_rect(
+                PARTNER_HEART_COLOR,
+                &Rect {
+                    x: 20.0 + heart as f32 * 24.0,
+                    y: 44.0,
+                    width: 16.0,
+                    height: 16.0,
+                },
+            );
+        }
+    }
+
+    // Outlines where the latest collisions overlapped, with the newest one
+    // boxed a second time. They stay where they happened on screen, rather
+    // than scrolling with the world.
+    fn draw_contacts(&self, renderer: &Renderer) {
+        for contact in self.contacts.iter() {
+            renderer.draw_rect(CONTACT_COLOR, &contact.overlap);
+        }
+        if let Some(latest) = self.contacts.back() {
+            renderer.draw_rect(
+                CONTACT_COLOR,
+                &Rect {
+                    x: latest.overlap.x - 4.0,
+                    y: latest.overlap.y - 4.0,
+                    width: latest.overlap.width + 8.0,
+                    height: latest.overlap.height + 8.0,
+                },
+            );
+        }
+    }
+
+    // How far through the level something at screen x is, from 0.0 at the
+    // start to 1.0 at the boss arena. The world scrolls rather than RHB, so
+    // this goes by how far the boss still has to come.
+    fn progress_at(&self, x: f32) -> f32 {
+        let length = (BOSS_X - ARENA_X) as f32;
+        let remaining =
+            self.world().boss.bounding_box().x - ARENA_X as f32 - (x - RHB_POSITION as f32);
+        (1.0 - remaining / length).clamp(0.0, 1.0)
+    }
+
+    // Shown where the boss's health bar goes once the fight starts
+    fn draw_progress(&self, renderer: &Renderer) {
+        let bar = Rect {
+            x: 150.0,
+            y: 24.0,
+            width: 300.0,
+            height: 8.0,
+        };
+        renderer.fill_rect("#333333", &bar);
+        renderer.fill_rect(
+            "#ffffff",
+            &Rect {
+                width: bar.width * self.progress_at(RHB_POSITION.into()),
+                ..bar
+            },
+        );
+
+        let markers = self
+            .world()
+            .portals
+            .iter()
+            .map(|portal| {
+                (
+                    PORTAL_MARKER_COLOR,
+                    self.progress_at(portal.entrance.x.into()),
+                )
+            })
+            .chain(std::iter::once((BOSS_MARKER_COLOR, 1.0)));
+        for (color, progress) in markers {
+            renderer.fill_rect(
+                color,
+                &Rect {
+                    x: bar.x + bar.width * progress - 2.0,
+                    y: bar.y - 4.0,
+                    width: 4.0,
+                    height: bar.height + 8.0,
+                },
+            );
+        }
+    }
+
+    // The best run so far, drawn see-through so it's clearly not RHB
+    fn draw_ghost(&self, renderer: &Renderer) {
+        for frame in self.ghost.current().into_iter().chain(self.rival.as_ref()) {
+            renderer.set_alpha(GHOST_ALPHA);
+            self.world().rhb.animation.draw(
+                renderer,
+                frame.animation,
+                &frame.frame.into(),
+                &Point {
+                    x: self.world().camera.screen_x(frame.x),
+                    y: frame.y,
+                },
+            );
+            renderer.set_alpha(1.0);
+        }
+    }
+
+    fn draw_spectated(&self, renderer: &Renderer, frame: &ghost::Frame) {
+        self.world().rhb.animation.draw(
+            renderer,
+            frame.animation,
+            &frame.frame.into(),
+            &Point {
+                x: self.world().camera.screen_x(frame.x),
+                y: frame.y,
+            },
+        );
+    }
+
+    fn draw_high_scores(&self, renderer: &Renderer) {
+        let scores: Vec<String> = self
+            .high_scores
+            .scores()
+            .iter()
+            .map(|score| score.to_string())
+            .collect();
+        renderer.draw_text(
+            &format!("{}: {}", self.i18n.get("hud.best"), scores.join("  ")),
+            &Point { x: 120, y: 440 },
+            "#ffd700",
+            16,
+        );
+    }
 
-        if keystate.is_pressed("Space") {
-            self.rhb.jump();
+    fn draw_wardrobe(&self, renderer: &Renderer) {
+        renderer.fill_rect(
+            "rgba(0, 0, 0, 0.8)",
+            &Rect {
+                x: 100.0,
+                y: 150.0,
+                width: 400.0,
+                height: 270.0,
+            },
+        );
+        renderer.draw_text(
+            self.i18n.get("skins.title"),
+            &Point { x: 120, y: 190 },
+            "#ffffff",
+            28,
+        );
+        let lifetime_coins = self.lifetime_coins();
+        for (row, skin) in SKINS.iter().enumerate() {
+            let y = 230 + row as i16 * 50;
+            let highlighted = skin.id == self.wardrobe.highlighted().id;
+            let color = if skin.id == self.skin.id {
+                "#ffd700"
+            } else if skin.is_unlocked(&self.achievements, lifetime_coins) {
+                "#ffffff"
+            } else {
+                "#888888"
+            };
+            let marker = if highlighted { "> " } else { "" };
+            renderer.draw_text(
+                &format!("{}{}", marker, self.i18n.get(skin.name)),
+                &Point { x: 140, y },
+                color,
+                18,
+            );
+            if !skin.is_unlocked(&self.achievements, lifetime_coins) {
+                renderer.draw_text(
+                    self.i18n.get(skin.hint),
+                    &Point { x: 160, y: y + 20 },
+                    "#888888",
+                    14,
+                );
+            }
         }
+        renderer.draw_text(
+            self.i18n.get("skins.hint"),
+            &Point { x: 120, y: 400 },
+            "#ffffff",
+            14,
+        );
+    }
 
-        if keystate.is_pressed("ArrowDown") {
-            self.rhb.slide();
+    fn draw_settings(&self, renderer: &Renderer) {
+        renderer.fill_rect(
+            "rgba(0, 0, 0, 0.8)",
+            &Rect {
+                x: 100.0,
+                y: 150.0,
+                width: 400.0,
+                height: 270.0,
+            },
+        );
+        renderer.draw_text(
+            self.i18n.get("settings.title"),
+            &Point { x: 120, y: 190 },
+            "#ffffff",
+            28,
+        );
+        renderer.draw_text(
+            self.i18n.get("settings.controls"),
+            &Point { x: 120, y: 230 },
+            "#ffd700",
+            16,
+        );
+        for (row, profile) in controls::PROFILES.iter().enumerate() {
+            let color = if *profile == self.profile {
+                "#ffd700"
+            } else {
+                "#ffffff"
+            };
+            renderer.draw_text(
+                self.i18n.get(profile.name()),
+                &Point {
+                    x: 140,
+                    y: 260 + row as i16 * 28,
+                },
+                color,
+                18,
+            );
         }
+        let crash_reports = if self.crash_reports {
+            "settings.on"
+        } else {
+            "settings.off"
+        };
+        renderer.draw_text(
+            &format!(
+                "{}: {}",
+                self.i18n.get("settings.crash_reports"),
+                self.i18n.get(crash_reports)
+            ),
+            &Point { x: 120, y: 360 },
+            "#ffffff",
+            16,
+        );
+        renderer.draw_text(
+            self.i18n.get("settings.hint"),
+            &Point { x: 120, y: 400 },
+            "#ffffff",
+            14,
+        );
+    }
+}
 
-        self.rhb.update();
-
-        if self.rhb.is_dead() {
-            self.velocity = 0;
-        }
+fn load_crash_reports() -> bool {
+    platform::get_item(CRASH_REPORTS_KEY)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or(true)
+}
 
-        for (_, platform) in self.platforms.iter().enumerate() {
-            self.rhb.check_platform_collisions(platform);
-        }
+fn save_crash_reports(enabled: bool) {
+    if let Err(err) = platform::set_item(CRASH_REPORTS_KEY, &enabled.to_string()) {
+        log!("Could not save crash report setting {:#?}", err);
+    }
+}
 
-        if self.rhb.collides_with(&self.rock.bounding_box()) {
-            self.rhb.kill();
-        }
+// One of RHB's collisions, for the game to put on the event bus for the
+// collision view
+fn collision(
+    other: &'static str,
+    overlap: Option<Rect>,
+    resolution: &'static str,
+) -> Option<WorldEvent> {
+    overlap.map(|overlap| {
+        WorldEvent::Collision(Collision {
+            subject: "rhb",
+            other,
+            overlap,
+            resolution,
+        })
+    })
+}
 
-        if self.rhb.landing() {
-            self.rhb.land_on(FLOOR);
+fn generate_coins(rng: &mut StdRng, difficulty: &DifficultyCurve, level: u8) -> Vec<Coin> {
+    let head_start = f32::from(level) * DIFFICULTY_STEP;
+    let mut x = COINS_START;
+    let mut coins = vec![];
+    while x < COINS_END {
+        let settings = difficulty.at(x / PIXELS_PER_METER + head_start);
+        x += if settings.gap_max > settings.gap_min {
+            rng.gen_range(settings.gap_min..settings.gap_max)
+        } else {
+            settings.gap_min
+        };
+        if rng.gen_bool(settings.coin_density.clamp(0.0, 1.0).into()) {
+            coins.push(Coin {
+                position: FixedVector::from_vector(Vector {
+                    x,
+                    y: COIN_HEIGHTS[rng.gen_range(0..COIN_HEIGHTS.len())],
+                }),
+            });
         }
+    }
+    coins
+}
 
-        for (_, background) in self.backgrounds.iter_mut().enumerate() {
-            if background.x() == -BACKGROUND_WIDTH {
-                background.set_x(BACKGROUND_WIDTH);
-            }
-            background.move_horizontally(self.velocity);
+// Platforms of the kinds the difficulty curve weights most, with gaps from
+// the curve between them
+fn generate_platforms(
+    rng: &mut StdRng,
+    difficulty: &DifficultyCurve,
+    level: u8,
+    sheet: &Rc<SpriteSheet>,
+) -> Vec<Platform> {
+    let head_start = f32::from(level) * DIFFICULTY_STEP;
+    let mut x = PLATFORMS_START;
+    let mut platforms = vec![];
+    while x < PLATFORMS_END {
+        let settings = difficulty.at(x / PIXELS_PER_METER + head_start);
+        x += if settings.gap_max > settings.gap_min {
+            rng.gen_range(settings.gap_min..settings.gap_max)
+        } else {
+            settings.gap_min
+        };
+        if let Some(kind) = settings.obstacle_weights.pick(rng.gen()) {
+            let y = PLATFORM_HEIGHTS[rng.gen_range(0..PLATFORM_HEIGHTS.len())];
+            let platform = generated_platform(kind, sheet, Point { x: x as i16, y });
+            x += platform.bounding_box.width;
+            platforms.push(platform);
         }
+    }
+    platforms
+}
 
-        self.rock.move_horizontally(self.velocity);
-        for (_, platform) in self.platforms.iter_mut().enumerate() {
-            platform.move_horizontally(self.velocity);
+fn generated_platform(kind: ObstacleKind, sheet: &Rc<SpriteSheet>, position: Point) -> Platform {
+    let sheet = Rc::clone(sheet);
+    match kind {
+        ObstacleKind::Platform => Platform::new(sheet, position, LONG_PLATFORM),
+        ObstacleKind::Moving => Platform::new(sheet, position, SHORT_PLATFORM).with_path(
+            Path::new(vec![Point { x: 0, y: -150 }, Point { x: 0, y: 0 }], 2),
+        ),
+        ObstacleKind::Crumbling => Platform::new(sheet, position, CRUMBLING_BLOCK)
+            .with_height(CRUMBLING_HEIGHT)
+            .crumbling(),
+        ObstacleKind::Spring => Platform::new(
+            sheet,
+            Point {
+                x: position.x,
+                y: SPRING_Y,
+            },
+            SPRING_BLOCK,
+        )
+        .with_bounce(SPRING_VELOCITY),
+        ObstacleKind::Conveyor => {
+            Platform::new(sheet, position, LONG_PLATFORM).with_conveyor(CONVEYOR_SPEED)
         }
     }
+}
 
-    fn draw(&self, renderer: &Renderer) {
-        renderer.clear(&Rect {
-            x: 0.0,
-            y: 0.0,
-            width: 600.0,
-            height: 600.0,
-        });
-
-        self.backgrounds.iter().for_each(|background| {
-            background.draw(&renderer);
-        });
-        self.rock.draw(renderer);
-        self.rhb.draw(renderer);
-
-        self.draw_platform(renderer);
-    }
+// The game over screen builds up a piece at a time
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum GameOverScreen {
+    Hidden,
+    Starting,
+    Results,
+    HighScores,
+}
 
-    fn draw_platform(&self, renderer: &Renderer) {
-        self.platforms.first().unwrap().draw(renderer);
-    }
+async fn game_over_sequence(script: Script<WalkTheDogGame>) {
+    script.seconds(RESULTS_DELAY).await;
+    script.run(|game| game.game_over_screen = GameOverScreen::Results);
+    script.seconds(HIGH_SCORES_DELAY).await;
+    script.run(|game| {
+        game.game_over_screen = GameOverScreen::HighScores;
+        game.show_share_button();
+    });
 }
 
+#[derive(Clone)]
 struct RedHatBoy {
     state: RedHatBoyStateMachine,
-    animation: Animation,
+    animation: Animation<RhbAnimation>,
+    // Id of the platform RHB is standing on, so he can be carried along by it
+    grounded_on: Option<usize>,
+    grounding: Grounding,
+    health: u8,
+    invulnerable: Option<TimerHandle>,
+    effects: Vec<ActiveEffect>,
+    timers: Scheduler<RedHatBoy>,
+    // Set when a hit kills RHB, until the game has recorded it
+    killed_by: Option<DeathCause>,
+    special: Special,
 }
 
 impl RedHatBoy {
-    fn new(animation: Animation) -> Self {
+    fn new(animation: Animation<RhbAnimation>, physics: PhysicsConfig, special: Special) -> Self {
         RedHatBoy {
-            state: RedHatBoyStateMachine::Idle(RedHatBoyState::new()),
+            state: RedHatBoyStateMachine::new(physics),
             animation,
+            grounded_on: None,
+            grounding: Grounding::default(),
+            health: MAX_HEALTH,
+            invulnerable: None,
+            effects: vec![],
+            timers: Scheduler::default(),
+            killed_by: None,
+            special,
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
+    fn draw(&self, renderer: &Renderer, interpolation: f32) {
+        if self.has_effect(Effect::Shield) {
+            let center = self.center();
+            renderer.draw_circle(
+                SHIELD_COLOR,
+                &Point {
+                    x: center.x as i16,
+                    y: center.y as i16,
+                },
+                self.bounding_box().height * 0.6,
+            );
+        }
+
+        // Blink while invulnerable after taking a hit
+        let blinking = self
+            .invulnerable
+            .and_then(|timer| self.timers.remaining(timer))
+            .map(|remaining| (remaining / BLINK_MS) as u32 % 2 == 1)
+            .unwrap_or(false);
+        if blinking {
+            return;
+        }
+
         self.animation.draw(
             renderer,
-            self.animation_name(),
+            self.animation(),
             &(self.frame() / 3).into(),
-            &self.position(),
+            &self.state.interpolated_position(interpolation),
         );
     }
 
+    // Picking up an effect RHB already has restarts its timer
+    fn add_effect(&mut self, effect: Effect) {
+        self.remove_effect(effect);
+        let expiry = effect.duration().map(|duration| {
+            self.timers.after(duration, move |rhb: &mut RedHatBoy| {
+                rhb.remove_effect(effect)
+            })
+        });
+        self.effects.push(ActiveEffect { effect, expiry });
+    }
+
+    fn has_effect(&self, effect: Effect) -> bool {
+        self.effects.iter().any(|active| active.effect == effect)
+    }
+
+    fn remove_effect(&mut self, effect: Effect) {
+        let timers = &mut self.timers;
+        self.effects.retain(|active| {
+            if active.effect != effect {
+                return true;
+            }
+            if let Some(expiry) = active.expiry {
+                timers.cancel(expiry);
+            }
+            false
+        });
+    }
+
+    fn speed_multiplier(&self) -> f32 {
+        if self.has_effect(Effect::SpeedBoost) {
+            BOOST_MULTIPLIER
+        } else {
+            1.0
+        }
+    }
+
+    fn become_invulnerable(&mut self) {
+        let timer = self.timers.after(INVULNERABLE_MS, |rhb: &mut RedHatBoy| {
+            rhb.invulnerable = None
+        });
+        self.invulnerable = Some(timer);
+    }
+
+    fn center(&self) -> Vector {
+        let bounding_box = self.bounding_box();
+        Vector {
+            x: bounding_box.x + bounding_box.width / 2.0,
+            y: bounding_box.y + bounding_box.height / 2.0,
+        }
+    }
+
+    // His position is where his feet are, and the frame hangs off that
     fn bounding_box(&self) -> Rect {
         let bounding_box = self
             .animation
-            .bounding_box_for(self.animation_name(), &((self.frame() / 3) as i16));
+            .bounding_box_for(self.animation(), &((self.frame() / 3) as i16));
 
         Rect {
             x: self.position().x as f32 + bounding_box.x,
@@ -260,392 +3491,608 @@ impl RedHatBoy {
         }
     }
 
-    fn check_platform_collisions(&mut self, platform: &Platform) {
-        if self.landing_on(&platform.bounding_box()) {
-            self.land_on(platform.position.y);
-        } else if self.collides_with(&platform.bounding_box()) {
-            self.kill();
+    fn check_platform_collisions(
+        &mut self,
+        platform: &Platform,
+        events: &mut Vec<WorldEvent>,
+    ) -> bool {
+        if platform.slope.is_some() {
+            return self.check_slope_collisions(platform);
+        }
+
+        let bounding_box = platform.bounding_box();
+        let overlap = self.bounding_box().intersection(&bounding_box);
+        if self.landing_on(&bounding_box) {
+            match platform.bounce {
+                Some(velocity) => {
+                    self.launch_from(bounding_box.y as i16, velocity);
+                    events.extend(collision("platform", overlap, "bounced"));
+                    false
+                }
+                None => {
+                    self.land_on(bounding_box.y as i16);
+                    // Standing on it lands him again every update
+                    if self.grounding.landed() {
+                        events.extend(collision("platform", overlap, "landed"));
+                    }
+                    true
+                }
+            }
+        } else {
+            // With his feet level with the top, he's only gone off the edge
+            if self.collides_with(&bounding_box) && !self.level_with(&bounding_box) {
+                let resolution = self.hit(DeathCause::Platform);
+                events.extend(collision("platform", overlap, resolution));
+            }
+            false
+        }
+    }
+
+    // Slopes are only solid from above. RHB's feet snap to the surface when
+    // they're on or just below it, which keeps him glued to it going uphill.
+    fn check_slope_collisions(&mut self, platform: &Platform) -> bool {
+        if self.state.velocity().y < 0.0 {
+            return false;
+        }
+
+        let feet = self.feet();
+        let feet_x = feet.x + feet.width / 2.0;
+        let feet_y = feet.y + feet.height;
+
+        match platform.surface_at(feet_x) {
+            Some(surface) if feet_y >= surface && feet_y <= surface + SLOPE_TOLERANCE => {
+                self.land_on(surface as i16);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn teleport(&mut self, distance: Point) {
+        self.ride(distance);
+        self.state.settle();
+    }
+
+    // Where thrown bones leave from
+    fn hand(&self) -> Point {
+        let bounding_box = self.bounding_box();
+        Point {
+            x: (bounding_box.x + bounding_box.width) as i16,
+            y: (bounding_box.y + bounding_box.height / 3.0) as i16,
         }
     }
 
+    fn push(&mut self, force: Vector) {
+        self.state.push(force);
+    }
+
+    fn ride(&mut self, delta: Point) {
+        self.state.ride(delta);
+    }
+
     fn collides_with(&self, rect: &Rect) -> bool {
         self.bounding_box().intersects(rect)
     }
 
+    // The sensor for what he's standing on
+    fn feet(&self) -> Rect {
+        let bounding_box = self.bounding_box();
+        let width = bounding_box.width * FEET_WIDTH;
+        Rect {
+            x: bounding_box.x + (bounding_box.width - width) / 2.0,
+            y: bounding_box.y + bounding_box.height - FEET_HEIGHT,
+            width,
+            height: FEET_HEIGHT,
+        }
+    }
+
     fn landing(&self) -> bool {
-        self.position().y as f32 + self.bounding_box().height > FLOOR as f32
+        let feet = self.feet();
+        feet.y + feet.height > FLOOR as f32
     }
 
+    // Coming down with his feet on the top of rect, rather than into its
+    // side or up into it from below
     fn landing_on(&self, rect: &Rect) -> bool {
-        self.bounding_box().intersects(rect) && (self.position().y as f32) < rect.y
+        self.state.velocity().y >= 0.0 && self.feet().intersects(rect) && self.level_with(rect)
+    }
+
+    fn level_with(&self, rect: &Rect) -> bool {
+        let feet = self.feet();
+        feet.y + feet.height <= rect.y + LANDING_TOLERANCE
     }
 
     fn land_on(&mut self, y: i16) {
-        self.state = self
-            .state
-            .land((y as f32 - self.bounding_box().height) as i16)
+        self.grounding.touch();
+        self.state = self.state.land(self.standing_on(y))
+    }
+
+    fn launch_from(&mut self, y: i16, velocity: f32) {
+        self.state = self.state.launch(self.standing_on(y), velocity)
+    }
+
+    // Where his feet go for the bottom of the current frame to touch y. The
+    // frames stop a little above the bottom of the sprite.
+    fn standing_on(&self, y: i16) -> i16 {
+        let bounding_box = self.bounding_box();
+        let bottom = bounding_box.y + bounding_box.height;
+        self.position().y + (y as f32 - bottom) as i16
     }
 
-    fn animation_name(&self) -> &str {
+    fn animation(&self) -> RhbAnimation {
         self.state.animation()
     }
 
     fn frame(&self) -> u8 {
-        self.state.game_object().frame
+        self.state.frame()
     }
 
     fn position(&self) -> &Point {
-        &self.state.game_object().position
+        self.state.position()
     }
 
-    fn is_dead(&self) -> bool {
-        self.state.is_dead()
+    fn can_throw(&self) -> bool {
+        self.state.can_throw()
     }
 
-    fn run(&mut self) {
+    fn walking_speed(&self) -> f32 {
+        self.state.velocity().x
+    }
+
+    fn scroll_speed(&self) -> i16 {
+        self.state.scroll()
+    }
+
+    // Returns whether RHB actually started running
+    fn run(&mut self) -> bool {
+        let before = std::mem::discriminant(&self.state);
         self.state = self.state.run();
+        std::mem::discriminant(&self.state) != before
     }
 
     fn kill(&mut self) {
         self.state = self.state.kill();
     }
 
-    fn jump(&mut self) {
+    // Hits cost a point of health and make RHB briefly invulnerable. He only
+    // crashes once he's out of health. Returns what the hit did, for the
+    // collision view.
+    fn hit(&mut self, cause: DeathCause) -> &'static str {
+        if self.invulnerable.is_some() || self.health == 0 {
+            return "ignored";
+        }
+
+        if self.has_effect(Effect::Shield) {
+            self.remove_effect(Effect::Shield);
+            self.become_invulnerable();
+            return "shielded";
+        }
+
+        self.health -= 1;
+        if self.health == 0 {
+            self.kill();
+            self.killed_by = Some(cause);
+            "killed"
+        } else {
+            self.become_invulnerable();
+            self.state = self.state.knock_back();
+            "hurt"
+        }
+    }
+
+    // Returns whether RHB actually started jumping
+    fn jump(&mut self) -> bool {
+        let before = std::mem::discriminant(&self.state);
         self.state = self.state.jump();
+        std::mem::discriminant(&self.state) != before
+    }
+
+    // Launches him out of a slide from where his feet are. Returns whether he
+    // was sliding.
+    fn slide_jump(&mut self, velocity: f32) -> bool {
+        if !self.state.is_sliding() {
+            return false;
+        }
+        let bounding_box = self.bounding_box();
+        self.launch_from((bounding_box.y + bounding_box.height) as i16, velocity);
+        true
     }
 
-    fn slide(&mut self) {
+    // Returns whether RHB actually started sliding
+    fn slide(&mut self) -> bool {
+        let before = std::mem::discriminant(&self.state);
         self.state = self.state.slide();
+        std::mem::discriminant(&self.state) != before
     }
 
-    fn update(&mut self) {
-        self.state = self.state.update();
+    fn is_game_over(&self) -> bool {
+        self.state.is_game_over()
     }
-}
 
-#[derive(Copy, Clone)]
-enum RedHatBoyStateMachine {
-    Idle(RedHatBoyState<Idle>),
-    Running(RedHatBoyState<Running>),
-    Jumping(RedHatBoyState<Jumping>),
-    Sliding(RedHatBoyState<Sliding>),
-    Crashing(RedHatBoyState<Crashing>),
-    GameOver(RedHatBoyState<GameOver>),
-}
+    fn swim(&mut self) {
+        self.state = self.state.swim();
+    }
 
-impl RedHatBoyStateMachine {
-    fn game_object(&self) -> &GameObject {
-        match self {
-            RedHatBoyStateMachine::Idle(val) => &val.object,
-            RedHatBoyStateMachine::Running(val) => &val.object,
-            RedHatBoyStateMachine::Jumping(val) => &val.object,
-            RedHatBoyStateMachine::Sliding(val) => &val.object,
-            RedHatBoyStateMachine::Crashing(val) => &val.object,
-            RedHatBoyStateMachine::GameOver(val) => &val.object,
-        }
+    fn surface(&mut self) {
+        self.state = self.state.surface();
     }
 
-    fn frame_count(&self) -> u8 {
-        match self {
-            RedHatBoyStateMachine::Idle(_) => 10,
-            RedHatBoyStateMachine::Running(_) => 8,
-            RedHatBoyStateMachine::Jumping(_) => 12,
-            RedHatBoyStateMachine::Sliding(_) => 5,
-            RedHatBoyStateMachine::Crashing(_) => 10,
-            RedHatBoyStateMachine::GameOver(_) => 29,
-        }
+    // Sliding into a breakable obstacle smashes it rather than killing RHB,
+    // and characters who can smash don't need to slide
+    fn can_smash(&self) -> bool {
+        self.state.is_sliding() || self.special == Special::Smash
     }
 
-    fn animation(&self) -> &str {
-        match self {
-            RedHatBoyStateMachine::Idle(_) => IDLE_ANIMATION,
-            RedHatBoyStateMachine::Running(_) => RUNNING_ANIMATION,
-            RedHatBoyStateMachine::Jumping(_) => JUMPING_ANIMATION,
-            RedHatBoyStateMachine::Sliding(_) => SLIDING_ANIMATION,
-            RedHatBoyStateMachine::Crashing(_) => DEAD_ANIMATION,
-            RedHatBoyStateMachine::GameOver(_) => DEAD_ANIMATION,
-        }
+    fn is_climbing(&self) -> bool {
+        self.state.is_climbing()
     }
 
-    fn is_dead(&self) -> bool {
-        match self {
-            RedHatBoyStateMachine::GameOver(_) | RedHatBoyStateMachine::Crashing(_) => true,
-            _ => false,
-        }
+    fn climb(&mut self, direction: f32) {
+        self.state = self.state.climb(direction);
     }
 
-    fn run(self) -> Self {
-        match self {
-            RedHatBoyStateMachine::Idle(val) => RedHatBoyStateMachine::Running(val.into()),
-            _ => self,
-        }
+    fn let_go(&mut self) {
+        self.state = self.state.let_go();
     }
 
-    fn jump(self) -> Self {
-        match self {
-            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Jumping(val.into()),
-            _ => self,
+    fn update(&mut self) {
+        for callback in self.timers.tick() {
+            callback(self);
         }
+        self.state.set_speed_multiplier(self.speed_multiplier());
+        self.state = self.state.update();
     }
+}
 
-    fn slide(self) -> Self {
-        match self {
-            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Sliding(val.into()),
-            _ => self,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    use wasm_engine::{
+        analytics::NoAnalytics,
+        engine::DrawCall,
+        input::{InputDevice, Step},
+        testing::{self, play},
+    };
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    // One key held down, through a profile's bindings
+    struct HeldKey(Profile, &'static str);
+
+    impl InputDevice<Action> for HeldKey {
+        fn value(&self, action: Action) -> f32 {
+            if self.0.keys(action).contains(&self.1) {
+                1.0
+            } else {
+                0.0
+            }
         }
     }
 
-    fn kill(self) -> Self {
-        match self {
-            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Crashing(val.into()),
-            RedHatBoyStateMachine::Jumping(val) => RedHatBoyStateMachine::Crashing(val.into()),
-            _ => self,
+    #[wasm_bindgen_test]
+    fn coop_players_never_share_a_key() {
+        for &chosen in controls::PROFILES.iter() {
+            let profile = controls::keyboard_profile(chosen, true);
+            let actions = <Action as wasm_engine::input::Action>::all();
+            for key in actions.iter().flat_map(|&action| profile.keys(action)) {
+                let mut input = InputState::new();
+                input.update(&[&HeldKey(profile, key)]);
+
+                let moves_one = Moves::player_one(&input, &[]) != Moves::default();
+                let moves_two = Moves::player_two(&input) != Moves::default();
+                assert!(
+                    !(moves_one && moves_two),
+                    "{} moves both players with {:?}",
+                    key,
+                    chosen
+                );
+            }
         }
     }
 
-    fn land(self, on: i16) -> Self {
-        match self {
-            RedHatBoyStateMachine::Jumping(mut val) => {
-                val.object = val.object.set_on(on);
-                RedHatBoyStateMachine::Running(val.into())
-            }
-            RedHatBoyStateMachine::Idle(mut val) => {
-                val.object = val.object.set_on(on);
-                RedHatBoyStateMachine::Idle(val)
-            }
-            RedHatBoyStateMachine::Running(mut val) => {
-                val.object = val.object.set_on(on);
-                RedHatBoyStateMachine::Running(val)
-            }
-            RedHatBoyStateMachine::Sliding(mut val) => {
-                val.object = val.object.set_on(on);
-                RedHatBoyStateMachine::Sliding(val)
-            }
-            RedHatBoyStateMachine::Crashing(mut val) => {
-                val.object = val.object.set_on(on);
-                RedHatBoyStateMachine::Crashing(val)
-            }
-            RedHatBoyStateMachine::GameOver(mut val) => {
-                val.object = val.object.set_on(on);
-                RedHatBoyStateMachine::GameOver(val)
-            }
+    fn start() -> Start {
+        Start {
+            analytics: Rc::new(NoAnalytics),
+            mode: Mode::from_location(None),
+            difficulty: 0,
+            spectate: false,
+            coop: false,
+            player: None,
+            muted: true,
+            clips: false,
+            server: None,
+            name: None,
+            profile: Profile::Default,
         }
     }
 
-    fn update(self) -> Self {
-        let frame_count = self.frame_count();
+    async fn new_game_from(start: Start) -> WalkTheDogGame {
+        testing::serve_static_assets();
+        WalkTheDogGame::initialize(start, &SKINS[0])
+            .await
+            .expect("Could not initialize game")
+    }
 
-        match self {
-            RedHatBoyStateMachine::Jumping(mut val) => {
-                val.object = val.object.update(frame_count);
+    async fn new_game() -> WalkTheDogGame {
+        new_game_from(start()).await
+    }
 
-                RedHatBoyStateMachine::Jumping(val)
-            }
-            RedHatBoyStateMachine::Sliding(mut val) => {
-                val.object = val.object.update(frame_count);
+    #[wasm_bindgen_test]
+    async fn running_into_the_rock_hurts_rhb() {
+        let mut game = new_game().await;
 
-                if val.object.animation_finished(frame_count) {
-                    RedHatBoyStateMachine::Running(val.into())
-                } else {
-                    RedHatBoyStateMachine::Sliding(val)
-                }
-            }
-            RedHatBoyStateMachine::Idle(mut val) => {
-                val.object = val.object.update(frame_count);
+        let mut hit = false;
+        play(
+            vec![Step {
+                action: Action::Run,
+                start: 0,
+                frames: 400,
+            }],
+            400,
+            |input| {
+                game.update(input);
+                hit = hit || game.world().rhb.invulnerable.is_some();
+            },
+        );
 
-                RedHatBoyStateMachine::Idle(val)
-            }
-            RedHatBoyStateMachine::Running(mut val) => {
-                val.object = val.object.update(frame_count);
+        assert!(hit);
+    }
 
-                RedHatBoyStateMachine::Running(val)
-            }
-            RedHatBoyStateMachine::Crashing(mut val) => {
-                val.object = val.object.update(frame_count);
+    #[wasm_bindgen_test]
+    async fn holding_jump_only_jumps_once() {
+        let mut game = new_game().await;
 
-                if val.object.animation_finished(frame_count) {
-                    RedHatBoyStateMachine::GameOver(val.into())
-                } else {
-                    RedHatBoyStateMachine::Crashing(val)
-                }
-            }
-            RedHatBoyStateMachine::GameOver(mut val) => {
-                val.object.frame = frame_count;
+        play(
+            vec![
+                Step {
+                    action: Action::Run,
+                    start: 0,
+                    frames: 60,
+                },
+                Step {
+                    action: Action::Jump,
+                    start: 20,
+                    frames: 10,
+                },
+            ],
+            60,
+            |input| game.update(input),
+        );
 
-                RedHatBoyStateMachine::GameOver(val)
-            }
-        }
+        assert_eq!(game.stats.run.jumps, 1);
     }
-}
 
-#[derive(Copy, Clone)]
-struct RedHatBoyState<S> {
-    _state: S,
-    object: GameObject,
-}
+    #[wasm_bindgen_test]
+    async fn jumping_out_of_a_slide_is_a_slide_jump() {
+        let mut game = new_game().await;
+        let mut highest = game.world().rhb.position().y;
 
-#[derive(Copy, Clone)]
-struct Idle;
-#[derive(Copy, Clone)]
-struct Jumping;
-#[derive(Copy, Clone)]
-struct Running;
-#[derive(Copy, Clone)]
-struct Sliding;
-#[derive(Copy, Clone)]
-struct Crashing;
-#[derive(Copy, Clone)]
-struct GameOver;
-
-impl RedHatBoyState<Idle> {
-    fn new() -> Self {
-        let game_object = GameObject {
-            frame: 0,
-            position: engine::Point {
-                x: RHB_POSITION,
-                y: 485,
+        play(
+            vec![
+                Step {
+                    action: Action::Run,
+                    start: 0,
+                    frames: 60,
+                },
+                Step {
+                    action: Action::Slide,
+                    start: 20,
+                    frames: 2,
+                },
+                Step {
+                    action: Action::Jump,
+                    start: 25,
+                    frames: 2,
+                },
+            ],
+            60,
+            |input| {
+                game.update(input);
+                highest = highest.min(game.world().rhb.position().y);
             },
-            velocity: Vector { x: 0.0, y: 0.0 },
-        };
+        );
 
-        RedHatBoyState {
-            _state: Idle {},
-            object: game_object,
-        }
+        assert_eq!(game.stats.run.jumps, 1);
+        assert!(game.world().rhb.position().y - highest > 0);
     }
-}
 
-impl From<RedHatBoyState<Idle>> for RedHatBoyState<Running> {
-    fn from(machine: RedHatBoyState<Idle>) -> Self {
-        RedHatBoyState {
-            _state: Running {},
-            object: machine.object,
-        }
-    }
-}
+    #[wasm_bindgen_test]
+    async fn the_world_steps_the_same_from_the_same_state() {
+        let game = new_game().await;
+        let mut first = game.world().clone();
+        let mut second = game.world().clone();
 
-impl From<RedHatBoyState<Running>> for RedHatBoyState<Sliding> {
-    fn from(machine: RedHatBoyState<Running>) -> Self {
-        RedHatBoyState {
-            _state: Sliding {},
-            object: machine.object.reset_frame().slide(),
+        for tick in 0..300 {
+            let moves = [Moves {
+                run: true,
+                jump: tick % 40 == 0,
+                ..Moves::default()
+            }];
+            first = Gameplay::step(&first, &moves);
+            second = Gameplay::step(&second, &moves);
         }
-    }
-}
 
-impl From<RedHatBoyState<Running>> for RedHatBoyState<Jumping> {
-    fn from(machine: RedHatBoyState<Running>) -> Self {
-        RedHatBoyState {
-            _state: Jumping {},
-            object: machine.object.reset_frame().jump(),
-        }
+        assert_eq!(first.camera.x(), second.camera.x());
+        assert_eq!(first.rhb.position().y, second.rhb.position().y);
+        assert_eq!(first.score, second.score);
     }
-}
 
-impl From<RedHatBoyState<Running>> for RedHatBoyState<Crashing> {
-    fn from(machine: RedHatBoyState<Running>) -> Self {
-        RedHatBoyState {
-            _state: Crashing {},
-            object: machine.object.reset_frame().kill(),
-        }
-    }
-}
+    #[wasm_bindgen_test]
+    async fn a_partners_late_moves_are_rolled_back_for() {
+        let mut game = new_game_from(Start {
+            coop: true,
+            ..start()
+        })
+        .await;
+        // As if the partner were on the other end of a session
+        game.remote = Some(1);
 
-impl From<RedHatBoyState<Jumping>> for RedHatBoyState<Running> {
-    fn from(machine: RedHatBoyState<Jumping>) -> Self {
-        RedHatBoyState {
-            _state: Running {},
-            object: machine.object.reset_frame().land(),
-        }
-    }
-}
+        play(vec![], 10, |input| game.update(input));
+        game.remote_moves.push((
+            2,
+            Moves {
+                run: true,
+                ..Moves::default()
+            },
+        ));
+        play(vec![], 1, |input| game.update(input));
 
-impl From<RedHatBoyState<Jumping>> for RedHatBoyState<Crashing> {
-    fn from(machine: RedHatBoyState<Jumping>) -> Self {
-        RedHatBoyState {
-            _state: Crashing {},
-            object: machine.object.reset_frame().kill(),
-        }
+        let world = game.world();
+        let partner = world.partner.as_ref().expect("No partner in co-op");
+        assert_eq!(partner.rhb.animation(), RhbAnimation::Run);
+        assert_eq!(world.rhb.animation(), RhbAnimation::Idle);
     }
-}
 
-impl From<RedHatBoyState<Sliding>> for RedHatBoyState<Running> {
-    fn from(machine: RedHatBoyState<Sliding>) -> Self {
-        RedHatBoyState {
-            _state: Running {},
-            object: machine.object.reset_frame().stand_up(),
-        }
-    }
-}
+    #[wasm_bindgen_test]
+    async fn plugging_in_a_gamepad_switches_the_prompts() {
+        let mut game = new_game().await;
 
-impl From<RedHatBoyState<Crashing>> for RedHatBoyState<GameOver> {
-    fn from(machine: RedHatBoyState<Crashing>) -> Self {
-        RedHatBoyState {
-            _state: GameOver {},
-            object: machine.object,
-        }
+        game.handle_event(&EngineEvent::GamepadConnected {
+            id: "pad".to_string(),
+        });
+        assert_eq!(game.glyphs(), Glyphs::Gamepad);
+
+        game.handle_event(&EngineEvent::GamepadDisconnected {
+            id: "pad".to_string(),
+        });
+        assert_eq!(game.glyphs(), Glyphs::Keyboard);
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-struct GameObject {
-    frame: u8,
-    position: Point,
-    velocity: Vector,
-}
+    #[wasm_bindgen_test]
+    async fn a_long_stall_pauses_the_game() {
+        let mut game = new_game().await;
 
-impl GameObject {
-    fn set_on(mut self, y: i16) -> GameObject {
-        self.position.y = y;
-        self
-    }
+        game.handle_event(&EngineEvent::Lag { dropped: 100.0 });
+        assert!(!game.settings_open);
 
-    fn jump(mut self) -> Self {
-        self.velocity.y = JUMP_VELOCITY;
-        self
+        game.handle_event(&EngineEvent::Lag { dropped: 2000.0 });
+        assert!(game.settings_open);
     }
 
-    fn update(mut self, frame_count: u8) -> Self {
-        if self.velocity.y < TERMINAL_VELOCITY {
-            self.velocity.y += GRAVITY;
+    #[wasm_bindgen_test]
+    async fn keeps_only_the_latest_contacts() {
+        let mut game = new_game().await;
+        let contact = |resolution| {
+            EngineEvent::Collision(Collision {
+                subject: "rhb",
+                other: "platform",
+                overlap: Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 1.0,
+                    height: 1.0,
+                },
+                resolution,
+            })
+        };
+
+        for _ in 0..CONTACTS_SHOWN {
+            game.handle_event(&contact("landed"));
         }
+        game.handle_event(&contact("killed"));
 
-        self.position.x += self.velocity.x as i16;
-        self.position.y += self.velocity.y as i16;
-        if self.frame < (frame_count * 3) - 1 {
-            self.frame += 1;
-        } else {
-            self.frame = 0;
-        };
-        self
+        assert_eq!(game.contacts.len(), CONTACTS_SHOWN);
+        assert_eq!(game.contacts.back().unwrap().resolution, "killed");
     }
 
-    fn land(mut self) -> Self {
-        self.velocity.y = 0.0;
-        self
+    #[wasm_bindgen_test]
+    async fn platforms_far_behind_are_dropped() {
+        let game = new_game().await;
+        let mut world = game.world().clone();
+        let ids = |world: &WorldState, behind: bool| -> Vec<usize> {
+            world
+                .platforms
+                .platforms
+                .iter()
+                .filter(|(_, platform)| {
+                    let bounding_box = platform.bounding_box();
+                    (bounding_box.x + bounding_box.width <= DESPAWN_X) == behind
+                })
+                .map(|(id, _)| *id)
+                .collect()
+        };
+
+        world.scroll(DESPAWN_X as i16);
+        let behind = ids(&world, true);
+        let kept = ids(&world, false);
+        world.platforms.update();
+
+        assert!(!behind.is_empty());
+        let remaining: Vec<usize> = world
+            .platforms
+            .platforms
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(remaining, kept);
     }
 
-    fn slide(mut self) -> Self {
-        self.position.y += 15;
-        self
+    #[wasm_bindgen_test]
+    async fn rewinding_puts_collected_coins_back() {
+        let mut game = new_game().await;
+        let coins = game.world().coins.len();
+        game.snapshots.record(game.snapshot());
+        game.world.amend(|world| {
+            world.coins.pop();
+            world.score += 100;
+        });
+        game.stats.run.coins += 1;
+
+        game.rewind();
+
+        assert_eq!(game.world().coins.len(), coins);
+        assert_eq!(game.world().score, 0);
+        assert_eq!(game.stats.run.coins, 0);
     }
 
-    fn stand_up(mut self) -> Self {
-        self.position.y -= 15;
-        self
+    #[wasm_bindgen_test]
+    fn platforms_survive_the_longest_rewind() {
+        let updates = REWIND_SECONDS * 1000.0 / engine::FRAME_SIZE;
+        assert!(-DESPAWN_X >= updates * f32::from(PORTAL_PAN_SPEED));
     }
 
-    fn reset_frame(mut self) -> Self {
-        self.frame = 0;
-        self
+    #[wasm_bindgen_test]
+    fn every_lesson_has_a_zone_in_the_level() {
+        let level: Level = serde_json::from_str(include_str!("../static/level.json"))
+            .expect("Could not parse level.json");
+        let lessons: Vec<Lesson> = level
+            .tutorial_zones
+            .iter()
+            .map(|zone| zone.lesson)
+            .collect();
+
+        assert_eq!(
+            lessons,
+            vec![Lesson::Run, Lesson::Jump, Lesson::Climb, Lesson::Throw]
+        );
     }
 
-    fn kill(mut self) -> Self {
-        self.velocity = Vector { x: 0.0, y: 0.0 };
-        self
+    #[wasm_bindgen_test]
+    fn the_level_chunks_parse() {
+        let chunks = [
+            include_str!("../static/chunks/0.json"),
+            include_str!("../static/chunks/1.json"),
+            include_str!("../static/chunks/2.json"),
+            include_str!("../static/chunks/3.json"),
+        ];
+        let level: Level = serde_json::from_str(include_str!("../static/level.json"))
+            .expect("Could not parse level.json");
+
+        assert_eq!(level.chunks, chunks.len());
+        for chunk in chunks.iter() {
+            let chunk: LevelChunk = serde_json::from_str(chunk).expect("Could not parse chunk");
+            assert!(!chunk.platforms.is_empty());
+        }
     }
 
-    fn animation_finished(&self, frame_count: u8) -> bool {
-        self.frame >= (frame_count * 3) - 1
+    #[wasm_bindgen_test]
+    async fn draws_the_score() {
+        let game = new_game().await;
+        let renderer = testing::hidden_canvas();
+
+        game.draw(&renderer);
+
+        assert!(renderer.calls().contains(&DrawCall::Text("0".to_string())));
     }
 }
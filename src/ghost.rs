@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{platform, red_hat_boy::RhbAnimation};
+
+// Where RHB was on one update, and what he looked like. x is measured along
+// the whole level rather than on screen, since the world scrolls past him.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Frame {
+    pub x: i64,
+    pub y: i16,
+    pub animation: RhbAnimation,
+    pub frame: u8,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Run {
+    score: u32,
+    frames: Vec<Frame>,
+}
+
+// Records every run, and plays back the best one so far alongside it. Only
+// the best run is kept, under its own local storage key.
+pub struct Ghost {
+    key: String,
+    best: Run,
+    recording: Vec<Frame>,
+}
+
+impl Ghost {
+    pub fn load(key: &str) -> Self {
+        let best = platform::get_item(key)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Ghost {
+            key: key.to_string(),
+            best,
+            recording: vec![],
+        }
+    }
+
+    // Called once per update, which moves the playback on too
+    pub fn record(&mut self, frame: Frame) {
+        self.recording.push(frame);
+    }
+
+    // The best run's frame for this update, until it runs out
+    pub fn current(&self) -> Option<&Frame> {
+        self.recording
+            .len()
+            .checked_sub(1)
+            .and_then(|index| self.best.frames.get(index))
+    }
+
+    pub fn best_run(&self) -> &[Frame] {
+        &self.best.frames
+    }
+
+    // Keeps this run if it beat the best one
+    pub fn finish(&mut self, score: u32) {
+        if score <= self.best.score {
+            return;
+        }
+        self.best = Run {
+            score,
+            frames: std::mem::take(&mut self.recording),
+        };
+        self.save();
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.best) {
+            Ok(json) => {
+                if let Err(err) = platform::set_item(&self.key, &json) {
+                    log!("Could not save ghost {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not serialize ghost {:#?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::platform;
+
+const TABLE_SIZE: usize = 5;
+
+// The best scores for one table, e.g. all time or a single day's challenge,
+// stored under their own local storage key.
+pub struct HighScores {
+    key: String,
+    scores: Vec<u32>,
+}
+
+impl HighScores {
+    pub fn load(key: &str) -> Self {
+        let scores = platform::get_item(key)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        HighScores {
+            key: key.to_string(),
+            scores,
+        }
+    }
+
+    pub fn record(&mut self, score: u32) {
+        self.scores.push(score);
+        self.scores.sort_unstable_by(|a, b| b.cmp(a));
+        self.scores.truncate(TABLE_SIZE);
+        self.save();
+    }
+
+    pub fn scores(&self) -> &[u32] {
+        &self.scores
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.scores) {
+            Ok(json) => {
+                if let Err(err) = platform::set_item(&self.key, &json) {
+                    log!("Could not save high scores {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not serialize high scores {:#?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::platform;
+
+const DEFAULT_LOCALE: &str = "en";
+// Set from settings to override the browser's language
+const LOCALE_KEY: &str = "walk_the_dog_locale";
+
+// UI strings for the player's locale. Keys missing from it fall back to the
+// default locale, and then to the key itself so they're easy to spot.
+pub struct I18n {
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl I18n {
+    pub async fn load() -> Result<Self> {
+        let fallback = load_locale(DEFAULT_LOCALE).await?;
+        let locale = selected_locale();
+        let strings = if locale == DEFAULT_LOCALE {
+            HashMap::new()
+        } else {
+            match load_locale(&locale).await {
+                Ok(strings) => strings,
+                Err(err) => {
+                    log!("Could not load locale {} {:#?}", locale, err);
+                    HashMap::new()
+                }
+            }
+        };
+
+        Ok(I18n { strings, fallback })
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(String::as_str)
+            .unwrap_or(key)
+    }
+}
+
+fn selected_locale() -> String {
+    platform::get_item(LOCALE_KEY)
+        .ok()
+        .flatten()
+        .or_else(|| platform::language().ok())
+        .and_then(|language| language.split('-').next().map(str::to_lowercase))
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+}
+
+async fn load_locale(locale: &str) -> Result<HashMap<String, String>> {
+    platform::load_json(&format!("locales/{}.json", locale)).await
+}
@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use wasm_engine::input::{Gamepads, Touches};
+
+use crate::{
+    controls::{self, Action, InputState, Profile},
+    dialog::Dialog,
+    engine::{self, Game, Image, KeyState, Point, Renderer, SpriteSheets},
+    game::{Start, WalkTheDog},
+    i18n::I18n,
+    platform,
+};
+
+const SEEN_KEY: &str = "walk_the_dog_intro_seen";
+const SCRIPT: &str = "intro";
+
+// The story so far, told once before the first run. Jump moves on a line as
+// well as skip, and settings skips the rest.
+pub struct Intro {
+    background: Image,
+    dialog: Dialog,
+    i18n: I18n,
+    // Taken once the intro is over
+    start: Option<Start>,
+    finished: bool,
+}
+
+impl Intro {
+    pub fn is_seen() -> bool {
+        matches!(platform::get_item(SEEN_KEY), Ok(Some(_)))
+    }
+
+    pub async fn load(start: Start) -> Result<Self> {
+        let mut dialog = Dialog::load(&mut SpriteSheets::default()).await?;
+        dialog.play(SCRIPT);
+        Ok(Intro {
+            background: Image::new(engine::load_image("BG.png").await?, Point { x: 0, y: 0 }),
+            dialog,
+            i18n: I18n::load().await?,
+            start: Some(start),
+            finished: false,
+        })
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+        if let Err(err) = platform::set_item(SEEN_KEY, "true") {
+            log!("Could not save that the intro was seen {:#?}", err);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Game for Intro {
+    type Action = Action;
+
+    async fn initialize(&mut self) -> Result<Box<dyn Game<Action = Action>>> {
+        Err(anyhow!("Intro is already loaded!"))
+    }
+
+    fn read_input(
+        &self,
+        keys: &KeyState,
+        gamepads: &Gamepads,
+        touches: &Touches,
+        input: &mut InputState,
+    ) {
+        let profile = self.start.as_ref().map_or(Profile::Default, Start::profile);
+        controls::read(keys, gamepads, touches, profile, input);
+    }
+
+    fn update(&mut self, input: &InputState) {
+        if self.finished {
+            return;
+        }
+        self.dialog.update(input);
+        if input.just_pressed(Action::Jump) {
+            self.dialog.next_line();
+        }
+        if input.just_pressed(Action::Settings) || !self.dialog.is_playing() {
+            self.finish();
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        self.background.draw(renderer, 1.0);
+        self.dialog.draw(renderer, &self.i18n);
+    }
+
+    fn replacement(&mut self) -> Option<Box<dyn Game<Action = Action>>> {
+        if !self.finished {
+            return None;
+        }
+        let start = self.start.take()?;
+        Some(Box::new(WalkTheDog::Choosing(start)))
+    }
+}
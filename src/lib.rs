@@ -1,21 +1,69 @@
 #[macro_use]
-mod browser;
-mod engine;
+extern crate wasm_engine;
+
+mod achievements;
+mod boss;
+mod challenge;
+mod character_select;
+mod characters;
+mod controls;
+mod coop;
+mod decorations;
+mod dialog;
+mod difficulty;
+mod entities;
 mod game;
+mod ghost;
+mod high_scores;
+mod i18n;
+mod intro;
+mod red_hat_boy;
+mod skins;
+mod spectator;
+mod stats;
+mod tiles;
+mod tutorial;
+mod world;
+
+// Imported at the root so the game's modules can use them as crate::engine and
+// crate::platform
+use wasm_engine::{engine, platform};
+
+use std::rc::Rc;
 
 use engine::GameLoop;
 use game::WalkTheDog;
 use wasm_bindgen::prelude::*;
+use wasm_engine::{
+    analytics::{Analytics, BeaconAnalytics, NoAnalytics},
+    crash,
+    viewport::Viewport,
+};
+
+// Where to send analytics events and crash reports, if anywhere
+const ANALYTICS_ENDPOINT: Option<&str> = None;
+const CRASH_ENDPOINT: Option<&str> = None;
 
 // This is like the `main` function, except for JavaScript.
 #[wasm_bindgen(start)]
 pub fn main_js() -> Result<(), JsValue> {
     console_error_panic_hook::set_once();
+    if let Some(endpoint) = CRASH_ENDPOINT {
+        crash::install(endpoint);
+    }
 
-    browser::spawn_local(async move {
-        let game = WalkTheDog::new();
+    wasm_engine::spawn_local(async move {
+        let analytics: Rc<dyn Analytics> = match ANALYTICS_ENDPOINT {
+            Some(endpoint) => Rc::new(BeaconAnalytics::new(endpoint)),
+            None => Rc::new(NoAnalytics),
+        };
+        let game = WalkTheDog::new(Rc::clone(&analytics));
 
-        GameLoop::start(game)
+        GameLoop::builder(game)
+            .viewport(Viewport::new(600.0, 600.0))
+            .analytics(analytics)
+            .configure_from_url()
+            .start()
             .await
             .expect("Could not start game loop");
     });
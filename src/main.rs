@@ -0,0 +1,8 @@
+// The game natively, in a desktop window. Run it from the repository root so
+// the assets in static/ are found.
+use anyhow::{anyhow, Result};
+
+fn main() -> Result<()> {
+    walk_the_dog::main_js().map_err(|_| anyhow!("Could not start the game"))?;
+    wasm_engine::platform::run()
+}
@@ -0,0 +1,229 @@
+use wasm_engine::fixed::{self, SubPixel};
+
+use crate::engine::{Point, Vector};
+
+use super::PhysicsConfig;
+
+const KNOCKBACK: Vector = Vector { x: -4.0, y: -10.0 };
+
+#[derive(Debug, Clone, Copy)]
+pub struct GameObject {
+    pub(super) frame: u8,
+    pub(super) position: Point,
+    // Where he was at the start of the last update
+    pub(super) previous_position: Point,
+    pub(super) velocity: Vector,
+    // The fractions of a pixel he's fallen or risen, and scrolled the world,
+    // short of a whole one
+    pub(super) fall: SubPixel,
+    pub(super) travel: SubPixel,
+    // Whole pixels the world scrolls past him in the last update
+    pub(super) scrolled: i16,
+    // Environmental forces acting on the object this frame, cleared after each update
+    pub(super) force: Vector,
+    pub(super) target_speed: f32,
+    // Scales both target_speed and the animation rate, e.g. for speed boosts
+    pub(super) speed_multiplier: f32,
+    // The part of an animation frame owed from earlier updates, so a
+    // multiplier like 1.75 advances seven frames every four updates
+    pub(super) frame_progress: f32,
+    pub(super) airborne: bool,
+    pub(super) physics: PhysicsConfig,
+}
+
+impl GameObject {
+    pub(super) fn set_on(mut self, y: i16) -> GameObject {
+        self.position.y = y;
+        self.fall.clear();
+        self
+    }
+
+    pub(super) fn jump(self) -> Self {
+        let velocity = self.physics.jump_velocity;
+        self.launch(velocity)
+    }
+
+    pub(super) fn launch(mut self, velocity: f32) -> Self {
+        self.set_velocity(Vector {
+            x: self.velocity.x,
+            y: velocity,
+        });
+        self.airborne = true;
+        self
+    }
+
+    // Every change to velocity goes through here or apply_impulse, which keep
+    // it within the physics' limits and on the fixed point grid, so nothing
+    // else has to clamp or round it
+    pub(super) fn set_velocity(&mut self, velocity: Vector) {
+        self.velocity = Vector {
+            x: fixed::quantize(
+                velocity
+                    .x
+                    .clamp(self.physics.min_speed, self.physics.max_speed),
+            ),
+            y: fixed::quantize(
+                velocity
+                    .y
+                    .clamp(-self.physics.max_rise_speed, self.physics.terminal_velocity),
+            ),
+        };
+    }
+
+    pub(super) fn apply_impulse(&mut self, impulse: Vector) {
+        self.set_velocity(Vector {
+            x: self.velocity.x + impulse.x,
+            y: self.velocity.y + impulse.y,
+        });
+    }
+
+    pub(super) fn run(mut self) -> Self {
+        self.target_speed = self.physics.running_speed;
+        self
+    }
+
+    pub(super) fn update(self, frame_count: u8) -> Self {
+        self.update_at(frame_count, 1.0)
+    }
+
+    // An update heading for a fraction of his usual speed, e.g. while sliding
+    pub(super) fn update_at(mut self, frame_count: u8, speed_factor: f32) -> Self {
+        self.apply_impulse(Vector {
+            x: 0.0,
+            y: self.physics.gravity + self.force.y,
+        });
+
+        self.update_horizontal(speed_factor);
+        self.step(frame_count)
+    }
+
+    // Underwater, gravity is offset by buoyancy and drag stops RHB from
+    // building up speed in either direction.
+    pub(super) fn swim(mut self, frame_count: u8) -> Self {
+        self.apply_impulse(Vector {
+            x: 0.0,
+            y: self.physics.water_gravity - self.physics.buoyancy,
+        });
+        self.set_velocity(Vector {
+            x: self.velocity.x,
+            y: self.velocity.y * self.physics.water_drag,
+        });
+
+        self.update_horizontal(self.physics.water_speed);
+        self.step(frame_count)
+    }
+
+    pub(super) fn step(mut self, frame_count: u8) -> Self {
+        self.force = Vector { x: 0.0, y: 0.0 };
+        self.position.y += self.fall.step(self.velocity.y);
+        self.scrolled = self.travel.step(self.velocity.x);
+        self.frame_progress += self.speed_multiplier;
+        while self.frame_progress >= 1.0 {
+            self.advance_frame(frame_count);
+            self.frame_progress -= 1.0;
+        }
+        self
+    }
+
+    pub(super) fn advance_frame(&mut self, frame_count: u8) {
+        if self.frame < (frame_count * 3) - 1 {
+            self.frame += 1;
+        } else {
+            self.frame = 0;
+        };
+    }
+
+    // The world scrolls past RHB, so velocity.x is his walking speed rather
+    // than a change in position. It eases towards target_speed, with reduced
+    // control while he's in the air.
+    pub(super) fn update_horizontal(&mut self, speed_factor: f32) {
+        let control = if self.airborne {
+            self.physics.air_control
+        } else {
+            1.0
+        };
+        let target_speed =
+            (self.target_speed * speed_factor * self.speed_multiplier + self.force.x).max(0.0);
+
+        let x = if self.velocity.x < target_speed {
+            (self.velocity.x + self.physics.acceleration * control).min(target_speed)
+        } else {
+            (self.velocity.x - self.physics.deceleration * control).max(target_speed)
+        };
+        self.set_velocity(Vector {
+            x,
+            y: self.velocity.y,
+        });
+    }
+
+    // Climbing stops RHB in place, so the world stops scrolling past him
+    pub(super) fn grab(mut self) -> Self {
+        self.target_speed = 0.0;
+        self.set_velocity(Vector { x: 0.0, y: 0.0 });
+        self.airborne = false;
+        self
+    }
+
+    pub(super) fn climb(mut self, direction: f32) -> Self {
+        self.set_velocity(Vector {
+            x: self.velocity.x,
+            y: direction * self.physics.climb_speed,
+        });
+        self
+    }
+
+    pub(super) fn dive(mut self) -> Self {
+        self.airborne = false;
+        self
+    }
+
+    pub(super) fn take_off(mut self) -> Self {
+        self.airborne = true;
+        self
+    }
+
+    pub(super) fn land(mut self) -> Self {
+        self.set_velocity(Vector {
+            x: self.velocity.x,
+            y: 0.0,
+        });
+        self.fall.clear();
+        self.airborne = false;
+        self
+    }
+
+    pub(super) fn slide(mut self) -> Self {
+        self.position.y += self.physics.slide_offset;
+        self
+    }
+
+    pub(super) fn stand_up(mut self) -> Self {
+        self.position.y -= self.physics.slide_offset;
+        self
+    }
+
+    pub(super) fn reset_frame(mut self) -> Self {
+        self.frame = 0;
+        self.frame_progress = 0.0;
+        self
+    }
+
+    pub(super) fn knock_back(mut self) -> Self {
+        self.set_velocity(KNOCKBACK);
+        self.airborne = true;
+        self
+    }
+
+    pub(super) fn kill(mut self) -> Self {
+        self.set_velocity(Vector {
+            x: self.velocity.x,
+            y: 0.0,
+        });
+        self.target_speed = 0.0;
+        self
+    }
+
+    pub(super) fn animation_finished(&self, frame_count: u8) -> bool {
+        self.frame >= (frame_count * 3) - 1
+    }
+}
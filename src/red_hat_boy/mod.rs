@@ -0,0 +1,819 @@
+// RHB's state machine and the movement maths behind it. Nothing in here
+// touches the browser, so it can be tested natively. Each state is its own
+// type in states, so transitions that don't make sense don't compile, and
+// GameObject in game_object does the physics they share. Other characters
+// can follow the same layout.
+mod game_object;
+mod states;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{AnimationSet, Point, Vector};
+
+use self::{
+    game_object::GameObject,
+    states::{
+        Climbing, Crashing, GameOver, Hurt, Idle, Jumping, Launching, RedHatBoyState, Running,
+        Sliding, Swimming,
+    },
+};
+
+/// The animations in rhb.png. Each variant is named after its frames, which
+/// is also how ghosts save them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RhbAnimation {
+    Idle,
+    Run,
+    Jump,
+    Slide,
+    Dead,
+    Hurt,
+}
+
+impl AnimationSet for RhbAnimation {
+    fn all() -> &'static [Self] {
+        &[
+            RhbAnimation::Idle,
+            RhbAnimation::Run,
+            RhbAnimation::Jump,
+            RhbAnimation::Slide,
+            RhbAnimation::Dead,
+            RhbAnimation::Hurt,
+        ]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            RhbAnimation::Idle => "Idle",
+            RhbAnimation::Run => "Run",
+            RhbAnimation::Jump => "Jump",
+            RhbAnimation::Slide => "Slide",
+            RhbAnimation::Dead => "Dead",
+            RhbAnimation::Hurt => "Hurt",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+pub const RHB_POSITION: i16 = 100;
+// There's no launch art in rhb.png, so he holds the tucked Jump frame on his
+// way up off a spring
+const LAUNCH_FRAME: u8 = 12;
+
+/// Tuning for RHB's movement, loaded from physics.json
+#[derive(Deserialize, Debug, Copy, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicsConfig {
+    gravity: f32,
+    jump_velocity: f32,
+    terminal_velocity: f32,
+    running_speed: f32,
+    acceleration: f32,
+    deceleration: f32,
+    air_control: f32,
+    slide_offset: i16,
+    // A fraction of running speed
+    slide_speed: f32,
+    water_gravity: f32,
+    buoyancy: f32,
+    water_drag: f32,
+    water_speed: f32,
+    climb_speed: f32,
+    // Limits on his velocity, however it's changed. Going backwards is only
+    // ever from being knocked back.
+    min_speed: f32,
+    max_speed: f32,
+    max_rise_speed: f32,
+}
+
+/// Every state RHB can be in. Transitions consume the state and return the
+/// next one, which is the same state when the transition doesn't apply, so
+/// callers can always write `state = state.jump()`.
+#[derive(Copy, Clone)]
+pub enum RedHatBoyStateMachine {
+    Idle(RedHatBoyState<Idle>),
+    Running(RedHatBoyState<Running>),
+    Jumping(RedHatBoyState<Jumping>),
+    Launching(RedHatBoyState<Launching>),
+    Sliding(RedHatBoyState<Sliding>),
+    Swimming(RedHatBoyState<Swimming>),
+    Climbing(RedHatBoyState<Climbing>),
+    Hurt(RedHatBoyState<Hurt>),
+    Crashing(RedHatBoyState<Crashing>),
+    GameOver(RedHatBoyState<GameOver>),
+}
+
+impl RedHatBoyStateMachine {
+    /// RHB standing still at his start position
+    pub fn new(physics: PhysicsConfig) -> Self {
+        RedHatBoyStateMachine::Idle(RedHatBoyState::new(physics))
+    }
+
+    /// The animation frame, which advances three times per sprite
+    pub fn frame(&self) -> u8 {
+        self.game_object().frame
+    }
+
+    pub fn position(&self) -> &Point {
+        &self.game_object().position
+    }
+
+    /// Where to draw him between the last update and this one, where an
+    /// interpolation of 1.0 is his position now
+    pub fn interpolated_position(&self, interpolation: f32) -> Point {
+        let object = self.game_object();
+        object
+            .previous_position
+            .lerp(&object.position, interpolation)
+    }
+
+    /// RHB stays put while the world scrolls past, so velocity.x is his
+    /// walking speed rather than a change in his position
+    pub fn velocity(&self) -> Vector {
+        self.game_object().velocity
+    }
+
+    /// How fast the world scrolls past RHB. It follows his walking speed,
+    /// but holds still while he's idle and once he's crashed.
+    pub fn scroll_speed(&self) -> f32 {
+        match self {
+            RedHatBoyStateMachine::Idle(_)
+            | RedHatBoyStateMachine::Crashing(_)
+            | RedHatBoyStateMachine::GameOver(_) => 0.0,
+            _ => self.velocity().x,
+        }
+    }
+
+    /// The whole pixels the world scrolled past him in the last update, at
+    /// scroll_speed with the fractions of a pixel carried between updates
+    pub fn scroll(&self) -> i16 {
+        if self.scroll_speed() == 0.0 {
+            0
+        } else {
+            self.game_object().scrolled
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        matches!(self, RedHatBoyStateMachine::GameOver(_))
+    }
+
+    pub fn is_sliding(&self) -> bool {
+        matches!(self, RedHatBoyStateMachine::Sliding(_))
+    }
+
+    pub fn is_climbing(&self) -> bool {
+        matches!(self, RedHatBoyStateMachine::Climbing(_))
+    }
+
+    /// Whether his hands are free to throw: he's on his feet and on the move,
+    /// rather than idle, climbing, knocked back or down
+    pub fn can_throw(&self) -> bool {
+        matches!(
+            self,
+            RedHatBoyStateMachine::Running(_)
+                | RedHatBoyStateMachine::Jumping(_)
+                | RedHatBoyStateMachine::Launching(_)
+                | RedHatBoyStateMachine::Sliding(_)
+        )
+    }
+
+    /// Adds an environmental force, like wind, for the next update only
+    pub fn push(&mut self, force: Vector) {
+        let object = self.game_object_mut();
+        object.force.x += force.x;
+        object.force.y += force.y;
+    }
+
+    /// Moves RHB without changing his state, e.g. when carried by a platform
+    pub fn ride(&mut self, delta: Point) {
+        let object = self.game_object_mut();
+        object.position.x += delta.x;
+        object.position.y += delta.y;
+    }
+
+    /// Forgets how he moved in the last update, so a jump in position like
+    /// a teleport isn't drawn as a slide across the screen
+    pub fn settle(&mut self) {
+        let object = self.game_object_mut();
+        object.previous_position = object.position;
+    }
+
+    /// Scales his speed and animation rate, e.g. for speed boosts
+    pub fn set_speed_multiplier(&mut self, speed_multiplier: f32) {
+        self.game_object_mut().speed_multiplier = speed_multiplier;
+    }
+
+    fn game_object(&self) -> &GameObject {
+        match self {
+            RedHatBoyStateMachine::Idle(val) => &val.object,
+            RedHatBoyStateMachine::Running(val) => &val.object,
+            RedHatBoyStateMachine::Jumping(val) => &val.object,
+            RedHatBoyStateMachine::Launching(val) => &val.object,
+            RedHatBoyStateMachine::Sliding(val) => &val.object,
+            RedHatBoyStateMachine::Swimming(val) => &val.object,
+            RedHatBoyStateMachine::Climbing(val) => &val.object,
+            RedHatBoyStateMachine::Hurt(val) => &val.object,
+            RedHatBoyStateMachine::Crashing(val) => &val.object,
+            RedHatBoyStateMachine::GameOver(val) => &val.object,
+        }
+    }
+
+    fn game_object_mut(&mut self) -> &mut GameObject {
+        match self {
+            RedHatBoyStateMachine::Idle(val) => &mut val.object,
+            RedHatBoyStateMachine::Running(val) => &mut val.object,
+            RedHatBoyStateMachine::Jumping(val) => &mut val.object,
+            RedHatBoyStateMachine::Launching(val) => &mut val.object,
+            RedHatBoyStateMachine::Sliding(val) => &mut val.object,
+            RedHatBoyStateMachine::Swimming(val) => &mut val.object,
+            RedHatBoyStateMachine::Climbing(val) => &mut val.object,
+            RedHatBoyStateMachine::Hurt(val) => &mut val.object,
+            RedHatBoyStateMachine::Crashing(val) => &mut val.object,
+            RedHatBoyStateMachine::GameOver(val) => &mut val.object,
+        }
+    }
+
+    fn frame_count(&self) -> u8 {
+        match self {
+            RedHatBoyStateMachine::Idle(_) => 10,
+            RedHatBoyStateMachine::Running(_) => 8,
+            RedHatBoyStateMachine::Jumping(_) => 12,
+            RedHatBoyStateMachine::Launching(_) => 12,
+            RedHatBoyStateMachine::Sliding(_) => 5,
+            RedHatBoyStateMachine::Swimming(_) => 12,
+            RedHatBoyStateMachine::Climbing(_) => 10,
+            RedHatBoyStateMachine::Hurt(_) => 8,
+            RedHatBoyStateMachine::Crashing(_) => 10,
+            RedHatBoyStateMachine::GameOver(_) => 29,
+        }
+    }
+
+    /// The animation for the current state. There's no swimming or
+    /// climbing art in rhb.png yet, so those borrow the jump and idle frames.
+    pub fn animation(&self) -> RhbAnimation {
+        match self {
+            RedHatBoyStateMachine::Idle(_) => RhbAnimation::Idle,
+            RedHatBoyStateMachine::Running(_) => RhbAnimation::Run,
+            RedHatBoyStateMachine::Jumping(_) => RhbAnimation::Jump,
+            RedHatBoyStateMachine::Launching(_) => RhbAnimation::Jump,
+            RedHatBoyStateMachine::Sliding(_) => RhbAnimation::Slide,
+            RedHatBoyStateMachine::Swimming(_) => RhbAnimation::Jump,
+            RedHatBoyStateMachine::Climbing(_) => RhbAnimation::Idle,
+            RedHatBoyStateMachine::Hurt(_) => RhbAnimation::Hurt,
+            RedHatBoyStateMachine::Crashing(_) => RhbAnimation::Dead,
+            RedHatBoyStateMachine::GameOver(_) => RhbAnimation::Dead,
+        }
+    }
+
+    /// Starts running from idle
+    pub fn run(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Idle(val) => RedHatBoyStateMachine::Running(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Jumps when running, or off a ladder
+    pub fn jump(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Jumping(val.into()),
+            RedHatBoyStateMachine::Climbing(val) => RedHatBoyStateMachine::Jumping(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Slides when running
+    pub fn slide(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Sliding(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Dives in when RHB enters water
+    pub fn swim(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Swimming(val.into()),
+            RedHatBoyStateMachine::Jumping(val) => RedHatBoyStateMachine::Swimming(val.into()),
+            RedHatBoyStateMachine::Launching(val) => RedHatBoyStateMachine::Swimming(val.into()),
+            RedHatBoyStateMachine::Sliding(val) => RedHatBoyStateMachine::Swimming(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Jumps back out when RHB leaves water
+    pub fn surface(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Swimming(val) => RedHatBoyStateMachine::Jumping(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Grabs a ladder, or climbs one RHB is already on. A negative direction
+    /// is up.
+    pub fn climb(self, direction: f32) -> Self {
+        match self {
+            RedHatBoyStateMachine::Idle(val) => {
+                RedHatBoyStateMachine::Climbing(val.grab_ladder(direction))
+            }
+            RedHatBoyStateMachine::Running(val) => {
+                RedHatBoyStateMachine::Climbing(val.grab_ladder(direction))
+            }
+            RedHatBoyStateMachine::Jumping(val) => {
+                RedHatBoyStateMachine::Climbing(val.grab_ladder(direction))
+            }
+            RedHatBoyStateMachine::Launching(val) => {
+                RedHatBoyStateMachine::Climbing(val.grab_ladder(direction))
+            }
+            RedHatBoyStateMachine::Climbing(mut val) => {
+                val.object = val.object.climb(direction);
+                RedHatBoyStateMachine::Climbing(val)
+            }
+            _ => self,
+        }
+    }
+
+    /// Drops off a ladder
+    pub fn let_go(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Climbing(val) => RedHatBoyStateMachine::Jumping(val.let_go()),
+            _ => self,
+        }
+    }
+
+    /// Knocks RHB back after a hit he survives
+    pub fn knock_back(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Idle(val) => RedHatBoyStateMachine::Hurt(val.into()),
+            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Hurt(val.into()),
+            RedHatBoyStateMachine::Jumping(val) => RedHatBoyStateMachine::Hurt(val.into()),
+            RedHatBoyStateMachine::Launching(val) => RedHatBoyStateMachine::Hurt(val.into()),
+            RedHatBoyStateMachine::Sliding(val) => RedHatBoyStateMachine::Hurt(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Crashes, ending in GameOver
+    pub fn kill(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Running(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            RedHatBoyStateMachine::Jumping(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            RedHatBoyStateMachine::Launching(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            RedHatBoyStateMachine::Sliding(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            RedHatBoyStateMachine::Swimming(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            RedHatBoyStateMachine::Climbing(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            RedHatBoyStateMachine::Hurt(val) => RedHatBoyStateMachine::Crashing(val.into()),
+            _ => self,
+        }
+    }
+
+    /// Throws RHB upwards from a surface at y = on, e.g. off a spring. He's
+    /// Launching until he starts to fall, then Jumping like any other jump.
+    pub fn launch(self, on: i16, velocity: f32) -> Self {
+        match self {
+            RedHatBoyStateMachine::Running(val) => {
+                RedHatBoyStateMachine::Launching(val.launch(on, velocity))
+            }
+            RedHatBoyStateMachine::Jumping(val) => {
+                RedHatBoyStateMachine::Launching(val.launch(on, velocity))
+            }
+            RedHatBoyStateMachine::Launching(val) => {
+                RedHatBoyStateMachine::Launching(val.launch(on, velocity))
+            }
+            RedHatBoyStateMachine::Sliding(val) => {
+                RedHatBoyStateMachine::Launching(val.launch(on, velocity))
+            }
+            _ => self,
+        }
+    }
+
+    /// Stands RHB on a surface at y = on
+    pub fn land(self, on: i16) -> Self {
+        match self {
+            RedHatBoyStateMachine::Jumping(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Running(val.into())
+            }
+            RedHatBoyStateMachine::Launching(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Running(val.into())
+            }
+            RedHatBoyStateMachine::Idle(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Idle(val)
+            }
+            RedHatBoyStateMachine::Running(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Running(val)
+            }
+            RedHatBoyStateMachine::Sliding(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Sliding(val)
+            }
+            RedHatBoyStateMachine::Swimming(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Swimming(val)
+            }
+            RedHatBoyStateMachine::Climbing(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Running(val.into())
+            }
+            RedHatBoyStateMachine::Hurt(mut val) => {
+                val.object = val.object.set_on(on).land();
+                RedHatBoyStateMachine::Hurt(val)
+            }
+            RedHatBoyStateMachine::Crashing(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::Crashing(val)
+            }
+            RedHatBoyStateMachine::GameOver(mut val) => {
+                val.object = val.object.set_on(on);
+                RedHatBoyStateMachine::GameOver(val)
+            }
+        }
+    }
+
+    /// Advances one frame
+    pub fn update(mut self) -> Self {
+        let frame_count = self.frame_count();
+        self.settle();
+
+        match self {
+            RedHatBoyStateMachine::Jumping(mut val) => {
+                val.object = val.object.update(frame_count);
+
+                RedHatBoyStateMachine::Jumping(val)
+            }
+            RedHatBoyStateMachine::Launching(mut val) => {
+                val.object = val.object.update(frame_count);
+                val.object.frame = LAUNCH_FRAME;
+
+                if val.object.velocity.y >= 0.0 {
+                    RedHatBoyStateMachine::Jumping(val.into())
+                } else {
+                    RedHatBoyStateMachine::Launching(val)
+                }
+            }
+            RedHatBoyStateMachine::Sliding(mut val) => {
+                let slide_speed = val.object.physics.slide_speed;
+                val.object = val.object.update_at(frame_count, slide_speed);
+
+                if val.object.animation_finished(frame_count) {
+                    RedHatBoyStateMachine::Running(val.into())
+                } else {
+                    RedHatBoyStateMachine::Sliding(val)
+                }
+            }
+            RedHatBoyStateMachine::Idle(mut val) => {
+                val.object = val.object.update(frame_count);
+
+                RedHatBoyStateMachine::Idle(val)
+            }
+            RedHatBoyStateMachine::Swimming(mut val) => {
+                val.object = val.object.swim(frame_count);
+
+                RedHatBoyStateMachine::Swimming(val)
+            }
+            RedHatBoyStateMachine::Climbing(mut val) => {
+                val.object = val.object.step(frame_count);
+
+                RedHatBoyStateMachine::Climbing(val)
+            }
+            RedHatBoyStateMachine::Hurt(mut val) => {
+                val.object = val.object.update(frame_count);
+
+                if val.object.animation_finished(frame_count) {
+                    RedHatBoyStateMachine::Running(val.into())
+                } else {
+                    RedHatBoyStateMachine::Hurt(val)
+                }
+            }
+            RedHatBoyStateMachine::Running(mut val) => {
+                val.object = val.object.update(frame_count);
+
+                RedHatBoyStateMachine::Running(val)
+            }
+            RedHatBoyStateMachine::Crashing(mut val) => {
+                val.object = val.object.update(frame_count);
+
+                if val.object.animation_finished(frame_count) {
+                    RedHatBoyStateMachine::GameOver(val.into())
+                } else {
+                    RedHatBoyStateMachine::Crashing(val)
+                }
+            }
+            RedHatBoyStateMachine::GameOver(mut val) => {
+                val.object.frame = frame_count;
+
+                RedHatBoyStateMachine::GameOver(val)
+            }
+        }
+    }
+}
+
+/// Whether RHB's feet are on something, worked out afresh each update, so
+/// landing can be told apart from standing
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Grounding {
+    grounded: bool,
+    was_grounded: bool,
+}
+
+impl Grounding {
+    /// Starts an update's checks with his feet on nothing
+    pub fn begin(&mut self) {
+        self.was_grounded = self.grounded;
+        self.grounded = false;
+    }
+
+    /// Something's under his feet this update
+    pub fn touch(&mut self) {
+        self.grounded = true;
+    }
+
+    /// True for the one update he lands on, and not while he stands there
+    pub fn landed(&self) -> bool {
+        self.grounded && !self.was_grounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Debug, Copy, Clone)]
+    enum Event {
+        Run,
+        Jump,
+        Slide,
+        Swim,
+        Surface,
+        Climb(f32),
+        LetGo,
+        KnockBack,
+        Kill,
+        Launch(i16, f32),
+        Land(i16),
+        Push(f32, f32),
+        Update,
+    }
+
+    fn event() -> impl Strategy<Value = Event> {
+        prop_oneof![
+            3 => prop::sample::select(vec![
+                Event::Run,
+                Event::Jump,
+                Event::Slide,
+                Event::Swim,
+                Event::Surface,
+                Event::LetGo,
+                Event::KnockBack,
+                Event::Kill,
+            ]),
+            1 => (-1.0f32..1.0).prop_map(Event::Climb),
+            1 => (0i16..600, -40.0f32..0.0).prop_map(|(on, velocity)| Event::Launch(on, velocity)),
+            1 => (0i16..600).prop_map(Event::Land),
+            1 => (-3.0f32..3.0, -1.0f32..1.0).prop_map(|(x, y)| Event::Push(x, y)),
+            // Most frames are nothing but an update
+            8 => Just(Event::Update),
+        ]
+    }
+
+    fn apply(state: RedHatBoyStateMachine, event: Event) -> RedHatBoyStateMachine {
+        match event {
+            Event::Run => state.run(),
+            Event::Jump => state.jump(),
+            Event::Slide => state.slide(),
+            Event::Swim => state.swim(),
+            Event::Surface => state.surface(),
+            Event::Climb(direction) => state.climb(direction),
+            Event::LetGo => state.let_go(),
+            Event::KnockBack => state.knock_back(),
+            Event::Kill => state.kill(),
+            Event::Launch(on, velocity) => state.launch(on, velocity),
+            Event::Land(on) => state.land(on),
+            Event::Push(x, y) => {
+                let mut state = state;
+                state.push(Vector { x, y });
+                state
+            }
+            Event::Update => state.update(),
+        }
+    }
+
+    fn physics() -> PhysicsConfig {
+        serde_json::from_str(include_str!("../../static/physics.json"))
+            .expect("Could not parse physics.json")
+    }
+
+    fn idle() -> RedHatBoyStateMachine {
+        RedHatBoyStateMachine::new(physics())
+    }
+
+    fn game_over() -> RedHatBoyStateMachine {
+        let mut state = idle().run().kill();
+        while !state.is_game_over() {
+            state = state.update();
+        }
+        state
+    }
+
+    #[test]
+    fn only_throws_while_on_the_move() {
+        assert!(!idle().can_throw());
+        assert!(idle().run().can_throw());
+        assert!(idle().run().jump().can_throw());
+        assert!(!idle().run().knock_back().can_throw());
+        assert!(!game_over().can_throw());
+    }
+
+    #[test]
+    fn holds_the_launch_pose_until_the_peak() {
+        let mut state = idle().run().launch(500, -35.0);
+        let mut updates = 0;
+        while matches!(state, RedHatBoyStateMachine::Launching(_)) {
+            assert!(state.velocity().y < 0.0);
+            state = state.update();
+            updates += 1;
+        }
+
+        assert!(updates > 1);
+        assert!(matches!(state, RedHatBoyStateMachine::Jumping(_)));
+        assert_eq!(state.frame(), LAUNCH_FRAME);
+    }
+
+    #[test]
+    fn sliding_slows_the_scroll() {
+        let mut state = idle().run();
+        for _ in 0..60 {
+            state = state.update();
+        }
+        let running = state.scroll_speed();
+
+        state = state.slide();
+        for _ in 0..5 {
+            state = state.update();
+        }
+
+        assert!(state.is_sliding());
+        assert!(state.scroll_speed() < running);
+    }
+
+    #[test]
+    fn speeds_stay_on_the_fixed_point_grid() {
+        let mut state = idle().run();
+        for update in 0..120 {
+            if update == 30 {
+                state = state.jump();
+            }
+            state.push(Vector { x: 0.1, y: -0.3 });
+            state = state.update();
+
+            let velocity = state.velocity();
+            assert_eq!((velocity.x * 256.0).fract(), 0.0);
+            assert_eq!((velocity.y * 256.0).fract(), 0.0);
+        }
+    }
+
+    #[test]
+    fn fractions_of_a_pixel_add_up_while_scrolling() {
+        let mut state = idle().run();
+        let mut scrolled = 0;
+        let mut exact = 0.0;
+        for _ in 0..10 {
+            state = state.update();
+            scrolled += state.scroll();
+            exact += state.scroll_speed();
+        }
+
+        assert_eq!(scrolled, exact as i16);
+    }
+
+    #[test]
+    fn animation_keeps_pace_with_the_speed_multiplier() {
+        let mut state = idle().run();
+        state.set_speed_multiplier(1.75);
+        for _ in 0..4 {
+            state = state.update();
+        }
+
+        assert_eq!(state.frame(), 7);
+    }
+
+    #[test]
+    fn velocity_stays_within_the_limits() {
+        let physics = PhysicsConfig {
+            max_speed: 5.0,
+            max_rise_speed: 10.0,
+            ..physics()
+        };
+        let mut state = RedHatBoyStateMachine::new(physics).run();
+        for _ in 0..60 {
+            state.push(Vector { x: 20.0, y: 0.0 });
+            state = state.update();
+        }
+        assert_eq!(state.velocity().x, 5.0);
+
+        state = state.jump();
+        assert_eq!(state.velocity().y, -10.0);
+    }
+
+    #[test]
+    fn a_heavier_character_jumps_lower() {
+        let peak = |physics: PhysicsConfig| {
+            let mut state = RedHatBoyStateMachine::new(physics).run().jump();
+            let mut highest = state.position().y;
+            for _ in 0..60 {
+                state = state.update();
+                highest = highest.min(state.position().y);
+            }
+            highest
+        };
+        let heavy: PhysicsConfig =
+            serde_json::from_str(include_str!("../../static/physics_heavy.json"))
+                .expect("Could not parse physics_heavy.json");
+
+        assert!(peak(heavy) > peak(physics()));
+    }
+
+    #[test]
+    fn lands_once_per_landing() {
+        let mut grounding = Grounding::default();
+        let touching = [false, true, true, true, false, true];
+
+        let landings: Vec<bool> = touching
+            .iter()
+            .map(|touching| {
+                grounding.begin();
+                if *touching {
+                    grounding.touch();
+                }
+                grounding.landed()
+            })
+            .collect();
+
+        assert_eq!(landings, vec![false, true, false, false, false, true]);
+    }
+
+    // Ghosts saved before animations were an enum stored their names
+    #[test]
+    fn animations_load_from_their_names() {
+        for animation in RhbAnimation::all() {
+            let json = format!("\"{}\"", animation.name());
+            let loaded: RhbAnimation = serde_json::from_str(&json).unwrap();
+            assert_eq!(loaded, *animation);
+        }
+    }
+
+    #[test]
+    fn interpolates_from_the_last_update() {
+        let mut state = idle().run().jump();
+        let before = *state.position();
+        state = state.update();
+
+        let start = state.interpolated_position(0.0);
+        let end = state.interpolated_position(1.0);
+
+        assert_eq!((start.x, start.y), (before.x, before.y));
+        assert_eq!((end.x, end.y), (state.position().x, state.position().y));
+        assert!(end.y < start.y);
+    }
+
+    proptest! {
+        #[test]
+        fn frame_stays_within_the_animation(events in prop::collection::vec(event(), 0..300)) {
+            let mut state = idle();
+            for event in events {
+                state = apply(state, event);
+                prop_assert!(state.frame() < state.frame_count() * 3);
+            }
+        }
+
+        #[test]
+        fn falling_never_exceeds_terminal_velocity(
+            events in prop::collection::vec(event(), 0..300)
+        ) {
+            let terminal_velocity = physics().terminal_velocity;
+            let mut state = idle();
+            for event in events {
+                state = apply(state, event);
+                prop_assert!(state.velocity().y <= terminal_velocity);
+            }
+        }
+
+        // There's no reset yet, so once RHB is down he stays down
+        #[test]
+        fn game_over_is_final(events in prop::collection::vec(event(), 0..100)) {
+            let mut state = game_over();
+            for event in events {
+                state = apply(state, event);
+                prop_assert!(state.is_game_over());
+                prop_assert_eq!(state.scroll_speed(), 0.0);
+            }
+        }
+    }
+}
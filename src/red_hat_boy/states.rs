@@ -0,0 +1,342 @@
+use wasm_engine::fixed::SubPixel;
+
+use crate::engine::{Point, Vector};
+
+use super::{game_object::GameObject, PhysicsConfig, RHB_POSITION};
+
+#[derive(Copy, Clone)]
+pub struct RedHatBoyState<S> {
+    _state: S,
+    pub(super) object: GameObject,
+}
+
+#[derive(Copy, Clone)]
+pub struct Idle;
+#[derive(Copy, Clone)]
+pub struct Jumping;
+#[derive(Copy, Clone)]
+pub struct Launching;
+#[derive(Copy, Clone)]
+pub struct Running;
+#[derive(Copy, Clone)]
+pub struct Sliding;
+#[derive(Copy, Clone)]
+pub struct Swimming;
+#[derive(Copy, Clone)]
+pub struct Climbing;
+#[derive(Copy, Clone)]
+pub struct Hurt;
+#[derive(Copy, Clone)]
+pub struct Crashing;
+#[derive(Copy, Clone)]
+pub struct GameOver;
+
+impl RedHatBoyState<Idle> {
+    pub(super) fn new(physics: PhysicsConfig) -> Self {
+        let position = Point {
+            x: RHB_POSITION,
+            y: 613,
+        };
+        let game_object = GameObject {
+            frame: 0,
+            position,
+            previous_position: position,
+            velocity: Vector { x: 0.0, y: 0.0 },
+            fall: SubPixel::default(),
+            travel: SubPixel::default(),
+            scrolled: 0,
+            force: Vector { x: 0.0, y: 0.0 },
+            target_speed: 0.0,
+            speed_multiplier: 1.0,
+            frame_progress: 0.0,
+            airborne: false,
+            physics,
+        };
+
+        RedHatBoyState {
+            _state: Idle {},
+            object: game_object,
+        }
+    }
+}
+
+impl<S> RedHatBoyState<S> {
+    pub(super) fn launch(self, on: i16, velocity: f32) -> RedHatBoyState<Launching> {
+        RedHatBoyState {
+            _state: Launching {},
+            object: self.object.set_on(on).reset_frame().launch(velocity),
+        }
+    }
+}
+
+impl<S> RedHatBoyState<S> {
+    pub(super) fn grab_ladder(self, direction: f32) -> RedHatBoyState<Climbing> {
+        RedHatBoyState {
+            _state: Climbing {},
+            object: self.object.reset_frame().grab().climb(direction),
+        }
+    }
+}
+
+impl RedHatBoyState<Climbing> {
+    pub(super) fn let_go(self) -> RedHatBoyState<Jumping> {
+        RedHatBoyState {
+            _state: Jumping {},
+            object: self.object.reset_frame().run().take_off(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Climbing>> for RedHatBoyState<Running> {
+    fn from(machine: RedHatBoyState<Climbing>) -> Self {
+        RedHatBoyState {
+            _state: Running {},
+            object: machine.object.reset_frame().run().land(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Climbing>> for RedHatBoyState<Jumping> {
+    fn from(machine: RedHatBoyState<Climbing>) -> Self {
+        RedHatBoyState {
+            _state: Jumping {},
+            object: machine.object.reset_frame().run().jump(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Idle>> for RedHatBoyState<Running> {
+    fn from(machine: RedHatBoyState<Idle>) -> Self {
+        RedHatBoyState {
+            _state: Running {},
+            object: machine.object.reset_frame().run(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Running>> for RedHatBoyState<Sliding> {
+    fn from(machine: RedHatBoyState<Running>) -> Self {
+        RedHatBoyState {
+            _state: Sliding {},
+            object: machine.object.reset_frame().slide(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Running>> for RedHatBoyState<Jumping> {
+    fn from(machine: RedHatBoyState<Running>) -> Self {
+        RedHatBoyState {
+            _state: Jumping {},
+            object: machine.object.reset_frame().jump(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Running>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Running>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().kill(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Jumping>> for RedHatBoyState<Running> {
+    fn from(machine: RedHatBoyState<Jumping>) -> Self {
+        RedHatBoyState {
+            _state: Running {},
+            object: machine.object.reset_frame().land(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Jumping>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Jumping>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().kill(),
+        }
+    }
+}
+
+// Past the peak of a launch, he carries on through the jump from the pose
+// he held on the way up
+impl From<RedHatBoyState<Launching>> for RedHatBoyState<Jumping> {
+    fn from(machine: RedHatBoyState<Launching>) -> Self {
+        RedHatBoyState {
+            _state: Jumping {},
+            object: machine.object,
+        }
+    }
+}
+
+impl From<RedHatBoyState<Launching>> for RedHatBoyState<Running> {
+    fn from(machine: RedHatBoyState<Launching>) -> Self {
+        RedHatBoyState {
+            _state: Running {},
+            object: machine.object.reset_frame().land(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Launching>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Launching>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().kill(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Sliding>> for RedHatBoyState<Running> {
+    fn from(machine: RedHatBoyState<Sliding>) -> Self {
+        RedHatBoyState {
+            _state: Running {},
+            object: machine.object.reset_frame().stand_up(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Running>> for RedHatBoyState<Swimming> {
+    fn from(machine: RedHatBoyState<Running>) -> Self {
+        RedHatBoyState {
+            _state: Swimming {},
+            object: machine.object.reset_frame().dive(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Jumping>> for RedHatBoyState<Swimming> {
+    fn from(machine: RedHatBoyState<Jumping>) -> Self {
+        RedHatBoyState {
+            _state: Swimming {},
+            object: machine.object.reset_frame().dive(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Launching>> for RedHatBoyState<Swimming> {
+    fn from(machine: RedHatBoyState<Launching>) -> Self {
+        RedHatBoyState {
+            _state: Swimming {},
+            object: machine.object.reset_frame().dive(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Sliding>> for RedHatBoyState<Swimming> {
+    fn from(machine: RedHatBoyState<Sliding>) -> Self {
+        RedHatBoyState {
+            _state: Swimming {},
+            object: machine.object.reset_frame().stand_up().dive(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Swimming>> for RedHatBoyState<Jumping> {
+    fn from(machine: RedHatBoyState<Swimming>) -> Self {
+        RedHatBoyState {
+            _state: Jumping {},
+            object: machine.object.reset_frame().take_off(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Sliding>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Sliding>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().stand_up().kill(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Swimming>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Swimming>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().kill(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Climbing>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Climbing>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().kill(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Idle>> for RedHatBoyState<Hurt> {
+    fn from(machine: RedHatBoyState<Idle>) -> Self {
+        RedHatBoyState {
+            _state: Hurt {},
+            object: machine.object.reset_frame().knock_back(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Running>> for RedHatBoyState<Hurt> {
+    fn from(machine: RedHatBoyState<Running>) -> Self {
+        RedHatBoyState {
+            _state: Hurt {},
+            object: machine.object.reset_frame().knock_back(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Jumping>> for RedHatBoyState<Hurt> {
+    fn from(machine: RedHatBoyState<Jumping>) -> Self {
+        RedHatBoyState {
+            _state: Hurt {},
+            object: machine.object.reset_frame().knock_back(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Launching>> for RedHatBoyState<Hurt> {
+    fn from(machine: RedHatBoyState<Launching>) -> Self {
+        RedHatBoyState {
+            _state: Hurt {},
+            object: machine.object.reset_frame().knock_back(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Sliding>> for RedHatBoyState<Hurt> {
+    fn from(machine: RedHatBoyState<Sliding>) -> Self {
+        RedHatBoyState {
+            _state: Hurt {},
+            object: machine.object.reset_frame().stand_up().knock_back(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Hurt>> for RedHatBoyState<Running> {
+    fn from(machine: RedHatBoyState<Hurt>) -> Self {
+        RedHatBoyState {
+            _state: Running {},
+            object: machine.object.reset_frame().run(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Hurt>> for RedHatBoyState<Crashing> {
+    fn from(machine: RedHatBoyState<Hurt>) -> Self {
+        RedHatBoyState {
+            _state: Crashing {},
+            object: machine.object.reset_frame().kill(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Crashing>> for RedHatBoyState<GameOver> {
+    fn from(machine: RedHatBoyState<Crashing>) -> Self {
+        RedHatBoyState {
+            _state: GameOver {},
+            object: machine.object,
+        }
+    }
+}
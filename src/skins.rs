@@ -0,0 +1,141 @@
+use crate::{
+    achievements::Achievements,
+    characters::{Character, HEAVY, RHB},
+    platform,
+};
+
+const SKIN_KEY: &str = "walk_the_dog_skin";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unlock {
+    Free,
+    Achievement(&'static str),
+    // Coins collected over every run
+    LifetimeCoins(u32),
+}
+
+// How a character looks. Skins of the same character share their sheet and
+// tell themselves apart with a canvas filter, so a recolor doesn't need a
+// sheet of its own.
+#[derive(Debug)]
+pub struct Skin {
+    pub id: &'static str,
+    // The i18n key for its name
+    pub name: &'static str,
+    pub character: &'static Character,
+    pub filter: Option<&'static str>,
+    pub unlock: Unlock,
+    // The i18n key for how to unlock it
+    pub hint: &'static str,
+}
+
+pub const SKINS: [Skin; 4] = [
+    Skin {
+        id: "classic",
+        name: "skins.classic",
+        character: &RHB,
+        filter: None,
+        unlock: Unlock::Free,
+        hint: "",
+    },
+    Skin {
+        id: "forest",
+        name: "skins.forest",
+        character: &RHB,
+        filter: Some("hue-rotate(120deg)"),
+        unlock: Unlock::Achievement("marathon"),
+        hint: "skins.forest_hint",
+    },
+    Skin {
+        id: "midnight",
+        name: "skins.midnight",
+        character: &RHB,
+        filter: Some("grayscale(0.6) brightness(0.7)"),
+        unlock: Unlock::LifetimeCoins(500),
+        hint: "skins.midnight_hint",
+    },
+    Skin {
+        id: "heavy",
+        name: "skins.heavy",
+        character: &HEAVY,
+        filter: Some("saturate(2) contrast(1.3)"),
+        unlock: Unlock::Free,
+        hint: "",
+    },
+];
+
+impl Skin {
+    pub fn is_unlocked(&self, achievements: &Achievements, lifetime_coins: u32) -> bool {
+        match self.unlock {
+            Unlock::Free => true,
+            Unlock::Achievement(id) => achievements.is_unlocked(id),
+            Unlock::LifetimeCoins(coins) => lifetime_coins >= coins,
+        }
+    }
+
+    // The last skin chosen, or the first one if it's gone or was never chosen
+    pub fn load() -> &'static Skin {
+        let saved = platform::get_item(SKIN_KEY).ok().flatten();
+        SKINS
+            .iter()
+            .find(|skin| Some(skin.id) == saved.as_deref())
+            .unwrap_or(&SKINS[0])
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = platform::set_item(SKIN_KEY, self.id) {
+            log!("Could not save skin {:#?}", err);
+        }
+    }
+}
+
+// The wardrobe screen, for choosing a skin. The highlight moves over every
+// skin, but only unlocked ones can be worn.
+#[derive(Default)]
+pub struct Wardrobe {
+    pub open: bool,
+    highlighted: usize,
+}
+
+impl Wardrobe {
+    pub fn show(&mut self, worn: &Skin) {
+        self.open = true;
+        self.highlighted = SKINS
+            .iter()
+            .position(|skin| skin.id == worn.id)
+            .unwrap_or(0);
+    }
+
+    pub fn highlighted(&self) -> &'static Skin {
+        &SKINS[self.highlighted]
+    }
+
+    // Wraps around at either end
+    pub fn move_highlight(&mut self, rows: isize) {
+        let count = SKINS.len() as isize;
+        self.highlighted = (self.highlighted as isize + rows).rem_euclid(count) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_highlight_wraps_around() {
+        let mut wardrobe = Wardrobe::default();
+        wardrobe.show(&SKINS[0]);
+
+        wardrobe.move_highlight(-1);
+        assert_eq!(wardrobe.highlighted().id, SKINS[SKINS.len() - 1].id);
+        wardrobe.move_highlight(1);
+        assert_eq!(wardrobe.highlighted().id, SKINS[0].id);
+    }
+
+    #[test]
+    fn skins_have_unique_ids() {
+        for (index, skin) in SKINS.iter().enumerate() {
+            assert!(SKINS[index + 1..].iter().all(|other| other.id != skin.id));
+        }
+    }
+}
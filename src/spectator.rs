@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+
+use crate::ghost::Frame;
+
+// Further behind the stream than this and playback skips ahead
+const MAX_BEHIND: usize = 30;
+
+// Watches someone else's run from their stream of frames, without simulating
+// anything locally. Frames play back one an update, the rate they were
+// recorded at. Watching live, playback skips ahead if it falls too far behind
+// the stream.
+pub struct Spectator {
+    stream: UnboundedReceiver<Frame>,
+    live: bool,
+    buffered: VecDeque<Frame>,
+    current: Option<Frame>,
+}
+
+impl Spectator {
+    pub fn new(stream: UnboundedReceiver<Frame>, live: bool) -> Self {
+        Spectator {
+            stream,
+            live,
+            buffered: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    // Someone else's run as it happens, with frames sent in as they arrive
+    pub fn live() -> (UnboundedSender<Frame>, Self) {
+        let (sender, receiver) = unbounded();
+        (sender, Spectator::new(receiver, true))
+    }
+
+    // A run that's already over, played back from the start
+    pub fn replay(frames: &[Frame]) -> Self {
+        let (sender, receiver) = unbounded();
+        for frame in frames {
+            let _ = sender.unbounded_send(*frame);
+        }
+        Spectator::new(receiver, false)
+    }
+
+    // Moves on one frame. The last one stays when the stream runs dry, so
+    // RHB holds still rather than vanishing.
+    pub fn update(&mut self) {
+        while let Ok(frame) = self.stream.try_recv() {
+            self.buffered.push_back(frame);
+        }
+        if self.live && self.buffered.len() > MAX_BEHIND {
+            let skipped = self.buffered.len() - MAX_BEHIND;
+            self.buffered.drain(..skipped);
+        }
+        if let Some(frame) = self.buffered.pop_front() {
+            self.current = Some(frame);
+        }
+    }
+
+    pub fn current(&self) -> Option<&Frame> {
+        self.current.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::red_hat_boy::RhbAnimation;
+
+    fn frame(x: i64) -> Frame {
+        Frame {
+            x,
+            y: 0,
+            animation: RhbAnimation::Run,
+            frame: 0,
+        }
+    }
+
+    #[test]
+    fn replays_a_frame_each_update_and_holds_the_last() {
+        let mut spectator = Spectator::replay(&[frame(0), frame(1)]);
+
+        let xs: Vec<i64> = (0..3)
+            .map(|_| {
+                spectator.update();
+                spectator.current().unwrap().x
+            })
+            .collect();
+
+        assert_eq!(xs, vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn plays_frames_as_they_are_sent() {
+        let (feed, mut spectator) = Spectator::live();
+
+        spectator.update();
+        assert!(spectator.current().is_none());
+
+        feed.unbounded_send(frame(5)).unwrap();
+        spectator.update();
+        assert_eq!(spectator.current().unwrap().x, 5);
+    }
+
+    #[test]
+    fn skips_ahead_when_the_stream_gets_too_far_ahead() {
+        let (sender, receiver) = unbounded();
+        let mut spectator = Spectator::new(receiver, true);
+        for x in 0..100 {
+            sender.unbounded_send(frame(x)).unwrap();
+        }
+
+        spectator.update();
+
+        assert_eq!(spectator.current().unwrap().x, 70);
+    }
+}
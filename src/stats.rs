@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{Point, Rect, Renderer},
+    i18n::I18n,
+    platform,
+};
+
+const STORAGE_KEY: &str = "walk_the_dog_stats";
+
+#[derive(Copy, Clone)]
+pub enum DeathCause {
+    Obstacle,
+    Platform,
+    BossShot,
+}
+
+impl DeathCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Obstacle => "obstacle",
+            DeathCause::Platform => "platform",
+            DeathCause::BossShot => "boss",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Tally {
+    // In meters
+    pub distance: f64,
+    pub jumps: u32,
+    pub slides: u32,
+    pub coins: u32,
+    pub near_misses: u32,
+    pub deaths: HashMap<String, u32>,
+}
+
+impl Tally {
+    fn add(&mut self, other: &Tally) {
+        self.distance += other.distance;
+        self.jumps += other.jumps;
+        self.slides += other.slides;
+        self.coins += other.coins;
+        self.near_misses += other.near_misses;
+        for (cause, count) in other.deaths.iter() {
+            *self.deaths.entry(cause.clone()).or_insert(0) += count;
+        }
+    }
+}
+
+// Stats for the current run, and for every run before it. The lifetime
+// stats are only saved when a run ends.
+pub struct Stats {
+    pub run: Tally,
+    pub lifetime: Tally,
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        let lifetime = platform::get_item(STORAGE_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Stats {
+            run: Tally::default(),
+            lifetime,
+        }
+    }
+
+    pub fn record_death(&mut self, cause: DeathCause) {
+        *self.run.deaths.entry(cause.name().to_string()).or_insert(0) += 1;
+        self.lifetime.add(&self.run);
+        self.save();
+    }
+
+    pub fn draw(&self, renderer: &Renderer, i18n: &I18n) {
+        renderer.fill_rect(
+            "rgba(0, 0, 0, 0.7)",
+            &Rect {
+                x: 100.0,
+                y: 120.0,
+                width: 400.0,
+                height: 300.0,
+            },
+        );
+        renderer.draw_text(
+            i18n.get("stats.game_over"),
+            &Point { x: 230, y: 160 },
+            "#ffffff",
+            28,
+        );
+
+        let rows = [
+            (
+                i18n.get("stats.distance"),
+                format!("{:.0}m", self.run.distance),
+                format!("{:.0}m", self.lifetime.distance),
+            ),
+            (
+                i18n.get("stats.jumps"),
+                self.run.jumps.to_string(),
+                self.lifetime.jumps.to_string(),
+            ),
+            (
+                i18n.get("stats.slides"),
+                self.run.slides.to_string(),
+                self.lifetime.slides.to_string(),
+            ),
+            (
+                i18n.get("stats.coins"),
+                self.run.coins.to_string(),
+                self.lifetime.coins.to_string(),
+            ),
+            (
+                i18n.get("stats.near_misses"),
+                self.run.near_misses.to_string(),
+                self.lifetime.near_misses.to_string(),
+            ),
+        ];
+
+        renderer.draw_text(
+            i18n.get("stats.this_run"),
+            &Point { x: 280, y: 200 },
+            "#ffd700",
+            16,
+        );
+        renderer.draw_text(
+            i18n.get("stats.all_time"),
+            &Point { x: 390, y: 200 },
+            "#ffd700",
+            16,
+        );
+        for (row, (label, run, lifetime)) in rows.iter().enumerate() {
+            let y = 230 + row as i16 * 28;
+            renderer.draw_text(label, &Point { x: 120, y }, "#ffffff", 16);
+            renderer.draw_text(run, &Point { x: 280, y }, "#ffffff", 16);
+            renderer.draw_text(lifetime, &Point { x: 390, y }, "#ffffff", 16);
+        }
+
+        let mut deaths: Vec<String> = self
+            .lifetime
+            .deaths
+            .iter()
+            .map(|(cause, count)| format!("{} {}", i18n.get(&format!("death.{}", cause)), count))
+            .collect();
+        deaths.sort();
+        renderer.draw_text(
+            &format!("{}: {}", i18n.get("stats.deaths"), deaths.join(", ")),
+            &Point { x: 120, y: 400 },
+            "#ffffff",
+            16,
+        );
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.lifetime) {
+            Ok(json) => {
+                if let Err(err) = platform::set_item(STORAGE_KEY, &json) {
+                    log!("Could not save stats {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not serialize stats {:#?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::engine::{Point, Rect};
+
+// Where each tile of a shape made of rows of tiles goes. Tiles run left to
+// right, each as wide as its cell, and each row starts under the tallest tile
+// of the row above. Offsets are from the shape's top left, in the same order
+// as the tiles.
+#[derive(Debug)]
+pub struct TileLayout {
+    pub offsets: Vec<Point>,
+    // The box around every tile, at 0, 0
+    pub bounds: Rect,
+}
+
+impl TileLayout {
+    // Takes each tile's bounding box, by row from the top
+    pub fn new(rows: &[Vec<Rect>]) -> Self {
+        let mut offsets = vec![];
+        let mut width: f32 = 0.0;
+        let mut y = 0.0;
+        for row in rows {
+            let mut x = 0.0;
+            let mut height: f32 = 0.0;
+            for tile in row {
+                offsets.push(Point {
+                    x: x as i16,
+                    y: y as i16,
+                });
+                x += tile.width;
+                height = height.max(tile.height);
+            }
+            width = width.max(x);
+            y += height;
+        }
+        TileLayout {
+            offsets,
+            bounds: Rect {
+                x: 0.0,
+                y: 0.0,
+                width,
+                height: y,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(width: f32, height: f32) -> Rect {
+        Rect {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn lays_a_row_out_by_each_tiles_width() {
+        let layout =
+            TileLayout::new(&[vec![tile(128.0, 93.0), tile(64.0, 93.0), tile(128.0, 93.0)]]);
+
+        let xs: Vec<i16> = layout.offsets.iter().map(|offset| offset.x).collect();
+        assert_eq!(xs, vec![0, 128, 192]);
+        assert_eq!(layout.bounds, tile(320.0, 93.0));
+    }
+
+    #[test]
+    fn stacks_rows_under_the_tallest_tile_above() {
+        let layout = TileLayout::new(&[
+            vec![tile(128.0, 93.0), tile(128.0, 128.0)],
+            vec![tile(128.0, 128.0)],
+        ]);
+
+        let offsets: Vec<(i16, i16)> = layout
+            .offsets
+            .iter()
+            .map(|offset| (offset.x, offset.y))
+            .collect();
+        assert_eq!(offsets, vec![(0, 0), (128, 0), (0, 128)]);
+        assert_eq!(layout.bounds, tile(256.0, 256.0));
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controls::{Action, Glyphs, InputState},
+    engine::{Point, Rect, Renderer},
+    i18n::I18n,
+    platform,
+};
+
+const STORAGE_KEY: &str = "walk_the_dog_tutorial";
+const PROMPT_FRAMES: u16 = 240;
+
+pub struct Prompt {
+    id: &'static str,
+    text: &'static str,
+    gamepad_text: &'static str,
+    // Doing this action shows the player has got it, so the prompt goes away
+    action: Action,
+    // The dialog script RHB talks the player through it with, if any
+    pub dialog: Option<&'static str>,
+}
+
+const RUN: Prompt = Prompt {
+    id: "run",
+    text: "tutorial.run",
+    gamepad_text: "tutorial.run_gamepad",
+    action: Action::Run,
+    dialog: None,
+};
+
+const JUMP: Prompt = Prompt {
+    id: "jump",
+    text: "tutorial.jump",
+    gamepad_text: "tutorial.jump_gamepad",
+    action: Action::Jump,
+    dialog: None,
+};
+
+const CLIMB: Prompt = Prompt {
+    id: "climb",
+    text: "tutorial.climb",
+    gamepad_text: "tutorial.climb_gamepad",
+    action: Action::Up,
+    dialog: Some("climb"),
+};
+
+const THROW: Prompt = Prompt {
+    id: "throw",
+    text: "tutorial.throw",
+    gamepad_text: "tutorial.throw_gamepad",
+    action: Action::Throw,
+    dialog: Some("throw"),
+};
+
+// How level.json names each prompt
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Lesson {
+    Run,
+    Jump,
+    Climb,
+    Throw,
+}
+
+impl Lesson {
+    pub fn prompt(self) -> &'static Prompt {
+        match self {
+            Lesson::Run => &RUN,
+            Lesson::Jump => &JUMP,
+            Lesson::Climb => &CLIMB,
+            Lesson::Throw => &THROW,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SavedTutorial {
+    seen: Vec<String>,
+}
+
+struct Overlay {
+    prompt: &'static Prompt,
+    frames: u16,
+}
+
+// Each prompt is only ever shown once, the first time RHB reaches one of its
+// trigger zones.
+pub struct Tutorial {
+    saved: SavedTutorial,
+    overlay: Option<Overlay>,
+}
+
+impl Tutorial {
+    pub fn load() -> Self {
+        let saved = platform::get_item(STORAGE_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Tutorial {
+            saved,
+            overlay: None,
+        }
+    }
+
+    // Whether the prompt is shown, which only happens the first time
+    pub fn trigger(&mut self, prompt: &'static Prompt) -> bool {
+        if self.overlay.is_some() || self.has_seen(prompt) {
+            return false;
+        }
+
+        self.saved.seen.push(prompt.id.to_string());
+        self.save();
+        self.overlay = Some(Overlay {
+            prompt,
+            frames: PROMPT_FRAMES,
+        });
+        true
+    }
+
+    pub fn update(&mut self, input: &InputState) {
+        if let Some(overlay) = self.overlay.as_mut() {
+            overlay.frames = overlay.frames.saturating_sub(1);
+            if overlay.frames == 0 || input.just_pressed(overlay.prompt.action) {
+                self.overlay = None;
+            }
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer, i18n: &I18n, glyphs: Glyphs) {
+        if let Some(overlay) = &self.overlay {
+            let text = match glyphs {
+                Glyphs::Keyboard => overlay.prompt.text,
+                Glyphs::Gamepad => overlay.prompt.gamepad_text,
+            };
+            renderer.fill_rect(
+                "rgba(0, 0, 0, 0.7)",
+                &Rect {
+                    x: 150.0,
+                    y: 80.0,
+                    width: 300.0,
+                    height: 44.0,
+                },
+            );
+            renderer.draw_text(i18n.get(text), &Point { x: 170, y: 108 }, "#ffffff", 18);
+        }
+    }
+
+    fn has_seen(&self, prompt: &Prompt) -> bool {
+        self.saved.seen.iter().any(|seen| seen == prompt.id)
+    }
+
+    fn save(&self) {
+        match serde_json::to_string(&self.saved) {
+            Ok(json) => {
+                if let Err(err) = platform::set_item(STORAGE_KEY, &json) {
+                    log!("Could not save tutorial {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not serialize tutorial {:#?}", err);
+            }
+        }
+    }
+}
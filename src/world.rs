@@ -0,0 +1,80 @@
+// Sprites are kept in screen space as i16s, which is plenty for what's on
+// screen but overflows a few minutes into a run. So the screen is a floating
+// origin that moves with the camera, and anything that needs to know where
+// it is along the whole run measures from the start in world space, as an
+// i64, through the camera.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Camera {
+    // The screen's left edge, in world pixels from the start of the run
+    x: i64,
+}
+
+impl Camera {
+    // Velocity is how far the world scrolls past, so it's negative going
+    // forward, as for move_horizontally
+    pub fn scroll(&mut self, velocity: i16) {
+        self.x -= i64::from(velocity);
+    }
+
+    pub fn x(&self) -> i64 {
+        self.x
+    }
+
+    pub fn world_x(&self, screen_x: i16) -> i64 {
+        self.x + i64::from(screen_x)
+    }
+
+    // Anything too far away to fit in an i16 is held at the edge of the
+    // range, well off screen either way
+    pub fn screen_x(&self, world_x: i64) -> i16 {
+        (world_x - self.x).clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16
+    }
+}
+
+// Moves a screen x as the world scrolls. Something left far enough behind
+// would wrap round to the other side of the screen, so it stays at the edge
+// of the range instead.
+pub fn shift(x: i16, velocity: i16) -> i16 {
+    x.saturating_add(velocity)
+}
+
+// How far to scroll the world to cover a distance in world space, held at
+// the edge of the range when it's further than a single update can scroll
+pub fn saturate(distance: i64) -> i16 {
+    distance.clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_track_of_the_world_long_after_sprites_would_overflow() {
+        let mut camera = Camera::default();
+        for _ in 0..100_000 {
+            camera.scroll(-i16::MAX);
+        }
+
+        let world_x = camera.world_x(100);
+        assert_eq!(world_x, 100_000 * i64::from(i16::MAX) + 100);
+        assert_eq!(camera.screen_x(world_x), 100);
+        assert_eq!(camera.screen_x(0), i16::MIN);
+    }
+
+    #[test]
+    fn left_behind_sprites_stay_off_screen() {
+        let mut x = 0;
+        for _ in 0..10_000 {
+            x = shift(x, -8);
+        }
+
+        assert_eq!(x, i16::MIN);
+    }
+
+    #[test]
+    fn long_distances_scroll_as_far_as_they_can() {
+        assert_eq!(saturate(-100_000), i16::MIN);
+        assert_eq!(saturate(100_000), i16::MAX);
+        assert_eq!(saturate(-8), -8);
+    }
+}
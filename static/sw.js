@@ -0,0 +1,39 @@
+// Serves the game from a cache once it has been played online. The game
+// sends the list of assets to cache after it loads assets.json, which names
+// the bundle and the wasm too, so everything the game needs to start is
+// there.
+const CACHE = "walk-the-dog";
+
+self.addEventListener("install", () => self.skipWaiting());
+self.addEventListener("activate", (event) => event.waitUntil(self.clients.claim()));
+
+self.addEventListener("message", (event) => {
+  if (event.data && event.data.type === "precache") {
+    event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(event.data.assets)));
+  }
+});
+
+// Cache first, so the game starts straight away whether or not there's a
+// connection. Anything cached is refreshed in the background at the same
+// time, so an update is picked up the next time the game starts.
+self.addEventListener("fetch", (event) => {
+  if (event.request.method !== "GET") {
+    return;
+  }
+  const refreshed = fetch(event.request).then((response) => {
+    if (response.ok) {
+      const copy = response.clone();
+      caches.open(CACHE).then((cache) => cache.put(event.request, copy));
+    }
+    return response;
+  });
+  event.respondWith(
+    caches.match(event.request).then((cached) => {
+      if (cached) {
+        refreshed.catch(() => {});
+        return cached;
+      }
+      return refreshed;
+    })
+  );
+});
@@ -11,7 +11,11 @@ module.exports = {
   },
   output: {
     path: dist,
-    filename: "[name].js"
+    filename: "[name].js",
+    // Named rather than numbered or hashed, so assets.json can list them
+    // for the service worker to cache
+    chunkFilename: "[name].js",
+    webassemblyModuleFilename: "game.wasm"
   },
   devServer: {
     contentBase: dist,